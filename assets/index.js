@@ -3,7 +3,11 @@
  * @property {"Dir"|"SymlinkDir"|"File"|"SymlinkFile"} path_type
  * @property {string} name
  * @property {number} mtime
+ * @property {string} [mtime_display]
  * @property {number} size
+ * @property {number} [mode]
+ * @property {boolean} [is_symlink]
+ * @property {string} [target]
  */
 
 /**
@@ -19,6 +23,9 @@
  * @property {boolean} auth
  * @property {string} user
  * @property {boolean} dir_exists
+ * @property {boolean} truncated
+ * @property {string} [banner]
+ * @property {string[]} [columns]
  * @property {string} editable
  */
 
@@ -341,6 +348,12 @@ function addBreadcrumb(href, uri_prefix) {
 }
 
 async function setupIndexPage() {
+  if (DATA.banner) {
+    const $banner = document.querySelector(".banner");
+    $banner.textContent = DATA.banner;
+    $banner.classList.remove("hidden");
+  }
+
   if (DATA.allow_archive) {
     const $download = document.querySelector(".download");
     $download.href = baseUrl() + "?zip";
@@ -363,14 +376,42 @@ async function setupIndexPage() {
     setupSearch();
   }
 
+  if (["name", "mtime", "size"].includes(PARAMS.sort)) {
+    const order = PARAMS.order === "desc" ? "desc" : "asc";
+    document.cookie = `dufs_sort=${PARAMS.sort}:${order}; path=${DATA.uri_prefix}; max-age=31536000; samesite=lax`;
+  }
+
   renderPathsTableHead();
   renderPathsTableBody();
 }
 
+/**
+ * The sort of the listing, from the query or else the `dufs_sort` cookie the server sorted by
+ * @returns {{sort?: string, order?: string}}
+ */
+function currentSort() {
+  if (PARAMS.sort) {
+    return { sort: PARAMS.sort, order: PARAMS.order };
+  }
+  const cookie = document.cookie.split("; ").find(v => v.startsWith("dufs_sort="));
+  if (!cookie) return {};
+  const [sort, order] = cookie.slice("dufs_sort=".length).split(":");
+  return { sort, order };
+}
+
+/**
+ * Whether the listing shows a column, picked by `--listing-columns`
+ * @param {string} name
+ */
+function hasColumn(name) {
+  return (DATA.columns || ["name", "mtime", "size"]).includes(name);
+}
+
 /**
  * Render path table thead
  */
 function renderPathsTableHead() {
+  const current = currentSort();
   const headerItems = [
     {
       name: "name",
@@ -386,15 +427,23 @@ function renderPathsTableHead() {
       name: "size",
       props: ``,
       text: "Size",
+    },
+    {
+      name: "mode",
+      props: ``,
+      text: "Mode",
     }
-  ];
+  ].filter(item => hasColumn(item.name));
   $pathsTableHead.insertAdjacentHTML("beforeend", `
     <tr>
       ${headerItems.map(item => {
+    if (item.name === "mode") {
+      return `<th class="cell-mode">${item.text}</th>`;
+    }
     let svg = `<svg width="12" height="12" viewBox="0 0 16 16"><path fill-rule="evenodd" d="M11.5 15a.5.5 0 0 0 .5-.5V2.707l3.146 3.147a.5.5 0 0 0 .708-.708l-4-4a.5.5 0 0 0-.708 0l-4 4a.5.5 0 1 0 .708.708L11 2.707V14.5a.5.5 0 0 0 .5.5zm-7-14a.5.5 0 0 1 .5.5v11.793l3.146-3.147a.5.5 0 0 1 .708.708l-4 4a.5.5 0 0 1-.708 0l-4-4a.5.5 0 0 1 .708-.708L4 13.293V1.5a.5.5 0 0 1 .5-.5z"/></svg>`;
     let order = "desc";
-    if (PARAMS.sort === item.name) {
-      if (PARAMS.order === "desc") {
+    if (current.sort === item.name) {
+      if (current.order === "desc") {
         order = "asc";
         svg = `<svg width="12" height="12" viewBox="0 0 16 16"><path fill-rule="evenodd" d="M8 1a.5.5 0 0 1 .5.5v11.793l3.146-3.147a.5.5 0 0 1 .708.708l-4 4a.5.5 0 0 1-.708 0l-4-4a.5.5 0 0 1 .708-.708L7.5 13.293V1.5A.5.5 0 0 1 8 1z"/></svg>`
       } else {
@@ -422,6 +471,11 @@ function renderPathsTableBody() {
     for (let i = 0; i < len; i++) {
       addPath(DATA.paths[i], i);
     }
+    if (DATA.truncated) {
+      const $truncatedNote = document.querySelector(".truncated-note");
+      $truncatedNote.textContent = `Only the first ${len} entries are listed`;
+      $truncatedNote.classList.remove("hidden");
+    }
   } else {
     $emptyFolder.textContent = DIR_EMPTY_NOTE;
     $emptyFolder.classList.remove("hidden");
@@ -486,10 +540,11 @@ function addPath(file, index) {
     ${getPathSvg(file.path_type)}
   </td>
   <td class="path cell-name">
-    <a href="${url}" ${isDir ? "" : `target="_blank"`}>${encodedName}</a>
+    <a href="${url}" ${isDir ? "" : `target="_blank"`} ${file.target ? `title="${encodedName} → ${encodedStr(file.target)}"` : ""}>${encodedName}</a>
   </td>
-  <td class="cell-mtime">${formatMtime(file.mtime)}</td>
-  <td class="cell-size">${sizeDisplay}</td>
+  ${hasColumn("mtime") ? `<td class="cell-mtime">${file.mtime_display ? encodedStr(file.mtime_display) : formatMtime(file.mtime)}</td>` : ""}
+  ${hasColumn("size") ? `<td class="cell-size">${sizeDisplay}</td>` : ""}
+  ${hasColumn("mode") ? `<td class="cell-mode">${file.mode != null ? file.mode.toString(8).padStart(3, "0") : ""}</td>` : ""}
   ${actionCell}
 </tr>`);
 }
@@ -603,7 +658,7 @@ async function setupEditorPage() {
     $deleteFile.addEventListener("click", async () => {
       const url = baseUrl();
       const name = baseName(url);
-      await doDeletePath(name, url, () => {
+      await doDeletePath(name, url, false, () => {
         location.href = location.href.split("/").slice(0, -1).join("/");
       });
     });
@@ -656,7 +711,8 @@ async function setupEditorPage() {
 async function deletePath(index) {
   const file = DATA.paths[index];
   if (!file) return;
-  await doDeletePath(file.name, newUrl(file.name), () => {
+  const isDir = file.path_type.endsWith("Dir");
+  await doDeletePath(file.name, newUrl(file.name), isDir, () => {
     document.getElementById(`addPath${index}`)?.remove();
     DATA.paths[index] = null;
     if (!DATA.paths.find(v => !!v)) {
@@ -667,12 +723,13 @@ async function deletePath(index) {
   });
 }
 
-async function doDeletePath(name, url, cb) {
-  if (!confirm(`Delete \`${name}\`?`)) return;
+async function doDeletePath(name, url, isDir, cb) {
+  if (!confirm(`Delete \`${name}\`${isDir ? " and everything in it" : ""}?`)) return;
   try {
     await checkAuth();
     const res = await fetch(url, {
       method: "DELETE",
+      headers: isDir ? { "Depth": "infinity" } : {},
     });
     await assertResOK(res);
     cb();
@@ -833,7 +890,8 @@ function newUrl(name) {
 }
 
 function baseUrl() {
-  return location.href.split('?')[0];
+  // The path stays percent-encoded, without the query or fragment
+  return location.origin + location.pathname;
 }
 
 function baseName(url) {
@@ -0,0 +1,21 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    if env::var_os("CARGO_FEATURE_EMBED").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-env-changed=DUFS_EMBED_DIR");
+    let dir = match env::var_os("DUFS_EMBED_DIR") {
+        Some(dir) => PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join(dir),
+        None => {
+            // Keep `--all-features` builds working, `--serve-embedded` then serves nothing
+            println!("cargo:warning=DUFS_EMBED_DIR is not set, embedding an empty directory");
+            let dir = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("embedded");
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        }
+    };
+    println!("cargo:rerun-if-changed={}", dir.display());
+    println!("cargo:rustc-env=DUFS_EMBED_DIR={}", dir.display());
+}
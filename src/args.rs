@@ -1,17 +1,42 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_zip::Compression;
 use clap::builder::{PossibleValue, PossibleValuesParser};
 use clap::{value_parser, Arg, ArgAction, ArgMatches, Command, ValueEnum};
 use clap_complete::{generate, Generator, Shell};
 use serde::{Deserialize, Deserializer};
 use smart_default::SmartDefault;
+use std::collections::BTreeMap;
 use std::env;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use uuid::Uuid;
 
 use crate::auth::AccessControl;
+#[cfg(feature = "compression-dictionary")]
+use crate::dictionary::CompressionDictionary;
+#[cfg(feature = "encrypt")]
+use crate::encrypt::EncryptionKey;
 use crate::http_logger::HttpLogger;
-use crate::utils::encode_uri;
+use crate::proxy::IpCidr;
+use crate::rewrite::{parse_rewrites, RewriteRule};
+use crate::throttle::Rate;
+use crate::time_format::{validate_time_format, Zone};
+use crate::upload_hook::UploadHook;
+use crate::upload_pipe::UploadPipe;
+#[cfg(feature = "reverse-proxy")]
+use crate::upstream::Upstream;
+use crate::utils::{encode_uri, parse_size};
+use crate::webhook::WebhookTarget;
+
+/// The columns `--listing-columns` picks from.
+const LISTING_COLUMNS: &[&str] = &["name", "size", "mtime", "mode"];
+
+/// The smallest read buffer hyper accepts, requests are parsed from a single buffer.
+const MIN_MAX_HEADER_SIZE: u64 = 8192;
+
+/// The `--read-buffer-size` accepted, past a few MiB each download holds more memory than it
+/// gains throughput.
+const READ_BUFFER_SIZES: std::ops::RangeInclusive<u64> = 4096..=16 * 1024 * 1024;
 
 pub fn build_cli() -> Command {
     let app = Command::new(env!("CARGO_CRATE_NAME"))
@@ -68,6 +93,14 @@ pub fn build_cli() -> Command {
                 .value_name("path")
                 .help("Specify a path prefix"),
         )
+        .arg(
+            Arg::new("create-serve-dir")
+                .env("DUFS_CREATE_SERVE_DIR")
+                .hide_env(true)
+                .long("create-serve-dir")
+                .action(ArgAction::SetTrue)
+                .help("Create the serve path along with its parents if it doesn't exist"),
+        )
         .arg(
             Arg::new("hidden")
                 .env("DUFS_HIDDEN")
@@ -78,6 +111,56 @@ pub fn build_cli() -> Command {
                 .help("Hide paths from directory listings, e.g. tmp,*.log,*.lock")
                 .value_name("value"),
         )
+        .arg(
+            Arg::new("serve-hidden-with-auth")
+                .env("DUFS_SERVE_HIDDEN_WITH_AUTH")
+                .hide_env(true)
+                .long("serve-hidden-with-auth")
+                .action(ArgAction::SetTrue)
+                .help("Only list and serve paths matched by --hidden to authenticated users"),
+        )
+        .arg(
+            Arg::new("hidden-deny-write")
+                .env("DUFS_HIDDEN_DENY_WRITE")
+                .hide_env(true)
+                .long("hidden-deny-write")
+                .action(ArgAction::SetTrue)
+                .help("Forbid uploading or creating paths matched by --hidden"),
+        )
+        .arg(
+            Arg::new("protect")
+                .env("DUFS_PROTECT")
+                .hide_env(true)
+                .long("protect")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("glob")
+                .help("Forbid overwriting, deleting or moving files matched by the glob, e.g. '*.pdf,archive/**'"),
+        )
+        .arg(
+            Arg::new("expose-well-known")
+                .env("DUFS_EXPOSE_WELL_KNOWN")
+                .hide_env(true)
+                .long("expose-well-known")
+                .action(ArgAction::SetTrue)
+                .help("Keep .well-known directories listable even if --hidden matches them"),
+        )
+        .arg(
+            Arg::new("well-known-no-auth")
+                .env("DUFS_WELL_KNOWN_NO_AUTH")
+                .hide_env(true)
+                .long("well-known-no-auth")
+                .action(ArgAction::SetTrue)
+                .help("Allow anyone to read /.well-known, e.g. for ACME HTTP-01 challenges"),
+        )
+        .arg(
+            Arg::new("deny-dotfiles")
+                .env("DUFS_DENY_DOTFILES")
+                .hide_env(true)
+                .long("deny-dotfiles")
+                .action(ArgAction::SetTrue)
+                .help("Answer 404 for paths with a component starting with a dot, except /.well-known"),
+        )
         .arg(
             Arg::new("auth")
                 .env("DUFS_AUTH")
@@ -99,6 +182,31 @@ pub fn build_cli() -> Command {
                 .default_value("digest")
                 .value_name("value"),
         )
+        .arg(
+            Arg::new("auth-session")
+                .env("DUFS_AUTH_SESSION")
+                .hide_env(true)
+                .long("auth-session")
+                .action(ArgAction::SetTrue)
+                .help("Keep users signed in with a session cookie once they passed auth"),
+        )
+        .arg(
+            Arg::new("session-secret")
+                .env("DUFS_SESSION_SECRET")
+                .hide_env(true)
+                .long("session-secret")
+                .value_name("secret")
+                .help("Sign session cookies with the secret, a random one lasts until dufs restarts"),
+        )
+        .arg(
+            Arg::new("session-ttl")
+                .env("DUFS_SESSION_TTL")
+                .hide_env(true)
+                .long("session-ttl")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Expire session cookies after the secs [default: 86400]"),
+        )
         .arg(
             Arg::new("allow-all")
                 .env("DUFS_ALLOW_ALL")
@@ -124,6 +232,14 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Allow delete files/folders"),
         )
+        .arg(
+            Arg::new("no-recursive-delete")
+                .env("DUFS_NO_RECURSIVE_DELETE")
+                .hide_env(true)
+                .long("no-recursive-delete")
+                .action(ArgAction::SetTrue)
+                .help("Forbid delete folders with contents, only empty folders can be deleted"),
+        )
         .arg(
             Arg::new("allow-search")
                 .env("DUFS_ALLOW_SEARCH")
@@ -140,6 +256,15 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Allow symlink to files/folders outside root directory"),
         )
+        .arg(
+            Arg::new("symlink-policy")
+                .env("DUFS_SYMLINK_POLICY")
+                .hide_env(true)
+                .value_parser(clap::builder::EnumValueParser::<SymlinkPolicy>::new())
+                .long("symlink-policy")
+                .value_name("policy")
+                .help("List symlinks dereferenced, as links with their target, or not at all [default: show]"),
+        )
         .arg(
             Arg::new("allow-archive")
                 .env("DUFS_ALLOW_ARCHIVE")
@@ -148,6 +273,14 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Allow zip archive generation"),
         )
+        .arg(
+            Arg::new("no-overwrite")
+                .env("DUFS_NO_OVERWRITE")
+                .hide_env(true)
+                .long("no-overwrite")
+                .action(ArgAction::SetTrue)
+                .help("Store uploads to an existing file as `name (1).ext` instead of replacing it"),
+        )
         .arg(
             Arg::new("enable-cors")
                 .env("DUFS_ENABLE_CORS")
@@ -156,6 +289,40 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Enable CORS, sets `Access-Control-Allow-Origin: *`"),
         )
+        .arg(
+            Arg::new("trusted-proxies")
+                .env("DUFS_TRUSTED_PROXIES")
+                .hide_env(true)
+                .long("trusted-proxies")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("cidr")
+                .help("Honor X-Forwarded-For from these proxies, e.g. 10.0.0.0/8,127.0.0.1"),
+        )
+        .arg(
+            Arg::new("no-default-robots")
+                .env("DUFS_NO_DEFAULT_ROBOTS")
+                .hide_env(true)
+                .long("no-default-robots")
+                .action(ArgAction::SetTrue)
+                .help("Don't serve a built-in robots.txt disallowing all crawlers"),
+        )
+        .arg(
+            Arg::new("no-ui")
+                .env("DUFS_NO_UI")
+                .hide_env(true)
+                .long("no-ui")
+                .action(ArgAction::SetTrue)
+                .help("Don't serve the web UI, directories are listed as json"),
+        )
+        .arg(
+            Arg::new("disable-webdav")
+                .env("DUFS_DISABLE_WEBDAV")
+                .hide_env(true)
+                .long("disable-webdav")
+                .action(ArgAction::SetTrue)
+                .help("Disable WebDAV methods, they return 405"),
+        )
         .arg(
             Arg::new("render-index")
                 .env("DUFS_RENDER_INDEX")
@@ -180,15 +347,215 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Serve SPA(Single Page Application)"),
         )
+        .arg(
+            Arg::new("negotiate-language")
+                .env("DUFS_NEGOTIATE_LANGUAGE")
+                .hide_env(true)
+                .long("negotiate-language")
+                .action(ArgAction::SetTrue)
+                .help("Serve page.fr.html for page.html to clients preferring French by Accept-Language"),
+        )
+        .arg(
+            Arg::new("default-language")
+                .env("DUFS_DEFAULT_LANGUAGE")
+                .hide_env(true)
+                .long("default-language")
+                .value_name("lang")
+                .help("Serve this language variant when none of the preferred ones exists [default: en]"),
+        )
+        .arg(
+            Arg::new("language-variant")
+                .env("DUFS_LANGUAGE_VARIANT")
+                .hide_env(true)
+                .value_parser(clap::builder::EnumValueParser::<LanguageVariant>::new())
+                .long("language-variant")
+                .value_name("scheme")
+                .help("Name variants page.fr.html (infix) or page.html.fr (suffix) [default: infix]"),
+        )
+        .arg(
+            Arg::new("root-redirect")
+                .env("DUFS_ROOT_REDIRECT")
+                .hide_env(true)
+                .long("root-redirect")
+                .value_name("path")
+                .help("Redirect requests for the root to the given path, e.g. /welcome"),
+        )
+        .arg(
+            Arg::new("root-redirect-permanent")
+                .env("DUFS_ROOT_REDIRECT_PERMANENT")
+                .hide_env(true)
+                .long("root-redirect-permanent")
+                .action(ArgAction::SetTrue)
+                .help("Use a permanent redirect (301) for --root-redirect instead of 302"),
+        )
+        .arg(
+            Arg::new("no-redirect-slash")
+                .env("DUFS_NO_REDIRECT_SLASH")
+                .hide_env(true)
+                .long("no-redirect-slash")
+                .action(ArgAction::SetTrue)
+                .help("Don't redirect directories without a trailing slash and files with one"),
+        )
+        .arg(
+            Arg::new("join-parts")
+                .env("DUFS_JOIN_PARTS")
+                .hide_env(true)
+                .long("join-parts")
+                .action(ArgAction::SetTrue)
+                .help("Serve a missing file as the concatenation of its .part0, .part1, ... files"),
+        )
+        .arg(
+            Arg::new("gunzip")
+                .env("DUFS_GUNZIP")
+                .hide_env(true)
+                .long("gunzip")
+                .action(ArgAction::SetTrue)
+                .help("Serve a missing file from its .gz, decompressed for clients that don't accept gzip"),
+        )
+        .arg(
+            Arg::new("default-sort-dirs")
+                .env("DUFS_DEFAULT_SORT_DIRS")
+                .hide_env(true)
+                .value_parser(clap::builder::EnumValueParser::<SortDirs>::new())
+                .long("default-sort-dirs")
+                .value_name("position")
+                .help("List folders before files, or mixed with them, unless ?dirs= says otherwise [default: first]"),
+        )
+        .arg(
+            Arg::new("listing-limit")
+                .env("DUFS_LISTING_LIMIT")
+                .hide_env(true)
+                .long("listing-limit")
+                .value_parser(value_parser!(usize))
+                .value_name("num")
+                .help("Return at most num entries per directory listing"),
+        )
+        .arg(
+            Arg::new("listing-preview-bytes")
+                .env("DUFS_LISTING_PREVIEW_BYTES")
+                .hide_env(true)
+                .long("listing-preview-bytes")
+                .value_parser(value_parser!(usize))
+                .value_name("num")
+                .help("Include the first num bytes of text files in JSON listings"),
+        )
+        .arg(
+            Arg::new("listing-checksums")
+                .env("DUFS_LISTING_CHECKSUMS")
+                .hide_env(true)
+                .long("listing-checksums")
+                .action(ArgAction::SetTrue)
+                .help("Include the sha256 of files in JSON listings"),
+        )
+        .arg(
+            Arg::new("listing-compress-threshold")
+                .env("DUFS_LISTING_COMPRESS_THRESHOLD")
+                .hide_env(true)
+                .long("listing-compress-threshold")
+                .value_name("size")
+                .help("Gzip HTML and JSON listings of at least the size when the client accepts it, e.g. 1k"),
+        )
+        .arg(
+            Arg::new("listing-columns")
+                .env("DUFS_LISTING_COLUMNS")
+                .hide_env(true)
+                .long("listing-columns")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("columns")
+                .help("Show these of name, size, mtime and mode in listings [default: name,size,mtime]"),
+        )
+        .arg(
+            Arg::new("time-format")
+                .env("DUFS_TIME_FORMAT")
+                .hide_env(true)
+                .long("time-format")
+                .value_name("format")
+                .help("Render listing timestamps with a strftime format, e.g. '%d/%m/%Y %H:%M'"),
+        )
+        .arg(
+            Arg::new("time-zone")
+                .env("DUFS_TIME_ZONE")
+                .hide_env(true)
+                .long("time-zone")
+                .value_name("zone")
+                .help("Render listing timestamps in a zone, e.g. UTC, local, +08:00"),
+        )
         .arg(
             Arg::new("assets")
                 .env("DUFS_ASSETS")
 				.hide_env(true)
                 .long("assets")
+                .alias("assets-dir")
                 .help("Set the path to the assets directory for overriding the built-in assets")
                 .value_parser(value_parser!(PathBuf))
                 .value_name("path")
         )
+        .arg(
+            Arg::new("mime")
+                .env("DUFS_MIME")
+                .hide_env(true)
+                .long("mime")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("ext=type")
+                .help("Override the MIME type of an extension, e.g. .ts=application/typescript"),
+        )
+        .arg(
+            Arg::new("mime-file")
+                .env("DUFS_MIME_FILE")
+                .hide_env(true)
+                .long("mime-file")
+                .value_name("file")
+                .value_parser(value_parser!(PathBuf))
+                .help("Load MIME type overrides from a file in mime.types format"),
+        )
+        .arg(
+            Arg::new("rewrites")
+                .env("DUFS_REWRITES")
+                .hide_env(true)
+                .long("rewrites")
+                .value_name("file")
+                .value_parser(value_parser!(PathBuf))
+                .help("Redirect or rewrite paths by the `pattern target [status]` lines of a file"),
+        )
+        .arg(
+            Arg::new("inline-types")
+                .env("DUFS_INLINE_TYPES")
+                .hide_env(true)
+                .long("inline-types")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("mime")
+                .help("Display these MIME types in the browser over --attachment-types, e.g. image/*,application/pdf"),
+        )
+        .arg(
+            Arg::new("attachment-types")
+                .env("DUFS_ATTACHMENT_TYPES")
+                .hide_env(true)
+                .long("attachment-types")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("mime")
+                .help("Download these MIME types instead of displaying them [default: archive types]"),
+        )
+        .arg(
+            Arg::new("template-var")
+                .env("DUFS_TEMPLATE_VAR")
+                .hide_env(true)
+                .long("template-var")
+                .action(ArgAction::Append)
+                .value_name("name=value")
+                .help("Substitute __NAME__ in the index.html of --assets, e.g. title=Files"),
+        )
+        .arg(
+            Arg::new("banner")
+                .env("DUFS_BANNER")
+                .hide_env(true)
+                .long("banner")
+                .value_name("text")
+                .help("Show a notice atop the root listing, or the content of a file given as @path"),
+        )
         .arg(
             Arg::new("log-format")
                 .env("DUFS_LOG_FORMAT")
@@ -197,6 +564,16 @@ pub fn build_cli() -> Command {
                 .value_name("format")
                 .help("Customize http log format"),
         )
+        .arg(
+            Arg::new("log-ignore")
+                .env("DUFS_LOG_IGNORE")
+                .hide_env(true)
+                .long("log-ignore")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("glob")
+                .help("Omit requests whose path matches the glob from http log, e.g. /__dufs__/*"),
+        )
         .arg(
             Arg::new("log-file")
                 .env("DUFS_LOG_FILE")
@@ -206,6 +583,39 @@ pub fn build_cli() -> Command {
                 .value_parser(value_parser!(PathBuf))
                 .help("Specify the file to save logs to, other than stdout/stderr"),
         )
+        .arg(
+            Arg::new("log-max-size")
+                .env("DUFS_LOG_MAX_SIZE")
+                .hide_env(true)
+                .long("log-max-size")
+                .value_name("size")
+                .help("Rotate the log file once it reaches the size, e.g. 10M"),
+        )
+        .arg(
+            Arg::new("log-max-files")
+                .env("DUFS_LOG_MAX_FILES")
+                .hide_env(true)
+                .long("log-max-files")
+                .value_parser(value_parser!(usize))
+                .value_name("num")
+                .help("Keep at most num rotated log files, deleting the oldest"),
+        )
+        .arg(
+            Arg::new("log-compress")
+                .env("DUFS_LOG_COMPRESS")
+                .hide_env(true)
+                .long("log-compress")
+                .action(ArgAction::SetTrue)
+                .help("Compress rotated log files with gzip"),
+        )
+        .arg(
+            Arg::new("debug-bodies")
+                .env("DUFS_DEBUG_BODIES")
+                .hide_env(true)
+                .long("debug-bodies")
+                .action(ArgAction::SetTrue)
+                .help("Trace request headers and bodies with credentials redacted, for debugging"),
+        )
         .arg(
             Arg::new("compress")
                 .env("DUFS_COMPRESS")
@@ -216,34 +626,522 @@ pub fn build_cli() -> Command {
                 .help("Set zip compress level [default: low]")
         )
         .arg(
-            Arg::new("completions")
-                .long("completions")
-                .value_name("shell")
-                .value_parser(value_parser!(Shell))
-                .help("Print shell completion script for <shell>"),
-        );
-
-    #[cfg(feature = "tls")]
-    let app = app
+            Arg::new("archive-strip-components")
+                .env("DUFS_ARCHIVE_STRIP_COMPONENTS")
+                .hide_env(true)
+                .long("archive-strip-components")
+                .value_parser(value_parser!(usize))
+                .value_name("num")
+                .help("Remove the num leading components from entry names of zip downloads"),
+        )
         .arg(
-            Arg::new("tls-cert")
-                .env("DUFS_TLS_CERT")
+            Arg::new("archive-keep-shallow")
+                .env("DUFS_ARCHIVE_KEEP_SHALLOW")
                 .hide_env(true)
-                .long("tls-cert")
-                .value_name("path")
-                .value_parser(value_parser!(PathBuf))
-                .help("Path to an SSL/TLS certificate to serve with HTTPS"),
+                .long("archive-keep-shallow")
+                .action(ArgAction::SetTrue)
+                .help("Keep entries with too few components to strip by their file name"),
         )
         .arg(
-            Arg::new("tls-key")
-                .env("DUFS_TLS_KEY")
+            Arg::new("gzip")
+                .env("DUFS_GZIP")
                 .hide_env(true)
-                .long("tls-key")
-                .value_name("path")
+                .long("gzip")
+                .action(ArgAction::SetTrue)
+                .help("Compress text files with gzip when the client accepts it"),
+        )
+        .arg(
+            Arg::new("zero-copy")
+                .env("DUFS_ZERO_COPY")
+                .hide_env(true)
+                .long("zero-copy")
+                .action(ArgAction::SetTrue)
+                .help("Serve whole files from memory maps over plain HTTP, truncating a file while it is served crashes dufs"),
+        )
+        .arg(
+            Arg::new("coalesce-downloads")
+                .env("DUFS_COALESCE_DOWNLOADS")
+                .hide_env(true)
+                .long("coalesce-downloads")
+                .action(ArgAction::SetTrue)
+                .help("Read a file once for all the concurrent downloads of it"),
+        )
+        .arg(
+            Arg::new("no-cache")
+                .env("DUFS_NO_CACHE")
+                .hide_env(true)
+                .long("no-cache")
+                .action(ArgAction::SetTrue)
+                .help("Send `Cache-Control: no-store` without validators and ignore conditional reads"),
+        )
+        .arg(
+            Arg::new("no-streaming-cache")
+                .env("DUFS_NO_STREAMING_CACHE")
+                .hide_env(true)
+                .long("no-streaming-cache")
+                .action(ArgAction::SetTrue)
+                .help("Don't set the Cache-Control of HLS/DASH manifests and segments"),
+        )
+        .arg(
+            Arg::new("etag")
+                .env("DUFS_ETAG")
+                .hide_env(true)
+                .value_parser(clap::builder::EnumValueParser::<EtagMode>::new())
+                .long("etag")
+                .value_name("mode")
+                .help("Derive the ETag of files from their mtime, or from a hash of their content [default: mtime]"),
+        )
+        .arg(
+            Arg::new("block-size")
+                .env("DUFS_BLOCK_SIZE")
+                .hide_env(true)
+                .long("block-size")
+                .value_name("size")
+                .help("Split files into blocks of the size in ?manifest [default: 4M]"),
+        )
+        .arg(
+            Arg::new("read-buffer-size")
+                .env("DUFS_READ_BUFFER_SIZE")
+                .hide_env(true)
+                .long("read-buffer-size")
+                .value_name("size")
+                .help("Read files by chunks of the size when serving them, from 4K to 16M [default: 64K]"),
+        )
+        .arg(
+            Arg::new("stat-cache-ttl")
+                .env("DUFS_STAT_CACHE_TTL")
+                .hide_env(true)
+                .long("stat-cache-ttl")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Keep the metadata of requested paths for the secs, files changed outside of dufs may look stale meanwhile"),
+        )
+        .arg(
+            Arg::new("max-upload-size")
+                .env("DUFS_MAX_UPLOAD_SIZE")
+                .hide_env(true)
+                .long("max-upload-size")
+                .value_name("size")
+                .help("Reject uploads with a body larger than the size, e.g. 1G"),
+        )
+        .arg(
+            Arg::new("upload-allow-ext")
+                .env("DUFS_UPLOAD_ALLOW_EXT")
+                .hide_env(true)
+                .long("upload-allow-ext")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("exts")
+                .help("Only accept uploads with these extensions, e.g. jpg,png,pdf"),
+        )
+        .arg(
+            Arg::new("upload-deny-ext")
+                .env("DUFS_UPLOAD_DENY_EXT")
+                .hide_env(true)
+                .long("upload-deny-ext")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("exts")
+                .help("Refuse uploads with these extensions, e.g. exe,sh,php"),
+        )
+        .arg(
+            Arg::new("upload-allow-no-ext")
+                .env("DUFS_UPLOAD_ALLOW_NO_EXT")
+                .hide_env(true)
+                .long("upload-allow-no-ext")
+                .action(ArgAction::SetTrue)
+                .help("Accept uploads without an extension despite --upload-allow-ext"),
+        )
+        .arg(
+            Arg::new("verify-content-type")
+                .env("DUFS_VERIFY_CONTENT_TYPE")
+                .hide_env(true)
+                .value_parser(clap::builder::EnumValueParser::<VerifyContentType>::new())
+                .long("verify-content-type")
+                .value_name("mode")
+                .help("Reject uploads whose content doesn't match their Content-Type or extension"),
+        )
+        .arg(
+            Arg::new("verify-content-type-allow")
+                .env("DUFS_VERIFY_CONTENT_TYPE_ALLOW")
+                .hide_env(true)
+                .long("verify-content-type-allow")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("expected=sniffed")
+                .help("Accept content sniffed as a type despite the expected one, e.g. video/*=text/plain"),
+        )
+        .arg(
+            Arg::new("temp-dir")
+                .env("DUFS_TEMP_DIR")
+                .hide_env(true)
+                .long("temp-dir")
                 .value_parser(value_parser!(PathBuf))
-                .help("Path to the SSL/TLS certificate's private key"),
+                .value_name("path")
+                .help("Write uploads to a folder until they are complete instead of next to them"),
+        )
+        .arg(
+            Arg::new("clamav-socket")
+                .env("DUFS_CLAMAV_SOCKET")
+                .hide_env(true)
+                .long("clamav-socket")
+                .value_name("addr")
+                .help("Scan uploaded files with clamd listening on a unix socket or host:port"),
+        )
+        .arg(
+            Arg::new("clamav-timeout")
+                .env("DUFS_CLAMAV_TIMEOUT")
+                .hide_env(true)
+                .long("clamav-timeout")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Set the timeout of a clamd scan [default: 30]"),
+        )
+        .arg(
+            Arg::new("on-upload")
+                .env("DUFS_ON_UPLOAD")
+                .hide_env(true)
+                .long("on-upload")
+                .value_name("command")
+                .help("Run a command after each upload, supports {path}, {name} and {size}"),
+        )
+        .arg(
+            Arg::new("hook-shell")
+                .env("DUFS_HOOK_SHELL")
+                .hide_env(true)
+                .long("hook-shell")
+                .action(ArgAction::SetTrue)
+                .help("Run the --on-upload command through the system shell"),
+        )
+        .arg(
+            Arg::new("webhook-url")
+                .env("DUFS_WEBHOOK_URL")
+                .hide_env(true)
+                .long("webhook-url")
+                .value_name("url")
+                .help("POST a JSON event to the url after each upload, delete and move"),
+        )
+        .arg(
+            Arg::new("webhook-secret")
+                .env("DUFS_WEBHOOK_SECRET")
+                .hide_env(true)
+                .long("webhook-secret")
+                .value_name("secret")
+                .help("Sign the --webhook-url events with an HMAC-SHA256 of the secret"),
+        )
+        .arg(
+            Arg::new("pipe")
+                .env("DUFS_PIPE")
+                .hide_env(true)
+                .long("pipe")
+                .action(ArgAction::Append)
+                .value_name("path=command")
+                .help("Stream the body of a PUT to the path into the stdin of a command, e.g. '/ingest=tar -x -C /data'"),
+        )
+        .arg(
+            Arg::new("ingest")
+                .env("DUFS_INGEST")
+                .hide_env(true)
+                .long("ingest")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("path")
+                .help("Store files PUT to the folder under dated subfolders, e.g. /ingest"),
+        )
+        .arg(
+            Arg::new("ingest-layout")
+                .env("DUFS_INGEST_LAYOUT")
+                .hide_env(true)
+                .long("ingest-layout")
+                .value_name("format")
+                .help("Name the dated subfolders of --ingest with the strftime format [default: %Y/%m/%d]"),
+        )
+        .arg(
+            Arg::new("pipe-timeout")
+                .env("DUFS_PIPE_TIMEOUT")
+                .hide_env(true)
+                .long("pipe-timeout")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Kill a --pipe command still running after the secs [default: 3600]"),
+        )
+        .arg(
+            Arg::new("idempotency-ttl")
+                .env("DUFS_IDEMPOTENCY_TTL")
+                .hide_env(true)
+                .long("idempotency-ttl")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Replay the result of an upload to its retries with the same Idempotency-Key for the secs"),
+        )
+        .arg(
+            Arg::new("download-rate")
+                .env("DUFS_DOWNLOAD_RATE")
+                .hide_env(true)
+                .long("download-rate")
+                .value_name("rate")
+                .help("Limit the download rate of each connection in bytes/s, e.g. 10M"),
+        )
+        .arg(
+            Arg::new("upload-rate")
+                .env("DUFS_UPLOAD_RATE")
+                .hide_env(true)
+                .long("upload-rate")
+                .value_name("rate")
+                .help("Limit the upload rate of each connection in bytes/s, e.g. 5M"),
+        )
+        .arg(
+            Arg::new("global-rate")
+                .env("DUFS_GLOBAL_RATE")
+                .hide_env(true)
+                .long("global-rate")
+                .value_name("rate")
+                .help("Limit the combined rate of all transfers in bytes/s, e.g. 20M"),
+        )
+        .arg(
+            Arg::new("keep-alive-timeout")
+                .env("DUFS_KEEP_ALIVE_TIMEOUT")
+                .hide_env(true)
+                .long("keep-alive-timeout")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Close connections idle between requests for longer than the secs"),
+        )
+        .arg(
+            Arg::new("header-read-timeout")
+                .env("DUFS_HEADER_READ_TIMEOUT")
+                .hide_env(true)
+                .long("header-read-timeout")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Drop connections that don't send complete request headers within the secs"),
+        )
+        .arg(
+            Arg::new("retry-after-base")
+                .env("DUFS_RETRY_AFTER_BASE")
+                .hide_env(true)
+                .long("retry-after-base")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Ask clients to retry 503 responses after the secs [default: 60]"),
+        )
+        .arg(
+            Arg::new("retry-after-jitter")
+                .env("DUFS_RETRY_AFTER_JITTER")
+                .hide_env(true)
+                .long("retry-after-jitter")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Add up to the secs at random to the Retry-After of 503 responses"),
+        )
+        .arg(
+            Arg::new("heavy-task-concurrency")
+                .env("DUFS_HEAVY_TASK_CONCURRENCY")
+                .hide_env(true)
+                .long("heavy-task-concurrency")
+                .value_parser(value_parser!(usize))
+                .value_name("num")
+                .help("Run at most num image conversions, checksums and archives at a time"),
+        )
+        .arg(
+            Arg::new("heavy-task-policy")
+                .env("DUFS_HEAVY_TASK_POLICY")
+                .hide_env(true)
+                .value_parser(clap::builder::EnumValueParser::<HeavyTaskPolicy>::new())
+                .long("heavy-task-policy")
+                .value_name("policy")
+                .help("Queue requests past --heavy-task-concurrency, or reject them with 503 [default: queue]"),
+        )
+        .arg(
+            Arg::new("max-header-size")
+                .env("DUFS_MAX_HEADER_SIZE")
+                .hide_env(true)
+                .long("max-header-size")
+                .value_name("size")
+                .help("Answer 431 to requests with headers larger than the size, at least 8K"),
+        )
+        .arg(
+            Arg::new("max-request-line-length")
+                .env("DUFS_MAX_REQUEST_LINE_LENGTH")
+                .hide_env(true)
+                .long("max-request-line-length")
+                .value_name("size")
+                .help("Answer 414 to requests with a URI longer than the size"),
+        )
+        .arg(
+            Arg::new("completions")
+                .long("completions")
+                .value_name("shell")
+                .value_parser(value_parser!(Shell))
+                .help("Print shell completion script for <shell>"),
         );
 
+    #[cfg(feature = "tls")]
+    let app = app
+        .arg(
+            Arg::new("tls-cert")
+                .env("DUFS_TLS_CERT")
+                .hide_env(true)
+                .long("tls-cert")
+                .value_name("path")
+                .value_parser(value_parser!(PathBuf))
+                .help("Path to an SSL/TLS certificate to serve with HTTPS"),
+        )
+        .arg(
+            Arg::new("tls-key")
+                .env("DUFS_TLS_KEY")
+                .hide_env(true)
+                .long("tls-key")
+                .value_name("path")
+                .value_parser(value_parser!(PathBuf))
+                .help("Path to the SSL/TLS certificate's private key"),
+        )
+        .arg(
+            Arg::new("tls-ocsp-file")
+                .env("DUFS_TLS_OCSP_FILE")
+                .hide_env(true)
+                .long("tls-ocsp-file")
+                .value_name("path")
+                .value_parser(value_parser!(PathBuf))
+                .help("Staple the DER-encoded OCSP response of a file to the certificate"),
+        )
+        .arg(
+            Arg::new("tls-ocsp-refresh")
+                .env("DUFS_TLS_OCSP_REFRESH")
+                .hide_env(true)
+                .long("tls-ocsp-refresh")
+                .value_parser(value_parser!(u64))
+                .value_name("secs")
+                .help("Read the --tls-ocsp-file again every secs"),
+        );
+
+    #[cfg(unix)]
+    let app = app
+        .arg(
+            Arg::new("create-serve-dir-mode")
+                .env("DUFS_CREATE_SERVE_DIR_MODE")
+                .hide_env(true)
+                .long("create-serve-dir-mode")
+                .value_name("mode")
+                .help("Set the permissions of folders made by --create-serve-dir [default: 755]"),
+        )
+        .arg(
+            Arg::new("chroot")
+                .env("DUFS_CHROOT")
+                .hide_env(true)
+                .long("chroot")
+                .action(ArgAction::SetTrue)
+                .help("Chroot into the served directory once listening"),
+        )
+        .arg(
+            Arg::new("user")
+                .env("DUFS_USER")
+                .hide_env(true)
+                .long("user")
+                .value_name("user")
+                .help("Switch to the user once listening, by name or uid"),
+        )
+        .arg(
+            Arg::new("group")
+                .env("DUFS_GROUP")
+                .hide_env(true)
+                .long("group")
+                .value_name("group")
+                .help(
+                    "Switch to the group once listening, defaults to the primary group of --user",
+                ),
+        );
+
+    #[cfg(feature = "embed")]
+    let app = app.arg(
+        Arg::new("serve-embedded")
+            .env("DUFS_SERVE_EMBEDDED")
+            .hide_env(true)
+            .long("serve-embedded")
+            .action(ArgAction::SetTrue)
+            .help("Serve the directory embedded at compile time, read-only"),
+    );
+
+    #[cfg(feature = "image")]
+    let app = app.arg(
+        Arg::new("image-quality")
+            .env("DUFS_IMAGE_QUALITY")
+            .hide_env(true)
+            .long("image-quality")
+            .value_parser(value_parser!(u8).range(1..=100))
+            .value_name("quality")
+            .help("Set the encoder quality of ?format=avif image conversion [default: 80]"),
+    );
+
+    #[cfg(feature = "heic")]
+    let app = app
+        .arg(
+            Arg::new("convert-heic")
+                .env("DUFS_CONVERT_HEIC")
+                .hide_env(true)
+                .long("convert-heic")
+                .action(ArgAction::SetTrue)
+                .help("Store uploaded .heic/.heif images as JPEG"),
+        )
+        .arg(
+            Arg::new("keep-heic")
+                .env("DUFS_KEEP_HEIC")
+                .hide_env(true)
+                .long("keep-heic")
+                .action(ArgAction::SetTrue)
+                .help("Keep the original next to the JPEG of --convert-heic"),
+        );
+
+    #[cfg(feature = "encrypt")]
+    let app = app
+        .arg(
+            Arg::new("encrypt-at-rest")
+                .env("DUFS_ENCRYPT_AT_REST")
+                .hide_env(true)
+                .long("encrypt-at-rest")
+                .action(ArgAction::SetTrue)
+                .help("Store uploaded content encrypted, decrypting it on download"),
+        )
+        .arg(
+            Arg::new("encryption-key-file")
+                .env("DUFS_ENCRYPTION_KEY_FILE")
+                .hide_env(true)
+                .long("encryption-key-file")
+                .value_parser(value_parser!(PathBuf))
+                .value_name("path")
+                .help("Read the key of --encrypt-at-rest from a file of 32 bytes or 64 hex digits"),
+        );
+
+    #[cfg(feature = "compression-dictionary")]
+    let app = app.arg(
+        Arg::new("compression-dictionary")
+            .env("DUFS_COMPRESSION_DICTIONARY")
+            .hide_env(true)
+            .long("compression-dictionary")
+            .value_parser(value_parser!(PathBuf))
+            .value_name("path")
+            .help("Serve a dictionary JSON listings are compressed against with dcb/dcz when clients hold it"),
+    );
+
+    #[cfg(feature = "reverse-proxy")]
+    let app = app.arg(
+        Arg::new("proxy")
+            .env("DUFS_PROXY")
+            .hide_env(true)
+            .long("proxy")
+            .action(ArgAction::Append)
+            .value_name("path=url")
+            .help("Forward the requests below the path to an HTTP upstream, e.g. '/api=http://127.0.0.1:8080'"),
+    )
+    .arg(
+        Arg::new("proxy-pass-credentials")
+            .env("DUFS_PROXY_PASS_CREDENTIALS")
+            .hide_env(true)
+            .long("proxy-pass-credentials")
+            .action(ArgAction::SetTrue)
+            .help("Forward the Authorization header and dufs cookies of --proxy requests upstream"),
+    );
+
     app
 }
 
@@ -258,6 +1156,10 @@ pub struct Args {
     #[serde(default = "default_serve_path")]
     #[default(default_serve_path())]
     pub serve_path: PathBuf,
+    pub serve_embedded: bool,
+    pub create_serve_dir: bool,
+    #[serde(deserialize_with = "deserialize_mode")]
+    pub create_serve_dir_mode: Option<u32>,
     #[serde(deserialize_with = "deserialize_bind_addrs")]
     #[serde(rename = "bind")]
     #[serde(default = "default_addrs")]
@@ -273,26 +1175,187 @@ pub struct Args {
     pub uri_prefix: String,
     #[serde(deserialize_with = "deserialize_string_or_vec")]
     pub hidden: Vec<String>,
+    pub hidden_deny_write: bool,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub protect: Vec<String>,
+    pub serve_hidden_with_auth: bool,
+    pub expose_well_known: bool,
+    pub well_known_no_auth: bool,
+    pub deny_dotfiles: bool,
     #[serde(deserialize_with = "deserialize_access_control")]
     pub auth: AccessControl,
+    pub auth_session: bool,
+    pub session_secret: Option<String>,
+    #[serde(default = "default_session_ttl")]
+    #[default(default_session_ttl())]
+    pub session_ttl: u64,
     pub allow_all: bool,
     pub allow_upload: bool,
     pub allow_delete: bool,
+    pub no_recursive_delete: bool,
     pub allow_search: bool,
     pub allow_symlink: bool,
+    pub symlink_policy: SymlinkPolicy,
     pub allow_archive: bool,
+    pub no_overwrite: bool,
     pub render_index: bool,
     pub render_spa: bool,
+    pub negotiate_language: bool,
+    #[serde(default = "default_language")]
+    #[default(default_language())]
+    pub default_language: String,
+    pub language_variant: LanguageVariant,
     pub render_try_index: bool,
+    pub root_redirect: Option<String>,
+    pub root_redirect_permanent: bool,
+    pub no_redirect_slash: bool,
+    pub join_parts: bool,
+    pub gunzip: bool,
+    pub default_sort_dirs: SortDirs,
+    pub listing_limit: Option<usize>,
+    pub listing_preview_bytes: Option<usize>,
+    pub listing_checksums: bool,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub listing_compress_threshold: Option<u64>,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub listing_columns: Vec<String>,
+    pub time_format: Option<String>,
+    #[serde(deserialize_with = "deserialize_time_zone")]
+    pub time_zone: Option<Zone>,
     pub enable_cors: bool,
+    pub disable_webdav: bool,
+    pub no_default_robots: bool,
+    pub no_ui: bool,
+    #[serde(deserialize_with = "deserialize_trusted_proxies")]
+    pub trusted_proxies: Vec<IpCidr>,
     pub assets: Option<PathBuf>,
+    pub template_vars: BTreeMap<String, String>,
+    pub banner: Option<String>,
+    pub mime: BTreeMap<String, String>,
+    pub mime_file: Option<PathBuf>,
+    pub rewrites: Option<PathBuf>,
+    #[serde(skip)]
+    pub rewrite_rules: Vec<RewriteRule>,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub inline_types: Vec<String>,
+    #[serde(
+        default = "default_attachment_types",
+        deserialize_with = "deserialize_string_or_vec"
+    )]
+    #[default(default_attachment_types())]
+    pub attachment_types: Vec<String>,
     #[serde(deserialize_with = "deserialize_log_http")]
     #[serde(rename = "log-format")]
     pub http_logger: HttpLogger,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub log_ignore: Vec<String>,
     pub log_file: Option<PathBuf>,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub log_max_size: Option<u64>,
+    pub log_max_files: Option<usize>,
+    pub log_compress: bool,
+    pub debug_bodies: bool,
     pub compress: Compress,
+    pub archive_strip_components: usize,
+    pub archive_keep_shallow: bool,
+    pub gzip: bool,
+    pub zero_copy: bool,
+    pub coalesce_downloads: bool,
+    pub no_cache: bool,
+    pub no_streaming_cache: bool,
+    pub etag: EtagMode,
+    #[serde(
+        default = "default_block_size",
+        deserialize_with = "deserialize_block_size"
+    )]
+    #[default(default_block_size())]
+    pub block_size: u64,
+    #[serde(
+        default = "default_read_buffer_size",
+        deserialize_with = "deserialize_read_buffer_size"
+    )]
+    #[default(default_read_buffer_size())]
+    pub read_buffer_size: u64,
+    pub stat_cache_ttl: Option<u64>,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_upload_size: Option<u64>,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub upload_allow_ext: Vec<String>,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub upload_deny_ext: Vec<String>,
+    pub upload_allow_no_ext: bool,
+    pub verify_content_type: Option<VerifyContentType>,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub verify_content_type_allow: Vec<String>,
+    pub temp_dir: Option<PathBuf>,
+    pub clamav_socket: Option<String>,
+    #[serde(default = "default_clamav_timeout")]
+    #[default(default_clamav_timeout())]
+    pub clamav_timeout: u64,
+    pub on_upload: Option<String>,
+    pub hook_shell: bool,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<String>,
+    #[serde(skip)]
+    pub webhook: Option<WebhookTarget>,
+    pub pipe: BTreeMap<String, String>,
+    #[serde(skip)]
+    pub upload_pipes: BTreeMap<String, UploadPipe>,
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    pub ingest: Vec<String>,
+    #[serde(default = "default_ingest_layout")]
+    #[default(default_ingest_layout())]
+    pub ingest_layout: String,
+    #[serde(default = "default_pipe_timeout")]
+    #[default(default_pipe_timeout())]
+    pub pipe_timeout: u64,
+    pub idempotency_ttl: Option<u64>,
+    #[serde(skip)]
+    pub upload_hook: Option<UploadHook>,
+    #[serde(deserialize_with = "deserialize_rate")]
+    pub download_rate: Option<Rate>,
+    #[serde(deserialize_with = "deserialize_rate")]
+    pub upload_rate: Option<Rate>,
+    #[serde(deserialize_with = "deserialize_rate")]
+    pub global_rate: Option<Rate>,
+    pub keep_alive_timeout: Option<u64>,
+    pub header_read_timeout: Option<u64>,
+    #[serde(default = "default_retry_after_base")]
+    #[default(default_retry_after_base())]
+    pub retry_after_base: u64,
+    pub retry_after_jitter: u64,
+    pub heavy_task_concurrency: Option<usize>,
+    pub heavy_task_policy: HeavyTaskPolicy,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_header_size: Option<u64>,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_request_line_length: Option<u64>,
     pub tls_cert: Option<PathBuf>,
     pub tls_key: Option<PathBuf>,
+    pub tls_ocsp_file: Option<PathBuf>,
+    pub tls_ocsp_refresh: Option<u64>,
+    pub chroot: bool,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    #[serde(default = "default_image_quality")]
+    #[default(default_image_quality())]
+    pub image_quality: u8,
+    pub convert_heic: bool,
+    pub keep_heic: bool,
+    pub encrypt_at_rest: bool,
+    pub encryption_key_file: Option<PathBuf>,
+    #[cfg(feature = "encrypt")]
+    #[serde(skip)]
+    pub encryption_key: Option<EncryptionKey>,
+    pub compression_dictionary: Option<PathBuf>,
+    #[cfg(feature = "compression-dictionary")]
+    #[serde(skip)]
+    pub dictionary: Option<CompressionDictionary>,
+    pub proxy: BTreeMap<String, String>,
+    pub proxy_pass_credentials: bool,
+    #[cfg(feature = "reverse-proxy")]
+    #[serde(skip)]
+    pub upstreams: Vec<Upstream>,
 }
 
 impl Args {
@@ -314,6 +1377,39 @@ impl Args {
             args.serve_path.clone_from(path)
         }
 
+        #[cfg(feature = "embed")]
+        {
+            if !args.serve_embedded {
+                args.serve_embedded = matches.get_flag("serve-embedded");
+            }
+            if args.serve_embedded {
+                args.serve_path = crate::embedded::extract()?;
+            }
+        }
+        #[cfg(not(feature = "embed"))]
+        {
+            args.serve_embedded = false;
+        }
+
+        if !args.create_serve_dir {
+            args.create_serve_dir = matches.get_flag("create-serve-dir");
+        }
+        #[cfg(unix)]
+        if let Some(mode) = matches.get_one::<String>("create-serve-dir-mode") {
+            args.create_serve_dir_mode = Some(
+                parse_mode(mode)
+                    .ok_or_else(|| anyhow!("Invalid create-serve-dir-mode `{mode}`"))?,
+            );
+        }
+        if !args.serve_path.exists() {
+            if !args.create_serve_dir {
+                bail!(
+                    "Path `{}` doesn't exist, use --create-serve-dir to create it",
+                    args.serve_path.display()
+                );
+            }
+            create_serve_dir(&args.serve_path, args.create_serve_dir_mode)?;
+        }
         args.serve_path = Self::sanitize_path(args.serve_path)?;
 
         if let Some(port) = matches.get_one::<u16>("port") {
@@ -348,66 +1444,529 @@ impl Args {
                 .collect();
         }
 
-        if !args.enable_cors {
-            args.enable_cors = matches.get_flag("enable-cors");
+        if !args.hidden_deny_write {
+            args.hidden_deny_write = matches.get_flag("hidden-deny-write");
+        }
+
+        if let Some(temp_dir) = matches.get_one::<PathBuf>("temp-dir") {
+            args.temp_dir = Some(temp_dir.clone());
+        }
+        if let Some(temp_dir) = args.temp_dir.take() {
+            std::fs::create_dir_all(&temp_dir)
+                .with_context(|| format!("Failed to create temp dir `{}`", temp_dir.display()))?;
+            let temp_dir = Self::sanitize_path(temp_dir)?;
+            // Uploads in progress never show up in listings of the served folder.
+            if let Ok(relative_path) = temp_dir.strip_prefix(&args.serve_path) {
+                let relative_path = relative_path.to_string_lossy().replace('\\', "/");
+                if relative_path.is_empty() {
+                    bail!("Invalid temp dir, it must not be the served folder");
+                }
+                args.hidden.push(format!("/{relative_path}/"));
+            }
+            args.temp_dir = Some(temp_dir);
+        }
+        if let Some(protect) = matches.get_many::<String>("protect") {
+            args.protect = protect.cloned().collect();
+        }
+        if !args.serve_hidden_with_auth {
+            args.serve_hidden_with_auth = matches.get_flag("serve-hidden-with-auth");
+        }
+
+        if !args.expose_well_known {
+            args.expose_well_known = matches.get_flag("expose-well-known");
+        }
+
+        if !args.well_known_no_auth {
+            args.well_known_no_auth = matches.get_flag("well-known-no-auth");
+        }
+
+        if !args.deny_dotfiles {
+            args.deny_dotfiles = matches.get_flag("deny-dotfiles");
+        }
+
+        if !args.enable_cors {
+            args.enable_cors = matches.get_flag("enable-cors");
+        }
+
+        if let Some(trusted_proxies) = matches.get_many::<String>("trusted-proxies") {
+            args.trusted_proxies = trusted_proxies.map(|v| v.parse()).collect::<Result<_>>()?;
+        }
+
+        if !args.disable_webdav {
+            args.disable_webdav = matches.get_flag("disable-webdav");
+        }
+
+        if !args.no_default_robots {
+            args.no_default_robots = matches.get_flag("no-default-robots");
+        }
+
+        if !args.no_ui {
+            args.no_ui = matches.get_flag("no-ui");
+        }
+
+        if let Some(rules) = matches.get_many::<String>("auth") {
+            let rules: Vec<_> = rules.map(|v| v.as_str()).collect();
+            args.auth = AccessControl::new(&rules)?;
+        }
+
+        if !args.auth_session {
+            args.auth_session = matches.get_flag("auth-session");
+        }
+        if let Some(secret) = matches.get_one::<String>("session-secret") {
+            args.session_secret = Some(secret.clone());
+        }
+        if args.auth_session && args.session_secret.is_none() {
+            args.session_secret = Some(format!("{}{}", Uuid::new_v4(), Uuid::new_v4()));
+        }
+        if let Some(secs) = matches.get_one::<u64>("session-ttl") {
+            args.session_ttl = *secs;
+        }
+
+        if !args.allow_all {
+            args.allow_all = matches.get_flag("allow-all");
+        }
+
+        let allow_all = args.allow_all;
+
+        if !args.allow_upload {
+            args.allow_upload = allow_all || matches.get_flag("allow-upload");
+        }
+        if !args.allow_delete {
+            args.allow_delete = allow_all || matches.get_flag("allow-delete");
+        }
+        if !args.no_recursive_delete {
+            args.no_recursive_delete = matches.get_flag("no-recursive-delete");
+        }
+        if args.serve_embedded {
+            args.allow_upload = false;
+            args.allow_delete = false;
+        }
+        if !args.allow_search {
+            args.allow_search = allow_all || matches.get_flag("allow-search");
+        }
+        if !args.allow_symlink {
+            args.allow_symlink = allow_all || matches.get_flag("allow-symlink");
+        }
+        if let Some(policy) = matches.get_one::<SymlinkPolicy>("symlink-policy") {
+            args.symlink_policy = *policy;
+        }
+        if !args.allow_archive {
+            args.allow_archive = allow_all || matches.get_flag("allow-archive");
+        }
+        if !args.no_overwrite {
+            args.no_overwrite = matches.get_flag("no-overwrite");
+        }
+        if !args.render_index {
+            args.render_index = matches.get_flag("render-index");
+        }
+
+        if !args.render_try_index {
+            args.render_try_index = matches.get_flag("render-try-index");
+        }
+
+        if !args.render_spa {
+            args.render_spa = matches.get_flag("render-spa");
+        }
+
+        if !args.negotiate_language {
+            args.negotiate_language = matches.get_flag("negotiate-language");
+        }
+        if let Some(lang) = matches.get_one::<String>("default-language") {
+            args.default_language = lang.clone();
+        }
+        if !is_language_tag(&args.default_language) {
+            bail!("Invalid default-language `{}`", args.default_language);
+        }
+        args.default_language = args.default_language.to_lowercase();
+        if let Some(variant) = matches.get_one::<LanguageVariant>("language-variant") {
+            args.language_variant = *variant;
+        }
+
+        if let Some(root_redirect) = matches.get_one::<String>("root-redirect") {
+            args.root_redirect = Some(root_redirect.clone());
+        }
+
+        if !args.root_redirect_permanent {
+            args.root_redirect_permanent = matches.get_flag("root-redirect-permanent");
+        }
+        if !args.no_redirect_slash {
+            args.no_redirect_slash = matches.get_flag("no-redirect-slash");
+        }
+
+        if !args.join_parts {
+            args.join_parts = matches.get_flag("join-parts");
+        }
+
+        if !args.gunzip {
+            args.gunzip = matches.get_flag("gunzip");
+        }
+
+        if let Some(sort_dirs) = matches.get_one::<SortDirs>("default-sort-dirs") {
+            args.default_sort_dirs = *sort_dirs;
+        }
+
+        if let Some(listing_limit) = matches.get_one::<usize>("listing-limit") {
+            args.listing_limit = Some(*listing_limit);
+        }
+
+        if let Some(preview_bytes) = matches.get_one::<usize>("listing-preview-bytes") {
+            args.listing_preview_bytes = Some(*preview_bytes);
+        }
+        if !args.listing_checksums {
+            args.listing_checksums = matches.get_flag("listing-checksums");
+        }
+        if let Some(threshold) = matches.get_one::<String>("listing-compress-threshold") {
+            args.listing_compress_threshold = Some(
+                parse_size(threshold)
+                    .ok_or_else(|| anyhow!("Invalid listing-compress-threshold `{threshold}`"))?,
+            );
+        }
+        if let Some(columns) = matches.get_many::<String>("listing-columns") {
+            args.listing_columns = columns.cloned().collect();
+        }
+        args.listing_columns = args
+            .listing_columns
+            .iter()
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim().to_lowercase())
+            .collect();
+        if let Some(column) = args
+            .listing_columns
+            .iter()
+            .find(|v| !LISTING_COLUMNS.contains(&v.as_str()))
+        {
+            bail!("Invalid listing column `{column}`, expected one of name, size, mtime, mode");
+        }
+        if !args.listing_columns.is_empty() && !args.listing_columns.iter().any(|v| v == "name") {
+            bail!("Invalid listing columns, they must include name");
+        }
+
+        if let Some(time_format) = matches.get_one::<String>("time-format") {
+            args.time_format = Some(time_format.clone());
+        }
+        if let Some(time_format) = args.time_format.as_ref() {
+            validate_time_format(time_format)?;
+        }
+
+        if let Some(time_zone) = matches.get_one::<String>("time-zone") {
+            args.time_zone = Some(time_zone.parse()?);
+        }
+
+        if let Some(assets_path) = matches.get_one::<PathBuf>("assets") {
+            args.assets = Some(assets_path.clone());
+        }
+
+        if let Some(assets_path) = &args.assets {
+            args.assets = Some(Args::sanitize_assets_path(assets_path)?);
+        }
+
+        if let Some(template_vars) = matches.get_many::<String>("template-var") {
+            args.template_vars = template_vars
+                .map(|v| {
+                    v.split_once('=')
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .ok_or_else(|| anyhow!("Invalid template var `{v}`, expected name=value"))
+                })
+                .collect::<Result<_>>()?;
+        }
+        if let Some(banner) = matches.get_one::<String>("banner") {
+            args.banner = Some(banner.clone());
+        }
+        if let Some(path) = args.banner.as_ref().and_then(|v| v.strip_prefix('@')) {
+            let banner = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read banner `{path}`"))?;
+            args.banner = Some(banner.trim_end().to_string());
+        }
+        if let Some(mime) = matches.get_many::<String>("mime") {
+            args.mime = mime
+                .map(|v| {
+                    v.split_once('=')
+                        .map(|(ext, mime)| (ext.to_string(), mime.to_string()))
+                        .ok_or_else(|| anyhow!("Invalid mime `{v}`, expected ext=type"))
+                })
+                .collect::<Result<_>>()?;
+        }
+        if let Some(mime_file) = matches.get_one::<PathBuf>("mime-file") {
+            args.mime_file = Some(mime_file.clone());
+        }
+        let normalize_ext = |ext: &str| ext.trim_start_matches('.').to_lowercase();
+        args.mime = std::mem::take(&mut args.mime)
+            .into_iter()
+            .map(|(ext, mime)| (normalize_ext(&ext), mime))
+            .collect();
+        if let Some(mime_file) = &args.mime_file {
+            let contents = std::fs::read_to_string(mime_file)
+                .with_context(|| format!("Failed to read mime file `{}`", mime_file.display()))?;
+            for (ext, mime) in parse_mime_types(&contents) {
+                args.mime.entry(normalize_ext(&ext)).or_insert(mime);
+            }
+        }
+
+        if let Some(rewrites) = matches.get_one::<PathBuf>("rewrites") {
+            args.rewrites = Some(rewrites.clone());
+        }
+        if let Some(rewrites) = &args.rewrites {
+            let contents = std::fs::read_to_string(rewrites)
+                .with_context(|| format!("Failed to read rewrites `{}`", rewrites.display()))?;
+            args.rewrite_rules = parse_rewrites(&contents)?;
+        }
+
+        if let Some(inline_types) = matches.get_many::<String>("inline-types") {
+            args.inline_types = inline_types.cloned().collect();
+        }
+        if let Some(attachment_types) = matches.get_many::<String>("attachment-types") {
+            args.attachment_types = attachment_types.cloned().collect();
+        }
+
+        for name in args.template_vars.keys() {
+            let valid = !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !matches!(name.to_uppercase().as_str(), "ASSETS_PREFIX" | "INDEX_DATA");
+            if !valid {
+                bail!("Invalid template var name `{name}`");
+            }
+        }
+
+        if let Some(log_format) = matches.get_one::<String>("log-format") {
+            args.http_logger = log_format.parse()?;
+        }
+
+        if let Some(log_ignore) = matches.get_many::<String>("log-ignore") {
+            args.log_ignore = log_ignore.cloned().collect();
+        }
+
+        if let Some(log_file) = matches.get_one::<PathBuf>("log-file") {
+            args.log_file = Some(log_file.clone());
+        }
+
+        if let Some(log_max_size) = matches.get_one::<String>("log-max-size") {
+            args.log_max_size = Some(
+                parse_size(log_max_size)
+                    .ok_or_else(|| anyhow!("Invalid log-max-size `{log_max_size}`"))?,
+            );
+        }
+
+        if let Some(log_max_files) = matches.get_one::<usize>("log-max-files") {
+            args.log_max_files = Some(*log_max_files);
+        }
+
+        if !args.log_compress {
+            args.log_compress = matches.get_flag("log-compress");
+        }
+
+        if !args.debug_bodies {
+            args.debug_bodies = matches.get_flag("debug-bodies");
+        }
+
+        if let Some(compress) = matches.get_one::<Compress>("compress") {
+            args.compress = *compress;
+        }
+
+        if let Some(strip) = matches.get_one::<usize>("archive-strip-components") {
+            args.archive_strip_components = *strip;
+        }
+
+        if !args.archive_keep_shallow {
+            args.archive_keep_shallow = matches.get_flag("archive-keep-shallow");
+        }
+
+        if !args.gzip {
+            args.gzip = matches.get_flag("gzip");
+        }
+
+        if !args.zero_copy {
+            args.zero_copy = matches.get_flag("zero-copy");
+        }
+        if !args.coalesce_downloads {
+            args.coalesce_downloads = matches.get_flag("coalesce-downloads");
+        }
+        if !args.no_cache {
+            args.no_cache = matches.get_flag("no-cache");
+        }
+        if !args.no_streaming_cache {
+            args.no_streaming_cache = matches.get_flag("no-streaming-cache");
+        }
+        if let Some(etag) = matches.get_one::<EtagMode>("etag") {
+            args.etag = *etag;
+        }
+
+        if let Some(block_size) = matches.get_one::<String>("block-size") {
+            args.block_size = parse_size(block_size)
+                .filter(|v| *v > 0)
+                .ok_or_else(|| anyhow!("Invalid block-size `{block_size}`"))?;
+        }
+
+        if let Some(read_buffer_size) = matches.get_one::<String>("read-buffer-size") {
+            args.read_buffer_size = parse_size(read_buffer_size)
+                .ok_or_else(|| anyhow!("Invalid read-buffer-size `{read_buffer_size}`"))?;
+        }
+        if !READ_BUFFER_SIZES.contains(&args.read_buffer_size) {
+            bail!("The read-buffer-size must be between 4K and 16M");
+        }
+
+        if let Some(secs) = matches.get_one::<u64>("stat-cache-ttl") {
+            args.stat_cache_ttl = Some(*secs);
+        }
+
+        if let Some(max_upload_size) = matches.get_one::<String>("max-upload-size") {
+            args.max_upload_size = Some(
+                parse_size(max_upload_size)
+                    .ok_or_else(|| anyhow!("Invalid max-upload-size `{max_upload_size}`"))?,
+            );
+        }
+
+        if let Some(exts) = matches.get_many::<String>("upload-allow-ext") {
+            args.upload_allow_ext = exts.cloned().collect();
+        }
+        if let Some(exts) = matches.get_many::<String>("upload-deny-ext") {
+            args.upload_deny_ext = exts.cloned().collect();
+        }
+        let normalize_exts = |exts: Vec<String>| -> Vec<String> {
+            exts.iter()
+                .flat_map(|v| v.split(','))
+                .map(|v| v.trim().trim_start_matches('.').to_lowercase())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+        args.upload_allow_ext = normalize_exts(std::mem::take(&mut args.upload_allow_ext));
+        args.upload_deny_ext = normalize_exts(std::mem::take(&mut args.upload_deny_ext));
+        if !args.upload_allow_no_ext {
+            args.upload_allow_no_ext = matches.get_flag("upload-allow-no-ext");
+        }
+        if let Some(mode) = matches.get_one::<VerifyContentType>("verify-content-type") {
+            args.verify_content_type = Some(*mode);
+        }
+        if let Some(pairs) = matches.get_many::<String>("verify-content-type-allow") {
+            args.verify_content_type_allow = pairs.cloned().collect();
+        }
+        for pair in args.verify_content_type_allow.iter_mut() {
+            if !pair.contains('=') {
+                bail!("Invalid verify-content-type-allow `{pair}`, expected expected=sniffed");
+            }
+            *pair = pair.trim().to_lowercase();
+        }
+
+        if let Some(clamav_socket) = matches.get_one::<String>("clamav-socket") {
+            args.clamav_socket = Some(clamav_socket.clone());
+        }
+
+        if let Some(clamav_timeout) = matches.get_one::<u64>("clamav-timeout") {
+            args.clamav_timeout = *clamav_timeout;
+        }
+
+        if let Some(on_upload) = matches.get_one::<String>("on-upload") {
+            args.on_upload = Some(on_upload.clone());
         }
 
-        if let Some(rules) = matches.get_many::<String>("auth") {
-            let rules: Vec<_> = rules.map(|v| v.as_str()).collect();
-            args.auth = AccessControl::new(&rules)?;
+        if !args.hook_shell {
+            args.hook_shell = matches.get_flag("hook-shell");
         }
 
-        if !args.allow_all {
-            args.allow_all = matches.get_flag("allow-all");
+        if let Some(on_upload) = &args.on_upload {
+            args.upload_hook = Some(UploadHook::new(on_upload, args.hook_shell)?);
         }
 
-        let allow_all = args.allow_all;
+        if let Some(webhook_url) = matches.get_one::<String>("webhook-url") {
+            args.webhook_url = Some(webhook_url.clone());
+        }
+        if let Some(webhook_secret) = matches.get_one::<String>("webhook-secret") {
+            args.webhook_secret = Some(webhook_secret.clone());
+        }
+        if let Some(webhook_url) = &args.webhook_url {
+            args.webhook = Some(WebhookTarget::new(
+                webhook_url,
+                args.webhook_secret.clone(),
+            )?);
+        }
 
-        if !args.allow_upload {
-            args.allow_upload = allow_all || matches.get_flag("allow-upload");
+        if let Some(pipe) = matches.get_many::<String>("pipe") {
+            args.pipe = pipe
+                .map(|v| {
+                    v.split_once('=')
+                        .map(|(path, command)| (path.to_string(), command.to_string()))
+                        .ok_or_else(|| anyhow!("Invalid pipe `{v}`, expected path=command"))
+                })
+                .collect::<Result<_>>()?;
         }
-        if !args.allow_delete {
-            args.allow_delete = allow_all || matches.get_flag("allow-delete");
+        args.upload_pipes = args
+            .pipe
+            .iter()
+            .map(|(path, command)| UploadPipe::parse_mapping(&format!("{path}={command}")))
+            .collect::<Result<_>>()?;
+        if let Some(ingest) = matches.get_many::<String>("ingest") {
+            args.ingest = ingest.cloned().collect();
         }
-        if !args.allow_search {
-            args.allow_search = allow_all || matches.get_flag("allow-search");
+        for path in args.ingest.iter_mut() {
+            *path = path.trim_matches('/').to_string();
+            if path.is_empty() {
+                bail!("Invalid ingest, the path must not be the root");
+            }
         }
-        if !args.allow_symlink {
-            args.allow_symlink = allow_all || matches.get_flag("allow-symlink");
+        if let Some(layout) = matches.get_one::<String>("ingest-layout") {
+            args.ingest_layout = layout.clone();
         }
-        if !args.allow_archive {
-            args.allow_archive = allow_all || matches.get_flag("allow-archive");
+        validate_time_format(&args.ingest_layout)?;
+
+        if let Some(pipe_timeout) = matches.get_one::<u64>("pipe-timeout") {
+            args.pipe_timeout = *pipe_timeout;
         }
-        if !args.render_index {
-            args.render_index = matches.get_flag("render-index");
+
+        if let Some(secs) = matches.get_one::<u64>("idempotency-ttl") {
+            args.idempotency_ttl = Some(*secs);
         }
 
-        if !args.render_try_index {
-            args.render_try_index = matches.get_flag("render-try-index");
+        if let Some(download_rate) = matches.get_one::<String>("download-rate") {
+            args.download_rate = Some(download_rate.parse()?);
         }
 
-        if !args.render_spa {
-            args.render_spa = matches.get_flag("render-spa");
+        if let Some(upload_rate) = matches.get_one::<String>("upload-rate") {
+            args.upload_rate = Some(upload_rate.parse()?);
         }
 
-        if let Some(assets_path) = matches.get_one::<PathBuf>("assets") {
-            args.assets = Some(assets_path.clone());
+        if let Some(global_rate) = matches.get_one::<String>("global-rate") {
+            args.global_rate = Some(global_rate.parse()?);
         }
 
-        if let Some(assets_path) = &args.assets {
-            args.assets = Some(Args::sanitize_assets_path(assets_path)?);
+        if let Some(keep_alive_timeout) = matches.get_one::<u64>("keep-alive-timeout") {
+            args.keep_alive_timeout = Some(*keep_alive_timeout);
         }
 
-        if let Some(log_format) = matches.get_one::<String>("log-format") {
-            args.http_logger = log_format.parse()?;
+        if let Some(header_read_timeout) = matches.get_one::<u64>("header-read-timeout") {
+            args.header_read_timeout = Some(*header_read_timeout);
         }
 
-        if let Some(log_file) = matches.get_one::<PathBuf>("log-file") {
-            args.log_file = Some(log_file.clone());
+        if let Some(secs) = matches.get_one::<u64>("retry-after-base") {
+            args.retry_after_base = *secs;
+        }
+        if let Some(secs) = matches.get_one::<u64>("retry-after-jitter") {
+            args.retry_after_jitter = *secs;
         }
 
-        if let Some(compress) = matches.get_one::<Compress>("compress") {
-            args.compress = *compress;
+        if let Some(num) = matches.get_one::<usize>("heavy-task-concurrency") {
+            args.heavy_task_concurrency = Some(*num);
+        }
+        if args.heavy_task_concurrency == Some(0) {
+            bail!("The heavy-task-concurrency must be at least 1");
+        }
+        if let Some(policy) = matches.get_one::<HeavyTaskPolicy>("heavy-task-policy") {
+            args.heavy_task_policy = *policy;
+        }
+
+        if let Some(max_header_size) = matches.get_one::<String>("max-header-size") {
+            args.max_header_size = Some(
+                parse_size(max_header_size)
+                    .ok_or_else(|| anyhow!("Invalid max-header-size `{max_header_size}`"))?,
+            );
+        }
+        if matches!(args.max_header_size, Some(v) if v < MIN_MAX_HEADER_SIZE) {
+            bail!("The max-header-size must be at least {MIN_MAX_HEADER_SIZE} bytes");
+        }
+
+        if let Some(length) = matches.get_one::<String>("max-request-line-length") {
+            args.max_request_line_length = Some(
+                parse_size(length)
+                    .ok_or_else(|| anyhow!("Invalid max-request-line-length `{length}`"))?,
+            );
         }
 
         #[cfg(feature = "tls")]
@@ -426,11 +1985,104 @@ impl Args {
                 (_, Some(_)) => bail!("No tls-cert set"),
                 (None, None) => {}
             }
+
+            if let Some(tls_ocsp_file) = matches.get_one::<PathBuf>("tls-ocsp-file") {
+                args.tls_ocsp_file = Some(tls_ocsp_file.clone())
+            }
+            if let Some(tls_ocsp_refresh) = matches.get_one::<u64>("tls-ocsp-refresh") {
+                args.tls_ocsp_refresh = Some(*tls_ocsp_refresh)
+            }
+            if args.tls_ocsp_file.is_some() && args.tls_cert.is_none() {
+                bail!("No tls-cert set for the tls-ocsp-file");
+            }
+            if args.tls_ocsp_refresh.is_some() && args.tls_ocsp_file.is_none() {
+                bail!("No tls-ocsp-file set to refresh");
+            }
+            if args.tls_ocsp_refresh == Some(0) {
+                bail!("The tls-ocsp-refresh must be at least 1 second");
+            }
         }
         #[cfg(not(feature = "tls"))]
         {
             args.tls_cert = None;
             args.tls_key = None;
+            args.tls_ocsp_file = None;
+            args.tls_ocsp_refresh = None;
+        }
+
+        #[cfg(unix)]
+        {
+            if !args.chroot {
+                args.chroot = matches.get_flag("chroot");
+            }
+            if let Some(user) = matches.get_one::<String>("user") {
+                args.user = Some(user.clone());
+            }
+            if let Some(group) = matches.get_one::<String>("group") {
+                args.group = Some(group.clone());
+            }
+        }
+
+        #[cfg(feature = "image")]
+        if let Some(image_quality) = matches.get_one::<u8>("image-quality") {
+            args.image_quality = *image_quality;
+        }
+
+        #[cfg(feature = "heic")]
+        {
+            if !args.convert_heic {
+                args.convert_heic = matches.get_flag("convert-heic");
+            }
+            if !args.keep_heic {
+                args.keep_heic = matches.get_flag("keep-heic");
+            }
+        }
+
+        #[cfg(feature = "encrypt")]
+        {
+            if !args.encrypt_at_rest {
+                args.encrypt_at_rest = matches.get_flag("encrypt-at-rest");
+            }
+            if let Some(path) = matches.get_one::<PathBuf>("encryption-key-file") {
+                args.encryption_key_file = Some(path.clone());
+            }
+            if args.encrypt_at_rest {
+                let Some(path) = args.encryption_key_file.as_ref() else {
+                    bail!("The encrypt-at-rest requires an encryption-key-file");
+                };
+                args.encryption_key = Some(EncryptionKey::load(path)?);
+            }
+        }
+
+        #[cfg(feature = "compression-dictionary")]
+        {
+            if let Some(path) = matches.get_one::<PathBuf>("compression-dictionary") {
+                args.compression_dictionary = Some(path.clone());
+            }
+            if let Some(path) = args.compression_dictionary.as_ref() {
+                args.dictionary = Some(CompressionDictionary::load(path)?);
+            }
+        }
+
+        #[cfg(feature = "reverse-proxy")]
+        {
+            if let Some(proxy) = matches.get_many::<String>("proxy") {
+                args.proxy = proxy
+                    .map(|v| {
+                        v.split_once('=')
+                            .map(|(path, url)| (path.to_string(), url.to_string()))
+                            .ok_or_else(|| anyhow!("Invalid proxy `{v}`, expected path=url"))
+                    })
+                    .collect::<Result<_>>()?;
+            }
+            if !args.proxy_pass_credentials {
+                args.proxy_pass_credentials = matches.get_flag("proxy-pass-credentials");
+            }
+            args.upstreams = args
+                .proxy
+                .iter()
+                .map(|(path, url)| Upstream::parse_mapping(&format!("{path}={url}")))
+                .collect::<Result<_>>()?;
         }
 
         Ok(args)
@@ -492,21 +2144,16 @@ impl BindAddr {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Compress {
     None,
+    #[default]
     Low,
     Medium,
     High,
 }
 
-impl Default for Compress {
-    fn default() -> Self {
-        Self::Low
-    }
-}
-
 impl ValueEnum for Compress {
     fn value_variants<'a>() -> &'a [Self] {
         &[Self::None, Self::Low, Self::Medium, Self::High]
@@ -533,6 +2180,175 @@ impl Compress {
     }
 }
 
+/// Where the language goes in the name of a variant of `page.html`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LanguageVariant {
+    /// `page.fr.html`
+    #[default]
+    Infix,
+    /// `page.html.fr`
+    Suffix,
+}
+
+impl ValueEnum for LanguageVariant {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Infix, Self::Suffix]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            LanguageVariant::Infix => PossibleValue::new("infix"),
+            LanguageVariant::Suffix => PossibleValue::new("suffix"),
+        })
+    }
+}
+
+impl LanguageVariant {
+    /// The file name of the `lang` variant of `name`.
+    pub fn file_name(self, name: &str, lang: &str) -> String {
+        match (self, name.rsplit_once('.')) {
+            (LanguageVariant::Infix, Some((stem, ext))) if !stem.is_empty() => {
+                format!("{stem}.{lang}.{ext}")
+            }
+            _ => format!("{name}.{lang}"),
+        }
+    }
+}
+
+/// A language tag like `fr` or `pt-BR`, safe to put into a file name.
+pub fn is_language_tag(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 35
+        && value
+            .split('-')
+            .all(|v| !v.is_empty() && v.len() <= 8 && v.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// How symlinks appear in listings, whether they can be followed is up to `--allow-symlink`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    Follow,
+    #[default]
+    Show,
+    Hide,
+}
+
+impl ValueEnum for SymlinkPolicy {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Follow, Self::Show, Self::Hide]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            SymlinkPolicy::Follow => PossibleValue::new("follow"),
+            SymlinkPolicy::Show => PossibleValue::new("show"),
+            SymlinkPolicy::Hide => PossibleValue::new("hide"),
+        })
+    }
+}
+
+/// What happens to expensive requests while --heavy-task-concurrency of them are running.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeavyTaskPolicy {
+    #[default]
+    Queue,
+    Reject,
+}
+
+impl ValueEnum for HeavyTaskPolicy {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Queue, Self::Reject]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            HeavyTaskPolicy::Queue => PossibleValue::new("queue"),
+            HeavyTaskPolicy::Reject => PossibleValue::new("reject"),
+        })
+    }
+}
+
+/// How closely `--verify-content-type` compares uploads with their declared types.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyContentType {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+impl ValueEnum for VerifyContentType {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Lenient, Self::Strict]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            VerifyContentType::Lenient => PossibleValue::new("lenient"),
+            VerifyContentType::Strict => PossibleValue::new("strict"),
+        })
+    }
+}
+
+/// Where folders go in sorted listings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirs {
+    #[default]
+    First,
+    Mixed,
+}
+
+impl SortDirs {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "first" => Some(Self::First),
+            "mixed" => Some(Self::Mixed),
+            _ => None,
+        }
+    }
+}
+
+impl ValueEnum for SortDirs {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::First, Self::Mixed]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            SortDirs::First => PossibleValue::new("first"),
+            SortDirs::Mixed => PossibleValue::new("mixed"),
+        })
+    }
+}
+
+/// How the ETag of a file is derived.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EtagMode {
+    /// From the mtime and size, cheap but changed by a touch.
+    #[default]
+    Mtime,
+    /// From the SHA-256 of the content, stable as long as the content is.
+    Strong,
+}
+
+impl ValueEnum for EtagMode {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Mtime, Self::Strong]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            EtagMode::Mtime => PossibleValue::new("mtime"),
+            EtagMode::Strong => PossibleValue::new("strong"),
+        })
+    }
+}
+
 fn deserialize_bind_addrs<'de, D>(deserializer: D) -> Result<Vec<BindAddr>, D::Error>
 where
     D: Deserializer<'de>,
@@ -597,6 +2413,24 @@ where
     deserializer.deserialize_any(StringOrVec)
 }
 
+fn deserialize_trusted_proxies<'de, D>(deserializer: D) -> Result<Vec<IpCidr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_string_or_vec(deserializer)?
+        .iter()
+        .map(|v| v.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn deserialize_time_zone<'de, D>(deserializer: D) -> Result<Option<Zone>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
 fn deserialize_access_control<'de, D>(deserializer: D) -> Result<AccessControl, D::Error>
 where
     D: Deserializer<'de>,
@@ -605,6 +2439,105 @@ where
     AccessControl::new(&rules).map_err(serde::de::Error::custom)
 }
 
+/// Parse the `type ext1 ext2` lines of a mime.types file into an extension to type map.
+fn parse_mime_types(contents: &str) -> BTreeMap<String, String> {
+    let mut output = BTreeMap::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        if let Some(mime) = words.next() {
+            for ext in words {
+                output.insert(ext.to_string(), mime.to_string());
+            }
+        }
+    }
+    output
+}
+
+fn deserialize_string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(v) => v,
+        StringOrNumber::Number(v) => v.to_string(),
+    })
+}
+
+fn deserialize_mode<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = deserialize_string_or_number(deserializer)?;
+    parse_mode(&value)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("Invalid mode `{value}`")))
+}
+
+/// Parse octal permissions, e.g. `750`.
+fn parse_mode(value: &str) -> Option<u32> {
+    u32::from_str_radix(value.trim_start_matches("0o"), 8)
+        .ok()
+        .filter(|v| *v <= 0o7777)
+}
+
+/// Create a missing serve path along with its parents, with `mode` on unix.
+fn create_serve_dir(path: &Path, mode: Option<u32>) -> Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, mode.unwrap_or(0o755));
+    #[cfg(not(unix))]
+    let _ = mode;
+    builder
+        .create(path)
+        .with_context(|| format!("Failed to create serve path `{}`", path.display()))
+}
+
+fn deserialize_rate<'de, D>(deserializer: D) -> Result<Option<Rate>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = deserialize_string_or_number(deserializer)?;
+    value.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = deserialize_string_or_number(deserializer)?;
+    parse_size(&value)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("Invalid size `{value}`")))
+}
+
+fn deserialize_block_size<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = deserialize_string_or_number(deserializer)?;
+    parse_size(&value)
+        .filter(|v| *v > 0)
+        .ok_or_else(|| serde::de::Error::custom(format!("Invalid block-size `{value}`")))
+}
+
+fn deserialize_read_buffer_size<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = deserialize_string_or_number(deserializer)?;
+    parse_size(&value)
+        .ok_or_else(|| serde::de::Error::custom(format!("Invalid read-buffer-size `{value}`")))
+}
+
 fn deserialize_log_http<'de, D>(deserializer: D) -> Result<HttpLogger, D::Error>
 where
     D: Deserializer<'de>,
@@ -625,6 +2558,59 @@ fn default_port() -> u16 {
     5000
 }
 
+fn default_attachment_types() -> Vec<String> {
+    [
+        "application/zip",
+        "application/gzip",
+        "application/x-compressed",
+        "application/x-tar",
+        "application/x-bzip2",
+        "application/x-xz",
+        "application/x-7z-compressed",
+        "application/x-rar-compressed",
+        "application/vnd.android.package-archive",
+    ]
+    .into_iter()
+    .map(|v| v.to_string())
+    .collect()
+}
+
+fn default_clamav_timeout() -> u64 {
+    30
+}
+
+fn default_ingest_layout() -> String {
+    "%Y/%m/%d".to_string()
+}
+
+fn default_retry_after_base() -> u64 {
+    60
+}
+
+fn default_session_ttl() -> u64 {
+    86400
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_block_size() -> u64 {
+    4 * 1024 * 1024
+}
+
+fn default_read_buffer_size() -> u64 {
+    64 * 1024
+}
+
+fn default_pipe_timeout() -> u64 {
+    3600
+}
+
+fn default_image_quality() -> u8 {
+    80
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -748,4 +2734,31 @@ hidden:
         );
         assert_eq!(args.hidden, ["tmp", "*.log", "*.lock"]);
     }
+
+    #[test]
+    fn test_args_mime() {
+        let tmpdir = assert_fs::TempDir::new().unwrap();
+        let mime_file = tmpdir.child("mime.types");
+        let contents = r#"
+# comment
+application/typescript  ts tsx
+text/x-custom           cst
+"#;
+        mime_file.write_str(contents).unwrap();
+
+        let cli = build_cli();
+        let matches = cli
+            .try_get_matches_from(vec![
+                "",
+                "--mime-file",
+                &mime_file.to_string_lossy(),
+                "--mime",
+                ".CST=text/plain",
+            ])
+            .unwrap();
+        let args = Args::parse(matches).unwrap();
+        assert_eq!(args.mime.get("ts").unwrap(), "application/typescript");
+        assert_eq!(args.mime.get("tsx").unwrap(), "application/typescript");
+        assert_eq!(args.mime.get("cst").unwrap(), "text/plain");
+    }
 }
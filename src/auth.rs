@@ -1,12 +1,16 @@
 use crate::{args::Args, server::Response, utils::unix_now};
 
 use anyhow::{anyhow, bail, Result};
-use base64::{engine::general_purpose::STANDARD, Engine as _};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine as _,
+};
 use headers::HeaderValue;
 use hyper::{header::WWW_AUTHENTICATE, Method};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use md5::Context;
+use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -15,6 +19,7 @@ use uuid::Uuid;
 
 const REALM: &str = "DUFS";
 const DIGEST_AUTH_TIMEOUT: u32 = 604800; // 7 days
+pub const SESSION_COOKIE: &str = "dufs_session";
 
 lazy_static! {
     static ref NONCESTARTHASH: Context = {
@@ -127,12 +132,62 @@ impl AccessControl {
 
         (None, None)
     }
+
+    /// Like `guard`, for a user already authenticated by a session cookie.
+    pub fn guard_session(
+        &self,
+        path: &str,
+        method: &Method,
+        user: &str,
+    ) -> (Option<String>, Option<AccessPaths>) {
+        match self.users.get(user) {
+            Some(_) if method == Method::OPTIONS => (
+                Some(user.to_string()),
+                Some(AccessPaths::new(AccessPerm::ReadOnly)),
+            ),
+            Some((_, paths)) => (
+                Some(user.to_string()),
+                paths.find(path, !is_readonly_method(method)),
+            ),
+            None => (None, None),
+        }
+    }
+
+    /// The paths of a user already signed in by `guard` or `guard_session`, or of the anonymous
+    /// user for `None`.
+    pub fn user_paths(&self, user: Option<&str>) -> Option<&AccessPaths> {
+        match user {
+            Some(user) => self.users.get(user).map(|(_, paths)| paths),
+            None => self.anonymous.as_ref(),
+        }
+    }
+
+    /// Whether the credentials of a request grant access to `path`, regardless of the request's
+    /// own method. `method` is still needed to verify digest credentials, which are bound to it.
+    pub fn permits(
+        &self,
+        path: &str,
+        method: &Method,
+        authorization: Option<&HeaderValue>,
+        writable: bool,
+    ) -> bool {
+        let paths = match authorization {
+            Some(authorization) => get_auth_user(authorization).and_then(|user| {
+                let (pass, paths) = self.users.get(&user)?;
+                check_auth(authorization, method.as_str(), &user, pass)?;
+                Some(paths)
+            }),
+            None => self.anonymous.as_ref(),
+        };
+        paths.is_some_and(|v| v.find(path, writable).is_some())
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct AccessPaths {
     perm: AccessPerm,
     children: IndexMap<String, AccessPaths>,
+    reveal_hidden: bool,
 }
 
 impl AccessPaths {
@@ -147,6 +202,15 @@ impl AccessPaths {
         self.perm
     }
 
+    /// Whether the paths matched by `--hidden` are listed and served, with --serve-hidden-with-auth.
+    pub fn reveal_hidden(&self) -> bool {
+        self.reveal_hidden
+    }
+
+    pub fn set_reveal_hidden(&mut self, reveal_hidden: bool) {
+        self.reveal_hidden = reveal_hidden;
+    }
+
     pub fn set_perm(&mut self, perm: AccessPerm) {
         if !perm.indexonly() {
             self.perm = perm;
@@ -285,6 +349,58 @@ pub fn www_authenticate(res: &mut Response, args: &Args) -> Result<()> {
     Ok(())
 }
 
+/// Sign the session of `user`, valid until `expiry` secs since the epoch.
+///
+/// The value is `user.expiry.signature`, with the user base64 encoded so that it can't
+/// contain the separator.
+pub fn sign_session(secret: &str, user: &str, expiry: u64) -> String {
+    let payload = format!("{}.{expiry}", URL_SAFE_NO_PAD.encode(user));
+    let signature = hmac_sha256(secret.as_bytes(), payload.as_bytes());
+    format!("{payload}.{signature}")
+}
+
+/// The user of a session signed by `sign_session`, unless it's forged or has expired.
+pub fn verify_session(secret: &str, value: &str) -> Option<String> {
+    let (payload, signature) = value.rsplit_once('.')?;
+    let expected = hmac_sha256(secret.as_bytes(), payload.as_bytes());
+    // Compare in constant time, not to leak how much of a forged signature is right.
+    if signature.len() != expected.len()
+        || signature
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            != 0
+    {
+        return None;
+    }
+    let (user, expiry) = payload.split_once('.')?;
+    if expiry.parse::<u64>().ok()? <= unix_now().ok()?.as_secs() {
+        return None;
+    }
+    String::from_utf8(URL_SAFE_NO_PAD.decode(user).ok()?).ok()
+}
+
+/// The hex HMAC-SHA256 of `data` as specified by RFC 2104.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> String {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |v: u8| block.iter().map(|b| b ^ v).collect::<Vec<u8>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(data)
+        .finalize();
+    let outer = Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize();
+    format!("{:x}", outer)
+}
+
 pub fn get_auth_user(authorization: &HeaderValue) -> Option<String> {
     if let Some(value) = strip_prefix(authorization.as_bytes(), b"Basic ") {
         let value: Vec<u8> = STANDARD.decode(value).ok()?;
@@ -424,7 +540,7 @@ fn validate_nonce(nonce: &[u8]) -> Result<bool> {
     bail!("invalid nonce");
 }
 
-fn is_readonly_method(method: &Method) -> bool {
+pub fn is_readonly_method(method: &Method) -> bool {
     method == Method::GET
         || method == Method::OPTIONS
         || method == Method::HEAD
@@ -532,6 +648,27 @@ fn split_rules(rules: &[&str]) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_session() {
+        let expiry = unix_now().unwrap().as_secs() + 60;
+        let value = sign_session("secret", "us.er", expiry);
+        assert_eq!(verify_session("secret", &value).as_deref(), Some("us.er"));
+        assert_eq!(verify_session("other", &value), None);
+        let forged = value.replacen(&URL_SAFE_NO_PAD.encode("us.er"), "YWRtaW4", 1);
+        assert_eq!(verify_session("secret", &forged), None);
+        let expired = sign_session("secret", "us.er", expiry - 120);
+        assert_eq!(verify_session("secret", &expired), None);
+    }
+
     #[test]
     fn test_split_account_paths() {
         assert_eq!(
@@ -0,0 +1,85 @@
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+
+const CHUNK_SIZE: usize = 65536;
+
+/// Scan the content of `path`, read from `reader`, with clamd using the INSTREAM command.
+///
+/// `addr` is either a `host:port` pair or, on unix, the path of clamd's local socket.
+/// Returns the signature name if a virus was found.
+pub async fn scan<R: AsyncRead + Unpin>(
+    addr: &str,
+    path: &Path,
+    reader: R,
+    scan_timeout: Duration,
+) -> Result<Option<String>> {
+    timeout(scan_timeout, scan_impl(addr, reader))
+        .await
+        .map_err(|_| anyhow!("Timeout scanning `{}` with clamd", path.display()))?
+}
+
+async fn scan_impl<R: AsyncRead + Unpin>(addr: &str, reader: R) -> Result<Option<String>> {
+    if addr.parse::<std::net::SocketAddr>().is_ok() {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        return instream(stream, reader).await;
+    }
+    #[cfg(unix)]
+    {
+        let stream = tokio::net::UnixStream::connect(addr).await?;
+        instream(stream, reader).await
+    }
+    #[cfg(not(unix))]
+    bail!("Invalid clamd address `{addr}`")
+}
+
+async fn instream<S: AsyncRead + AsyncWrite + Unpin, R: AsyncRead + Unpin>(
+    mut stream: S,
+    mut reader: R,
+) -> Result<Option<String>> {
+    stream.write_all(b"zINSTREAM\0").await?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let bytes_read = reader.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            break;
+        }
+        stream.write_all(&(bytes_read as u32).to_be_bytes()).await?;
+        stream.write_all(&buffer[..bytes_read]).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+    stream.flush().await?;
+
+    let mut reply = vec![];
+    stream.read_to_end(&mut reply).await?;
+    parse_reply(&String::from_utf8_lossy(&reply))
+}
+
+fn parse_reply(reply: &str) -> Result<Option<String>> {
+    let reply = reply.trim_end_matches(['\0', '\n']);
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if result == "OK" {
+        Ok(None)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(Some(signature.to_string()))
+    } else {
+        bail!("Unexpected clamd reply `{reply}`")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(parse_reply("stream: OK\0").unwrap(), None);
+        assert_eq!(
+            parse_reply("stream: Eicar-Signature FOUND\0").unwrap(),
+            Some("Eicar-Signature".to_string())
+        );
+        assert!(parse_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, Stream};
+use log::debug;
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::SystemTime,
+};
+use tokio::{fs::File, io::AsyncReadExt, sync::watch};
+
+/// Larger files are read per download, since a shared read keeps the whole file in memory.
+pub const COALESCE_MAX_SIZE: u64 = 67108864; // 64M
+/// The memory all shared reads may hold together, further files are read per download.
+const COALESCE_MAX_TOTAL_SIZE: u64 = 268435456; // 256M
+const CHUNK_SIZE: usize = 65536;
+
+/// A file is only shared for as long as its size and mtime stay the same.
+type Key = (PathBuf, u64, Option<SystemTime>);
+
+/// The chunks of a shared read, which gives back its share of the budget once dropped.
+#[derive(Debug)]
+struct SharedRead {
+    sender: watch::Sender<ReadState>,
+    size: u64,
+    buffered: Arc<AtomicU64>,
+}
+
+impl Drop for SharedRead {
+    fn drop(&mut self) {
+        self.buffered.fetch_sub(self.size, Ordering::Relaxed);
+    }
+}
+
+/// The chunks read so far, and how the read ended once it did.
+#[derive(Debug, Default)]
+struct ReadState {
+    chunks: Vec<Bytes>,
+    end: Option<Result<(), String>>,
+}
+
+/// Shares a single read of a file among the downloads requesting it at the same time.
+///
+/// The chunks are buffered until the last of these downloads is done, so that downloads
+/// starting meanwhile join from the first chunk instead of reading the file again.
+#[derive(Debug)]
+pub struct Coalescer {
+    inflight: Mutex<HashMap<Key, Weak<SharedRead>>>,
+    reads: AtomicUsize,
+    /// The sizes of the files read in flight, bounded by `budget`.
+    buffered: Arc<AtomicU64>,
+    budget: u64,
+}
+
+impl Default for Coalescer {
+    fn default() -> Self {
+        Self::new(COALESCE_MAX_TOTAL_SIZE)
+    }
+}
+
+impl Coalescer {
+    fn new(budget: u64) -> Self {
+        Self {
+            inflight: Default::default(),
+            reads: AtomicUsize::new(0),
+            buffered: Default::default(),
+            budget,
+        }
+    }
+
+    /// Stream the first `size` bytes of `path`, joining a read of it that is still in flight.
+    ///
+    /// `None` when a new shared read would exceed the memory budget, the file is then read by
+    /// the download alone.
+    pub fn open(
+        &self,
+        path: &Path,
+        size: u64,
+        mtime: Option<SystemTime>,
+    ) -> Option<impl Stream<Item = io::Result<Bytes>> + Send + 'static> {
+        let key = (path.to_path_buf(), size, mtime);
+        let mut inflight = self.inflight.lock().unwrap();
+        inflight.retain(|_, v| v.strong_count() > 0);
+        let shared = match inflight.get(&key).and_then(|v| v.upgrade()) {
+            Some(shared) => shared,
+            None => {
+                let reserved =
+                    self.buffered
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                            (v + size <= self.budget).then_some(v + size)
+                        });
+                if reserved.is_err() {
+                    debug!("No memory left to coalesce reads of {}", path.display());
+                    return None;
+                }
+                let (sender, _) = watch::channel(ReadState::default());
+                let shared = Arc::new(SharedRead {
+                    sender,
+                    size,
+                    buffered: self.buffered.clone(),
+                });
+                inflight.insert(key, Arc::downgrade(&shared));
+                let reads = self.reads.fetch_add(1, Ordering::Relaxed) + 1;
+                debug!("Coalesced read #{reads} of {}", path.display());
+                tokio::spawn(read_shared(
+                    path.to_path_buf(),
+                    size,
+                    Arc::downgrade(&shared),
+                ));
+                shared
+            }
+        };
+        Some(shared_chunks(shared))
+    }
+}
+
+/// Read the file into `shared`, giving up once no download is left to share it with.
+async fn read_shared(path: PathBuf, size: u64, shared: Weak<SharedRead>) {
+    let mut file = match File::open(&path).await {
+        Ok(file) => file.take(size),
+        Err(err) => {
+            if let Some(shared) = shared.upgrade() {
+                shared
+                    .sender
+                    .send_modify(|v| v.end = Some(Err(err.to_string())));
+            }
+            return;
+        }
+    };
+    loop {
+        let mut buf = BytesMut::with_capacity(CHUNK_SIZE);
+        let ret = file.read_buf(&mut buf).await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        match ret {
+            Ok(0) => {
+                shared.sender.send_modify(|v| v.end = Some(Ok(())));
+                return;
+            }
+            Ok(_) => shared.sender.send_modify(|v| v.chunks.push(buf.freeze())),
+            Err(err) => {
+                shared
+                    .sender
+                    .send_modify(|v| v.end = Some(Err(err.to_string())));
+                return;
+            }
+        }
+    }
+}
+
+/// Yield the chunks of `shared` from the first one, waiting for those not read yet.
+fn shared_chunks(shared: Arc<SharedRead>) -> impl Stream<Item = io::Result<Bytes>> {
+    let receiver = shared.sender.subscribe();
+    stream::unfold(Some((shared, receiver, 0)), |state| async move {
+        let (shared, mut receiver, index) = state?;
+        loop {
+            let item = {
+                let state = receiver.borrow_and_update();
+                match (state.chunks.get(index), &state.end) {
+                    (Some(chunk), _) => Some(Ok(chunk.clone())),
+                    (None, Some(Ok(()))) => return None,
+                    (None, Some(Err(err))) => Some(Err(io::Error::other(err.clone()))),
+                    (None, None) => None,
+                }
+            };
+            match item {
+                Some(Ok(chunk)) => return Some((Ok(chunk), Some((shared, receiver, index + 1)))),
+                Some(Err(err)) => return Some((Err(err), None)),
+                None => {
+                    // The sender lives in `shared`, so it can't be dropped while waiting.
+                    let _ = receiver.changed().await;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+
+    #[test]
+    fn test_coalesce_reads() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let dir = assert_fs::TempDir::new().unwrap();
+            let path = dir.path().join("hot.bin");
+            let data: Vec<u8> = (0..300_000u32).map(|v| v as u8).collect();
+            std::fs::write(&path, &data).unwrap();
+            let size = data.len() as u64;
+
+            let coalescer = Coalescer::default();
+            let downloads: Vec<_> = (0..8)
+                .map(|_| coalescer.open(&path, size, None).unwrap())
+                .collect();
+            let bodies = futures_util::future::join_all(downloads.into_iter().map(|v| {
+                v.try_fold(vec![], |mut acc, chunk| async move {
+                    acc.extend_from_slice(&chunk);
+                    Ok(acc)
+                })
+            }))
+            .await;
+            assert!(bodies.into_iter().all(|v| v.unwrap() == data));
+            assert_eq!(coalescer.reads.load(Ordering::Relaxed), 1);
+
+            let body: Vec<Bytes> = coalescer
+                .open(&path, 10, None)
+                .unwrap()
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(body.concat(), &data[..10]);
+            assert_eq!(coalescer.reads.load(Ordering::Relaxed), 2);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_budget() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("hot.bin");
+        std::fs::write(&path, [0u8; 100]).unwrap();
+
+        let coalescer = Coalescer::new(150);
+        let first = coalescer.open(&path, 100, None).unwrap();
+        // Joining the read in flight costs nothing, another read is past the budget
+        let joined = coalescer.open(&path, 100, None).unwrap();
+        assert!(coalescer.open(&path, 60, None).is_none());
+
+        let body: Vec<Bytes> = first.try_collect().await.unwrap();
+        assert_eq!(body.concat().len(), 100);
+        drop(joined);
+        assert_eq!(coalescer.buffered.load(Ordering::Relaxed), 0);
+        assert!(coalescer.open(&path, 60, None).is_some());
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::ffi::CString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::args::Args;
+
+/// Confine the process to the served directory with `--chroot`, then drop to `--user`/`--group`.
+///
+/// Called once the listeners are bound and the certificates are loaded. Paths of `args` needed
+/// later on are rewritten relative to the new root.
+pub fn confine(args: &mut Args) -> Result<()> {
+    // Look the names up first, the passwd and group databases are gone after the chroot.
+    let user = args.user.as_deref().map(resolve_user).transpose()?;
+    let gid = match (args.group.as_deref(), user) {
+        (Some(group), _) => Some(resolve_group(group)?),
+        (None, Some((_, Some(gid)))) => Some(gid),
+        (None, Some((uid, None))) => bail!("No group known for user `{uid}`, set --group"),
+        (None, None) => None,
+    };
+
+    if args.chroot {
+        let root = match args.path_is_file {
+            true => args.serve_path.parent().unwrap_or(Path::new("/")),
+            false => args.serve_path.as_path(),
+        }
+        .to_path_buf();
+        let assets = match args.assets.as_ref() {
+            Some(assets) => Some(relocate(&root, assets).ok_or_else(|| {
+                anyhow!(
+                    "Assets `{}` must be inside the served directory with --chroot",
+                    assets.display()
+                )
+            })?),
+            None => None,
+        };
+        #[cfg(feature = "tls")]
+        let ocsp_file = match (args.tls_ocsp_file.as_ref(), args.tls_ocsp_refresh) {
+            (Some(ocsp_file), Some(_)) => Some(relocate(&root, ocsp_file).ok_or_else(|| {
+                anyhow!(
+                    "OCSP file `{}` must be inside the served directory to refresh with --chroot",
+                    ocsp_file.display()
+                )
+            })?),
+            (ocsp_file, _) => ocsp_file.cloned(),
+        };
+        std::os::unix::fs::chroot(&root)
+            .with_context(|| format!("Failed to chroot into `{}`", root.display()))?;
+        std::env::set_current_dir("/")?;
+        args.serve_path = relocate(&root, &args.serve_path).unwrap_or_else(|| "/".into());
+        args.assets = assets;
+        #[cfg(feature = "tls")]
+        {
+            args.tls_ocsp_file = ocsp_file;
+        }
+    }
+
+    if let Some(gid) = gid {
+        // Otherwise the supplementary groups of root are kept.
+        if unsafe { libc::setgroups(1, &gid) } != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to set groups");
+        }
+        if unsafe { libc::setgid(gid) } != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to set group");
+        }
+    }
+    if let Some((uid, _)) = user {
+        if unsafe { libc::setuid(uid) } != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to set user");
+        }
+        if uid != 0 && unsafe { libc::setuid(0) } == 0 {
+            bail!("Failed to drop root privileges");
+        }
+    }
+    Ok(())
+}
+
+/// Resolve a user name or id to its uid and, when it has a passwd entry, its primary gid.
+fn resolve_user(user: &str) -> Result<(libc::uid_t, Option<libc::gid_t>)> {
+    let uid = user.parse::<libc::uid_t>().ok();
+    let name = CString::new(user)?;
+    let entry = match uid {
+        Some(uid) => unsafe { libc::getpwuid(uid) },
+        None => unsafe { libc::getpwnam(name.as_ptr()) },
+    };
+    if entry.is_null() {
+        return uid
+            .map(|uid| (uid, None))
+            .ok_or_else(|| anyhow!("Unknown user `{user}`"));
+    }
+    let entry = unsafe { &*entry };
+    Ok((entry.pw_uid, Some(entry.pw_gid)))
+}
+
+fn resolve_group(group: &str) -> Result<libc::gid_t> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    let name = CString::new(group)?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        bail!("Unknown group `{group}`");
+    }
+    Ok(unsafe { (*entry).gr_gid })
+}
+
+/// The path `path` is reachable at once `root` became `/`.
+fn relocate(root: &Path, path: &Path) -> Option<PathBuf> {
+    path.strip_prefix(root).ok().map(|v| Path::new("/").join(v))
+}
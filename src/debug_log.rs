@@ -0,0 +1,150 @@
+use bytes::{Bytes, BytesMut};
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::{HeaderMap, Method, Request};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// How much of each body is logged by `--debug-bodies`.
+pub const DEBUG_BODY_MAX_SIZE: usize = 4096;
+
+const REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+/// Trace the request line and headers, credentials are redacted. An upload is only described by
+/// its size and type since its bytes go straight to disk.
+pub fn log_request<B>(req: &Request<B>) {
+    trace!(
+        "Request {} {}\n{}",
+        req.method(),
+        req.uri(),
+        format_headers(req.headers())
+    );
+    if matches!(*req.method(), Method::PUT | Method::PATCH) {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-")
+                .to_string()
+        };
+        trace!(
+            "Request body of {} {} not logged, content-length: {}, content-type: {}",
+            req.method(),
+            req.uri().path(),
+            header("content-length"),
+            header("content-type")
+        );
+    }
+}
+
+/// Trace a request body parsed by dufs itself, such as a PROPFIND.
+pub fn log_request_body(method: &str, path: &str, body: &[u8]) {
+    trace!("Request body of {method} {path}\n{}", format_body(body));
+}
+
+pub fn format_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                "[redacted]".to_string()
+            } else {
+                value.as_bytes().escape_ascii().to_string()
+            };
+            format!("{name}: {value}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_body(body: &[u8]) -> String {
+    let mut output = body[..body.len().min(DEBUG_BODY_MAX_SIZE)]
+        .escape_ascii()
+        .to_string();
+    if body.len() > DEBUG_BODY_MAX_SIZE {
+        output.push_str(&format!(
+            " ... ({} bytes truncated)",
+            body.len() - DEBUG_BODY_MAX_SIZE
+        ));
+    }
+    output
+}
+
+pin_project_lite::pin_project! {
+    /// A response body that traces its first bytes once it ends or is dropped.
+    pub struct DebugBody<B: Body> {
+        #[pin]
+        inner: B,
+        label: String,
+        head: BytesMut,
+        size: usize,
+        logged: bool,
+    }
+
+    impl<B: Body> PinnedDrop for DebugBody<B> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            log_response_body(this.label, this.head, *this.size, this.logged);
+        }
+    }
+}
+
+impl<B: Body> DebugBody<B> {
+    pub fn new(inner: B, label: String) -> Self {
+        Self {
+            inner,
+            label,
+            head: BytesMut::new(),
+            size: 0,
+            logged: false,
+        }
+    }
+}
+
+impl<B: Body<Data = Bytes>> Body for DebugBody<B> {
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = futures_util::ready!(this.inner.as_mut().poll_frame(cx));
+        if let Some(data) = frame.as_ref().and_then(|v| v.as_ref().ok()?.data_ref()) {
+            let room = DEBUG_BODY_MAX_SIZE.saturating_sub(this.head.len());
+            this.head.extend_from_slice(&data[..data.len().min(room)]);
+            *this.size += data.len();
+        }
+        if frame.is_none() || this.inner.is_end_stream() {
+            log_response_body(this.label, this.head, *this.size, this.logged);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+fn log_response_body(label: &str, head: &[u8], size: usize, logged: &mut bool) {
+    if *logged {
+        return;
+    }
+    *logged = true;
+    let mut body = format_body(head);
+    if size > head.len() {
+        body.push_str(&format!(" ... ({} bytes truncated)", size - head.len()));
+    }
+    trace!("Response body of {label}\n{body}");
+}
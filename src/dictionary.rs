@@ -0,0 +1,164 @@
+use anyhow::{bail, Context, Result};
+use async_compression::{tokio::bufread::ZstdEncoder, Level};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hyper::{header::ACCEPT_ENCODING, HeaderMap};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+const AVAILABLE_DICTIONARY: &str = "available-dictionary";
+/// Past this, clients may not keep the dictionary, nor fit it in the compression window.
+const MAX_DICTIONARY_SIZE: usize = 1024 * 1024; // 1M
+const DCB_MAGIC: &[u8] = &[0xff, 0x44, 0x43, 0x42];
+const DCZ_MAGIC: &[u8] = &[0x5e, 0x2a, 0x4d, 0x18, 0x20, 0x00, 0x00, 0x00];
+
+/// The content codings compressing against a shared dictionary, RFC 9842.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DictionaryEncoding {
+    /// Brotli
+    Dcb,
+    /// Zstandard
+    Dcz,
+}
+
+impl DictionaryEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DictionaryEncoding::Dcb => "dcb",
+            DictionaryEncoding::Dcz => "dcz",
+        }
+    }
+}
+
+/// The dictionary of `--compression-dictionary`, which JSON listings are compressed against.
+#[derive(Clone, PartialEq)]
+pub struct CompressionDictionary {
+    pub data: Vec<u8>,
+    hash: [u8; 32],
+}
+
+impl std::fmt::Debug for CompressionDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressionDictionary")
+            .field("size", &self.data.len())
+            .finish()
+    }
+}
+
+impl CompressionDictionary {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| {
+            format!(
+                "Failed to read compression dictionary at {}",
+                path.display()
+            )
+        })?;
+        if data.is_empty() || data.len() > MAX_DICTIONARY_SIZE {
+            bail!(
+                "Invalid compression dictionary at {}, expected 1 byte to 1M",
+                path.display()
+            );
+        }
+        let hash = Sha256::digest(&data).into();
+        Ok(Self { data, hash })
+    }
+
+    /// The coding to use when the client holds this dictionary and accepts one of them.
+    pub fn negotiate(&self, headers: &HeaderMap) -> Option<DictionaryEncoding> {
+        let available = headers.get(AVAILABLE_DICTIONARY)?.to_str().ok()?.trim();
+        let hash = available
+            .strip_prefix(':')
+            .and_then(|v| v.strip_suffix(':'))
+            .and_then(|v| STANDARD.decode(v).ok())?;
+        if hash != self.hash {
+            return None;
+        }
+        let accepted: Vec<String> = headers
+            .get_all(ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|v| {
+                let mut parts = v.split(';').map(|v| v.trim());
+                let coding = parts.next().unwrap_or_default().to_ascii_lowercase();
+                let qvalue = parts
+                    .find_map(|v| v.strip_prefix("q="))
+                    .and_then(|v| v.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (qvalue > 0.0).then_some(coding)
+            })
+            .collect();
+        [DictionaryEncoding::Dcz, DictionaryEncoding::Dcb]
+            .into_iter()
+            .find(|v| accepted.iter().any(|coding| coding == v.as_str()))
+    }
+
+    /// Compress `data` against the dictionary, prefixed with the header of the coding.
+    pub async fn compress(&self, encoding: DictionaryEncoding, data: &[u8]) -> Result<Vec<u8>> {
+        let mut output = vec![];
+        match encoding {
+            DictionaryEncoding::Dcb => {
+                output.extend_from_slice(DCB_MAGIC);
+                output.extend_from_slice(&self.hash);
+                // async-compression can't take a dictionary for brotli, so use the encoder directly
+                let params = brotli::enc::BrotliEncoderParams {
+                    quality: 5,
+                    lgwin: 22,
+                    ..Default::default()
+                };
+                brotli::enc::BrotliCompressCustomIoCustomDict(
+                    &mut brotli::IoReaderWrapper(&mut &data[..]),
+                    &mut brotli::IoWriterWrapper(&mut output),
+                    &mut [0u8; 4096],
+                    &mut [0u8; 4096],
+                    &params,
+                    brotli::enc::StandardAlloc::default(),
+                    &mut |_, _, _, _| (),
+                    &self.data,
+                    std::io::Error::from(std::io::ErrorKind::UnexpectedEof),
+                )?;
+            }
+            DictionaryEncoding::Dcz => {
+                output.extend_from_slice(DCZ_MAGIC);
+                output.extend_from_slice(&self.hash);
+                ZstdEncoder::with_dict(data, Level::Default, &self.data)?
+                    .read_to_end(&mut output)
+                    .await?;
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn test_negotiate() {
+        let dictionary = CompressionDictionary {
+            data: b"dict".to_vec(),
+            hash: Sha256::digest(b"dict").into(),
+        };
+        let available = format!(":{}:", STANDARD.encode(dictionary.hash));
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip, dcb, dcz"));
+        assert_eq!(dictionary.negotiate(&headers), None);
+        headers.insert(AVAILABLE_DICTIONARY, available.parse().unwrap());
+        assert_eq!(
+            dictionary.negotiate(&headers),
+            Some(DictionaryEncoding::Dcz)
+        );
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("dcz;q=0, DCB"));
+        assert_eq!(
+            dictionary.negotiate(&headers),
+            Some(DictionaryEncoding::Dcb)
+        );
+        headers.insert(
+            AVAILABLE_DICTIONARY,
+            HeaderValue::from_static(":AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=:"),
+        );
+        assert_eq!(dictionary.negotiate(&headers), None);
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use include_dir::{include_dir, Dir, DirEntry};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+static EMBEDDED_DIR: Dir<'static> = include_dir!("$DUFS_EMBED_DIR");
+
+/// Extract the directory bundled at compile time into a temp directory named after its content.
+///
+/// Serving the extracted copy keeps listing, range and MIME handling identical to a disk
+/// directory. Later starts of the same build reuse it instead of leaving a copy behind each time.
+pub fn extract() -> Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("dufs-embedded-{}", content_hash()));
+    if path.is_dir() {
+        return Ok(path);
+    }
+    // Extract aside then move in place, so that a concurrent start never sees a partial copy
+    let staging = path.with_extension(Uuid::new_v4().to_string());
+    let ret = std::fs::create_dir_all(&staging)
+        .and_then(|_| EMBEDDED_DIR.extract(&staging))
+        .and_then(|_| std::fs::rename(&staging, &path));
+    if ret.is_err() {
+        let _ = std::fs::remove_dir_all(&staging);
+        if !path.is_dir() {
+            ret.with_context(|| {
+                format!("Failed to extract embedded files to `{}`", path.display())
+            })?;
+        }
+    }
+    Ok(path)
+}
+
+fn content_hash() -> String {
+    fn walk(dir: &Dir, hasher: &mut Sha256) {
+        for entry in dir.entries() {
+            hasher.update(entry.path().to_string_lossy().as_bytes());
+            hasher.update([0]);
+            match entry {
+                DirEntry::Dir(dir) => walk(dir, hasher),
+                DirEntry::File(file) => {
+                    hasher.update((file.contents().len() as u64).to_be_bytes());
+                    hasher.update(file.contents());
+                }
+            }
+        }
+    }
+    let mut hasher = Sha256::new();
+    walk(&EMBEDDED_DIR, &mut hasher);
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_reused() {
+        let path = extract().unwrap();
+        assert!(path.is_dir());
+        assert_eq!(extract().unwrap(), path);
+    }
+}
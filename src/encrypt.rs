@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures_util::Stream;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+/// Starts every encrypted file, so files stored before --encrypt-at-rest are told apart.
+const MAGIC: &[u8; 8] = b"DUFSENC1";
+const SALT_LEN: usize = 32;
+pub const HEADER_LEN: u64 = (MAGIC.len() + SALT_LEN) as u64;
+/// The plaintext bytes sealed together, the last chunk of a file may be shorter.
+const CHUNK_SIZE: usize = 65536;
+const TAG_LEN: usize = 16;
+const SEALED_CHUNK_SIZE: u64 = (CHUNK_SIZE + TAG_LEN) as u64;
+
+/// The key content is encrypted with, with `--encryption-key-file`.
+#[derive(Clone, PartialEq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl EncryptionKey {
+    /// Load a key file holding 32 raw bytes or 64 hex digits.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read encryption key at {}", path.display()))?;
+        let hex = std::str::from_utf8(&data).map(|v| v.trim());
+        let key = match hex {
+            Ok(hex) if hex.len() == 64 => (0..32)
+                .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| anyhow!("Invalid encryption key, bad hex digits"))?,
+            _ if data.len() == 32 => data,
+            _ => bail!("Invalid encryption key, expected 32 bytes or 64 hex digits"),
+        };
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&key);
+        Ok(Self(bytes))
+    }
+
+    /// Each file gets its own key from a random salt, so that nonces only count chunks.
+    fn file_key(&self, salt: &[u8]) -> LessSafeKey {
+        let prk = Salt::new(HKDF_SHA256, salt).extract(&self.0);
+        let okm = prk
+            .expand(&[b"dufs-encrypt-at-rest"], &AES_256_GCM)
+            .expect("AES-256-GCM key length is valid for HKDF");
+        LessSafeKey::new(UnboundKey::from(okm))
+    }
+}
+
+/// The nonce of a chunk, flagging the last one so that a truncated file fails to decrypt.
+fn chunk_nonce(index: u64, last: bool) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[..8].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Encrypt everything read from `reader` into `writer`, returning the plaintext size.
+pub async fn encrypt<R, W>(key: &EncryptionKey, reader: &mut R, writer: &mut W) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut salt = [0; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| io::Error::other("Failed to generate a salt"))?;
+    let file_key = key.file_key(&salt);
+    writer.write_all(MAGIC).await?;
+    writer.write_all(&salt).await?;
+
+    // One byte past the chunk tells whether it is the last one.
+    let mut buf = Vec::with_capacity(CHUNK_SIZE + 1);
+    let mut total = 0;
+    let mut index = 0;
+    loop {
+        while buf.len() <= CHUNK_SIZE {
+            let n = (&mut *reader)
+                .take((CHUNK_SIZE + 1 - buf.len()) as u64)
+                .read_to_end(&mut buf)
+                .await?;
+            if n == 0 {
+                break;
+            }
+        }
+        let last = buf.len() <= CHUNK_SIZE;
+        let mut chunk: Vec<u8> = buf.drain(..buf.len().min(CHUNK_SIZE)).collect();
+        total += chunk.len() as u64;
+        file_key
+            .seal_in_place_append_tag(chunk_nonce(index, last), Aad::empty(), &mut chunk)
+            .map_err(|_| io::Error::other("Failed to encrypt"))?;
+        writer.write_all(&chunk).await?;
+        if last {
+            break;
+        }
+        index += 1;
+    }
+    writer.flush().await?;
+    Ok(total)
+}
+
+/// An encrypted file opened for reading.
+pub struct EncryptedFile {
+    file: File,
+    key: LessSafeKey,
+    chunks: u64,
+    pub size: u64,
+}
+
+impl EncryptedFile {
+    /// Open a file stored encrypted, `None` if it was stored as is.
+    pub async fn open(key: &EncryptionKey, path: &Path) -> Result<Option<Self>> {
+        let mut file = File::open(path).await?;
+        let stored_size = file.metadata().await?.len();
+        let mut header = [0; HEADER_LEN as usize];
+        if stored_size < HEADER_LEN + TAG_LEN as u64 {
+            return Ok(None);
+        }
+        file.read_exact(&mut header).await?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Ok(None);
+        }
+        let (chunks, size) = plaintext_size(stored_size)
+            .ok_or_else(|| anyhow!("Invalid encrypted file `{}`", path.display()))?;
+        Ok(Some(Self {
+            key: key.file_key(&header[MAGIC.len()..]),
+            file,
+            chunks,
+            size,
+        }))
+    }
+
+    /// Decrypt `len` bytes from `start`, only reading the chunks they are in.
+    pub fn read(
+        mut self,
+        start: u64,
+        len: u64,
+    ) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+        try_stream! {
+            let mut index = start / CHUNK_SIZE as u64;
+            let mut skip = (start % CHUNK_SIZE as u64) as usize;
+            let mut remaining = len;
+            self.file
+                .seek(SeekFrom::Start(HEADER_LEN + index * SEALED_CHUNK_SIZE))
+                .await?;
+            while remaining > 0 && index < self.chunks {
+                let mut chunk = Vec::with_capacity(SEALED_CHUNK_SIZE as usize);
+                (&mut self.file)
+                    .take(SEALED_CHUNK_SIZE)
+                    .read_to_end(&mut chunk)
+                    .await?;
+                let last = index + 1 == self.chunks;
+                let plain = self
+                    .key
+                    .open_in_place(chunk_nonce(index, last), Aad::empty(), &mut chunk)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to decrypt"))?;
+                let end = plain.len().min(skip + remaining as usize);
+                let data = Bytes::copy_from_slice(&plain[skip.min(end)..end]);
+                remaining -= data.len() as u64;
+                skip = 0;
+                index += 1;
+                yield data;
+            }
+        }
+    }
+}
+
+/// The number of chunks and plaintext size of an encrypted file of `stored_size` bytes.
+fn plaintext_size(stored_size: u64) -> Option<(u64, u64)> {
+    let body = stored_size.checked_sub(HEADER_LEN)?;
+    let chunks = body.div_ceil(SEALED_CHUNK_SIZE).max(1);
+    if body - (chunks - 1) * SEALED_CHUNK_SIZE < TAG_LEN as u64 {
+        return None;
+    }
+    Some((chunks, body - chunks * TAG_LEN as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+
+    #[test]
+    fn test_plaintext_size() {
+        let sealed = SEALED_CHUNK_SIZE;
+        assert_eq!(plaintext_size(HEADER_LEN + 16), Some((1, 0)));
+        assert_eq!(plaintext_size(HEADER_LEN + 26), Some((1, 10)));
+        assert_eq!(
+            plaintext_size(HEADER_LEN + sealed),
+            Some((1, CHUNK_SIZE as u64))
+        );
+        assert_eq!(
+            plaintext_size(HEADER_LEN + sealed + 17),
+            Some((2, CHUNK_SIZE as u64 + 1))
+        );
+        assert_eq!(plaintext_size(HEADER_LEN + sealed + 3), None);
+        assert_eq!(plaintext_size(HEADER_LEN - 1), None);
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("file");
+        let key = EncryptionKey([7; 32]);
+        for size in [0, 10, CHUNK_SIZE, CHUNK_SIZE * 2 + 5] {
+            let data: Vec<u8> = (0..size).map(|v| v as u8).collect();
+            let mut file = File::create(&path).await.unwrap();
+            let written = encrypt(&key, &mut data.as_slice(), &mut file)
+                .await
+                .unwrap();
+            assert_eq!(written, size as u64);
+
+            let (start, len) = (size as u64 / 3, size as u64 / 2);
+            let file = EncryptedFile::open(&key, &path).await.unwrap().unwrap();
+            assert_eq!(file.size, size as u64);
+            let chunks: Vec<Bytes> = file.read(start, len).try_collect().await.unwrap();
+            assert_eq!(
+                chunks.concat(),
+                &data[start as usize..(start + len) as usize]
+            );
+        }
+
+        // A truncated file can't be read
+        let data = vec![0; CHUNK_SIZE * 2];
+        let mut file = File::create(&path).await.unwrap();
+        encrypt(&key, &mut data.as_slice(), &mut file)
+            .await
+            .unwrap();
+        drop(file);
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(HEADER_LEN + SEALED_CHUNK_SIZE).unwrap();
+        let file = EncryptedFile::open(&key, &path).await.unwrap().unwrap();
+        let ret: io::Result<Vec<Bytes>> = file.read(0, CHUNK_SIZE as u64).try_collect().await;
+        assert!(ret.is_err());
+    }
+}
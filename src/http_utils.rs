@@ -1,12 +1,25 @@
 use bytes::{Bytes, BytesMut};
 use futures_util::Stream;
+use headers::{
+    ETag, HeaderMap, HeaderMapExt, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince,
+};
 use http_body_util::{combinators::BoxBody, BodyExt, Full};
-use hyper::body::{Body, Incoming};
+use hyper::{
+    body::{Body, Incoming},
+    header::{ACCEPT, ACCEPT_ENCODING},
+    Method, StatusCode,
+};
 use std::{
+    io,
     pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
 };
-use tokio::io::AsyncRead;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio_util::io::poll_read_buf;
 
 #[derive(Debug)]
@@ -37,6 +50,126 @@ impl Stream for IncomingStream {
     }
 }
 
+/// When a connection last read or wrote any bytes, and how many of its requests are in flight.
+#[derive(Debug, Clone)]
+pub struct Activity {
+    start: Instant,
+    last: Arc<AtomicU64>,
+    busy: Arc<AtomicUsize>,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            last: Arc::new(AtomicU64::new(0)),
+            busy: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl Activity {
+    fn touch(&self) {
+        let elapsed = self.start.elapsed().as_millis() as u64;
+        self.last.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Mark a request as in flight until the guard is dropped.
+    pub fn busy(&self) -> BusyGuard {
+        self.busy.fetch_add(1, Ordering::Relaxed);
+        BusyGuard(self.clone())
+    }
+
+    /// How long the connection has been idle, a request still being handled counts as activity.
+    pub fn idle(&self) -> Duration {
+        if self.busy.load(Ordering::Relaxed) > 0 {
+            return Duration::ZERO;
+        }
+        let last = Duration::from_millis(self.last.load(Ordering::Relaxed));
+        self.start.elapsed().saturating_sub(last)
+    }
+}
+
+pub struct BusyGuard(Activity);
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        self.0.touch();
+        self.0.busy.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// A connection stream recording its activity, so idle keep-alive connections can be closed.
+    pub struct ActivityStream<T> {
+        #[pin]
+        inner: T,
+        activity: Activity,
+    }
+}
+
+impl<T> ActivityStream<T> {
+    pub fn new(inner: T, activity: Activity) -> Self {
+        Self { inner, activity }
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for ActivityStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        let filled = buf.filled().len();
+        let ret = this.inner.poll_read(cx, buf);
+        if buf.filled().len() > filled {
+            this.activity.touch();
+        }
+        ret
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for ActivityStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let ret = this.inner.poll_write(cx, buf);
+        if matches!(ret, Poll::Ready(Ok(n)) if n > 0) {
+            this.activity.touch();
+        }
+        ret
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let ret = this.inner.poll_write_vectored(cx, bufs);
+        if matches!(ret, Poll::Ready(Ok(n)) if n > 0) {
+            this.activity.touch();
+        }
+        ret
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
 pin_project_lite::pin_project! {
     pub struct LengthLimitedStream<R> {
         #[pin]
@@ -48,12 +181,13 @@ pin_project_lite::pin_project! {
 }
 
 impl<R> LengthLimitedStream<R> {
-    pub fn new(reader: R, limit: usize) -> Self {
+    /// Read `limit` bytes by chunks of at most `capacity` bytes.
+    pub fn new(reader: R, limit: usize, capacity: usize) -> Self {
         Self {
             reader: Some(reader),
             remaining: limit,
             buf: BytesMut::new(),
-            capacity: 4096,
+            capacity: capacity.min(limit),
         }
     }
 }
@@ -103,3 +237,304 @@ pub fn body_full(content: impl Into<hyper::body::Bytes>) -> BoxBody<Bytes, anyho
         .map_err(anyhow::Error::new)
         .boxed()
 }
+
+/// Evaluate the conditional request headers in the order defined by RFC 9110 section 13.2.2.
+///
+/// `validators` holds the current representation's etag and modification time, or `None` if the
+/// target resource does not exist. Returns the status to respond with if a precondition fails.
+pub fn check_preconditions(
+    method: &Method,
+    headers: &HeaderMap,
+    validators: Option<(&ETag, SystemTime)>,
+) -> Option<StatusCode> {
+    let is_get_or_head = matches!(*method, Method::GET | Method::HEAD);
+    if let Some(if_match) = headers.typed_get::<IfMatch>() {
+        let passes = match validators {
+            Some((etag, _)) => if_match.precondition_passes(etag),
+            None => false,
+        };
+        if !passes {
+            return Some(StatusCode::PRECONDITION_FAILED);
+        }
+    } else if let Some(if_unmodified_since) = headers.typed_get::<IfUnmodifiedSince>() {
+        if let Some((_, last_modified)) = validators {
+            if !if_unmodified_since.precondition_passes(last_modified) {
+                return Some(StatusCode::PRECONDITION_FAILED);
+            }
+        }
+    }
+    if let Some(if_none_match) = headers.typed_get::<IfNoneMatch>() {
+        let passes = match validators {
+            Some((etag, _)) => if_none_match.precondition_passes(etag),
+            None => true,
+        };
+        if !passes {
+            return if is_get_or_head {
+                Some(StatusCode::NOT_MODIFIED)
+            } else {
+                Some(StatusCode::PRECONDITION_FAILED)
+            };
+        }
+    } else if is_get_or_head {
+        if let Some(if_modified_since) = headers.typed_get::<IfModifiedSince>() {
+            if let Some((_, last_modified)) = validators {
+                if !if_modified_since.is_modified(last_modified) {
+                    return Some(StatusCode::NOT_MODIFIED);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Whether the `Accept-Encoding` request header allows a gzip encoded response.
+pub fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            let mut parts = v.split(';').map(|v| v.trim());
+            let coding = parts.next().unwrap_or_default();
+            let qvalue = parts
+                .find_map(|v| v.strip_prefix("q="))
+                .and_then(|v| v.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (coding.eq_ignore_ascii_case("gzip") || coding == "*") && qvalue > 0.0
+        })
+}
+
+/// Whether the `Accept` request header allows a json response, as it does when there is none.
+pub fn accepts_json(headers: &HeaderMap) -> bool {
+    let mut values = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .peekable();
+    if values.peek().is_none() {
+        return true;
+    }
+    values.any(|v| {
+        let mut parts = v.split(';').map(|v| v.trim());
+        let range = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let qvalue = parts
+            .find_map(|v| v.strip_prefix("q="))
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        matches!(range.as_str(), "application/json" | "application/*" | "*/*") && qvalue > 0.0
+    })
+}
+
+/// Whether a `Prefer` request header asks for `return=minimal`, a response without a body.
+pub fn prefers_return_minimal(headers: &HeaderMap) -> bool {
+    headers
+        .get_all("prefer")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            let preference = v.split(';').next().unwrap_or_default();
+            match preference.split_once('=') {
+                Some((name, value)) => {
+                    name.trim().eq_ignore_ascii_case("return")
+                        && value
+                            .trim()
+                            .trim_matches('"')
+                            .eq_ignore_ascii_case("minimal")
+                }
+                None => false,
+            }
+        })
+}
+
+/// The modification time requested by an upload through `X-OC-Mtime` or `X-Dufs-Mtime`,
+/// given as unix seconds with an optional fraction.
+pub fn upload_mtime(headers: &HeaderMap) -> Option<SystemTime> {
+    let value = ["x-oc-mtime", "x-dufs-mtime"]
+        .iter()
+        .find_map(|name| headers.get(*name))?
+        .to_str()
+        .ok()?
+        .trim();
+    let (secs, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let secs: u64 = secs.parse().ok()?;
+    let nanos = match fraction {
+        "" => 0,
+        v if v.len() <= 9 && v.bytes().all(|b| b.is_ascii_digit()) => {
+            format!("{v:0<9}").parse().ok()?
+        }
+        _ => return None,
+    };
+    SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headers::HeaderValue;
+
+    const ETAG: &str = r#""1700000000000-42""#;
+
+    fn check(method: Method, pairs: &[(&'static str, String)]) -> Option<StatusCode> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        let etag: ETag = ETAG.parse().unwrap();
+        check_preconditions(&method, &headers, Some((&etag, mtime())))
+    }
+
+    fn mtime() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    fn http_date(time: SystemTime) -> String {
+        let time: chrono::DateTime<chrono::Utc> = time.into();
+        time.format("%a, %d %b %Y %T GMT").to_string()
+    }
+
+    #[test]
+    fn test_if_none_match_takes_precedence() {
+        let stale = http_date(mtime() - Duration::from_secs(86400));
+        let fresh = http_date(mtime() + Duration::from_secs(86400));
+        assert_eq!(
+            check(
+                Method::GET,
+                &[("if-none-match", ETAG.into()), ("if-modified-since", stale)]
+            ),
+            Some(StatusCode::NOT_MODIFIED)
+        );
+        assert_eq!(
+            check(
+                Method::GET,
+                &[
+                    ("if-none-match", r#""other""#.into()),
+                    ("if-modified-since", fresh)
+                ]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let fresh = http_date(mtime() + Duration::from_secs(86400));
+        assert_eq!(
+            check(Method::GET, &[("if-modified-since", fresh.clone())]),
+            Some(StatusCode::NOT_MODIFIED)
+        );
+        assert_eq!(check(Method::PUT, &[("if-modified-since", fresh)]), None);
+    }
+
+    #[test]
+    fn test_mutating_preconditions() {
+        let stale = http_date(mtime() - Duration::from_secs(86400));
+        let fresh = http_date(mtime() + Duration::from_secs(86400));
+        assert_eq!(check(Method::PUT, &[("if-match", ETAG.into())]), None);
+        assert_eq!(
+            check(Method::PUT, &[("if-match", r#""other""#.into())]),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(
+            check(Method::DELETE, &[("if-unmodified-since", stale.clone())]),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(
+            check(Method::DELETE, &[("if-unmodified-since", fresh)]),
+            None
+        );
+        assert_eq!(
+            check(
+                Method::PUT,
+                &[("if-match", ETAG.into()), ("if-unmodified-since", stale)]
+            ),
+            None
+        );
+        assert_eq!(
+            check(Method::PUT, &[("if-none-match", "*".into())]),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+    }
+
+    #[test]
+    fn test_prefers_return_minimal() {
+        let prefers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("prefer", HeaderValue::from_str(value).unwrap());
+            prefers_return_minimal(&headers)
+        };
+        assert!(prefers("return=minimal"));
+        assert!(prefers("respond-async, RETURN = \"minimal\"; foo=bar"));
+        assert!(!prefers("return=representation"));
+        assert!(!prefers("minimal"));
+        assert!(!prefers_return_minimal(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_missing_resource() {
+        let headers = |name, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert_eq!(
+            check_preconditions(&Method::PUT, &headers("if-match", "*"), None),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(
+            check_preconditions(&Method::PUT, &headers("if-none-match", "*"), None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        let accepts = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_ENCODING, HeaderValue::from_str(value).unwrap());
+            accepts_gzip(&headers)
+        };
+        assert!(accepts("gzip, deflate, br"));
+        assert!(accepts("br;q=1.0, gzip;q=0.8"));
+        assert!(accepts("*"));
+        assert!(!accepts("br"));
+        assert!(!accepts("gzip;q=0, br"));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_accepts_json() {
+        let accepts = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT, HeaderValue::from_str(value).unwrap());
+            accepts_json(&headers)
+        };
+        assert!(accepts("application/json"));
+        assert!(accepts("text/html,application/xhtml+xml,*/*;q=0.8"));
+        assert!(!accepts("text/html"));
+        assert!(!accepts("text/html, application/json;q=0"));
+        assert!(accepts_json(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_upload_mtime() {
+        let mtime = |name: &'static str, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+            upload_mtime(&headers)
+        };
+        assert_eq!(
+            mtime("x-oc-mtime", "1700000000"),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(
+            mtime("x-dufs-mtime", "1700000000.25"),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_250))
+        );
+        assert_eq!(mtime("x-oc-mtime", "-1"), None);
+        assert_eq!(mtime("x-oc-mtime", "1.2.3"), None);
+        assert_eq!(upload_mtime(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,212 @@
+use hyper::{body::Bytes, header::SET_COOKIE, HeaderMap, StatusCode};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Set on the responses replayed from the cache.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+const MAX_KEY_LEN: usize = 255;
+
+/// A response kept to answer the retries of its request.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Tells the reservation apart from a later one, once this one expired.
+    id: u64,
+    expires: Instant,
+    /// The content type and length of the request, a retry sending others is refused.
+    ///
+    /// The body itself is streamed to disk before it could be compared, another body of the same
+    /// type and length is answered from the cache.
+    fingerprint: String,
+    /// `None` while the first request is still processed.
+    response: Option<CachedResponse>,
+}
+
+/// What to do with a request carrying an `Idempotency-Key`.
+#[derive(Debug)]
+pub enum Lookup {
+    /// The first request with the key, processed as usual.
+    Reserved(Arc<Reservation>),
+    /// A retry, answered from the cache.
+    Replay(CachedResponse),
+    /// The first request with the key is still processed.
+    InProgress,
+    /// The key was already used with another content type or length.
+    Mismatch,
+}
+
+/// The results of the uploads sent with an `Idempotency-Key`, for `--idempotency-ttl`.
+///
+/// Keys are scoped per user, method and path, and forgotten once the ttl is over.
+#[derive(Debug)]
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+    next_id: AtomicU64,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            ttl,
+            entries: Default::default(),
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    pub fn is_valid_key(key: &str) -> bool {
+        !key.is_empty() && key.len() <= MAX_KEY_LEN
+    }
+
+    pub fn begin(self: &Arc<Self>, scope: String, fingerprint: String) -> Lookup {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, v| v.expires > now);
+        if let Some(entry) = entries.get(&scope) {
+            return match &entry.response {
+                _ if entry.fingerprint != fingerprint => Lookup::Mismatch,
+                Some(response) => Lookup::Replay(response.clone()),
+                None => Lookup::InProgress,
+            };
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        entries.insert(
+            scope.clone(),
+            Entry {
+                id,
+                expires: now + self.ttl,
+                fingerprint,
+                response: None,
+            },
+        );
+        Lookup::Reserved(Arc::new(Reservation {
+            cache: self.clone(),
+            scope,
+            id,
+            done: AtomicBool::new(false),
+        }))
+    }
+}
+
+/// The key taken by a request, released unless its response gets cached.
+#[derive(Debug)]
+pub struct Reservation {
+    cache: Arc<IdempotencyCache>,
+    scope: String,
+    id: u64,
+    done: AtomicBool,
+}
+
+impl Reservation {
+    /// Keep the response for the retries, server errors are not kept so that retries run again.
+    ///
+    /// `Set-Cookie` is dropped, a replay must not hand out the session of the first request.
+    pub fn complete(&self, mut response: CachedResponse) {
+        if response.status.is_server_error() {
+            return;
+        }
+        response.headers.remove(SET_COOKIE);
+        let mut entries = self.cache.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&self.scope).filter(|v| v.id == self.id) {
+            entry.expires = Instant::now() + self.cache.ttl;
+            entry.response = Some(response);
+            self.done.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if !self.done.load(Ordering::Relaxed) {
+            let mut entries = self.cache.entries.lock().unwrap();
+            if entries.get(&self.scope).is_some_and(|v| v.id == self.id) {
+                entries.remove(&self.scope);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: StatusCode) -> CachedResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(SET_COOKIE, "session=abc".parse().unwrap());
+        CachedResponse {
+            status,
+            headers,
+            body: Bytes::from_static(b"ok"),
+        }
+    }
+
+    #[test]
+    fn test_idempotency_cache() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let Lookup::Reserved(reservation) = cache.begin("a".into(), "text/plain\n3".into()) else {
+            panic!("not reserved");
+        };
+        assert!(matches!(
+            cache.begin("a".into(), "text/plain\n3".into()),
+            Lookup::InProgress
+        ));
+        reservation.complete(response(StatusCode::CREATED));
+        drop(reservation);
+        match cache.begin("a".into(), "text/plain\n3".into()) {
+            Lookup::Replay(v) => {
+                assert_eq!(v.status, StatusCode::CREATED);
+                assert!(v.headers.get(SET_COOKIE).is_none());
+            }
+            v => panic!("unexpected {v:?}"),
+        }
+        assert!(matches!(
+            cache.begin("a".into(), "text/plain\n4".into()),
+            Lookup::Mismatch
+        ));
+        assert!(matches!(
+            cache.begin("a".into(), "text/html\n3".into()),
+            Lookup::Mismatch
+        ));
+        assert!(matches!(
+            cache.begin("b".into(), "text/plain\n3".into()),
+            Lookup::Reserved(_)
+        ));
+    }
+
+    #[test]
+    fn test_idempotency_cache_release() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let Lookup::Reserved(reservation) = cache.begin("a".into(), String::new()) else {
+            panic!("not reserved");
+        };
+        reservation.complete(response(StatusCode::INTERNAL_SERVER_ERROR));
+        drop(reservation);
+        assert!(matches!(
+            cache.begin("a".into(), String::new()),
+            Lookup::Reserved(_)
+        ));
+
+        let cache = IdempotencyCache::new(Duration::ZERO);
+        let Lookup::Reserved(reservation) = cache.begin("a".into(), String::new()) else {
+            panic!("not reserved");
+        };
+        reservation.complete(response(StatusCode::CREATED));
+        assert!(matches!(
+            cache.begin("a".into(), String::new()),
+            Lookup::Reserved(_)
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+#[cfg(feature = "heic")]
+use anyhow::anyhow;
+use anyhow::{bail, Result};
+#[cfg(feature = "heic")]
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::{avif::AvifEncoder, webp::WebPEncoder};
+use image::{ImageReader, Limits};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::utils::append_ext;
+
+const AVIF_SPEED: u8 = 8;
+/// Past this, the oldest results are removed from the cache.
+const IMAGE_CACHE_MAX_SIZE: u64 = 268435456; // 256M
+/// Bigger images are refused rather than decoded, the decoder allocates all the pixels at once.
+const MAX_IMAGE_DIMENSION: u32 = 16384;
+const MAX_IMAGE_ALLOC: u64 = 268435456; // 256M
+#[cfg(feature = "heic")]
+const HEIC_JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Webp,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn ext(&self) -> &'static str {
+        match self {
+            ImageFormat::Webp => "webp",
+            ImageFormat::Avif => "avif",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webp" => Ok(ImageFormat::Webp),
+            "avif" => Ok(ImageFormat::Avif),
+            _ => bail!("Invalid image format `{s}`"),
+        }
+    }
+}
+
+/// Transcode an image, returning the path of the cached result.
+///
+/// Results are cached in the system temp directory, keyed by source path, mtime, format and
+/// quality, and bounded by `IMAGE_CACHE_MAX_SIZE`. Returns `None` if the source can not be decoded,
+/// or exceeds the decoder limits. WebP output is always lossless, so
+/// `quality` only affects AVIF.
+pub async fn convert_image(
+    path: &Path,
+    mtime: SystemTime,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<Option<PathBuf>> {
+    let cache_dir = std::env::temp_dir().join("dufs-image-cache");
+    let mtime = mtime
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let key = format!("{}|{mtime}|{}|{quality}", path.display(), format.ext());
+    let cache_path = cache_dir.join(format!(
+        "{:x}.{}",
+        Sha256::digest(key.as_bytes()),
+        format.ext()
+    ));
+    if fs::metadata(&cache_path).await.is_ok() {
+        return Ok(Some(cache_path));
+    }
+    fs::create_dir_all(&cache_dir).await?;
+
+    let source = path.to_path_buf();
+    let target = cache_path.clone();
+    let cache_dir = cache_dir.clone();
+    tokio::task::spawn_blocking(move || -> Result<Option<PathBuf>> {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+        limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+        limits.max_alloc = Some(MAX_IMAGE_ALLOC);
+        let img = match ImageReader::open(&source)?
+            .with_guessed_format()
+            .ok()
+            .and_then(|mut reader| {
+                reader.limits(limits);
+                reader.decode().ok()
+            }) {
+            Some(img) => img,
+            None => return Ok(None),
+        };
+        let mut output = vec![];
+        match format {
+            ImageFormat::Webp => img.write_with_encoder(WebPEncoder::new_lossless(&mut output))?,
+            ImageFormat::Avif => img.write_with_encoder(AvifEncoder::new_with_speed_quality(
+                &mut output,
+                AVIF_SPEED,
+                quality,
+            ))?,
+        };
+        // Write to a temp file first so concurrent requests never see a partial result.
+        let temp_path = append_ext(format!("{}.dufstmp", Uuid::new_v4()), target.clone());
+        std::fs::write(&temp_path, output)?;
+        std::fs::rename(&temp_path, &target)?;
+        prune_cache(&cache_dir, IMAGE_CACHE_MAX_SIZE, &target);
+        Ok(Some(target))
+    })
+    .await?
+}
+
+/// Remove the oldest results until the cache fits in `max_size`, `keep` is never removed.
+///
+/// Errors are ignored, a concurrent prune may remove the same files. A result served at that time
+/// is still read to the end on unix.
+fn prune_cache(cache_dir: &Path, max_size: u64, keep: &Path) {
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let meta = entry.metadata().ok()?;
+            let path = entry.path();
+            // Results still being written are left to their writer
+            if !meta.is_file() || path.extension().is_some_and(|v| v == "dufstmp") {
+                return None;
+            }
+            Some((meta.modified().ok()?, meta.len(), path))
+        })
+        .collect();
+    let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort();
+    for (_, len, path) in files {
+        if size <= max_size {
+            break;
+        }
+        if path != keep && std::fs::remove_file(&path).is_ok() {
+            size = size.saturating_sub(len);
+        }
+    }
+}
+
+/// Decode the primary image of a HEIC/HEIF file and store it as a JPEG at `target`.
+#[cfg(feature = "heic")]
+pub async fn convert_heic_to_jpeg(source: &Path, target: &Path) -> Result<()> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let source = source.to_path_buf();
+    let target = target.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let data = std::fs::read(&source)?;
+        let context = HeifContext::read_from_bytes(&data)?;
+        let handle = context.primary_image_handle()?;
+        let decoded = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+        let plane = decoded
+            .planes()
+            .interleaved
+            .ok_or_else(|| anyhow!("No interleaved plane"))?;
+        // Rows are padded to `stride`, the encoder wants them packed.
+        let row = plane.width as usize * 3;
+        let pixels: Vec<u8> = plane
+            .data
+            .chunks(plane.stride)
+            .take(plane.height as usize)
+            .flat_map(|v| v.get(..row).unwrap_or_default())
+            .copied()
+            .collect();
+        let img = image::RgbImage::from_raw(plane.width, plane.height, pixels)
+            .ok_or_else(|| anyhow!("Truncated image data"))?;
+        let mut output = vec![];
+        img.write_with_encoder(JpegEncoder::new_with_quality(
+            &mut output,
+            HEIC_JPEG_QUALITY,
+        ))?;
+        let temp_path = append_ext(format!("{}.dufstmp", Uuid::new_v4()), target.clone());
+        std::fs::write(&temp_path, output)?;
+        std::fs::rename(&temp_path, &target)?;
+        Ok(())
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+    use std::time::Duration;
+
+    #[test]
+    fn test_prune_cache() {
+        let tmpdir = TempDir::new().unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for (i, name) in ["a.webp", "b.webp", "c.webp", "d.webp.dufstmp"]
+            .iter()
+            .enumerate()
+        {
+            let file = tmpdir.child(name);
+            file.write_str("0123456789").unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(file.path())
+                .unwrap()
+                .set_modified(mtime + Duration::from_secs(i as u64))
+                .unwrap();
+        }
+        let keep = tmpdir.child("a.webp");
+        prune_cache(tmpdir.path(), 15, keep.path());
+        keep.assert(predicates::path::exists());
+        tmpdir.child("b.webp").assert(predicates::path::missing());
+        tmpdir.child("c.webp").assert(predicates::path::missing());
+        tmpdir
+            .child("d.webp.dufstmp")
+            .assert(predicates::path::exists());
+    }
+}
@@ -1,18 +1,134 @@
 use anyhow::{Context, Result};
 use chrono::{Local, SecondsFormat};
+use flate2::{write::GzEncoder, Compression};
 use log::{Level, LevelFilter, Metadata, Record};
-use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+/// When to rotate the log file and what to do with the rotated segments.
+#[derive(Debug, Clone, Default)]
+pub struct LogRotation {
+    pub max_size: Option<u64>,
+    pub max_files: Option<usize>,
+    pub compress: bool,
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    rotation: LogRotation,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, rotation: LogRotation) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open the log file at '{}'", path.display()))?;
+        let size = file.metadata().map(|v| v.len()).unwrap_or_default();
+        Ok(Self {
+            path,
+            file,
+            size,
+            rotation,
+        })
+    }
+
+    fn write(&mut self, text: &str) {
+        if writeln!(self.file, "{text}").is_ok() {
+            self.size += text.len() as u64 + 1;
+        }
+        if matches!(self.rotation.max_size, Some(max_size) if self.size >= max_size) {
+            if let Err(err) = self.rotate() {
+                eprintln!("Failed to rotate the log file, {err}");
+            }
+        }
+    }
+
+    /// Rename the current log with a timestamp suffix and start a fresh one.
+    fn rotate(&mut self) -> io::Result<()> {
+        let timestamp = Local::now().format("%Y%m%d%H%M%S%3f");
+        let segment = PathBuf::from(format!("{}.{timestamp}", self.path.display()));
+        fs::rename(&self.path, &segment)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+
+        let path = self.path.clone();
+        let rotation = self.rotation.clone();
+        if rotation.compress {
+            std::thread::spawn(move || {
+                if let Err(err) = compress_segment(&segment) {
+                    eprintln!("Failed to compress '{}', {err}", segment.display());
+                }
+                prune_segments(&path, rotation.max_files);
+            });
+        } else {
+            prune_segments(&path, rotation.max_files);
+        }
+        Ok(())
+    }
+}
+
+fn compress_segment(segment: &Path) -> io::Result<()> {
+    let target = PathBuf::from(format!("{}.gz", segment.display()));
+    let mut encoder = GzEncoder::new(File::create(&target)?, Compression::default());
+    io::copy(&mut File::open(segment)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(segment)
+}
+
+/// Delete the oldest rotated segments of `path`, keeping at most `max_files`.
+fn prune_segments(path: &Path, max_files: Option<usize>) {
+    let (Some(max_files), Some(parent), Some(name)) = (max_files, path.parent(), path.file_name())
+    else {
+        return;
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(parent) else {
+        return;
+    };
+    let mut segments: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|v| v.strip_prefix(&prefix))
+                .map(|v| v.trim_end_matches(".gz"))
+                .is_some_and(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    // Timestamps have a fixed width, so sorting by name sorts by age.
+    segments.sort();
+    let excess = segments.len().saturating_sub(max_files);
+    for segment in &segments[..excess] {
+        let _ = fs::remove_file(segment);
+    }
+}
+
 struct SimpleLogger {
-    file: Option<Mutex<File>>,
+    file: Option<Mutex<LogFile>>,
+    max_level: LevelFilter,
 }
 
 impl log::Log for SimpleLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
+        // Only dufs itself logs below info, dependencies would drown it out.
         metadata.level() <= Level::Info
+            || (metadata.level() <= self.max_level && metadata.target().starts_with("dufs"))
     }
 
     fn log(&self, record: &Record) {
@@ -22,7 +138,7 @@ impl log::Log for SimpleLogger {
             match &self.file {
                 Some(file) => {
                     if let Ok(mut file) = file.lock() {
-                        let _ = writeln!(file, "{text}");
+                        file.write(&text);
                     }
                 }
                 None => {
@@ -39,23 +155,19 @@ impl log::Log for SimpleLogger {
     fn flush(&self) {}
 }
 
-pub fn init(log_file: Option<PathBuf>) -> Result<()> {
+pub fn init(
+    log_file: Option<PathBuf>,
+    rotation: LogRotation,
+    max_level: LevelFilter,
+) -> Result<()> {
     let file = match log_file {
         None => None,
-        Some(log_file) => {
-            let file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&log_file)
-                .with_context(|| {
-                    format!("Failed to open the log file at '{}'", log_file.display())
-                })?;
-            Some(Mutex::new(file))
-        }
+        Some(log_file) => Some(Mutex::new(LogFile::open(log_file, rotation)?)),
     };
-    let logger = SimpleLogger { file };
+    let max_level = max_level.max(LevelFilter::Info);
+    let logger = SimpleLogger { file, max_level };
     log::set_boxed_logger(Box::new(logger))
-        .map(|_| log::set_max_level(LevelFilter::Info))
+        .map(|_| log::set_max_level(max_level))
         .with_context(|| "Failed to init logger")?;
     Ok(())
 }
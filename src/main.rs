@@ -1,27 +1,58 @@
 mod args;
 mod auth;
+mod clamav;
+mod coalesce;
+#[cfg(unix)]
+mod confine;
+mod debug_log;
+#[cfg(feature = "compression-dictionary")]
+mod dictionary;
+#[cfg(feature = "embed")]
+mod embedded;
+#[cfg(feature = "encrypt")]
+mod encrypt;
 mod http_logger;
 mod http_utils;
+mod idempotency;
+#[cfg(feature = "image")]
+mod image_convert;
 mod logger;
+mod proxy;
+mod rewrite;
 mod server;
+mod sniff;
+mod stat_cache;
+mod throttle;
+mod time_format;
+#[cfg(feature = "tls")]
+mod tls;
+mod untar;
+mod upload_hook;
+mod upload_pipe;
+#[cfg(feature = "reverse-proxy")]
+mod upstream;
 mod utils;
+mod webhook;
+mod zip_mount;
 
 #[macro_use]
 extern crate log;
 
 use crate::args::{build_cli, print_completions, Args};
+use crate::http_utils::{Activity, ActivityStream};
 use crate::server::Server;
 #[cfg(feature = "tls")]
-use crate::utils::{load_certs, load_private_key};
+use crate::tls::build_tls_config;
 
 use anyhow::{anyhow, Context, Result};
 use args::BindAddr;
 use clap_complete::Shell;
 use futures_util::future::join_all;
+use logger::LogRotation;
 
 use hyper::{body::Incoming, service::service_fn, Request};
 use hyper_util::{
-    rt::{TokioExecutor, TokioIo},
+    rt::{TokioExecutor, TokioIo, TokioTimer},
     server::conn::auto::Builder,
 };
 use std::net::{IpAddr, SocketAddr, TcpListener as StdTcpListener};
@@ -33,7 +64,8 @@ use std::time::Duration;
 use tokio::time::timeout;
 use tokio::{net::TcpListener, task::JoinHandle};
 #[cfg(feature = "tls")]
-use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+use tokio_rustls::TlsAcceptor;
+use zip_mount::{is_zip_path, ZipMount};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -45,14 +77,29 @@ async fn main() -> Result<()> {
         return Ok(());
     }
     let mut args = Args::parse(matches)?;
-    logger::init(args.log_file.clone()).map_err(|e| anyhow!("Failed to init logger, {e}"))?;
+    let log_rotation = LogRotation {
+        max_size: args.log_max_size,
+        max_files: args.log_max_files,
+        compress: args.log_compress,
+    };
+    let log_level = if args.debug_bodies {
+        log::LevelFilter::Trace
+    } else {
+        log::LevelFilter::Info
+    };
+    logger::init(args.log_file.clone(), log_rotation, log_level)
+        .map_err(|e| anyhow!("Failed to init logger, {e}"))?;
     let (new_addrs, print_addrs) = check_addrs(&args)?;
     args.addrs = new_addrs;
     let running = Arc::new(AtomicBool::new(true));
+    let maintenance = Arc::new(AtomicBool::new(false));
     let listening = print_listening(&args, &print_addrs)?;
-    let handles = serve(args, running.clone())?;
+    let handles = serve(args, running.clone(), maintenance.clone()).await?;
     println!("{listening}");
 
+    #[cfg(unix)]
+    tokio::spawn(toggle_maintenance_on_signal(maintenance));
+
     tokio::select! {
         ret = join_all(handles) => {
             for r in ret {
@@ -69,76 +116,30 @@ async fn main() -> Result<()> {
     }
 }
 
-fn serve(args: Args, running: Arc<AtomicBool>) -> Result<Vec<JoinHandle<()>>> {
-    let addrs = args.addrs.clone();
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+async fn serve(
+    mut args: Args,
+    running: Arc<AtomicBool>,
+    maintenance: Arc<AtomicBool>,
+) -> Result<Vec<JoinHandle<()>>> {
     let port = args.port;
-    let tls_config = (args.tls_cert.clone(), args.tls_key.clone());
-    let server_handle = Arc::new(Server::init(args, running)?);
-    let mut handles = vec![];
-    for bind_addr in addrs.iter() {
-        let server_handle = server_handle.clone();
+    #[cfg(feature = "tls")]
+    let (tls_accepter, stapled_cert) = match build_tls_config(&args)? {
+        Some((config, cert)) => (Some(TlsAcceptor::from(Arc::new(config))), Some(cert)),
+        None => (None, None),
+    };
+    let mut listeners = vec![];
+    for bind_addr in args.addrs.iter() {
         match bind_addr {
             BindAddr::IpAddr(ip) => {
                 let listener = create_listener(SocketAddr::new(*ip, port))
                     .with_context(|| format!("Failed to bind `{ip}:{port}`"))?;
-
-                match &tls_config {
-                    #[cfg(feature = "tls")]
-                    (Some(cert_file), Some(key_file)) => {
-                        let certs = load_certs(cert_file)?;
-                        let key = load_private_key(key_file)?;
-                        let mut config = ServerConfig::builder()
-                            .with_no_client_auth()
-                            .with_single_cert(certs, key)?;
-                        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
-                        let config = Arc::new(config);
-                        let tls_accepter = TlsAcceptor::from(config);
-                        let handshake_timeout = Duration::from_secs(10);
-
-                        let handle = tokio::spawn(async move {
-                            loop {
-                                let Ok((stream, addr)) = listener.accept().await else {
-                                    continue;
-                                };
-                                let Some(stream) =
-                                    timeout(handshake_timeout, tls_accepter.accept(stream))
-                                        .await
-                                        .ok()
-                                        .and_then(|v| v.ok())
-                                else {
-                                    continue;
-                                };
-                                let stream = TokioIo::new(stream);
-                                tokio::spawn(handle_stream(
-                                    server_handle.clone(),
-                                    stream,
-                                    Some(addr),
-                                ));
-                            }
-                        });
-
-                        handles.push(handle);
-                    }
-                    (None, None) => {
-                        let handle = tokio::spawn(async move {
-                            loop {
-                                let Ok((stream, addr)) = listener.accept().await else {
-                                    continue;
-                                };
-                                let stream = TokioIo::new(stream);
-                                tokio::spawn(handle_stream(
-                                    server_handle.clone(),
-                                    stream,
-                                    Some(addr),
-                                ));
-                            }
-                        });
-                        handles.push(handle);
-                    }
-                    _ => {
-                        unreachable!()
-                    }
-                };
+                listeners.push(Listener::Tcp(listener));
             }
             #[cfg(unix)]
             BindAddr::SocketPath(path) => {
@@ -155,16 +156,84 @@ fn serve(args: Args, running: Arc<AtomicBool>) -> Result<Vec<JoinHandle<()>>> {
                 };
                 let listener = tokio::net::UnixListener::bind(socket_path)
                     .with_context(|| format!("Failed to bind `{}`", path))?;
+                listeners.push(Listener::Unix(listener));
+            }
+        }
+    }
+
+    // Everything read from outside the served directory must be loaded by now.
+    #[cfg(unix)]
+    confine::confine(&mut args)?;
+
+    #[cfg(feature = "tls")]
+    if let (Some(cert), Some(ocsp_file), Some(secs)) = (
+        stapled_cert,
+        args.tls_ocsp_file.clone(),
+        args.tls_ocsp_refresh,
+    ) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(err) = cert.staple(&ocsp_file) {
+                    warn!("Failed to refresh the OCSP response, {err:#}");
+                }
+            }
+        });
+    }
+
+    let zip_mount = match args.path_is_file && is_zip_path(&args.serve_path) {
+        true => Some(ZipMount::open(&args.serve_path).await?),
+        false => None,
+    };
+    let server_handle = Arc::new(Server::init(args, running, maintenance, zip_mount)?);
+    let mut handles = vec![];
+    for listener in listeners {
+        let server_handle = server_handle.clone();
+        match listener {
+            #[cfg(feature = "tls")]
+            Listener::Tcp(listener) if tls_accepter.is_some() => {
+                let tls_accepter = tls_accepter.clone().unwrap();
+                let handshake_timeout = Duration::from_secs(10);
+                let handle = tokio::spawn(async move {
+                    loop {
+                        let Ok((stream, addr)) = listener.accept().await else {
+                            continue;
+                        };
+                        let Some(stream) = timeout(handshake_timeout, tls_accepter.accept(stream))
+                            .await
+                            .ok()
+                            .and_then(|v| v.ok())
+                        else {
+                            continue;
+                        };
+                        tokio::spawn(handle_stream(server_handle.clone(), stream, Some(addr)));
+                    }
+                });
+                handles.push(handle);
+            }
+            Listener::Tcp(listener) => {
+                let handle = tokio::spawn(async move {
+                    loop {
+                        let Ok((stream, addr)) = listener.accept().await else {
+                            continue;
+                        };
+                        tokio::spawn(handle_stream(server_handle.clone(), stream, Some(addr)));
+                    }
+                });
+                handles.push(handle);
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
                 let handle = tokio::spawn(async move {
                     loop {
                         let Ok((stream, _addr)) = listener.accept().await else {
                             continue;
                         };
-                        let stream = TokioIo::new(stream);
                         tokio::spawn(handle_stream(server_handle.clone(), stream, None));
                     }
                 });
-
                 handles.push(handle);
             }
         }
@@ -172,17 +241,59 @@ fn serve(args: Args, running: Arc<AtomicBool>) -> Result<Vec<JoinHandle<()>>> {
     Ok(handles)
 }
 
-async fn handle_stream<T>(handle: Arc<Server>, stream: TokioIo<T>, addr: Option<SocketAddr>)
+async fn handle_stream<T>(handle: Arc<Server>, stream: T, addr: Option<SocketAddr>)
 where
     T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
 {
-    let hyper_service =
-        service_fn(move |request: Request<Incoming>| handle.clone().call(request, addr));
+    let throttle = handle.connection_throttle();
+    let keep_alive_timeout = handle.keep_alive_timeout();
+    let header_read_timeout = handle.header_read_timeout();
+    let max_header_size = handle.max_header_size();
+    let activity = Activity::default();
+    let stream = TokioIo::new(ActivityStream::new(stream, activity.clone()));
+    let service_activity = activity.clone();
+    let hyper_service = service_fn(move |request: Request<Incoming>| {
+        let busy = service_activity.busy();
+        let res = handle.clone().call(request, addr, throttle.clone());
+        async move {
+            let res = res.await;
+            drop(busy);
+            res
+        }
+    });
 
-    match Builder::new(TokioExecutor::new())
-        .serve_connection_with_upgrades(stream, hyper_service)
-        .await
-    {
+    let mut builder = Builder::new(TokioExecutor::new());
+    if let Some(header_read_timeout) = header_read_timeout {
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .header_read_timeout(header_read_timeout);
+    }
+    if let Some(max_header_size) = max_header_size {
+        // hyper answers 431 itself once a request head outgrows the read buffer.
+        builder.http1().max_buf_size(max_header_size);
+        builder
+            .http2()
+            .max_header_list_size(max_header_size.try_into().unwrap_or(u32::MAX));
+    }
+    let conn = builder.serve_connection_with_upgrades(stream, hyper_service);
+    tokio::pin!(conn);
+    let ret = match keep_alive_timeout {
+        None => conn.await,
+        Some(keep_alive_timeout) => loop {
+            let idle = activity.idle();
+            if idle >= keep_alive_timeout {
+                // Lets an in-flight request finish, an idle connection is closed right away.
+                conn.as_mut().graceful_shutdown();
+                break conn.await;
+            }
+            tokio::select! {
+                ret = conn.as_mut() => break ret,
+                _ = tokio::time::sleep(keep_alive_timeout - idle) => {}
+            }
+        },
+    };
+    match ret {
         Ok(()) => {}
         Err(_err) => {
             // This error only appears when the client doesn't send a request and terminate the connection.
@@ -298,6 +409,26 @@ fn print_listening(args: &Args, print_addrs: &[BindAddr]) -> Result<String> {
     Ok(output)
 }
 
+/// Toggle the maintenance mode, which rejects all writes, on each SIGUSR1.
+#[cfg(unix)]
+async fn toggle_maintenance_on_signal(maintenance: Arc<AtomicBool>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut stream = match signal(SignalKind::user_defined1()) {
+        Ok(v) => v,
+        Err(err) => {
+            error!("Failed to install SIGUSR1 signal handler, {err}");
+            return;
+        }
+    };
+    while stream.recv().await.is_some() {
+        let enabled = !maintenance.fetch_xor(true, Ordering::SeqCst);
+        info!(
+            "Maintenance mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+}
+
 async fn shutdown_signal() {
     tokio::signal::ctrl_c()
         .await
@@ -0,0 +1,126 @@
+use anyhow::{anyhow, bail, Result};
+use hyper::header::HeaderMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IP network such as `10.0.0.0/8`, a bare address matches only itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpCidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                mask_eq(u32::from(net).into(), u32::from(ip).into(), self.prefix, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                mask_eq(u128::from(net), u128::from(ip), self.prefix, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn mask_eq(a: u128, b: u128, prefix: u8, bits: u8) -> bool {
+    let shift = bits - prefix;
+    shift >= bits || (a >> shift) == (b >> shift)
+}
+
+impl FromStr for IpCidr {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!("Invalid cidr `{s}`");
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| err())?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| err())?,
+            None => bits,
+        };
+        if prefix > bits {
+            bail!(err());
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// Resolve the IP of the client behind any trusted proxies.
+///
+/// `X-Forwarded-For` is only honored when `peer` is trusted. The list is then walked from right
+/// to left, skipping trusted hops, so entries prepended by the client itself are never used.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpCidr]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|v| v.contains(ip));
+    if !is_trusted(peer) {
+        return peer;
+    }
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .collect();
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        match hop.trim().parse() {
+            Ok(ip) => {
+                client = ip;
+                if !is_trusted(ip) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn test_cidr_contains() {
+        let cidr: IpCidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains("10.1.2.3".parse().unwrap()));
+        assert!(cidr.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+        let cidr: IpCidr = "fd00::/8".parse().unwrap();
+        assert!(cidr.contains("fd12::1".parse().unwrap()));
+        assert!(!cidr.contains("10.1.2.3".parse().unwrap()));
+        let cidr: IpCidr = "0.0.0.0/0".parse().unwrap();
+        assert!(cidr.contains("1.2.3.4".parse().unwrap()));
+        let cidr: IpCidr = "127.0.0.1".parse().unwrap();
+        assert!(cidr.contains("127.0.0.1".parse().unwrap()));
+        assert!(!cidr.contains("127.0.0.2".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("10.0.0/8".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let trusted: Vec<IpCidr> =
+            vec!["127.0.0.1".parse().unwrap(), "10.0.0.0/8".parse().unwrap()];
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("1.1.1.1, 2.2.2.2, 10.0.0.2"),
+        );
+        let ip = |v: &str| v.parse::<IpAddr>().unwrap();
+        assert_eq!(
+            client_ip(ip("127.0.0.1"), &headers, &trusted),
+            ip("2.2.2.2")
+        );
+        assert_eq!(client_ip(ip("3.3.3.3"), &headers, &trusted), ip("3.3.3.3"));
+        assert_eq!(
+            client_ip(ip("127.0.0.1"), &HeaderMap::new(), &trusted),
+            ip("127.0.0.1")
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::{anyhow, bail, Result};
+use hyper::StatusCode;
+
+use crate::utils::glob;
+
+/// A `pattern target [status]` line of a `--rewrites` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewriteRule {
+    pattern: String,
+    target: String,
+    status: Option<StatusCode>,
+}
+
+/// What to do with a request whose path matched a rule.
+#[derive(Debug, PartialEq)]
+pub enum Rewrite<'a> {
+    /// Serve `target` as if it had been requested.
+    Internal(&'a str),
+    Redirect(&'a str, StatusCode),
+    /// Answer with a bare status, e.g. `410` for removed pages.
+    Status(StatusCode),
+}
+
+impl RewriteRule {
+    pub fn matches(&self, path: &str) -> bool {
+        glob(&self.pattern, path)
+    }
+
+    pub fn action(&self) -> Rewrite<'_> {
+        match self.status {
+            None | Some(StatusCode::OK) => Rewrite::Internal(&self.target),
+            Some(status) if status.is_redirection() => Rewrite::Redirect(&self.target, status),
+            Some(status) => Rewrite::Status(status),
+        }
+    }
+}
+
+/// Parse a rules file, one rule per line, blank lines and `#` comments are skipped.
+pub fn parse_rewrites(contents: &str) -> Result<Vec<RewriteRule>> {
+    let mut rules = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let err = || anyhow!("Invalid rewrite rule at line {}", index + 1);
+        // The status, when present, is the last word, so targets may contain spaces.
+        let (pattern, target, status) = match words.as_slice() {
+            [] => continue,
+            [_] => bail!(err()),
+            [pattern, rest @ .., last] if !rest.is_empty() && last.parse::<u16>().is_ok() => {
+                let status = last.parse().ok().and_then(|v| StatusCode::from_u16(v).ok());
+                (*pattern, rest.join(" "), Some(status.ok_or_else(err)?))
+            }
+            [pattern, rest @ ..] => (*pattern, rest.join(" "), None),
+        };
+        if ::glob::Pattern::new(pattern).is_err() {
+            bail!(err());
+        }
+        let is_internal = matches!(status, None | Some(StatusCode::OK));
+        if is_internal && !target.starts_with('/') {
+            bail!(
+                "Invalid rewrite rule at line {}, target must be a path",
+                index + 1
+            );
+        }
+        rules.push(RewriteRule {
+            pattern: pattern.to_string(),
+            target,
+            status,
+        });
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rewrites() {
+        let rules = parse_rewrites(
+            r#"
+# migrated docs
+/old/*      https://example.com/new  301
+/latest     /releases/v2.zip
+/手册       /docs/user manual.pdf
+/gone.html  -                        410
+"#,
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 4);
+        assert!(rules[0].matches("/old/a/b.html"));
+        assert!(!rules[0].matches("/older"));
+        assert_eq!(
+            rules[0].action(),
+            Rewrite::Redirect("https://example.com/new", StatusCode::MOVED_PERMANENTLY)
+        );
+        assert_eq!(rules[1].action(), Rewrite::Internal("/releases/v2.zip"));
+        assert_eq!(
+            rules[2].action(),
+            Rewrite::Internal("/docs/user manual.pdf")
+        );
+        assert_eq!(rules[3].action(), Rewrite::Status(StatusCode::GONE));
+        assert!(parse_rewrites("/a").is_err());
+        assert!(parse_rewrites("/a /b 99").is_err());
+        assert!(parse_rewrites("/a b").is_err());
+    }
+}
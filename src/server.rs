@@ -1,58 +1,103 @@
 #![allow(clippy::too_many_arguments)]
 
-use crate::auth::{www_authenticate, AccessPaths, AccessPerm};
-use crate::http_utils::{body_full, IncomingStream, LengthLimitedStream};
+use crate::args::{is_language_tag, EtagMode, HeavyTaskPolicy, SortDirs, SymlinkPolicy};
+use crate::auth::{
+    is_readonly_method, sign_session, verify_session, www_authenticate, AccessPaths, AccessPerm,
+    SESSION_COOKIE,
+};
+use crate::clamav;
+use crate::coalesce::{Coalescer, COALESCE_MAX_SIZE};
+use crate::debug_log::{self, DebugBody};
+#[cfg(feature = "encrypt")]
+use crate::encrypt;
+use crate::http_utils::{
+    accepts_gzip, accepts_json, body_full, check_preconditions, prefers_return_minimal,
+    upload_mtime, IncomingStream, LengthLimitedStream,
+};
+use crate::idempotency::{
+    CachedResponse, IdempotencyCache, Lookup, Reservation, IDEMPOTENCY_KEY, IDEMPOTENT_REPLAYED,
+};
+#[cfg(feature = "heic")]
+use crate::image_convert::convert_heic_to_jpeg;
+#[cfg(feature = "image")]
+use crate::image_convert::{convert_image, ImageFormat};
+use crate::proxy;
+use crate::rewrite::Rewrite;
+use crate::sniff;
+use crate::stat_cache::StatCache;
+use crate::throttle::{
+    ConnectionThrottle, Rate, RateLimiter, Throttle, ThrottledBody, ThrottledStream,
+};
+use crate::time_format::DEFAULT_TIME_FORMAT;
+use crate::untar::{normalize_entry_path, EntryKind, TarEntry, TarReader};
+use crate::upload_pipe::UploadPipe;
 use crate::utils::{
-    append_ext, decode_uri, encode_uri, get_file_mtime_and_mode, get_file_name, glob,
-    parse_range, try_get_file_name,
+    append_ext, decode_uri, encode_uri, get_file_mtime_and_mode, get_file_name, glob, glob_path,
+    move_file, natural_cmp, parse_range, parse_ranges, split_range, try_get_file_name, unix_now,
 };
+use crate::webhook::{Webhook, WebhookEvent};
+use crate::zip_mount::ZipMount;
 use crate::Args;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use async_compression::tokio::bufread::{GzipDecoder, GzipEncoder};
 use async_zip::{tokio::write::ZipFileWriter, Compression, ZipDateTime, ZipEntryBuilder};
-use base64::{engine::general_purpose::STANDARD, Engine as _};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine as _,
+};
 use bytes::Bytes;
-use chrono::{LocalResult, TimeZone, Utc};
+use chrono::{LocalResult, SecondsFormat, TimeZone, Utc};
+use filetime::FileTime;
 use futures_util::{pin_mut, TryStreamExt};
 use headers::{
     AcceptRanges, AccessControlAllowCredentials, AccessControlAllowOrigin, CacheControl,
-    ContentLength, ContentType, ETag, HeaderMap, HeaderMapExt, IfMatch, IfModifiedSince,
-    IfNoneMatch, IfRange, IfUnmodifiedSince, LastModified, Range,
+    ContentLength, ContentType, ETag, HeaderMap, HeaderMapExt, IfNoneMatch, IfRange, LastModified,
+    Range,
 };
-use http_body_util::{combinators::BoxBody, BodyExt, StreamBody};
+use http_body_util::{combinators::BoxBody, BodyExt, Limited, StreamBody};
 use hyper::body::Frame;
 use hyper::{
     body::Incoming,
     header::{
-        HeaderValue, AUTHORIZATION, CONNECTION, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
-        CONTENT_TYPE, RANGE,
+        HeaderValue, ACCEPT_LANGUAGE, ACCEPT_RANGES, ALLOW, AUTHORIZATION, CONNECTION,
+        CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_LOCATION,
+        CONTENT_RANGE, CONTENT_TYPE, COOKIE, ETAG, HOST, IF_MATCH, IF_MODIFIED_SINCE,
+        IF_NONE_MATCH, IF_RANGE, IF_UNMODIFIED_SINCE, LAST_MODIFIED, LOCATION, RANGE, RETRY_AFTER,
+        SET_COOKIE, VARY,
     },
-    Method, StatusCode, Uri,
+    Method, StatusCode,
 };
-use serde::Serialize;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::Metadata;
 use std::io::SeekFrom;
 use std::net::SocketAddr;
 use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
 use std::sync::atomic::{self, AtomicBool};
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio::{fs, io};
 
 use tokio_util::compat::FuturesAsyncWriteCompatExt;
 use tokio_util::io::{ReaderStream, StreamReader};
 use uuid::Uuid;
 use walkdir::WalkDir;
-use xml::escape::escape_str_pcdata;
+use xml::escape::{escape_str_attribute, escape_str_pcdata};
+use xml::reader::{EventReader, XmlEvent};
 
 pub type Request = hyper::Request<Incoming>;
 pub type Response = hyper::Response<BoxBody<Bytes, anyhow::Error>>;
+/// The block digests of a file, with the mtime and size they were hashed at.
+type BlockCache = HashMap<PathBuf, (u64, u64, Arc<Vec<String>>)>;
 
 const INDEX_HTML: &str = include_str!("../assets/index.html");
 const INDEX_CSS: &str = include_str!("../assets/index.css");
@@ -60,9 +105,35 @@ const INDEX_JS: &str = include_str!("../assets/index.js");
 const FAVICON_ICO: &[u8] = include_bytes!("../assets/favicon.ico");
 const INDEX_NAME: &str = "index.html";
 const BUF_SIZE: usize = 65536;
+const SEARCH_REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+const RENAME_BODY_MAX_SIZE: usize = 65536;
+const PROPFIND_BODY_MAX_SIZE: usize = 65536;
+const CHECKSUM_CACHE_MAX_ENTRIES: usize = 10000;
 const EDITABLE_TEXT_MAX_SIZE: u64 = 4194304; // 4M
+const LISTING_PREVIEW_MAX_FILES: usize = 100;
 // const RESUMABLE_UPLOAD_MIN_SIZE: u64 = 20971520; // 20M
+const LISTING_CHECKSUM_MAX_SIZE: u64 = 16777216; // 16M
 const HEALTH_CHECK_PATH: &str = "__dufs__/health";
+#[cfg(feature = "compression-dictionary")]
+const DICTIONARY_PATH: &str = "__dufs__/dictionary";
+const FAVICON_PATH: &str = "favicon.ico";
+const ROBOTS_PATH: &str = "robots.txt";
+const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
+const WELL_KNOWN_DIR: &str = ".well-known";
+/// Extensions of HLS/DASH files, with their types and whether they are manifests.
+const STREAMING_TYPES: &[(&str, &str, bool)] = &[
+    ("m3u8", "application/vnd.apple.mpegurl", true),
+    ("mpd", "application/dash+xml", true),
+    ("ts", "video/mp2t", false),
+    ("m4s", "video/iso.segment", false),
+];
+const STREAMING_SEGMENT_MAX_AGE: u64 = 86400;
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const MAINTENANCE_PATH: &str = "__dufs__/maintenance";
+const LOGOUT_PATH: &str = "__dufs__/logout";
+const PROTECT_MARKER_EXT: &str = ".dufs-lock";
+/// The gzip header and trailer, a smaller file has no ISIZE.
+const GZIP_MIN_SIZE: u64 = 18;
 
 pub struct Server {
     args: Args,
@@ -70,10 +141,26 @@ pub struct Server {
     html: Cow<'static, str>,
     single_file_req_paths: Vec<String>,
     running: Arc<AtomicBool>,
+    maintenance: Arc<AtomicBool>,
+    global_limiter: Option<Arc<RateLimiter>>,
+    checksum_cache: Mutex<HashMap<PathBuf, (u64, u64, String)>>,
+    block_cache: Mutex<BlockCache>,
+    coalescer: Option<Coalescer>,
+    heavy_tasks: Option<Arc<Semaphore>>,
+    zip_mount: Option<ZipMount>,
+    webhook: Option<Webhook>,
+    idempotency: Option<Arc<IdempotencyCache>>,
+    stat_cache: Option<StatCache>,
+    content: ContentReader,
 }
 
 impl Server {
-    pub fn init(args: Args, running: Arc<AtomicBool>) -> Result<Self> {
+    pub fn init(
+        mut args: Args,
+        running: Arc<AtomicBool>,
+        maintenance: Arc<AtomicBool>,
+        zip_mount: Option<ZipMount>,
+    ) -> Result<Self> {
         let assets_prefix = format!("__dufs_v{}__/", env!("CARGO_PKG_VERSION"));
         let single_file_req_paths = if args.path_is_file {
             vec![
@@ -88,24 +175,196 @@ impl Server {
         } else {
             vec![]
         };
-        let html = match args.assets.as_ref() {
+        let mut html = match args.assets.as_ref() {
             Some(path) => Cow::Owned(std::fs::read_to_string(path.join("index.html"))?),
             None => Cow::Borrowed(INDEX_HTML),
         };
+        if let Some(path) = args.assets.as_ref() {
+            if !html.contains("__INDEX_DATA__") {
+                warn!(
+                    "`{}` lacks the __INDEX_DATA__ placeholder, falling back to the built-in assets",
+                    path.join("index.html").display()
+                );
+                args.assets = None;
+                html = Cow::Borrowed(INDEX_HTML);
+            }
+        }
+        for (name, value) in &args.template_vars {
+            let placeholder = format!("__{}__", name.to_uppercase());
+            html = Cow::Owned(html.replace(&placeholder, &escape_str_attribute(value)));
+        }
+        let global_limiter = args.global_rate.map(|v| Arc::new(RateLimiter::new(v)));
+        let coalescer = args.coalesce_downloads.then(Coalescer::default);
+        let heavy_tasks = args
+            .heavy_task_concurrency
+            .map(|v| Arc::new(Semaphore::new(v)));
+        let webhook = args.webhook.clone().map(Webhook::spawn);
+        let idempotency = args
+            .idempotency_ttl
+            .map(|v| IdempotencyCache::new(Duration::from_secs(v)));
+        let stat_cache = args
+            .stat_cache_ttl
+            .map(|v| StatCache::new(Duration::from_secs(v)));
+        let content = ContentReader {
+            #[cfg(feature = "encrypt")]
+            key: args.encryption_key.clone(),
+        };
         Ok(Self {
             args,
             running,
+            maintenance,
+            global_limiter,
+            checksum_cache: Default::default(),
+            block_cache: Default::default(),
+            coalescer,
+            heavy_tasks,
+            zip_mount,
+            webhook,
+            idempotency,
+            stat_cache,
+            content,
             single_file_req_paths,
             assets_prefix,
             html,
         })
     }
 
+    /// Create the throttles for a new connection, honoring the per-connection and global rates.
+    pub fn connection_throttle(&self) -> ConnectionThrottle {
+        let limiter = |rate: Option<Rate>| rate.map(|v| Arc::new(RateLimiter::new(v)));
+        ConnectionThrottle {
+            download: Throttle::new([
+                limiter(self.args.download_rate),
+                self.global_limiter.clone(),
+            ]),
+            upload: Throttle::new([limiter(self.args.upload_rate), self.global_limiter.clone()]),
+        }
+    }
+
+    /// The secs a `503` asks clients to wait, spread by --retry-after-jitter so that they don't
+    /// all retry at once.
+    fn retry_after(&self) -> u64 {
+        let jitter = self.args.retry_after_jitter;
+        let random = match jitter {
+            0 => 0,
+            _ => (Uuid::new_v4().as_u128() % (jitter as u128 + 1)) as u64,
+        };
+        self.args.retry_after_base + random
+    }
+
+    /// Take a slot of --heavy-task-concurrency, waiting for one unless the policy is to reject.
+    ///
+    /// `None` once rejected with `503`, the request is answered.
+    async fn heavy_task(&self, res: &mut Response) -> Option<Option<OwnedSemaphorePermit>> {
+        let permit = self.try_heavy_task().await;
+        if permit.is_none() {
+            *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            *res.body_mut() = body_full("Too many heavy tasks, retry later");
+        }
+        permit
+    }
+
+    /// Like `heavy_task`, for callers which do without the task rather than answer `503`.
+    async fn try_heavy_task(&self) -> Option<Option<OwnedSemaphorePermit>> {
+        let Some(semaphore) = self.heavy_tasks.clone() else {
+            return Some(None);
+        };
+        let permit = match self.args.heavy_task_policy {
+            HeavyTaskPolicy::Queue => semaphore.acquire_owned().await.ok(),
+            HeavyTaskPolicy::Reject => semaphore.try_acquire_owned().ok(),
+        };
+        permit.map(Some)
+    }
+
+    /// Reserve the `Idempotency-Key` of an upload, or answer its retry from the cache.
+    ///
+    /// `true` once the request is answered.
+    fn begin_idempotent(
+        &self,
+        method: &Method,
+        relative_path: &str,
+        user: Option<&str>,
+        headers: &HeaderMap<HeaderValue>,
+        res: &mut Response,
+    ) -> bool {
+        let (Some(cache), Some(key)) = (self.idempotency.as_ref(), headers.get(IDEMPOTENCY_KEY))
+        else {
+            return false;
+        };
+        let Some(key) = key
+            .to_str()
+            .ok()
+            .filter(|v| IdempotencyCache::is_valid_key(v))
+        else {
+            status_bad_request(res, "Invalid Idempotency-Key");
+            return true;
+        };
+        let scope = format!(
+            "{}\n{method}\n{relative_path}\n{key}",
+            user.unwrap_or_default()
+        );
+        let fingerprint = format!(
+            "{}\n{}",
+            headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default(),
+            headers
+                .typed_get::<ContentLength>()
+                .map(|v| v.0.to_string())
+                .unwrap_or_default()
+        );
+        match cache.begin(scope, fingerprint) {
+            Lookup::Reserved(reservation) => {
+                res.extensions_mut().insert(reservation);
+                return false;
+            }
+            Lookup::Replay(cached) => {
+                *res.status_mut() = cached.status;
+                *res.headers_mut() = cached.headers;
+                res.headers_mut()
+                    .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+                *res.body_mut() = body_full(cached.body);
+            }
+            Lookup::InProgress => {
+                *res.status_mut() = StatusCode::CONFLICT;
+                *res.body_mut() =
+                    body_full("A request with the Idempotency-Key is still processed");
+            }
+            Lookup::Mismatch => {
+                *res.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+                *res.body_mut() = body_full("The Idempotency-Key was used with another request");
+            }
+        }
+        true
+    }
+
+    /// How long a connection may sit idle between requests.
+    pub fn keep_alive_timeout(&self) -> Option<Duration> {
+        self.args.keep_alive_timeout.map(Duration::from_secs)
+    }
+
+    /// How long a client has to send the complete headers of a request.
+    pub fn header_read_timeout(&self) -> Option<Duration> {
+        self.args.header_read_timeout.map(Duration::from_secs)
+    }
+
+    /// The largest request head accepted before answering `431`.
+    pub fn max_header_size(&self) -> Option<usize> {
+        self.args.max_header_size.map(|v| v as usize)
+    }
+
     pub async fn call(
         self: Arc<Self>,
-        req: Request,
+        mut req: Request,
         addr: Option<SocketAddr>,
+        throttle: ConnectionThrottle,
     ) -> Result<Response, hyper::Error> {
+        req.extensions_mut().insert(throttle.upload);
+        let no_cache = self.args.no_cache;
+        if no_cache && matches!(*req.method(), Method::GET | Method::HEAD) {
+            remove_conditional_headers(req.headers_mut());
+        }
         let uri = req.uri().clone();
         let assets_prefix = &self.assets_prefix;
         let enable_cors = self.args.enable_cors;
@@ -117,13 +376,31 @@ impl Server {
             .unwrap_or_default();
         let mut http_log_data = self.args.http_logger.data(&req);
         if let Some(addr) = addr {
-            http_log_data.insert("remote_addr".to_string(), addr.ip().to_string());
+            let remote_addr =
+                proxy::client_ip(addr.ip(), req.headers(), &self.args.trusted_proxies);
+            http_log_data.insert("remote_addr".to_string(), remote_addr.to_string());
         }
 
+        let log_ignored = self.args.log_ignore.iter().any(|v| glob(v, uri.path()));
+        let debug_label = self.args.debug_bodies.then(|| {
+            debug_log::log_request(&req);
+            format!("{} {}", req.method(), uri.path())
+        });
+
+        let return_minimal = matches!(req.method().as_str(), "PUT" | "POST" | "MKCOL" | "DELETE")
+            && prefers_return_minimal(req.headers());
+
+        let is_write = !is_readonly_method(req.method());
         let mut res = match self.clone().handle(req, is_microsoft_webdav).await {
-            Ok(res) => {
+            Ok(mut res) => {
+                if let Some(reservation) = res.extensions_mut().remove::<Arc<Reservation>>() {
+                    res = keep_idempotent_response(res, &reservation).await;
+                }
+                if return_minimal {
+                    apply_return_minimal(&mut res);
+                }
                 http_log_data.insert("status".to_string(), res.status().as_u16().to_string());
-                if !uri.path().starts_with(assets_prefix) {
+                if !uri.path().starts_with(assets_prefix) && !log_ignored {
                     self.args.http_logger.log(&http_log_data, None);
                 }
                 res
@@ -133,13 +410,18 @@ impl Server {
                 let status = StatusCode::INTERNAL_SERVER_ERROR;
                 *res.status_mut() = status;
                 http_log_data.insert("status".to_string(), status.as_u16().to_string());
-                self.args
-                    .http_logger
-                    .log(&http_log_data, Some(err.to_string()));
+                if !log_ignored {
+                    self.args
+                        .http_logger
+                        .log(&http_log_data, Some(err.to_string()));
+                }
                 res
             }
         };
 
+        if let Some(stat_cache) = self.stat_cache.as_ref().filter(|_| is_write) {
+            stat_cache.invalidate();
+        }
         if is_microsoft_webdav {
             // microsoft webdav requires this.
             res.headers_mut()
@@ -148,6 +430,26 @@ impl Server {
         if enable_cors {
             add_cors(&mut res);
         }
+        if no_cache {
+            disable_caching(&mut res);
+        }
+        if res.status() == StatusCode::SERVICE_UNAVAILABLE
+            && !res.headers().contains_key(RETRY_AFTER)
+        {
+            res.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(self.retry_after()));
+        }
+        if let Some(label) = debug_label {
+            trace!(
+                "Response {} to {label}\n{}",
+                res.status(),
+                debug_log::format_headers(res.headers())
+            );
+            res = res.map(|body| DebugBody::new(body, label).boxed());
+        }
+        if !throttle.download.is_empty() {
+            res = res.map(|body| ThrottledBody::new(body, throttle.download).boxed());
+        }
         Ok(res)
     }
 
@@ -162,6 +464,51 @@ impl Server {
         let headers = req.headers();
         let method = req.method().clone();
 
+        if let Some(max) = self.args.max_request_line_length {
+            let len = match req.uri().path_and_query() {
+                Some(v) if req.uri().authority().is_none() => v.as_str().len(),
+                _ => req.uri().to_string().len(),
+            };
+            if len as u64 > max {
+                *res.status_mut() = StatusCode::URI_TOO_LONG;
+                return Ok(res);
+            }
+        }
+
+        if method == Method::OPTIONS && req.uri() == "*" {
+            self.set_allow_headers(&mut res);
+            return Ok(res);
+        }
+
+        // Rules match the decoded request path, the first matching one wins.
+        let rewritten;
+        let req_path = match decode_uri(req_path)
+            .and_then(|path| self.args.rewrite_rules.iter().find(|v| v.matches(&path)))
+        {
+            None => req_path,
+            Some(rule) => match rule.action() {
+                Rewrite::Internal(target) => {
+                    rewritten = encode_uri(target);
+                    rewritten.as_str()
+                }
+                Rewrite::Redirect(target, status) => {
+                    let location = if target.contains("://") {
+                        target.to_string()
+                    } else {
+                        encode_uri(target)
+                    };
+                    *res.status_mut() = status;
+                    res.headers_mut()
+                        .insert(LOCATION, HeaderValue::from_str(&location)?);
+                    return Ok(res);
+                }
+                Rewrite::Status(status) => {
+                    *res.status_mut() = status;
+                    return Ok(res);
+                }
+            },
+        };
+
         let relative_path = match self.resolve_path(req_path) {
             Some(v) => v,
             None => {
@@ -170,6 +517,35 @@ impl Server {
             }
         };
 
+        let query = req.uri().query().unwrap_or_default();
+        let query_params: HashMap<String, String> = form_urlencoded::parse(query.as_bytes())
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        // `?entry=sub/file` addresses a file nested in the requested directory
+        let entry = match query_params.get("entry") {
+            Some(entry) if matches!(method, Method::GET | Method::HEAD) => {
+                match normalize_relative_path(entry) {
+                    Some(entry) if !entry.is_empty() => Some(entry),
+                    _ => {
+                        status_bad_request(&mut res, "Invalid Entry");
+                        return Ok(res);
+                    }
+                }
+            }
+            _ => None,
+        };
+        let relative_path = match entry.as_ref() {
+            Some(entry) if relative_path.is_empty() => entry.clone(),
+            Some(entry) => format!("{relative_path}/{entry}"),
+            None => relative_path,
+        };
+
+        if self.is_denied_dotfile(&relative_path) {
+            status_not_found(&mut res);
+            return Ok(res);
+        }
+
         if method == Method::GET
             && self
                 .handle_internal(&relative_path, headers, &mut res)
@@ -179,10 +555,27 @@ impl Server {
         }
 
         let authorization = headers.get(AUTHORIZATION);
-        let guard =
+        if self.args.auth_session && relative_path == LOGOUT_PATH {
+            self.clear_session(&mut res)?;
+            self.auth_reject(&mut res)?;
+            return Ok(res);
+        }
+        let session_user = match authorization {
+            None => self.session_user(headers),
+            Some(_) => None,
+        };
+        let is_public_well_known = self.args.well_known_no_auth
+            && matches!(method, Method::GET | Method::HEAD)
+            && is_well_known_path(&relative_path);
+        let guard = if is_public_well_known {
+            (None, Some(AccessPaths::new(AccessPerm::ReadOnly)))
+        } else if let Some(user) = session_user.as_deref() {
+            self.args.auth.guard_session(&relative_path, &method, user)
+        } else {
             self.args
                 .auth
-                .guard(&relative_path, &method, authorization, is_microsoft_webdav);
+                .guard(&relative_path, &method, authorization, is_microsoft_webdav)
+        };
 
         let (user, access_paths) = match guard {
             (None, None) => {
@@ -195,11 +588,14 @@ impl Server {
             }
             (x, Some(y)) => (x, y),
         };
+        let mut access_paths = access_paths;
+        access_paths.set_reveal_hidden(self.args.serve_hidden_with_auth && user.is_some());
 
-        let query = req.uri().query().unwrap_or_default();
-        let query_params: HashMap<String, String> = form_urlencoded::parse(query.as_bytes())
-            .map(|(k, v)| (k.to_string(), v.to_string()))
-            .collect();
+        if self.args.auth_session && authorization.is_some() {
+            if let Some(user) = user.as_deref() {
+                self.set_session(user, &mut res)?;
+            }
+        }
 
         if method.as_str() == "CHECKAUTH" {
             match user.clone() {
@@ -210,12 +606,100 @@ impl Server {
             }
             return Ok(res);
         } else if method.as_str() == "LOGOUT" {
+            if self.args.auth_session {
+                self.clear_session(&mut res)?;
+            }
             self.auth_reject(&mut res)?;
             return Ok(res);
         }
 
+        if relative_path == MAINTENANCE_PATH {
+            self.handle_maintenance(&method, user.as_deref(), &query_params, &mut res)?;
+            return Ok(res);
+        }
+
+        if !is_readonly_method(&method) && self.maintenance.load(atomic::Ordering::SeqCst) {
+            *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            *res.body_mut() = body_full("Under maintenance, writes are disabled");
+            return Ok(res);
+        }
+
+        #[cfg(feature = "reverse-proxy")]
+        if let Some(upstream) = self
+            .args
+            .upstreams
+            .iter()
+            .find(|v| v.matches(&relative_path))
+        {
+            return upstream
+                .forward(req, self.args.proxy_pass_credentials)
+                .await;
+        }
+
+        if matches!(method, Method::PUT | Method::PATCH)
+            && self.begin_idempotent(&method, &relative_path, user.as_deref(), headers, &mut res)
+        {
+            return Ok(res);
+        }
+
+        if method == Method::PUT {
+            if let Some(pipe) = self.args.upload_pipes.get(&relative_path) {
+                if !self.args.allow_upload {
+                    status_forbid(&mut res);
+                } else if self.is_upload_too_large(headers) {
+                    status_payload_too_large(&mut res);
+                } else {
+                    self.handle_pipe(pipe, req, &mut res).await?;
+                }
+                return Ok(res);
+            }
+        }
+
+        let relative_path = match method == Method::PUT && entry.is_none() {
+            true => match self.ingest_path(&relative_path)? {
+                Some(v) => v,
+                None => relative_path,
+            },
+            false => relative_path,
+        };
+
         let head_only = method == Method::HEAD;
 
+        if let Some(target) = self.args.root_redirect.as_ref() {
+            if relative_path.is_empty()
+                && query.is_empty()
+                && matches!(method, Method::GET | Method::HEAD)
+            {
+                let status = if self.args.root_redirect_permanent {
+                    StatusCode::MOVED_PERMANENTLY
+                } else {
+                    StatusCode::FOUND
+                };
+                *res.status_mut() = status;
+                res.headers_mut()
+                    .insert(LOCATION, HeaderValue::from_str(&encode_uri(target))?);
+                return Ok(res);
+            }
+        }
+
+        if let Some(zip_mount) = self.zip_mount.as_ref() {
+            self.handle_zip_mount(
+                zip_mount,
+                &access_paths,
+                &method,
+                req_path,
+                query,
+                &relative_path,
+                &query_params,
+                headers,
+                head_only,
+                user,
+                &mut res,
+            )
+            .await?;
+            return Ok(res);
+        }
+
         if self.args.path_is_file {
             if self
                 .single_file_req_paths
@@ -237,9 +721,29 @@ impl Server {
             }
         };
 
+        let path = match self.negotiate_language(&method, &relative_path, entry.is_some(), headers)
+        {
+            Some(variant) => {
+                res.headers_mut()
+                    .append(VARY, HeaderValue::from_static("Accept-Language"));
+                match variant {
+                    Some((variant, lang)) => {
+                        res.headers_mut()
+                            .insert(CONTENT_LANGUAGE, HeaderValue::from_str(&lang)?);
+                        variant
+                    }
+                    None => path,
+                }
+            }
+            None => path,
+        };
         let path = path.as_path();
 
-        let (is_miss, is_dir, is_file, size) = match fs::metadata(path).await.ok() {
+        let meta = match self.stat_cache.as_ref() {
+            Some(stat_cache) => stat_cache.metadata(path).await.ok(),
+            None => fs::metadata(path).await.ok(),
+        };
+        let (is_miss, is_dir, is_file, size) = match meta.as_ref() {
             Some(meta) => (false, meta.is_dir(), meta.is_file(), meta.len()),
             None => (true, false, false, 0),
         };
@@ -257,10 +761,113 @@ impl Server {
             return Ok(res);
         }
 
+        if entry.is_some() && !is_file {
+            status_not_found(&mut res);
+            return Ok(res);
+        }
+
+        // Relative links only resolve against `/dir/`, so give every resource a single URL.
+        if !self.args.no_redirect_slash
+            && matches!(method, Method::GET | Method::HEAD)
+            && entry.is_none()
+            && (is_dir || is_file)
+            && is_dir != req_path.ends_with('/')
+        {
+            let mut location = format!("{}{}", self.args.uri_prefix, encode_uri(&relative_path));
+            if is_dir && !location.ends_with('/') {
+                location.push('/');
+            }
+            if !query.is_empty() {
+                location.push('?');
+                location.push_str(query);
+            }
+            *res.status_mut() = StatusCode::MOVED_PERMANENTLY;
+            res.headers_mut()
+                .insert(LOCATION, HeaderValue::from_str(&location)?);
+            return Ok(res);
+        }
+
+        if matches!(method, Method::PUT | Method::PATCH | Method::DELETE) && !is_dir {
+            let validators = match meta.as_ref() {
+                Some(meta) => self.file_cache_headers(path, meta, "").await,
+                None => None,
+            };
+            let validators = validators
+                .as_ref()
+                .map(|(etag, last_modified)| (etag, (*last_modified).into()));
+            if let Some(status) = check_preconditions(&method, headers, validators) {
+                *res.status_mut() = status;
+                return Ok(res);
+            }
+        }
+
+        if self
+            .args
+            .temp_dir
+            .as_ref()
+            .is_some_and(|v| path.starts_with(v))
+        {
+            status_not_found(&mut res);
+            return Ok(res);
+        }
+
+        if self.args.serve_hidden_with_auth
+            && !access_paths.reveal_hidden()
+            && !is_miss
+            && self.is_hidden_path(&relative_path, is_dir)
+        {
+            status_not_found(&mut res);
+            return Ok(res);
+        }
+
+        if self.args.hidden_deny_write
+            && matches!(method.as_str(), "PUT" | "PATCH" | "MKCOL")
+            && self.is_hidden_path(&relative_path, is_dir || method.as_str() == "MKCOL")
+        {
+            status_forbid(&mut res);
+            return Ok(res);
+        }
+
+        if matches!(method.as_str(), "PUT" | "PATCH" | "DELETE" | "MOVE")
+            && is_file
+            && self.is_protected(path)
+        {
+            status_forbid(&mut res);
+            return Ok(res);
+        }
+
+        if matches!(method, Method::PUT | Method::PATCH)
+            && !is_dir
+            && !self.is_upload_ext_allowed(&relative_path)
+        {
+            status_forbid(&mut res);
+            return Ok(res);
+        }
+
         match method {
             Method::GET | Method::HEAD => {
                 if is_dir {
-                    if render_try_index {
+                    if query_params.contains_key("checksums") {
+                        self.clone()
+                            .handle_checksums_dir(
+                                path,
+                                &query_params,
+                                head_only,
+                                access_paths,
+                                &mut res,
+                            )
+                            .await?;
+                    } else if query_params.contains_key("feed") {
+                        self.handle_feed_dir(
+                            path,
+                            &query_params,
+                            headers,
+                            head_only,
+                            access_paths,
+                            &mut res,
+                        )
+                        .await?;
+                    } else if render_try_index {
                         if allow_archive && has_query_flag(&query_params, "zip") {
                             if !allow_archive {
                                 status_not_found(&mut res);
@@ -272,6 +879,7 @@ impl Server {
                             self.handle_search_dir(
                                 path,
                                 &query_params,
+                                headers,
                                 head_only,
                                 user,
                                 access_paths,
@@ -312,17 +920,23 @@ impl Server {
                         self.handle_search_dir(
                             path,
                             &query_params,
+                            headers,
                             head_only,
                             user,
                             access_paths,
                             &mut res,
                         )
                         .await?;
+                    } else if is_ndjson_request(&query_params, headers) {
+                        self.clone()
+                            .handle_ls_dir_ndjson(path, head_only, access_paths, &mut res)
+                            .await?;
                     } else {
                         self.handle_ls_dir(
                             path,
                             true,
                             &query_params,
+                            headers,
                             head_only,
                             user,
                             access_paths,
@@ -331,18 +945,68 @@ impl Server {
                         .await?;
                     }
                 } else if is_file {
-                    if has_query_flag(&query_params, "edit") {
-                        self.handle_edit_file(path, DataKind::Edit, head_only, user, &mut res)
+                    #[cfg(feature = "image")]
+                    if let Some(format) = query_params.get("format") {
+                        self.handle_convert_image(path, format, headers, head_only, &mut res)
                             .await?;
+                        return Ok(res);
+                    }
+                    let is_page = has_query_flag(&query_params, "edit")
+                        || has_query_flag(&query_params, "view");
+                    if is_page && self.args.no_ui {
+                        status_not_found(&mut res);
+                    } else if has_query_flag(&query_params, "edit") {
+                        self.handle_edit_file(
+                            path,
+                            DataKind::Edit,
+                            headers,
+                            head_only,
+                            user,
+                            &mut res,
+                        )
+                        .await?;
                     } else if has_query_flag(&query_params, "view") {
-                        self.handle_edit_file(path, DataKind::View, head_only, user, &mut res)
+                        self.handle_edit_file(
+                            path,
+                            DataKind::View,
+                            headers,
+                            head_only,
+                            user,
+                            &mut res,
+                        )
+                        .await?;
+                    } else if has_query_bool(&query_params, "follow") {
+                        self.handle_follow_file(path, &query_params, head_only, &mut res)
+                            .await?;
+                    } else if has_query_bool(&query_params, "manifest") {
+                        self.handle_block_manifest(path, headers, head_only, &mut res)
+                            .await?;
+                    } else if has_query_flag(&query_params, "hash")
+                        || query_params.contains_key("checksum")
+                    {
+                        self.handle_hash_file(path, &query_params, headers, head_only, &mut res)
                             .await?;
-                    } else if has_query_flag(&query_params, "hash") {
-                        self.handle_hash_file(path, head_only, &mut res).await?;
                     } else {
                         self.handle_send_file(path, headers, head_only, &mut res)
                             .await?;
+                        if has_query_bool(&query_params, "download") {
+                            set_content_disposition(&mut res, false, try_get_file_name(path)?)?;
+                        } else if has_query_bool(&query_params, "inline") {
+                            set_content_disposition(&mut res, true, try_get_file_name(path)?)?;
+                        }
                     }
+                } else if self.args.join_parts
+                    && self
+                        .handle_send_parts(path, headers, head_only, &mut res)
+                        .await?
+                {
+                    // Served as the concatenation of its parts
+                } else if self.args.gunzip
+                    && self
+                        .handle_send_gunzip(path, headers, head_only, &mut res)
+                        .await?
+                {
+                    // Served from its .gz
                 } else if render_spa {
                     self.handle_render_spa(path, headers, head_only, &mut res)
                         .await?;
@@ -351,6 +1015,7 @@ impl Server {
                         path,
                         false,
                         &query_params,
+                        headers,
                         head_only,
                         user,
                         access_paths,
@@ -362,11 +1027,29 @@ impl Server {
                 }
             }
             Method::OPTIONS => {
-                set_webdav_headers(&mut res);
+                let target = if is_miss {
+                    AllowTarget::Missing
+                } else if is_dir {
+                    AllowTarget::Dir
+                } else {
+                    AllowTarget::File
+                };
+                let auth = &self.args.auth;
+                let permits = |writable| match session_user.as_deref() {
+                    Some(user) => auth
+                        .user_paths(Some(user))
+                        .is_some_and(|v| v.find(&relative_path, writable).is_some()),
+                    None => auth.permits(&relative_path, &method, authorization, writable),
+                };
+                let (readable, writable) = (permits(false), permits(true));
+                self.set_allow_headers_for(&mut res, target, readable, writable);
             }
             Method::PUT => {
-                if is_dir || !allow_upload || (!allow_delete && size > 0) {
+                let replaces = size > 0 && !self.args.no_overwrite;
+                if is_dir || !allow_upload || (!allow_delete && replaces) {
                     status_forbid(&mut res);
+                } else if self.is_upload_too_large(headers) {
+                    status_payload_too_large(&mut res);
                 } else {
                     self.handle_upload(path, None, size, req, &mut res).await?;
                 }
@@ -376,6 +1059,8 @@ impl Server {
                     status_not_found(&mut res);
                 } else if !allow_upload {
                     status_forbid(&mut res);
+                } else if self.is_upload_too_large(headers) {
+                    status_payload_too_large(&mut res);
                 } else {
                     let offset = match parse_upload_offset(headers, size) {
                         Ok(v) => v,
@@ -402,25 +1087,100 @@ impl Server {
                 if !allow_delete {
                     status_forbid(&mut res);
                 } else if !is_miss {
-                    self.handle_delete(path, is_dir, &mut res).await?
+                    // A missing Depth means infinity, RFC 4918 9.6.1
+                    let recursive = match (query_params.get("recursive"), headers.get("depth")) {
+                        (Some(_), _) => has_query_bool(&query_params, "recursive"),
+                        (None, Some(depth)) => depth.as_bytes().eq_ignore_ascii_case(b"infinity"),
+                        (None, None) => true,
+                    };
+                    self.handle_delete(path, is_dir, recursive, &mut res)
+                        .await?
                 } else {
                     status_not_found(&mut res);
                 }
             }
+            Method::POST if has_query_bool(&query_params, "upload-tree") => {
+                if !allow_upload {
+                    status_forbid(&mut res);
+                } else if is_file {
+                    *res.status_mut() = StatusCode::CONFLICT;
+                    *res.body_mut() = body_full("Not a directory");
+                } else if self.is_upload_too_large(headers) {
+                    status_payload_too_large(&mut res);
+                } else {
+                    self.handle_upload_tree(
+                        path,
+                        &relative_path,
+                        allow_delete,
+                        user.as_deref(),
+                        req,
+                        &mut res,
+                    )
+                    .await?;
+                }
+            }
+            Method::POST if has_query_bool(&query_params, "mkdir") => {
+                if !allow_upload {
+                    status_forbid(&mut res);
+                } else {
+                    self.handle_mkdir(&relative_path, &query_params, user.as_deref(), &mut res)
+                        .await?;
+                }
+            }
+            Method::POST => {
+                if !allow_upload || !allow_delete {
+                    status_forbid(&mut res);
+                } else {
+                    self.handle_rename(
+                        &relative_path,
+                        &query_params,
+                        user.as_deref(),
+                        req,
+                        &mut res,
+                    )
+                    .await?;
+                }
+            }
+            _ if self.args.disable_webdav => {
+                self.set_allow_headers(&mut res);
+                *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            }
             method => match method.as_str() {
                 "PROPFIND" => {
+                    let depth = headers.get("depth").cloned();
+                    let is_anonymous = authorization.is_none();
+                    let req_path = req_path.to_string();
+                    let propfind = match Limited::new(req.into_body(), PROPFIND_BODY_MAX_SIZE)
+                        .collect()
+                        .await
+                        .map_err(|err| anyhow!("{err}"))
+                        .and_then(|body| {
+                            let body = body.to_bytes();
+                            if self.args.debug_bodies {
+                                debug_log::log_request_body("PROPFIND", &req_path, &body);
+                            }
+                            PropFind::parse(&body)
+                        }) {
+                        Ok(propfind) => propfind,
+                        Err(err) => {
+                            status_bad_request(
+                                &mut res,
+                                &format!("Invalid propfind request, {err}"),
+                            );
+                            return Ok(res);
+                        }
+                    };
                     if is_dir {
-                        let access_paths =
-                            if access_paths.perm().indexonly() && authorization.is_none() {
-                                // see https://github.com/sigoden/dufs/issues/229
-                                AccessPaths::new(AccessPerm::ReadOnly)
-                            } else {
-                                access_paths
-                            };
-                        self.handle_propfind_dir(path, headers, access_paths, &mut res)
+                        let access_paths = if access_paths.perm().indexonly() && is_anonymous {
+                            // see https://github.com/sigoden/dufs/issues/229
+                            AccessPaths::new(AccessPerm::ReadOnly)
+                        } else {
+                            access_paths
+                        };
+                        self.handle_propfind_dir(path, depth, &propfind, access_paths, &mut res)
                             .await?;
                     } else if is_file {
-                        self.handle_propfind_file(path, &mut res).await?;
+                        self.handle_propfind_file(path, &propfind, &mut res).await?;
                     } else {
                         status_not_found(&mut res);
                     }
@@ -448,7 +1208,8 @@ impl Server {
                     } else if is_miss {
                         status_not_found(&mut res);
                     } else {
-                        self.handle_copy(path, &req, &mut res).await?
+                        self.handle_copy(path, user.as_deref(), &req, &mut res)
+                            .await?
                     }
                 }
                 "MOVE" => {
@@ -457,7 +1218,8 @@ impl Server {
                     } else if is_miss {
                         status_not_found(&mut res);
                     } else {
-                        self.handle_move(path, &req, &mut res).await?
+                        self.handle_move(path, is_dir, user.as_deref(), &req, &mut res)
+                            .await?
                     }
                 }
                 "LOCK" => {
@@ -483,6 +1245,48 @@ impl Server {
         Ok(res)
     }
 
+    /// Read the throttled body of an upload, failing with `UploadTooLarge` past --max-upload-size.
+    fn upload_body_reader(&self, req: Request) -> impl AsyncRead {
+        let throttle = req.extensions().get::<Throttle>().cloned();
+        let stream = ThrottledStream::new(
+            IncomingStream::new(req.into_body()),
+            throttle.unwrap_or_default(),
+        );
+
+        // Bodies without a Content-Length are only caught once they exceed the limit.
+        let max_upload_size = self.args.max_upload_size;
+        let mut received = 0u64;
+        let body_with_io_error = stream.map_err(io::Error::other).and_then(move |chunk| {
+            received += chunk.len() as u64;
+            futures_util::future::ready(match max_upload_size {
+                Some(max_upload_size) if received > max_upload_size => {
+                    Err(io::Error::other(UploadTooLarge))
+                }
+                _ => Ok(chunk),
+            })
+        });
+        StreamReader::new(body_with_io_error)
+    }
+
+    async fn handle_pipe(&self, pipe: &UploadPipe, req: Request, res: &mut Response) -> Result<()> {
+        let body_reader = self.upload_body_reader(req);
+        pin_mut!(body_reader);
+        let limit = Duration::from_secs(self.args.pipe_timeout);
+        match pipe.run(&mut body_reader, limit).await {
+            Ok(status) => *res.status_mut() = status,
+            Err(err)
+                if err
+                    .downcast_ref::<io::Error>()
+                    .and_then(|v| v.get_ref())
+                    .is_some_and(|v| v.is::<UploadTooLarge>()) =>
+            {
+                status_payload_too_large(res)
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
     async fn handle_upload(
         &self,
         path: &Path,
@@ -491,9 +1295,25 @@ impl Server {
         req: Request,
         res: &mut Response,
     ) -> Result<()> {
+        // Encrypted chunks can't be resumed at an offset of the plaintext.
+        #[cfg(feature = "encrypt")]
+        if upload_offset.is_some() && self.args.encryption_key.is_some() {
+            *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            return Ok(());
+        }
         ensure_path_parent(path).await?;
 
-        let temp_path = append_ext("dufsupload", path.to_path_buf());
+        let mtime = upload_mtime(req.headers());
+        let temp_path = self.upload_temp_path(path);
+        // `If-None-Match: *` must not replace a file created since the preconditions were checked.
+        let create_new = upload_offset.is_none()
+            && req.headers().typed_get::<IfNoneMatch>() == Some(IfNoneMatch::any());
+
+        let declared_type = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
 
         let (mut temp_file, status) = match upload_offset {
             None => (fs::File::create(&temp_path).await?, StatusCode::CREATED),
@@ -507,51 +1327,743 @@ impl Server {
                 (temp_file, StatusCode::NO_CONTENT)
             }
         };
-        let stream = IncomingStream::new(req.into_body());
-
-        let body_with_io_error = stream.map_err(|err| io::Error::new(io::ErrorKind::Other, err));
-        let body_reader = StreamReader::new(body_with_io_error);
-
+        let body_reader = self.upload_body_reader(req);
         pin_mut!(body_reader);
 
+        #[cfg(feature = "encrypt")]
+        let ret = match self.args.encryption_key.as_ref() {
+            Some(key) => encrypt::encrypt(key, &mut body_reader, &mut temp_file).await,
+            None => io::copy(&mut body_reader, &mut temp_file).await,
+        };
+        #[cfg(not(feature = "encrypt"))]
         let ret = io::copy(&mut body_reader, &mut temp_file).await;
         // let size = fs::metadata(&temp_path)
-            // .await
-            // .map(|v| v.len())
-            // .unwrap_or_default();
-        if ret.is_err() {
+        // .await
+        // .map(|v| v.len())
+        // .unwrap_or_default();
+        if let Err(err) = ret {
             // Disable if, otherwise automatic removal is not done
             // if upload_offset.is_none() && size < RESUMABLE_UPLOAD_MIN_SIZE {
-                let _ = tokio::fs::remove_file(&temp_path).await;
+            let _ = tokio::fs::remove_file(&temp_path).await;
             // }
-            ret?;
+            if err.get_ref().is_some_and(|v| v.is::<UploadTooLarge>()) {
+                status_payload_too_large(res);
+                return Ok(());
+            }
+            return Err(err.into());
+        }
+
+        if let Some(reason) = self.vet_upload(path, &temp_path, declared_type).await? {
+            *res.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            *res.body_mut() = body_full(reason);
+            return Ok(());
         }
 
-        
         // It may not be compatible with resumable upload
         // I was not able to test
-        fs::rename(temp_path, path).await?;
+        let keep_existing = upload_offset.is_none() && self.args.no_overwrite;
+        let temp_path = match self.args.temp_dir.is_some() && (create_new || keep_existing) {
+            // Linking into place needs the temp file on the filesystem of the target.
+            true => {
+                let local_path = append_ext("dufsupload", path.to_path_buf());
+                if let Err(err) = move_file(&temp_path, &local_path).await {
+                    let _ = fs::remove_file(&temp_path).await;
+                    return Err(err.into());
+                }
+                local_path
+            }
+            false => temp_path,
+        };
+        let path = if create_new || keep_existing {
+            // Linking fails if the target exists, renaming would silently replace it.
+            let mut n = 0;
+            loop {
+                let target = numbered_path(path, n);
+                let linked = match fs::hard_link(&temp_path, &target).await {
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::Unsupported | io::ErrorKind::CrossesDevices
+                        ) =>
+                    {
+                        // Without links, reserve the name so that renaming over it can't
+                        // replace a file created meanwhile.
+                        fs::OpenOptions::new()
+                            .write(true)
+                            .create_new(true)
+                            .open(&target)
+                            .await
+                            .map(|_| false)
+                    }
+                    ret => ret.map(|_| true),
+                };
+                match linked {
+                    Ok(true) => {
+                        fs::remove_file(&temp_path).await?;
+                        break target;
+                    }
+                    Ok(false) => {
+                        if let Err(err) = fs::rename(&temp_path, &target).await {
+                            let _ = fs::remove_file(&target).await;
+                            let _ = fs::remove_file(&temp_path).await;
+                            return Err(err.into());
+                        }
+                        break target;
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                        if create_new {
+                            let _ = fs::remove_file(&temp_path).await;
+                            *res.status_mut() = StatusCode::PRECONDITION_FAILED;
+                            return Ok(());
+                        }
+                        n += 1;
+                    }
+                    Err(err) => {
+                        let _ = fs::remove_file(&temp_path).await;
+                        return Err(err.into());
+                    }
+                }
+            }
+        } else {
+            move_file(&temp_path, path).await?;
+            path.to_path_buf()
+        };
+        let path = path.as_path();
+
+        #[cfg(feature = "heic")]
+        let path = &self.convert_heic_upload(path).await;
+
+        if let Some(mtime) = mtime {
+            match filetime::set_file_mtime(path, FileTime::from_system_time(mtime)) {
+                Ok(()) => {
+                    res.headers_mut()
+                        .insert("x-oc-mtime", HeaderValue::from_static("accepted"));
+                }
+                Err(err) => warn!("Failed to set mtime of {}, {err}", path.display()),
+            }
+        }
+
+        if let Some(upload_hook) = self.args.upload_hook.as_ref() {
+            let size = fs::metadata(path)
+                .await
+                .map(|v| v.len())
+                .unwrap_or_default();
+            upload_hook.spawn(path, size);
+        }
+        if self.webhook.is_some() {
+            let size = fs::metadata(path).await.map(|v| v.len()).ok();
+            self.notify_webhook("upload", path, None, size);
+        }
 
+        self.set_resource_location(res, path, status)?;
         *res.status_mut() = status;
 
         Ok(())
     }
 
-    async fn handle_delete(&self, path: &Path, is_dir: bool, res: &mut Response) -> Result<()> {
-        match is_dir {
-            true => fs::remove_dir_all(path).await?,
-            false => fs::remove_file(path).await?,
+    /// Route a file PUT to an --ingest folder into its subfolder for the current date, like
+    /// `ingest/2024/06/15/file.log` for `ingest/file.log`.
+    fn ingest_path(&self, relative_path: &str) -> Result<Option<String>> {
+        let Some((prefix, rest)) = self.args.ingest.iter().find_map(|prefix| {
+            let rest = relative_path
+                .strip_prefix(prefix.as_str())?
+                .strip_prefix('/')?;
+            (!rest.is_empty()).then_some((prefix, rest))
+        }) else {
+            return Ok(None);
+        };
+        let now = unix_now()?;
+        let now = Utc
+            .timestamp_opt(now.as_secs() as i64, 0)
+            .single()
+            .ok_or_else(|| anyhow!("Invalid system time"))?;
+        let layout = self
+            .args
+            .time_zone
+            .unwrap_or_default()
+            .format(now, &self.args.ingest_layout);
+        // Only the strftime items of the layout are checked at startup, not its literal text.
+        let layout = normalize_relative_path(&layout)
+            .ok_or_else(|| anyhow!("Invalid ingest folder `{layout}`"))?;
+        if layout.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(format!("{prefix}/{layout}/{rest}")))
+    }
+
+    /// Extract a tar stream, gzip compressed or not, into the folder `dest` and report the
+    /// outcome of each entry. Files already extracted with the same size and mtime are kept, so
+    /// an interrupted upload resumes by sending the archive again.
+    async fn handle_upload_tree(
+        &self,
+        dest: &Path,
+        relative_path: &str,
+        allow_delete: bool,
+        user: Option<&str>,
+        req: Request,
+        res: &mut Response,
+    ) -> Result<()> {
+        let content_type = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let gzip = match content_type.as_str() {
+            "" | "application/x-tar" | "application/octet-stream" => false,
+            "application/gzip" | "application/x-gzip" | "application/x-compressed-tar" => true,
+            _ => {
+                *res.status_mut() = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+                *res.body_mut() = body_full("Only tar archives are extracted");
+                return Ok(());
+            }
+        };
+        fs::create_dir_all(dest).await?;
+
+        let body_reader = self.upload_body_reader(req);
+        let reader: Pin<Box<dyn AsyncRead + Send>> = match gzip {
+            true => Box::pin(GzipDecoder::new(BufReader::new(body_reader))),
+            false => Box::pin(body_reader),
+        };
+        let mut tar = TarReader::new(reader);
+        let mut entries = vec![];
+        let mut extracted = 0u64;
+        loop {
+            let entry = match tar.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(err) => return status_upload_tree_error(res, err),
+            };
+            let Some(name) = normalize_entry_path(&entry.path).filter(|v| !v.is_empty()) else {
+                if entry.path.trim_start_matches("./").is_empty() {
+                    continue;
+                }
+                entries.push(UploadTreeEntry::failed(
+                    &entry.path,
+                    StatusCode::BAD_REQUEST,
+                    "Unsafe path",
+                ));
+                continue;
+            };
+            let name = match relative_path.is_empty() {
+                true => name,
+                false => format!("{relative_path}/{name}"),
+            };
+            let is_dir = entry.kind == EntryKind::Dir;
+            if entry.kind == EntryKind::Other {
+                entries.push(UploadTreeEntry::failed(
+                    &name,
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "Only files and folders are extracted",
+                ));
+                continue;
+            }
+            let denied = !self.can_write(&name, user)
+                || self.is_denied_dotfile(&name)
+                || (self.args.hidden_deny_write && self.is_hidden_path(&name, is_dir))
+                || (!is_dir && !self.is_upload_ext_allowed(&name));
+            let target = match self.join_path(&name) {
+                Some(target) if !denied => target,
+                _ => {
+                    entries.push(UploadTreeEntry::failed(
+                        &name,
+                        StatusCode::FORBIDDEN,
+                        "Forbidden",
+                    ));
+                    continue;
+                }
+            };
+            extracted += entry.size;
+            if self.args.max_upload_size.is_some_and(|v| extracted > v) {
+                status_payload_too_large(res);
+                return Ok(());
+            }
+            let status = self
+                .extract_tree_entry(&mut tar, &entry, &target, allow_delete)
+                .await;
+            let status = match status {
+                Ok(status) => status,
+                Err(err) => match err.downcast::<io::Error>() {
+                    Ok(err) if is_upload_tree_error(&err) => {
+                        return status_upload_tree_error(res, err)
+                    }
+                    Ok(err) => return Err(err.into()),
+                    Err(err) => return Err(err),
+                },
+            };
+            entries.push(match status {
+                Ok(status) => UploadTreeEntry {
+                    path: name,
+                    status: status.as_u16(),
+                    error: None,
+                },
+                Err((status, error)) => UploadTreeEntry::failed(&name, status, &error),
+            });
+        }
+
+        let output = serde_json::to_string_pretty(&UploadTreeResult { entries })?;
+        res.headers_mut()
+            .typed_insert(ContentType::from(mime_guess::mime::APPLICATION_JSON));
+        res.headers_mut()
+            .typed_insert(ContentLength(output.len() as u64));
+        *res.body_mut() = body_full(output);
+        Ok(())
+    }
+
+    /// Extract the current entry of a `?upload-tree` archive to `target`, returning the status
+    /// of the entry or why it wasn't extracted.
+    async fn extract_tree_entry<R: AsyncRead + Unpin>(
+        &self,
+        tar: &mut TarReader<R>,
+        entry: &TarEntry,
+        target: &Path,
+        allow_delete: bool,
+    ) -> Result<Result<StatusCode, (StatusCode, String)>> {
+        let conflict = |error: &str| Ok(Err((StatusCode::CONFLICT, error.to_string())));
+        let existing = fs::symlink_metadata(target).await.ok();
+        if entry.kind == EntryKind::Dir {
+            return match existing {
+                Some(meta) if meta.is_dir() => Ok(Ok(StatusCode::OK)),
+                Some(_) => conflict("Not a directory"),
+                None => {
+                    if fs::create_dir_all(target).await.is_err() {
+                        return conflict("Parent is not a directory");
+                    }
+                    // Folders stay writable, their entries are extracted after them.
+                    set_upload_mode(target, entry.mode | 0o700).await;
+                    Ok(Ok(StatusCode::CREATED))
+                }
+            };
+        }
+        let parent = target.parent().unwrap_or(target);
+        if fs::create_dir_all(parent).await.is_err() {
+            return conflict("Parent is not a directory");
+        }
+        if !self.args.allow_symlink && !self.is_root_contained(parent).await {
+            return Ok(Err((StatusCode::FORBIDDEN, "Forbidden".to_string())));
+        }
+        match existing {
+            Some(meta) if meta.is_dir() => return conflict("Is a directory"),
+            Some(meta)
+                if meta.len() == entry.size
+                    && meta.modified().ok().map(|v| to_timestamp(&v) / 1000)
+                        == Some(entry.mtime) =>
+            {
+                return Ok(Ok(StatusCode::OK));
+            }
+            Some(_) if !allow_delete || self.args.no_overwrite || self.is_protected(target) => {
+                return conflict("Already exists");
+            }
+            _ => {}
+        }
+
+        let temp_path = self.upload_temp_path(target);
+        let mut temp_file = fs::File::create(&temp_path).await?;
+        #[cfg(feature = "encrypt")]
+        let ret = match self.args.encryption_key.as_ref() {
+            Some(key) => encrypt::encrypt(key, tar, &mut temp_file).await,
+            None => io::copy(tar, &mut temp_file).await,
+        };
+        #[cfg(not(feature = "encrypt"))]
+        let ret = io::copy(tar, &mut temp_file).await;
+        drop(temp_file);
+        if let Err(err) = ret {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(err.into());
+        }
+        if let Some(reason) = self.vet_upload(target, &temp_path, None).await? {
+            return Ok(Err((StatusCode::UNPROCESSABLE_ENTITY, reason)));
+        }
+        move_file(&temp_path, target).await?;
+        set_upload_mode(target, entry.mode & 0o777).await;
+        let mtime = FileTime::from_unix_time(entry.mtime as i64, 0);
+        if let Err(err) = filetime::set_file_mtime(target, mtime) {
+            warn!("Failed to set mtime of {}, {err}", target.display());
+        }
+        if let Some(upload_hook) = self.args.upload_hook.as_ref() {
+            upload_hook.spawn(target, entry.size);
+        }
+        self.notify_webhook("upload", target, None, Some(entry.size));
+        Ok(Ok(StatusCode::CREATED))
+    }
+
+    /// Check an upload staged at `temp_path` before it replaces `path`, returning why it is
+    /// rejected by --verify-content-type or --clamav-socket, in which case it is removed.
+    async fn vet_upload(
+        &self,
+        path: &Path,
+        temp_path: &Path,
+        declared_type: Option<String>,
+    ) -> Result<Option<String>> {
+        if let Some(mode) = self.args.verify_content_type {
+            let mut expected: Vec<String> = declared_type.into_iter().collect();
+            if let Some(ext) = path.extension() {
+                let ext = ext.to_string_lossy().to_lowercase();
+                match self.args.mime.get(&ext) {
+                    Some(mime) => expected.push(mime.clone()),
+                    None => expected.extend(
+                        mime_guess::from_ext(&ext)
+                            .first()
+                            .map(|v| v.essence_str().to_string()),
+                    ),
+                }
+            }
+            let head = match self.read_upload_head(temp_path).await {
+                Ok(v) => v,
+                Err(err) => {
+                    let _ = fs::remove_file(temp_path).await;
+                    return Err(err);
+                }
+            };
+            let allow = &self.args.verify_content_type_allow;
+            if let Some((expected, sniffed)) = sniff::find_mismatch(mode, allow, &expected, &head) {
+                let _ = fs::remove_file(temp_path).await;
+                warn!(
+                    "Rejected upload {}, content is {sniffed} not {expected}",
+                    path.display()
+                );
+                return Ok(Some(format!("Content is {sniffed}, not {expected}")));
+            }
+        }
+
+        if let Some(clamav_socket) = self.args.clamav_socket.as_ref() {
+            let scan_timeout = Duration::from_secs(self.args.clamav_timeout);
+            // Encrypted uploads are scanned as they were sent, not as they are stored
+            let ret = match self.content.open(temp_path, 0, u64::MAX).await {
+                Ok(reader) => clamav::scan(clamav_socket, path, reader, scan_timeout).await,
+                Err(err) => Err(err),
+            };
+            match ret {
+                Ok(None) => {}
+                Ok(Some(signature)) => {
+                    let _ = fs::remove_file(temp_path).await;
+                    warn!("Rejected upload {}, found {}", path.display(), signature);
+                    return Ok(Some(signature));
+                }
+                Err(err) => {
+                    let _ = fs::remove_file(temp_path).await;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Point at the URL of a created or updated resource, which may differ from the requested
+    /// one, e.g. after `--no-overwrite` or `--convert-heic` picked another name.
+    fn set_resource_location(
+        &self,
+        res: &mut Response,
+        path: &Path,
+        status: StatusCode,
+    ) -> Result<()> {
+        let Ok(relative_path) = path.strip_prefix(&self.args.serve_path) else {
+            return Ok(());
+        };
+        let mut url = format!(
+            "{}{}",
+            self.args.uri_prefix,
+            encode_uri(&normalize_path(relative_path))
+        );
+        if path.is_dir() && !url.ends_with('/') {
+            url.push('/');
+        }
+        let name = if status == StatusCode::CREATED {
+            LOCATION
+        } else {
+            CONTENT_LOCATION
+        };
+        res.headers_mut().insert(name, HeaderValue::from_str(&url)?);
+        Ok(())
+    }
+
+    /// Replace an uploaded HEIC/HEIF image with a JPEG, keeping the upload as is on failure.
+    #[cfg(feature = "heic")]
+    async fn convert_heic_upload(&self, path: &Path) -> PathBuf {
+        let is_heic = path
+            .extension()
+            .and_then(|v| v.to_str())
+            .is_some_and(|v| v.eq_ignore_ascii_case("heic") || v.eq_ignore_ascii_case("heif"));
+        if !self.args.convert_heic || !is_heic {
+            return path.to_path_buf();
+        }
+        let target = path.with_extension("jpg");
+        if fs::symlink_metadata(&target).await.is_ok() {
+            warn!(
+                "Not converting {}, {} already exists",
+                path.display(),
+                target.display()
+            );
+            return path.to_path_buf();
+        }
+        if let Err(err) = convert_heic_to_jpeg(path, &target).await {
+            warn!("Failed to convert {} to jpeg, {err}", path.display());
+            return path.to_path_buf();
+        }
+        if !self.args.keep_heic {
+            if let Err(err) = fs::remove_file(path).await {
+                warn!("Failed to remove {}, {err}", path.display());
+            }
+        }
+        target
+    }
+
+    /// Delete a file or an empty directory, a directory with contents only when `recursive`.
+    async fn handle_delete(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        recursive: bool,
+        res: &mut Response,
+    ) -> Result<()> {
+        if !is_dir {
+            fs::remove_file(path).await?;
+        } else if fs::read_dir(path).await?.next_entry().await?.is_none() {
+            fs::remove_dir(path).await?;
+        } else if !recursive {
+            *res.status_mut() = StatusCode::CONFLICT;
+            *res.body_mut() = body_full("Directory not empty");
+            return Ok(());
+        } else if self.args.no_recursive_delete || self.contains_protected(path).await? {
+            status_forbid(res);
+            return Ok(());
+        } else {
+            fs::remove_dir_all(path).await?;
         }
+        self.notify_webhook("delete", path, None, None);
 
         status_no_content(res);
         Ok(())
     }
 
+    /// Whether a file is kept from changes, by --protect or by a `.name.dufs-lock` marker.
+    fn is_protected(&self, path: &Path) -> bool {
+        is_protected(&self.args.protect, &self.args.serve_path, path)
+    }
+
+    async fn contains_protected(&self, dir: &Path) -> Result<bool> {
+        let protect = self.args.protect.clone();
+        let root = self.args.serve_path.clone();
+        let dir = dir.to_path_buf();
+        let found = tokio::task::spawn_blocking(move || {
+            WalkDir::new(&dir)
+                .into_iter()
+                .filter_map(|v| v.ok())
+                .filter(|v| !v.file_type().is_dir())
+                .any(|v| is_protected(&protect, &root, v.path()))
+        })
+        .await?;
+        Ok(found)
+    }
+
+    /// Queue a --webhook-url event about a path of the served directory.
+    fn notify_webhook(
+        &self,
+        event: &'static str,
+        path: &Path,
+        destination: Option<&Path>,
+        size: Option<u64>,
+    ) {
+        let Some(webhook) = self.webhook.as_ref() else {
+            return;
+        };
+        let url_path = |path: &Path| {
+            path.strip_prefix(&self.args.serve_path)
+                .map(|v| format!("/{}", normalize_path(v)))
+                .unwrap_or_default()
+        };
+        webhook.notify(WebhookEvent {
+            event,
+            path: url_path(path),
+            destination: destination.map(url_path),
+            size,
+            time: unix_now().map(|v| v.as_secs()).unwrap_or_default(),
+        });
+    }
+
+    /// Serve the entries of a mounted zip archive, read-only.
+    async fn handle_zip_mount(
+        &self,
+        zip_mount: &ZipMount,
+        access_paths: &AccessPaths,
+        method: &Method,
+        req_path: &str,
+        query: &str,
+        relative_path: &str,
+        query_params: &HashMap<String, String>,
+        headers: &HeaderMap<HeaderValue>,
+        head_only: bool,
+        user: Option<String>,
+        res: &mut Response,
+    ) -> Result<()> {
+        if !matches!(*method, Method::GET | Method::HEAD) {
+            *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            res.headers_mut()
+                .insert(ALLOW, HeaderValue::from_static("GET,HEAD"));
+            return Ok(());
+        }
+        let entry = match zip_mount.get(relative_path) {
+            Some(entry)
+                if access_paths.reveal_hidden()
+                    || !self.is_hidden_path(relative_path, entry.is_dir) =>
+            {
+                entry
+            }
+            _ => {
+                status_not_found(res);
+                return Ok(());
+            }
+        };
+        if !self.args.no_redirect_slash && entry.is_dir != req_path.ends_with('/') {
+            let mut location = format!("{}{}", self.args.uri_prefix, encode_uri(relative_path));
+            if entry.is_dir && !location.ends_with('/') {
+                location.push('/');
+            }
+            if !query.is_empty() {
+                location.push('?');
+                location.push_str(query);
+            }
+            *res.status_mut() = StatusCode::MOVED_PERMANENTLY;
+            res.headers_mut()
+                .insert(LOCATION, HeaderValue::from_str(&location)?);
+            return Ok(());
+        }
+
+        if entry.is_dir {
+            let paths = zip_mount
+                .list(relative_path)
+                .into_iter()
+                .filter(|(name, entry)| {
+                    if access_paths.reveal_hidden() {
+                        return true;
+                    }
+                    let path = match relative_path {
+                        "" => name.to_string(),
+                        _ => format!("{relative_path}/{name}"),
+                    };
+                    !is_hidden(
+                        &self.args.hidden,
+                        &path,
+                        entry.is_dir,
+                        self.args.expose_well_known,
+                    )
+                })
+                .map(|(name, entry)| PathItem {
+                    path_type: if entry.is_dir {
+                        PathType::Dir
+                    } else {
+                        PathType::File
+                    },
+                    name: name.to_string(),
+                    mtime: entry.mtime,
+                    size: entry.size,
+                    mtime_display: None,
+                    preview: None,
+                    sha256: None,
+                    mode: None,
+                    is_symlink: false,
+                    target: None,
+                })
+                .collect();
+            return self
+                .send_index(
+                    &self.args.serve_path.join(relative_path),
+                    paths,
+                    true,
+                    query_params,
+                    headers,
+                    head_only,
+                    user,
+                    AccessPaths::new(AccessPerm::ReadOnly),
+                    res,
+                )
+                .await;
+        }
+
+        let size = entry.size;
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_millis(entry.mtime);
+        let mut use_range = headers.typed_get::<Range>().is_some();
+        if let Some((etag, last_modified)) = make_cache_headers(mtime, size, "") {
+            if let Some(status) =
+                check_preconditions(&Method::GET, headers, Some((&etag, last_modified.into())))
+            {
+                *res.status_mut() = status;
+                return Ok(());
+            }
+            res.headers_mut().typed_insert(last_modified);
+            res.headers_mut().typed_insert(etag.clone());
+            use_range = use_range
+                && headers
+                    .typed_get::<IfRange>()
+                    .map(|if_range| !if_range.is_modified(Some(&etag), Some(&last_modified)))
+                    .unwrap_or(true);
+        }
+        let ext = relative_path
+            .rsplit_once('.')
+            .map(|(_, v)| v.to_lowercase());
+        let content_type = match ext.and_then(|v| self.args.mime.get(&v)) {
+            Some(mime) => mime.clone(),
+            None => mime_guess::from_path(relative_path)
+                .first_or_octet_stream()
+                .to_string(),
+        };
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        let filename = relative_path.rsplit('/').next().unwrap_or(relative_path);
+        set_content_disposition(res, self.is_inline_type(&content_type), filename)?;
+        res.headers_mut().typed_insert(AcceptRanges::bytes());
+
+        // Multiple ranges of a compressed entry would each decompress it again, the whole
+        // entry is sent instead.
+        let range = match use_range {
+            true => headers
+                .get(RANGE)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| parse_ranges(v, size))
+                .filter(|v| !matches!(v.as_deref(), Some([_, _, ..]))),
+            false => None,
+        };
+        let (start, len) = match range {
+            Some(Some(ranges)) => {
+                let (start, end) = ranges[0];
+                *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+                res.headers_mut().insert(
+                    CONTENT_RANGE,
+                    format!("bytes {start}-{end}/{size}").parse()?,
+                );
+                (start, end - start + 1)
+            }
+            Some(None) => {
+                *res.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                res.headers_mut()
+                    .insert(CONTENT_RANGE, format!("bytes */{size}").parse()?);
+                return Ok(());
+            }
+            None => (0, size),
+        };
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, format!("{len}").parse()?);
+        if head_only {
+            return Ok(());
+        }
+        let reader = zip_mount.read(entry, start, len).await?;
+        let reader_stream = ReaderStream::with_capacity(reader, BUF_SIZE);
+        let stream_body = StreamBody::new(
+            reader_stream
+                .map_ok(Frame::data)
+                .map_err(|err| anyhow!("{err}")),
+        );
+        *res.body_mut() = stream_body.boxed();
+        Ok(())
+    }
+
     async fn handle_ls_dir(
         &self,
         path: &Path,
         exist: bool,
         query_params: &HashMap<String, String>,
+        headers: &HeaderMap<HeaderValue>,
         head_only: bool,
         user: Option<String>,
         access_paths: AccessPaths,
@@ -572,35 +2084,489 @@ impl Server {
             paths,
             exist,
             query_params,
+            headers,
             head_only,
             user,
             access_paths,
             res,
         )
+        .await
     }
 
-    async fn handle_search_dir(
-        &self,
+    async fn handle_ls_dir_ndjson(
+        self: Arc<Self>,
         path: &Path,
-        query_params: &HashMap<String, String>,
+        head_only: bool,
+        access_paths: AccessPaths,
+        res: &mut Response,
+    ) -> Result<()> {
+        let entry_paths = if access_paths.perm().indexonly() {
+            None
+        } else {
+            match fs::read_dir(path).await {
+                Ok(rd) => Some(rd),
+                Err(_) => {
+                    status_forbid(res);
+                    return Ok(());
+                }
+            }
+        };
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-ndjson"),
+        );
+        res.headers_mut()
+            .typed_insert(CacheControl::new().with_no_cache());
+        if head_only {
+            return Ok(());
+        }
+        let (mut writer, reader) = tokio::io::duplex(BUF_SIZE);
+        let path = path.to_owned();
+        tokio::spawn(async move {
+            if let Err(e) = self
+                .write_ndjson(&mut writer, &path, entry_paths, access_paths)
+                .await
+            {
+                error!("Failed to list {}, {}", path.display(), e);
+            }
+        });
+        let reader_stream = ReaderStream::with_capacity(reader, BUF_SIZE);
+        let stream_body = StreamBody::new(
+            reader_stream
+                .map_ok(Frame::data)
+                .map_err(|err| anyhow!("{err}")),
+        );
+        let boxed_body = stream_body.boxed();
+        *res.body_mut() = boxed_body;
+        Ok(())
+    }
+
+    async fn write_ndjson<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        path: &Path,
+        rd: Option<fs::ReadDir>,
+        access_paths: AccessPaths,
+    ) -> Result<()> {
+        let hidden = self.hidden_rules(&access_paths);
+        match rd {
+            Some(mut rd) => {
+                while let Ok(Some(entry)) = rd.next_entry().await {
+                    if !self.running.load(atomic::Ordering::SeqCst) {
+                        break;
+                    }
+                    self.write_ndjson_item(writer, path, &entry.path(), hidden)
+                        .await?;
+                }
+            }
+            None => {
+                for name in access_paths.child_names() {
+                    self.write_ndjson_item(writer, path, &path.join(name), hidden)
+                        .await?;
+                }
+            }
+        }
+        writer.shutdown().await?;
+        Ok(())
+    }
+
+    async fn write_ndjson_item<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        base_path: &Path,
+        entry_path: &Path,
+        hidden: &[String],
+    ) -> Result<()> {
+        let mut paths = vec![];
+        self.add_pathitem(&mut paths, base_path, entry_path, hidden)
+            .await;
+        if let Some(item) = paths.pop() {
+            let mut line = serde_json::to_vec(&item)?;
+            line.push(b'\n');
+            writer.write_all(&line).await?;
+        }
+        Ok(())
+    }
+
+    /// Serve a `SHA256SUMS` manifest of the immediate files of a directory.
+    async fn handle_checksums_dir(
+        self: Arc<Self>,
+        path: &Path,
+        query_params: &HashMap<String, String>,
+        head_only: bool,
+        access_paths: AccessPaths,
+        res: &mut Response,
+    ) -> Result<()> {
+        if query_params.get("checksums").map(|v| v.as_str()) != Some("sha256") {
+            status_bad_request(res, "Unsupported checksum algorithm");
+            return Ok(());
+        }
+        let mut paths = match self.list_dir(path, path, access_paths).await {
+            Ok(paths) => paths,
+            Err(_) => {
+                status_forbid(res);
+                return Ok(());
+            }
+        };
+        // Dotfiles are left out along with the paths matched by --hidden.
+        paths.retain(|v| !v.is_dir() && !v.name.starts_with('.'));
+        paths.sort_by(|a, b| a.name.cmp(&b.name));
+        let permit = match head_only {
+            true => None,
+            false => match self.heavy_task(res).await {
+                Some(permit) => permit,
+                None => return Ok(()),
+            },
+        };
+        res.headers_mut()
+            .typed_insert(ContentType::from(mime_guess::mime::TEXT_PLAIN_UTF_8));
+        res.headers_mut()
+            .typed_insert(CacheControl::new().with_no_cache());
+        set_content_disposition(res, true, "SHA256SUMS")?;
+        if head_only {
+            return Ok(());
+        }
+        let (mut writer, reader) = tokio::io::duplex(BUF_SIZE);
+        let path = path.to_owned();
+        tokio::spawn(async move {
+            for item in paths {
+                if !self.running.load(atomic::Ordering::SeqCst) {
+                    break;
+                }
+                let line = match self
+                    .cached_sha256(&path.join(&item.name), item.mtime, item.size)
+                    .await
+                {
+                    Ok(digest) => format!("{digest}  {}\n", item.name),
+                    Err(e) => {
+                        error!("Failed to checksum {}, {}", item.name, e);
+                        break;
+                    }
+                };
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            let _ = writer.shutdown().await;
+            drop(permit);
+        });
+        let reader_stream = ReaderStream::with_capacity(reader, BUF_SIZE);
+        let stream_body = StreamBody::new(
+            reader_stream
+                .map_ok(Frame::data)
+                .map_err(|err| anyhow!("{err}")),
+        );
+        *res.body_mut() = stream_body.boxed();
+        Ok(())
+    }
+
+    /// The `scheme://host` of a request, escaped so that hrefs built on it are safe to embed,
+    /// the paths appended to it have to be percent-encoded.
+    fn request_origin(&self, headers: &HeaderMap<HeaderValue>) -> String {
+        let scheme = if self.args.tls_cert.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        headers
+            .get(HOST)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| escape_str_attribute(&format!("{scheme}://{v}")).to_string())
+            .unwrap_or_default()
+    }
+
+    /// OpenGraph tags describing a page, so that chat apps can preview links to it.
+    fn opengraph_meta(&self, path: &Path, headers: &HeaderMap<HeaderValue>) -> Result<String> {
+        let rel_path = normalize_path(path.strip_prefix(&self.args.serve_path)?);
+        let title = match rel_path.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => "/".to_string(),
+        };
+        let origin = self.request_origin(headers);
+        let mut href = encode_uri(&format!("{}{rel_path}", self.args.uri_prefix));
+        if path.is_dir() && !href.ends_with('/') {
+            href.push('/');
+        }
+        let mut output = format!(
+            r#"<meta property="og:title" content="{}" />
+  <meta property="og:type" content="website" />
+  <meta property="og:url" content="{origin}{href}" />"#,
+            escape_str_attribute(&title)
+        );
+        if path.is_file() && is_previewable_image(path) {
+            // There is no thumbnail, the image itself is the preview.
+            output.push_str(&format!(
+                r#"
+  <meta property="og:image" content="{origin}{href}" />"#
+            ));
+        }
+        Ok(output)
+    }
+
+    /// Render the files of a directory as an Atom or RSS feed, newest first.
+    async fn handle_feed_dir(
+        &self,
+        path: &Path,
+        query_params: &HashMap<String, String>,
+        headers: &HeaderMap<HeaderValue>,
+        head_only: bool,
+        access_paths: AccessPaths,
+        res: &mut Response,
+    ) -> Result<()> {
+        let kind = query_params.get("feed").map(|v| v.as_str());
+        let (is_atom, content_type) = match kind {
+            Some("atom") => (true, "application/atom+xml; charset=utf-8"),
+            Some("rss") => (false, "application/rss+xml; charset=utf-8"),
+            _ => {
+                status_bad_request(res, "Invalid feed, expect atom or rss");
+                return Ok(());
+            }
+        };
+        let paths = match self
+            .list_dir(path, &self.args.serve_path, access_paths)
+            .await
+        {
+            Ok(paths) => paths,
+            Err(_) => {
+                status_forbid(res);
+                return Ok(());
+            }
+        };
+        let mut entries = vec![];
+        for item in paths.into_iter().filter(|v| !v.is_dir()) {
+            let entry_path = self.args.serve_path.join(&item.name);
+            let (mtime, _) = get_file_mtime_and_mode(&entry_path).await?;
+            entries.push((item, mtime));
+        }
+        entries.sort_by_key(|v| std::cmp::Reverse(v.1));
+
+        // Feeds are read outside of the browser, so links have to be absolute.
+        let origin = self.request_origin(headers);
+        let dir_name = normalize_path(path.strip_prefix(&self.args.serve_path)?);
+        let dir_href = {
+            let mut href = encode_uri(&format!("{}{dir_name}", self.args.uri_prefix));
+            if !href.ends_with('/') {
+                href.push('/');
+            }
+            format!("{origin}{href}")
+        };
+        let title = escape_str_pcdata(&format!("Index of /{dir_name}")).to_string();
+        let updated = entries.first().map(|v| v.1).unwrap_or_else(Utc::now);
+
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        if is_atom {
+            output.push_str(&format!(
+                r#"<feed xmlns="http://www.w3.org/2005/Atom">
+<id>{dir_href}</id>
+<title>{title}</title>
+<link href="{dir_href}"/>
+<updated>{}</updated>
+<author><name>dufs</name></author>
+"#,
+                updated.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ));
+        } else {
+            output.push_str(&format!(
+                r#"<rss version="2.0">
+<channel>
+<title>{title}</title>
+<link>{dir_href}</link>
+<description>{title}</description>
+<lastBuildDate>{}</lastBuildDate>
+"#,
+                updated.to_rfc2822(),
+            ));
+        }
+        for (item, mtime) in &entries {
+            let href = format!(
+                "{origin}{}",
+                encode_uri(&format!("{}{}", self.args.uri_prefix, item.name))
+            );
+            let name = escape_str_pcdata(item.base_name());
+            if is_atom {
+                output.push_str(&format!(
+                    r#"<entry>
+<id>{href}</id>
+<title>{name}</title>
+<link href="{href}"/>
+<updated>{}</updated>
+</entry>
+"#,
+                    mtime.to_rfc3339_opts(SecondsFormat::Secs, true),
+                ));
+            } else {
+                output.push_str(&format!(
+                    r#"<item>
+<title>{name}</title>
+<link>{href}</link>
+<guid>{href}</guid>
+<pubDate>{}</pubDate>
+</item>
+"#,
+                    mtime.to_rfc2822(),
+                ));
+            }
+        }
+        output.push_str(if is_atom {
+            "</feed>\n"
+        } else {
+            "</channel>\n</rss>\n"
+        });
+
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        res.headers_mut()
+            .typed_insert(ContentLength(output.len() as u64));
+        res.headers_mut()
+            .typed_insert(CacheControl::new().with_no_cache());
+        if head_only {
+            return Ok(());
+        }
+        *res.body_mut() = body_full(output);
+        Ok(())
+    }
+
+    /// The SHA-256 of the content of a file, or of the inclusive byte range of it.
+    async fn sha256_file(&self, path: &Path, range: Option<(u64, u64)>) -> Result<String> {
+        let (start, len) = match range {
+            Some((start, end)) => (start, end - start + 1),
+            None => (0, u64::MAX),
+        };
+        let mut reader = self.content.open(path, start, len).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 8192];
+        loop {
+            let bytes_read = reader.read(&mut buffer).await?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// The hash of a file unless it changed since it was last hashed.
+    fn cached_sha256_hit(&self, path: &Path, mtime: u64, size: u64) -> Option<String> {
+        match self.checksum_cache.lock().unwrap().get(path) {
+            Some((cached_mtime, cached_size, digest))
+                if (*cached_mtime, *cached_size) == (mtime, size) =>
+            {
+                Some(digest.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Hash a file unless it is unchanged since it was last hashed.
+    async fn cached_sha256(&self, path: &Path, mtime: u64, size: u64) -> Result<String> {
+        let key = (mtime, size);
+        if let Some(digest) = self.cached_sha256_hit(path, mtime, size) {
+            return Ok(digest);
+        }
+        let digest = self.sha256_file(path, None).await?;
+        let mut cache = self.checksum_cache.lock().unwrap();
+        if cache.len() >= CHECKSUM_CACHE_MAX_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(path.to_owned(), (key.0, key.1, digest.clone()));
+        Ok(digest)
+    }
+
+    /// The validators of a file, with `--etag strong` its ETag is the hash of its content.
+    ///
+    /// Hashing takes a slot of --heavy-task-concurrency, the weak ETag is kept when none is free.
+    async fn file_cache_headers(
+        &self,
+        path: &Path,
+        meta: &Metadata,
+        suffix: &str,
+    ) -> Option<(ETag, LastModified)> {
+        let (etag, last_modified) = extract_cache_headers_with_suffix(meta, suffix)?;
+        if self.args.etag != EtagMode::Strong || !meta.is_file() {
+            return Some((etag, last_modified));
+        }
+        let mtime = to_timestamp(&meta.modified().ok()?);
+        let size = match self.content.size(path, meta).await {
+            Ok(size) => size,
+            Err(err) => {
+                warn!("Failed to hash {}, {err}", path.display());
+                return Some((etag, last_modified));
+            }
+        };
+        let permit = match self.cached_sha256_hit(path, mtime, size) {
+            Some(_) => None,
+            None => match self.try_heavy_task().await {
+                Some(permit) => permit,
+                None => return Some((etag, last_modified)),
+            },
+        };
+        let ret = self.cached_sha256(path, mtime, size).await;
+        drop(permit);
+        match ret {
+            Ok(digest) => Some((
+                format!(r#""{digest}{suffix}""#).parse().ok()?,
+                last_modified,
+            )),
+            Err(err) => {
+                warn!("Failed to hash {}, {err}", path.display());
+                Some((etag, last_modified))
+            }
+        }
+    }
+
+    async fn handle_search_dir(
+        &self,
+        path: &Path,
+        query_params: &HashMap<String, String>,
+        headers: &HeaderMap<HeaderValue>,
         head_only: bool,
         user: Option<String>,
         access_paths: AccessPaths,
         res: &mut Response,
     ) -> Result<()> {
         let mut paths: Vec<PathItem> = vec![];
-        let search = query_params
-            .get("q")
-            .ok_or_else(|| anyhow!("invalid q"))?
-            .to_lowercase();
+        let query = query_params.get("q").ok_or_else(|| anyhow!("invalid q"))?;
+        let search = query.to_lowercase();
+        let use_regex = query_params
+            .get("regex")
+            .is_some_and(|v| v.is_empty() || v == "1" || v == "true");
+        let regex = if use_regex && !query.is_empty() {
+            // The regex crate matches in linear time, bounding the compiled size guards the rest.
+            match RegexBuilder::new(query)
+                .case_insensitive(true)
+                .size_limit(SEARCH_REGEX_SIZE_LIMIT)
+                .dfa_size_limit(SEARCH_REGEX_SIZE_LIMIT)
+                .build()
+            {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    status_bad_request(res, &format!("Invalid regex, {err}"));
+                    return Ok(());
+                }
+            }
+        } else {
+            None
+        };
         if search.is_empty() {
             return self
-                .handle_ls_dir(path, true, query_params, head_only, user, access_paths, res)
+                .handle_ls_dir(
+                    path,
+                    true,
+                    query_params,
+                    headers,
+                    head_only,
+                    user,
+                    access_paths,
+                    res,
+                )
                 .await;
         } else {
             let path_buf = path.to_path_buf();
-            let hidden = Arc::new(self.args.hidden.to_vec());
+            let hidden = Arc::new(self.hidden_rules(&access_paths).to_vec());
             let hidden = hidden.clone();
+            let expose_well_known = self.args.expose_well_known;
+            let dotfiles_root = self.dotfiles_root();
+            let serve_path = self.args.serve_path.clone();
             let running = self.running.clone();
             let access_paths = access_paths.clone();
             let search_paths = tokio::task::spawn_blocking(move || {
@@ -626,13 +2592,20 @@ impl Server {
                                 }
                             }
                         }
-                        if is_hidden(&hidden, base_name, is_dir_type) {
+                        let relative_path = relative_to(&serve_path, entry_path);
+                        if is_hidden(&hidden, &relative_path, is_dir_type, expose_well_known)
+                            || is_dotfile(dotfiles_root.as_deref(), entry_path)
+                        {
                             if file_type.is_dir() {
                                 it.skip_current_dir();
                             }
                             continue;
                         }
-                        if !base_name.to_lowercase().contains(&search) {
+                        let matched = match regex.as_ref() {
+                            Some(regex) => regex.is_match(base_name),
+                            None => base_name.to_lowercase().contains(&search),
+                        };
+                        if !matched {
                             continue;
                         }
                         paths.push(entry_path.to_path_buf());
@@ -652,11 +2625,13 @@ impl Server {
             paths,
             true,
             query_params,
+            headers,
             head_only,
             user,
             access_paths,
             res,
         )
+        .await
     }
 
     async fn handle_zip_dir(
@@ -674,23 +2649,40 @@ impl Server {
         if head_only {
             return Ok(());
         }
+        let Some(permit) = self.heavy_task(res).await else {
+            return Ok(());
+        };
         let path = path.to_owned();
-        let hidden = self.args.hidden.clone();
+        let root = self.args.serve_path.clone();
+        let hidden = self.hidden_rules(&access_paths).to_vec();
+        let expose_well_known = self.args.expose_well_known;
+        let dotfiles_root = self.dotfiles_root();
         let running = self.running.clone();
         let compression = self.args.compress.to_compression();
+        let content = self.content.clone();
+        let strip = (
+            self.args.archive_strip_components,
+            self.args.archive_keep_shallow,
+        );
         tokio::spawn(async move {
             if let Err(e) = zip_dir(
                 &mut writer,
                 &path,
                 access_paths,
+                root,
                 &hidden,
+                expose_well_known,
+                dotfiles_root,
                 compression,
+                strip,
+                content,
                 running,
             )
             .await
             {
                 error!("Failed to zip {}, {}", path.display(), e);
             }
+            drop(permit);
         });
         let reader_stream = ReaderStream::with_capacity(reader, BUF_SIZE);
         let stream_body = StreamBody::new(
@@ -703,6 +2695,48 @@ impl Server {
         Ok(())
     }
 
+    fn handle_maintenance(
+        &self,
+        method: &Method,
+        user: Option<&str>,
+        query_params: &HashMap<String, String>,
+        res: &mut Response,
+    ) -> Result<()> {
+        if *method == Method::POST {
+            let is_admin = user.is_some()
+                && self
+                    .args
+                    .auth
+                    .user_paths(user)
+                    .and_then(|v| v.find("", true))
+                    .is_some_and(|v| v.perm().readwrite());
+            if !is_admin {
+                status_forbid(res);
+                return Ok(());
+            }
+            match query_params.get("write").map(|v| v.as_str()) {
+                Some("on") => self.maintenance.store(false, atomic::Ordering::SeqCst),
+                Some("off") => self.maintenance.store(true, atomic::Ordering::SeqCst),
+                _ => {
+                    status_bad_request(res, "Invalid write, expect on or off");
+                    return Ok(());
+                }
+            }
+        } else if *method != Method::GET && *method != Method::HEAD {
+            *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            return Ok(());
+        }
+        let write = if self.maintenance.load(atomic::Ordering::SeqCst) {
+            "off"
+        } else {
+            "on"
+        };
+        res.headers_mut()
+            .typed_insert(ContentType::from(mime_guess::mime::APPLICATION_JSON));
+        *res.body_mut() = body_full(format!(r#"{{"write":"{write}"}}"#));
+        Ok(())
+    }
+
     async fn handle_render_index(
         &self,
         path: &Path,
@@ -723,8 +2757,17 @@ impl Server {
             self.handle_send_file(&index_path, headers, head_only, res)
                 .await?;
         } else if self.args.render_try_index {
-            self.handle_ls_dir(path, true, query_params, head_only, user, access_paths, res)
-                .await?;
+            self.handle_ls_dir(
+                path,
+                true,
+                query_params,
+                headers,
+                head_only,
+                user,
+                access_paths,
+                res,
+            )
+            .await?;
         } else {
             status_not_found(res)
         }
@@ -754,7 +2797,32 @@ impl Server {
         headers: &HeaderMap<HeaderValue>,
         res: &mut Response,
     ) -> Result<bool> {
+        #[cfg(feature = "compression-dictionary")]
+        if let Some(dictionary) = self
+            .args
+            .dictionary
+            .as_ref()
+            .filter(|_| req_path == DICTIONARY_PATH)
+        {
+            res.headers_mut().typed_insert(ContentType::from(
+                mime_guess::mime::APPLICATION_OCTET_STREAM,
+            ));
+            res.headers_mut().insert(
+                "use-as-dictionary",
+                HeaderValue::from_str(&format!(r#"match="{}*""#, self.args.uri_prefix))?,
+            );
+            res.headers_mut().insert(
+                "cache-control",
+                HeaderValue::from_static("public, max-age=86400"),
+            );
+            *res.body_mut() = body_full(dictionary.data.clone());
+            return Ok(true);
+        }
         if let Some(name) = req_path.strip_prefix(&self.assets_prefix) {
+            if self.args.no_ui {
+                status_not_found(res);
+                return Ok(true);
+            }
             match self.args.assets.as_ref() {
                 Some(assets_path) => {
                     let path = assets_path.join(name);
@@ -805,6 +2873,22 @@ impl Server {
 
             *res.body_mut() = body_full(r#"{"status":"OK"}"#);
             Ok(true)
+        } else if req_path == FAVICON_PATH
+            && !self.args.no_ui
+            && !self.root_file_exists(req_path).await
+        {
+            res.headers_mut()
+                .insert("content-type", HeaderValue::from_static("image/x-icon"));
+            *res.body_mut() = body_full(FAVICON_ICO);
+            Ok(true)
+        } else if req_path == ROBOTS_PATH
+            && !self.args.no_default_robots
+            && !self.root_file_exists(req_path).await
+        {
+            res.headers_mut()
+                .typed_insert(ContentType::from(mime_guess::mime::TEXT_PLAIN_UTF_8));
+            *res.body_mut() = body_full(DEFAULT_ROBOTS_TXT);
+            Ok(true)
         } else {
             Ok(false)
         }
@@ -817,34 +2901,35 @@ impl Server {
         head_only: bool,
         res: &mut Response,
     ) -> Result<()> {
+        let read_buffer_size = self.args.read_buffer_size as usize;
+        #[cfg(feature = "encrypt")]
+        if let Some(key) = self.args.encryption_key.as_ref() {
+            if let Some(file) = encrypt::EncryptedFile::open(key, path).await? {
+                return self
+                    .handle_send_encrypted_file(path, file, headers, head_only, res)
+                    .await;
+            }
+        }
         let (file, meta) = tokio::join!(fs::File::open(path), fs::metadata(path),);
         let (mut file, meta) = (file?, meta?);
         let size = meta.len();
+        let content_type = get_content_type(path, &self.args.mime).await?;
+        let compressible = self.args.gzip && is_compressible(&content_type);
+        // Ranges refer to the identity encoding, so never compress a range request.
+        let use_gzip = compressible && accepts_gzip(headers) && headers.get(RANGE).is_none();
+        if compressible {
+            res.headers_mut()
+                .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+        }
         let mut use_range = true;
-        if let Some((etag, last_modified)) = extract_cache_headers(&meta) {
-            if let Some(if_unmodified_since) = headers.typed_get::<IfUnmodifiedSince>() {
-                if !if_unmodified_since.precondition_passes(last_modified.into()) {
-                    *res.status_mut() = StatusCode::PRECONDITION_FAILED;
-                    return Ok(());
-                }
-            }
-            if let Some(if_match) = headers.typed_get::<IfMatch>() {
-                if !if_match.precondition_passes(&etag) {
-                    *res.status_mut() = StatusCode::PRECONDITION_FAILED;
-                    return Ok(());
-                }
-            }
-            if let Some(if_modified_since) = headers.typed_get::<IfModifiedSince>() {
-                if !if_modified_since.is_modified(last_modified.into()) {
-                    *res.status_mut() = StatusCode::NOT_MODIFIED;
-                    return Ok(());
-                }
-            }
-            if let Some(if_none_match) = headers.typed_get::<IfNoneMatch>() {
-                if !if_none_match.precondition_passes(&etag) {
-                    *res.status_mut() = StatusCode::NOT_MODIFIED;
-                    return Ok(());
-                }
+        let etag_suffix = if use_gzip { "-gzip" } else { "" };
+        if let Some((etag, last_modified)) = self.file_cache_headers(path, &meta, etag_suffix).await
+        {
+            if let Some(status) =
+                check_preconditions(&Method::GET, headers, Some((&etag, last_modified.into())))
+            {
+                *res.status_mut() = status;
+                return Ok(());
             }
 
             res.headers_mut().typed_insert(last_modified);
@@ -866,24 +2951,63 @@ impl Server {
                 range
                     .to_str()
                     .ok()
-                    .and_then(|range| parse_range(range, size))
+                    .and_then(|range| parse_ranges(range, size))
             })
         } else {
             None
         };
 
-        res.headers_mut().insert(
-            CONTENT_TYPE,
-            HeaderValue::from_str(&get_content_type(path).await?)?,
-        );
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        if !self.args.no_streaming_cache {
+            // Manifests of live streams change while their segments never do, types
+            // overridden by --mime are left alone.
+            match streaming_type(path).filter(|(mime, _)| *mime == content_type) {
+                Some((_, true)) => {
+                    res.headers_mut()
+                        .typed_insert(CacheControl::new().with_no_cache());
+                }
+                Some((_, false)) => {
+                    res.headers_mut().typed_insert(
+                        CacheControl::new()
+                            .with_public()
+                            .with_max_age(Duration::from_secs(STREAMING_SEGMENT_MAX_AGE)),
+                    );
+                }
+                None => {}
+            }
+        }
 
         let filename = try_get_file_name(path)?;
-        set_content_disposition(res, true, filename)?;
+        let inline = self.is_inline_type(&content_type);
+        set_content_disposition(res, inline, filename)?;
+
+        if use_gzip {
+            res.headers_mut()
+                .insert(ACCEPT_RANGES, HeaderValue::from_static("none"));
+            res.headers_mut()
+                .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+            if head_only {
+                return Ok(());
+            }
+
+            let encoder = GzipEncoder::new(BufReader::new(file));
+            let reader_stream = ReaderStream::with_capacity(encoder, read_buffer_size);
+            let stream_body = StreamBody::new(
+                reader_stream
+                    .map_ok(Frame::data)
+                    .map_err(|err| anyhow!("{err}")),
+            );
+            *res.body_mut() = stream_body.boxed();
+            return Ok(());
+        }
 
         res.headers_mut().typed_insert(AcceptRanges::bytes());
 
         if let Some(range) = range {
-            if let Some((start, end)) = range {
+            if let Some(ranges) = range.as_ref().filter(|v| v.len() > 1) {
+                self.send_multipart_ranges(file, ranges, size, &content_type, head_only, res)?;
+            } else if let Some(&[(start, end)]) = range.as_deref() {
                 file.seek(SeekFrom::Start(start)).await?;
                 let range_size = end - start + 1;
                 *res.status_mut() = StatusCode::PARTIAL_CONTENT;
@@ -897,7 +3021,7 @@ impl Server {
                 }
 
                 let stream_body = StreamBody::new(
-                    LengthLimitedStream::new(file, range_size as usize)
+                    LengthLimitedStream::new(file, range_size as usize, read_buffer_size)
                         .map_ok(Frame::data)
                         .map_err(|err| anyhow!("{err}")),
                 );
@@ -915,7 +3039,28 @@ impl Server {
                 return Ok(());
             }
 
-            let reader_stream = ReaderStream::with_capacity(file, BUF_SIZE);
+            if self.args.zero_copy && self.args.tls_cert.is_none() && size > 0 {
+                let file = file.into_std().await;
+                // SAFETY: the map is read only, the help and README of --zero-copy warn that served
+                // files must not shrink, since touching pages past the new end of file faults.
+                let mmap = unsafe { memmap2::Mmap::map(&file)? };
+                *res.body_mut() = mmap_body(mmap);
+                return Ok(());
+            }
+
+            if let Some(stream) = self
+                .coalescer
+                .as_ref()
+                .filter(|_| size <= COALESCE_MAX_SIZE)
+                .and_then(|v| v.open(path, size, meta.modified().ok()))
+            {
+                let stream_body =
+                    StreamBody::new(stream.map_ok(Frame::data).map_err(|err| anyhow!("{err}")));
+                *res.body_mut() = stream_body.boxed();
+                return Ok(());
+            }
+
+            let reader_stream = ReaderStream::with_capacity(file, read_buffer_size);
             let stream_body = StreamBody::new(
                 reader_stream
                     .map_ok(Frame::data)
@@ -927,24 +3072,379 @@ impl Server {
         Ok(())
     }
 
+    /// Decrypt a file stored by --encrypt-at-rest, only a single range is served partially.
+    #[cfg(feature = "encrypt")]
+    async fn handle_send_encrypted_file(
+        &self,
+        path: &Path,
+        file: encrypt::EncryptedFile,
+        headers: &HeaderMap<HeaderValue>,
+        head_only: bool,
+        res: &mut Response,
+    ) -> Result<()> {
+        let meta = fs::metadata(path).await?;
+        let size = file.size;
+        let mut use_range = headers.typed_get::<Range>().is_some();
+        if let Some((etag, last_modified)) = self.file_cache_headers(path, &meta, "").await {
+            if let Some(status) =
+                check_preconditions(&Method::GET, headers, Some((&etag, last_modified.into())))
+            {
+                *res.status_mut() = status;
+                return Ok(());
+            }
+            res.headers_mut().typed_insert(last_modified);
+            res.headers_mut().typed_insert(etag.clone());
+            use_range = use_range
+                && headers
+                    .typed_get::<IfRange>()
+                    .map(|if_range| !if_range.is_modified(Some(&etag), Some(&last_modified)))
+                    .unwrap_or(true);
+        }
+
+        let ext = path.extension().map(|v| v.to_string_lossy().to_lowercase());
+        let content_type = match ext.and_then(|v| self.args.mime.get(&v)) {
+            Some(mime) => mime.clone(),
+            None => mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+        };
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        let inline = self.is_inline_type(&content_type);
+        set_content_disposition(res, inline, try_get_file_name(path)?)?;
+        res.headers_mut().typed_insert(AcceptRanges::bytes());
+
+        // Several ranges would each decrypt their chunks again, the whole file is sent instead.
+        let range = match headers.get(RANGE).filter(|_| use_range) {
+            Some(range) => match range.to_str().ok().and_then(|v| parse_ranges(v, size)) {
+                Some(ranges) if ranges.len() == 1 => Some(ranges[0]),
+                Some(_) => None,
+                None => {
+                    *res.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                    res.headers_mut()
+                        .insert(CONTENT_RANGE, format!("bytes */{size}").parse()?);
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        let (start, len) = match range {
+            Some((start, end)) => {
+                *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+                res.headers_mut().insert(
+                    CONTENT_RANGE,
+                    format!("bytes {start}-{end}/{size}").parse()?,
+                );
+                (start, end - start + 1)
+            }
+            None => (0, size),
+        };
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, format!("{len}").parse()?);
+        if head_only {
+            return Ok(());
+        }
+        let stream_body = StreamBody::new(
+            file.read(start, len)
+                .map_ok(Frame::data)
+                .map_err(|err| anyhow!("{err}")),
+        );
+        *res.body_mut() = stream_body.boxed();
+        Ok(())
+    }
+
+    /// Answer a request for several disjoint ranges with a `multipart/byteranges` body.
+    fn send_multipart_ranges(
+        &self,
+        file: File,
+        ranges: &[(u64, u64)],
+        size: u64,
+        content_type: &str,
+        head_only: bool,
+        res: &mut Response,
+    ) -> Result<()> {
+        let read_buffer_size = self.args.read_buffer_size as usize;
+        let boundary = Uuid::new_v4().simple().to_string();
+        let parts: Vec<(String, u64, u64)> = ranges
+            .iter()
+            .map(|(start, end)| {
+                let head = format!(
+                    "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: bytes {start}-{end}/{size}\r\n\r\n"
+                );
+                (head, *start, end - start + 1)
+            })
+            .collect();
+        let tail = format!("\r\n--{boundary}--\r\n");
+        let len: u64 = parts
+            .iter()
+            .map(|(head, _, len)| head.len() as u64 + len)
+            .sum::<u64>()
+            + tail.len() as u64;
+
+        *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            format!("multipart/byteranges; boundary={boundary}").parse()?,
+        );
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, format!("{len}").parse()?);
+        if head_only {
+            return Ok(());
+        }
+
+        let stream = async_stream::try_stream! {
+            let mut file = file;
+            for (head, start, len) in parts {
+                yield Frame::data(Bytes::from(head));
+                file.seek(SeekFrom::Start(start)).await?;
+                let mut reader = ReaderStream::with_capacity((&mut file).take(len), read_buffer_size);
+                while let Some(chunk) = reader.try_next().await? {
+                    yield Frame::data(chunk);
+                }
+            }
+            yield Frame::data(Bytes::from(tail));
+        };
+        let stream_body = StreamBody::new(stream.map_err(|err: io::Error| anyhow!("{err}")));
+        *res.body_mut() = stream_body.boxed();
+        Ok(())
+    }
+
+    /// Serve `path` as the concatenation of its `.part<N>` siblings, returns false if there are none.
+    async fn handle_send_parts(
+        &self,
+        path: &Path,
+        headers: &HeaderMap<HeaderValue>,
+        head_only: bool,
+        res: &mut Response,
+    ) -> Result<bool> {
+        let parts = find_file_parts(path).await?;
+        if parts.is_empty() {
+            return Ok(false);
+        }
+        let sizes: Vec<u64> = parts.iter().map(|(_, meta)| meta.len()).collect();
+        let size: u64 = sizes.iter().sum();
+        let mtime = parts
+            .iter()
+            .filter_map(|(_, meta)| meta.modified().ok())
+            .max();
+
+        let mut use_range = headers.typed_get::<Range>().is_some();
+        if let Some((etag, last_modified)) = mtime.and_then(|v| make_cache_headers(v, size, "")) {
+            if let Some(status) =
+                check_preconditions(&Method::GET, headers, Some((&etag, last_modified.into())))
+            {
+                *res.status_mut() = status;
+                return Ok(true);
+            }
+            res.headers_mut().typed_insert(last_modified);
+            res.headers_mut().typed_insert(etag.clone());
+            if let Some(if_range) = headers.typed_get::<IfRange>() {
+                use_range = use_range && !if_range.is_modified(Some(&etag), Some(&last_modified));
+            }
+        }
+
+        let ext = path.extension().map(|v| v.to_string_lossy().to_lowercase());
+        let content_type = match ext.and_then(|v| self.args.mime.get(&v)) {
+            Some(mime) => mime.clone(),
+            None => mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+        };
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        let inline = self.is_inline_type(&content_type);
+        set_content_disposition(res, inline, try_get_file_name(path)?)?;
+        res.headers_mut().typed_insert(AcceptRanges::bytes());
+
+        let (start, end) = if use_range {
+            let range = headers
+                .get(RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| parse_range(v, size));
+            match range {
+                Some((start, end)) => {
+                    *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    res.headers_mut().insert(
+                        CONTENT_RANGE,
+                        format!("bytes {start}-{end}/{size}").parse()?,
+                    );
+                    (start, end)
+                }
+                None => {
+                    *res.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                    res.headers_mut()
+                        .insert(CONTENT_RANGE, format!("bytes */{size}").parse()?);
+                    return Ok(true);
+                }
+            }
+        } else {
+            (0, size.saturating_sub(1))
+        };
+        let len = if size == 0 { 0 } else { end - start + 1 };
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, format!("{len}").parse()?);
+        if head_only || len == 0 {
+            return Ok(true);
+        }
+
+        let segments: Vec<(PathBuf, u64, u64)> = split_range(&sizes, start, end)
+            .into_iter()
+            .map(|(index, offset, len)| (parts[index].0.clone(), offset, len))
+            .collect();
+        let read_buffer_size = self.args.read_buffer_size as usize;
+        let stream = async_stream::try_stream! {
+            for (path, offset, len) in segments {
+                let mut file = fs::File::open(&path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+                let mut reader = ReaderStream::with_capacity(file.take(len), read_buffer_size);
+                while let Some(chunk) = reader.try_next().await? {
+                    yield Frame::data(chunk);
+                }
+            }
+        };
+        let stream_body = StreamBody::new(stream.map_err(|err: io::Error| anyhow!("{err}")));
+        *res.body_mut() = stream_body.boxed();
+        Ok(true)
+    }
+
+    /// Serve a missing file from `<name>.gz`, as is with `Content-Encoding: gzip` when the client
+    /// accepts it and decompressed otherwise. `false` if there is no such file.
+    ///
+    /// Ranges aren't supported, a decompressed response gets its `Content-Length` from the
+    /// gzip trailer when it can be trusted.
+    async fn handle_send_gunzip(
+        &self,
+        path: &Path,
+        headers: &HeaderMap<HeaderValue>,
+        head_only: bool,
+        res: &mut Response,
+    ) -> Result<bool> {
+        let gz_path = append_ext("gz", path.to_path_buf());
+        let meta = match fs::metadata(&gz_path).await {
+            Ok(meta) if meta.is_file() => meta,
+            _ => return Ok(false),
+        };
+        if !self.args.allow_symlink && !self.is_root_contained(&gz_path).await {
+            return Ok(false);
+        }
+        let use_gzip = accepts_gzip(headers);
+        res.headers_mut()
+            .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+        let etag_suffix = if use_gzip { "-gzip" } else { "-gunzip" };
+        if let Some((etag, last_modified)) = meta
+            .modified()
+            .ok()
+            .and_then(|v| make_cache_headers(v, meta.len(), etag_suffix))
+        {
+            if let Some(status) =
+                check_preconditions(&Method::GET, headers, Some((&etag, last_modified.into())))
+            {
+                *res.status_mut() = status;
+                return Ok(true);
+            }
+            res.headers_mut().typed_insert(last_modified);
+            res.headers_mut().typed_insert(etag);
+        }
+
+        let ext = path.extension().map(|v| v.to_string_lossy().to_lowercase());
+        let content_type = match ext.and_then(|v| self.args.mime.get(&v)) {
+            Some(mime) => mime.clone(),
+            None => mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+        };
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        let inline = self.is_inline_type(&content_type);
+        set_content_disposition(res, inline, try_get_file_name(path)?)?;
+        res.headers_mut()
+            .insert(ACCEPT_RANGES, HeaderValue::from_static("none"));
+
+        let mut file = fs::File::open(&gz_path).await?;
+        if use_gzip {
+            res.headers_mut()
+                .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+            res.headers_mut().typed_insert(ContentLength(meta.len()));
+        } else if let Some(size) = gzip_isize(&mut file, meta.len()).await? {
+            res.headers_mut().typed_insert(ContentLength(size));
+        }
+        if head_only {
+            return Ok(true);
+        }
+
+        let read_buffer_size = self.args.read_buffer_size as usize;
+        let reader: Pin<Box<dyn AsyncRead + Send + Sync>> = if use_gzip {
+            Box::pin(file)
+        } else {
+            Box::pin(GzipDecoder::new(BufReader::new(file)))
+        };
+        let reader = ReaderStream::with_capacity(reader, read_buffer_size);
+        let stream_body =
+            StreamBody::new(reader.map_ok(Frame::data).map_err(|err| anyhow!("{err}")));
+        *res.body_mut() = stream_body.boxed();
+        Ok(true)
+    }
+
+    #[cfg(feature = "image")]
+    async fn handle_convert_image(
+        &self,
+        path: &Path,
+        format: &str,
+        headers: &HeaderMap<HeaderValue>,
+        head_only: bool,
+        res: &mut Response,
+    ) -> Result<()> {
+        let format: ImageFormat = match format.parse() {
+            Ok(v) => v,
+            Err(err) => {
+                status_bad_request(res, &err.to_string());
+                return Ok(());
+            }
+        };
+        let mtime = fs::metadata(path).await?.modified()?;
+        let Some(permit) = self.heavy_task(res).await else {
+            return Ok(());
+        };
+        let converted = convert_image(path, mtime, format, self.args.image_quality).await?;
+        drop(permit);
+        match converted {
+            Some(converted_path) => {
+                self.handle_send_file(&converted_path, headers, head_only, res)
+                    .await?;
+                let filename = path.with_extension(format.ext());
+                set_content_disposition(res, true, try_get_file_name(&filename)?)?;
+            }
+            None => {
+                *res.status_mut() = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+                *res.body_mut() = body_full("Unsupported image format");
+            }
+        }
+        Ok(())
+    }
+
     async fn handle_edit_file(
         &self,
         path: &Path,
         kind: DataKind,
+        headers: &HeaderMap<HeaderValue>,
         head_only: bool,
         user: Option<String>,
         res: &mut Response,
     ) -> Result<()> {
-        let (file, meta) = tokio::join!(fs::File::open(path), fs::metadata(path),);
-        let (file, meta) = (file?, meta?);
+        let meta = fs::metadata(path).await?;
+        let size = self.content.size(path, &meta).await?;
         let href = format!(
             "/{}",
             normalize_path(path.strip_prefix(&self.args.serve_path)?)
         );
         let mut buffer: Vec<u8> = vec![];
-        file.take(1024).read_to_end(&mut buffer).await?;
+        self.content
+            .open(path, 0, 1024)
+            .await?
+            .read_to_end(&mut buffer)
+            .await?;
         let editable =
-            meta.len() <= EDITABLE_TEXT_MAX_SIZE && content_inspector::inspect(&buffer).is_text();
+            size <= EDITABLE_TEXT_MAX_SIZE && content_inspector::inspect(&buffer).is_text();
         let data = EditData {
             href,
             kind,
@@ -964,9 +3464,10 @@ impl Server {
                 "__ASSETS_PREFIX__",
                 &format!("{}{}", self.args.uri_prefix, self.assets_prefix),
             )
-            .replace("__INDEX_DATA__", &index_data);
+            .replace("__INDEX_DATA__", &index_data)
+            .replace("__OPENGRAPH__", &self.opengraph_meta(path, headers)?);
         res.headers_mut()
-            .typed_insert(ContentLength(output.as_bytes().len() as u64));
+            .typed_insert(ContentLength(output.len() as u64));
         if head_only {
             return Ok(());
         }
@@ -974,86 +3475,532 @@ impl Server {
         Ok(())
     }
 
+    /// Send the file then keep the response open, streaming the bytes appended to it like
+    /// `tail -f`, until the client goes away or `?timeout=<secs>` elapses.
+    ///
+    /// The file is polled, and reopened from its start once truncated or replaced.
+    async fn handle_follow_file(
+        &self,
+        path: &Path,
+        query_params: &HashMap<String, String>,
+        head_only: bool,
+        res: &mut Response,
+    ) -> Result<()> {
+        let limit = match query_params.get("timeout") {
+            Some(secs) => match secs.parse() {
+                Ok(secs) => Some(Duration::from_secs(secs)),
+                Err(_) => {
+                    status_bad_request(res, "Invalid timeout");
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        // Encrypted files are only ever replaced whole, and their chunks can't be sent as they grow
+        if self.content.encrypted_size(path).await?.is_some() {
+            *res.status_mut() = StatusCode::CONFLICT;
+            *res.body_mut() = body_full("Encrypted files can't be followed");
+            return Ok(());
+        }
+        let file = File::open(path).await?;
+        let content_type = get_content_type(path, &self.args.mime).await?;
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        res.headers_mut()
+            .typed_insert(CacheControl::new().with_no_store());
+        res.headers_mut().insert(
+            "x-content-type-options",
+            HeaderValue::from_static("nosniff"),
+        );
+        if head_only {
+            return Ok(());
+        }
+
+        let path = path.to_path_buf();
+        let deadline = limit.map(|v| tokio::time::Instant::now() + v);
+        let stream = async_stream::try_stream! {
+            let mut file = file;
+            let mut id = file_id(&file.metadata().await?);
+            let mut pos = 0;
+            let mut buf = vec![0; BUF_SIZE];
+            loop {
+                let n = file.read(&mut buf).await?;
+                if n > 0 {
+                    pos += n as u64;
+                    yield Frame::data(Bytes::copy_from_slice(&buf[..n]));
+                    continue;
+                }
+                if deadline.is_some_and(|v| tokio::time::Instant::now() >= v) {
+                    break;
+                }
+                tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+                if let Ok(meta) = fs::metadata(&path).await {
+                    if file_id(&meta) != id || meta.len() < pos {
+                        file = File::open(&path).await?;
+                        id = file_id(&meta);
+                        pos = 0;
+                    }
+                }
+            }
+        };
+        let stream_body = StreamBody::new(stream.map_err(|err: io::Error| anyhow!("{err}")));
+        *res.body_mut() = stream_body.boxed();
+        Ok(())
+    }
+
     async fn handle_hash_file(
         &self,
         path: &Path,
+        query_params: &HashMap<String, String>,
+        headers: &HeaderMap<HeaderValue>,
         head_only: bool,
         res: &mut Response,
     ) -> Result<()> {
-        let output = sha256_file(path).await?;
+        if let Some(algorithm) = query_params.get("checksum") {
+            if algorithm != "sha256" {
+                status_bad_request(res, "Unsupported checksum algorithm");
+                return Ok(());
+            }
+        }
+        let meta = fs::metadata(path).await?;
+        let size = self.content.size(path, &meta).await?;
+        let range = match query_params.get("range") {
+            Some(range) => match parse_range(range, size) {
+                Some((start, end)) => {
+                    res.headers_mut().insert(
+                        "x-checksum-range",
+                        format!("bytes {start}-{end}/{size}").parse()?,
+                    );
+                    Some((start, end))
+                }
+                None => {
+                    *res.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                    res.headers_mut()
+                        .insert(CONTENT_RANGE, format!("bytes */{size}").parse()?);
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        let suffix = match range {
+            Some((start, end)) => format!("-sha256-{start}-{end}"),
+            None => "-sha256".to_string(),
+        };
+        if send_not_modified(&meta, &suffix, headers, res) {
+            return Ok(());
+        }
+        let Some(permit) = self.heavy_task(res).await else {
+            return Ok(());
+        };
+        let output = match range {
+            Some(range) => self.sha256_file(path, Some(range)).await?,
+            None => {
+                let mtime = meta
+                    .modified()
+                    .map(|v| to_timestamp(&v))
+                    .unwrap_or_default();
+                self.cached_sha256(path, mtime, size).await?
+            }
+        };
+        drop(permit);
         res.headers_mut()
             .typed_insert(ContentType::from(mime_guess::mime::TEXT_HTML_UTF_8));
         res.headers_mut()
-            .typed_insert(ContentLength(output.as_bytes().len() as u64));
+            .typed_insert(ContentLength(output.len() as u64));
+        res.headers_mut()
+            .typed_insert(CacheControl::new().with_no_cache());
         if head_only {
             return Ok(());
         }
-        *res.body_mut() = body_full(output);
-        Ok(())
-    }
+        *res.body_mut() = body_full(output);
+        Ok(())
+    }
+
+    /// Serve the SHA-256 of each `--block-size` block of a file, so that a downloader
+    /// can fetch the blocks by range, verify them and only retry the broken ones.
+    async fn handle_block_manifest(
+        &self,
+        path: &Path,
+        headers: &HeaderMap<HeaderValue>,
+        head_only: bool,
+        res: &mut Response,
+    ) -> Result<()> {
+        let meta = fs::metadata(path).await?;
+        let size = self.content.size(path, &meta).await?;
+        let mtime = meta
+            .modified()
+            .map(|v| to_timestamp(&v))
+            .unwrap_or_default();
+        let block_size = self.args.block_size;
+        if send_not_modified(&meta, &format!("-manifest-{block_size}"), headers, res) {
+            return Ok(());
+        }
+        let Some(permit) = self.heavy_task(res).await else {
+            return Ok(());
+        };
+        let digests = self.cached_block_sha256(path, mtime, size).await?;
+        drop(permit);
+        let blocks = digests
+            .iter()
+            .enumerate()
+            .map(|(index, sha256)| {
+                let offset = index as u64 * block_size;
+                BlockItem {
+                    offset,
+                    size: block_size.min(size - offset),
+                    sha256: sha256.clone(),
+                }
+            })
+            .collect();
+        let manifest = BlockManifest {
+            name: try_get_file_name(path)?.to_string(),
+            size,
+            mtime,
+            block_size,
+            blocks,
+        };
+        let output = serde_json::to_string_pretty(&manifest)?;
+        res.headers_mut()
+            .typed_insert(ContentType::from(mime_guess::mime::APPLICATION_JSON));
+        res.headers_mut()
+            .typed_insert(ContentLength(output.len() as u64));
+        res.headers_mut()
+            .typed_insert(CacheControl::new().with_no_cache());
+        if head_only {
+            return Ok(());
+        }
+        *res.body_mut() = body_full(output);
+        Ok(())
+    }
+
+    /// Hash the blocks of a file unless it is unchanged since they were last hashed.
+    async fn cached_block_sha256(
+        &self,
+        path: &Path,
+        mtime: u64,
+        size: u64,
+    ) -> Result<Arc<Vec<String>>> {
+        if let Some((cached_mtime, cached_size, digests)) =
+            self.block_cache.lock().unwrap().get(path)
+        {
+            if (*cached_mtime, *cached_size) == (mtime, size) {
+                return Ok(digests.clone());
+            }
+        }
+        let block_size = self.args.block_size;
+        let mut digests = vec![];
+        let mut offset = 0;
+        while offset < size {
+            let end = (offset + block_size).min(size) - 1;
+            digests.push(self.sha256_file(path, Some((offset, end))).await?);
+            offset = end + 1;
+        }
+        let digests = Arc::new(digests);
+        let mut cache = self.block_cache.lock().unwrap();
+        if cache.len() >= CHECKSUM_CACHE_MAX_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(path.to_owned(), (mtime, size, digests.clone()));
+        Ok(digests)
+    }
+
+    async fn handle_propfind_dir(
+        &self,
+        path: &Path,
+        depth: Option<HeaderValue>,
+        propfind: &PropFind,
+        access_paths: AccessPaths,
+        res: &mut Response,
+    ) -> Result<()> {
+        let depth: u32 = match depth {
+            Some(v) => match v.to_str().ok().and_then(|v| v.parse().ok()) {
+                Some(0) => 0,
+                Some(1) => 1,
+                _ => {
+                    status_bad_request(res, "Invalid depth: only 0 and 1 are allowed.");
+                    return Ok(());
+                }
+            },
+            None => 1,
+        };
+        let mut paths = match self.to_pathitem(path, &self.args.serve_path).await? {
+            Some(v) => vec![v],
+            None => vec![],
+        };
+        if depth == 1 {
+            match self
+                .list_dir(path, &self.args.serve_path, access_paths)
+                .await
+            {
+                Ok(child) => paths.extend(child),
+                Err(_) => {
+                    status_forbid(res);
+                    return Ok(());
+                }
+            }
+        }
+        let output = paths
+            .iter()
+            .map(|v| v.to_dav_xml(self.args.uri_prefix.as_str(), propfind))
+            .fold(String::new(), |mut acc, v| {
+                acc.push_str(&v);
+                acc
+            });
+        res_multistatus(res, &output);
+        Ok(())
+    }
+
+    async fn handle_propfind_file(
+        &self,
+        path: &Path,
+        propfind: &PropFind,
+        res: &mut Response,
+    ) -> Result<()> {
+        if let Some(pathitem) = self.to_pathitem(path, &self.args.serve_path).await? {
+            res_multistatus(
+                res,
+                &pathitem.to_dav_xml(self.args.uri_prefix.as_str(), propfind),
+            );
+        } else {
+            status_not_found(res);
+        }
+        Ok(())
+    }
+
+    async fn handle_mkcol(&self, path: &Path, res: &mut Response) -> Result<()> {
+        fs::create_dir_all(&path).await?;
+        self.set_resource_location(res, path, StatusCode::CREATED)?;
+        *res.status_mut() = StatusCode::CREATED;
+        Ok(())
+    }
+
+    /// Create a directory through `POST /path?mkdir=1`, `parents=1` also creates the missing
+    /// ancestors like `mkdir -p`. Answers `200` if the directory exists, or `409` with `exist_ok=0`.
+    async fn handle_mkdir(
+        &self,
+        relative_path: &str,
+        query_params: &HashMap<String, String>,
+        user: Option<&str>,
+        res: &mut Response,
+    ) -> Result<()> {
+        if self.args.hidden_deny_write && self.is_hidden_path(relative_path, true) {
+            status_forbid(res);
+            return Ok(());
+        }
+        let parents = has_query_bool(query_params, "parents");
+        let exist_ok = !matches!(
+            query_params.get("exist_ok").map(|v| v.as_str()),
+            Some("0" | "false")
+        );
+        let levels: Vec<String> = relative_path
+            .split('/')
+            .filter(|v| !v.is_empty())
+            .scan(String::new(), |acc, name| {
+                if !acc.is_empty() {
+                    acc.push('/');
+                }
+                acc.push_str(name);
+                Some(acc.clone())
+            })
+            .collect();
+
+        let mut missing = levels.len();
+        for (i, level) in levels.iter().enumerate() {
+            let Some(dir) = self.join_path(level) else {
+                status_forbid(res);
+                return Ok(());
+            };
+            let Ok(meta) = fs::metadata(&dir).await else {
+                missing = i;
+                break;
+            };
+            if !self.args.allow_symlink && !self.is_root_contained(&dir).await {
+                status_forbid(res);
+                return Ok(());
+            }
+            if !meta.is_dir() {
+                *res.status_mut() = StatusCode::CONFLICT;
+                *res.body_mut() = body_full("Not a directory");
+                return Ok(());
+            }
+        }
+        if missing == levels.len() {
+            if exist_ok {
+                *res.status_mut() = StatusCode::OK;
+            } else {
+                *res.status_mut() = StatusCode::CONFLICT;
+                *res.body_mut() = body_full("Already exists");
+            }
+            return Ok(());
+        }
+        if !parents && missing + 1 < levels.len() {
+            *res.status_mut() = StatusCode::CONFLICT;
+            *res.body_mut() = body_full("Parent directory doesn't exist");
+            return Ok(());
+        }
+
+        // Every directory about to be created has to be writable, not only the deepest one.
+        for level in &levels[missing..] {
+            if !self.can_write(level, user) {
+                status_forbid(res);
+                return Ok(());
+            }
+        }
+
+        let Some(path) = self.join_path(relative_path) else {
+            status_forbid(res);
+            return Ok(());
+        };
+        fs::create_dir_all(&path).await?;
+        self.set_resource_location(res, &path, StatusCode::CREATED)?;
+        *res.status_mut() = StatusCode::CREATED;
+        Ok(())
+    }
+
+    /// Rename or move through `POST /path?rename=name`, or `POST /dir` with a JSON body of
+    /// `{"from": "a.txt", "to": "b.txt"}` relative to the directory.
+    async fn handle_rename(
+        &self,
+        relative_path: &str,
+        query_params: &HashMap<String, String>,
+        user: Option<&str>,
+        req: Request,
+        res: &mut Response,
+    ) -> Result<()> {
+        let mut overwrite = has_query_bool(query_params, "overwrite");
+        let (from, to) = match query_params.get("rename") {
+            Some(to) => (relative_path.to_string(), to.clone()),
+            None => {
+                let body = match Limited::new(req.into_body(), RENAME_BODY_MAX_SIZE)
+                    .collect()
+                    .await
+                {
+                    Ok(body) => body.to_bytes(),
+                    Err(_) => {
+                        status_bad_request(res, "Invalid rename request");
+                        return Ok(());
+                    }
+                };
+                if self.args.debug_bodies {
+                    debug_log::log_request_body("POST", relative_path, &body);
+                }
+                let data: RenameData = match serde_json::from_slice(&body) {
+                    Ok(data) => data,
+                    Err(err) => {
+                        status_bad_request(res, &format!("Invalid rename request, {err}"));
+                        return Ok(());
+                    }
+                };
+                overwrite = overwrite || data.overwrite;
+                match normalize_relative_path(&data.from) {
+                    Some(from) if relative_path.is_empty() => (from, data.to),
+                    Some(from) => (format!("{relative_path}/{from}"), data.to),
+                    None => {
+                        status_bad_request(res, "Invalid from");
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        // `to` is a path from the serve root when it starts with `/`, otherwise it is relative to
+        // the directory of `from`.
+        let to = match (to.starts_with('/'), normalize_relative_path(&to)) {
+            (true, Some(to)) => to,
+            (false, Some(to)) => match from.rsplit_once('/') {
+                Some((parent, _)) => format!("{parent}/{to}"),
+                None => to,
+            },
+            (_, None) => String::new(),
+        };
+        if from.is_empty() || to.is_empty() {
+            status_bad_request(res, "Invalid rename request");
+            return Ok(());
+        }
+        if to == from || to.starts_with(&format!("{from}/")) {
+            status_bad_request(res, "Cannot move a path into itself");
+            return Ok(());
+        }
+
+        if self.is_denied_dotfile(&from) {
+            status_not_found(res);
+            return Ok(());
+        }
+        for path in [&from, &to] {
+            if !self.can_write(path, user) || self.is_denied_dotfile(path) {
+                status_forbid(res);
+                return Ok(());
+            }
+        }
 
-    async fn handle_propfind_dir(
-        &self,
-        path: &Path,
-        headers: &HeaderMap<HeaderValue>,
-        access_paths: AccessPaths,
-        res: &mut Response,
-    ) -> Result<()> {
-        let depth: u32 = match headers.get("depth") {
-            Some(v) => match v.to_str().ok().and_then(|v| v.parse().ok()) {
-                Some(0) => 0,
-                Some(1) => 1,
-                _ => {
-                    status_bad_request(res, "Invalid depth: only 0 and 1 are allowed.");
-                    return Ok(());
-                }
-            },
-            None => 1,
+        let (Some(src), Some(dest)) = (self.join_path(&from), self.join_path(&to)) else {
+            status_forbid(res);
+            return Ok(());
         };
-        let mut paths = match self.to_pathitem(path, &self.args.serve_path).await? {
-            Some(v) => vec![v],
-            None => vec![],
+        let src_meta = match fs::symlink_metadata(&src).await {
+            Ok(meta) => meta,
+            Err(_) => {
+                status_not_found(res);
+                return Ok(());
+            }
         };
-        if depth == 1 {
-            match self
-                .list_dir(path, &self.args.serve_path, access_paths)
-                .await
-            {
-                Ok(child) => paths.extend(child),
-                Err(_) => {
+        if self.args.hidden_deny_write && self.is_hidden_path(&to, src_meta.is_dir()) {
+            status_forbid(res);
+            return Ok(());
+        }
+        if !src_meta.is_dir() && !self.is_upload_ext_allowed(&to) {
+            status_forbid(res);
+            return Ok(());
+        }
+        let dest_is_file = fs::symlink_metadata(&dest).await.is_ok_and(|v| !v.is_dir());
+        if (!src_meta.is_dir() && self.is_protected(&src))
+            || (dest_is_file && self.is_protected(&dest))
+        {
+            status_forbid(res);
+            return Ok(());
+        }
+
+        let status = match fs::symlink_metadata(&dest).await {
+            Ok(_) if !overwrite => {
+                *res.status_mut() = StatusCode::CONFLICT;
+                *res.body_mut() = body_full("Already exists");
+                return Ok(());
+            }
+            Ok(meta) if meta.is_dir() => {
+                // Replacing a folder deletes what it holds, as a recursive DELETE would
+                if fs::read_dir(&dest).await?.next_entry().await?.is_none() {
+                    fs::remove_dir(&dest).await?;
+                } else if self.args.no_recursive_delete || self.contains_protected(&dest).await? {
                     status_forbid(res);
                     return Ok(());
+                } else {
+                    fs::remove_dir_all(&dest).await?;
                 }
+                StatusCode::NO_CONTENT
             }
-        }
-        let output = paths
-            .iter()
-            .map(|v| v.to_dav_xml(self.args.uri_prefix.as_str()))
-            .fold(String::new(), |mut acc, v| {
-                acc.push_str(&v);
-                acc
-            });
-        res_multistatus(res, &output);
-        Ok(())
-    }
+            Ok(_) => {
+                fs::remove_file(&dest).await?;
+                StatusCode::NO_CONTENT
+            }
+            Err(_) => StatusCode::CREATED,
+        };
 
-    async fn handle_propfind_file(&self, path: &Path, res: &mut Response) -> Result<()> {
-        if let Some(pathitem) = self.to_pathitem(path, &self.args.serve_path).await? {
-            res_multistatus(res, &pathitem.to_dav_xml(self.args.uri_prefix.as_str()));
-        } else {
-            status_not_found(res);
-        }
-        Ok(())
-    }
+        ensure_path_parent(&dest).await?;
+        fs::rename(&src, &dest).await?;
+        self.notify_webhook("move", &src, Some(&dest), None);
 
-    async fn handle_mkcol(&self, path: &Path, res: &mut Response) -> Result<()> {
-        fs::create_dir_all(path).await?;
-        *res.status_mut() = StatusCode::CREATED;
+        self.set_resource_location(res, &dest, status)?;
+        *res.status_mut() = status;
         Ok(())
     }
 
-    async fn handle_copy(&self, path: &Path, req: &Request, res: &mut Response) -> Result<()> {
-        let dest = match self.extract_dest(req, res) {
+    async fn handle_copy(
+        &self,
+        path: &Path,
+        user: Option<&str>,
+        req: &Request,
+        res: &mut Response,
+    ) -> Result<()> {
+        let dest = match self.extract_dest(req, false, user, res) {
             Some(dest) => dest,
             None => {
                 return Ok(());
@@ -1061,7 +4008,7 @@ impl Server {
         };
 
         let meta = fs::symlink_metadata(path).await?;
-        if meta.is_dir() {
+        if meta.is_dir() || self.is_protected_dest(&dest).await {
             status_forbid(res);
             return Ok(());
         }
@@ -1074,22 +4021,40 @@ impl Server {
         Ok(())
     }
 
-    async fn handle_move(&self, path: &Path, req: &Request, res: &mut Response) -> Result<()> {
-        let dest = match self.extract_dest(req, res) {
+    async fn handle_move(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        user: Option<&str>,
+        req: &Request,
+        res: &mut Response,
+    ) -> Result<()> {
+        let dest = match self.extract_dest(req, is_dir, user, res) {
             Some(dest) => dest,
             None => {
                 return Ok(());
             }
         };
 
+        if self.is_protected_dest(&dest).await {
+            status_forbid(res);
+            return Ok(());
+        }
+
         ensure_path_parent(&dest).await?;
 
         fs::rename(path, &dest).await?;
+        self.notify_webhook("move", path, Some(&dest), None);
 
         status_no_content(res);
         Ok(())
     }
 
+    /// Whether a COPY or MOVE would replace a protected file.
+    async fn is_protected_dest(&self, dest: &Path) -> bool {
+        fs::symlink_metadata(dest).await.is_ok_and(|v| !v.is_dir()) && self.is_protected(dest)
+    }
+
     async fn handle_lock(&self, req_path: &str, auth: bool, res: &mut Response) -> Result<()> {
         let token = if auth {
             format!("opaquelocktoken:{}", Uuid::new_v4())
@@ -1130,34 +4095,58 @@ impl Server {
     }
 
     #[allow(clippy::too_many_arguments)]
-    fn send_index(
+    async fn send_index(
         &self,
         path: &Path,
         mut paths: Vec<PathItem>,
         exist: bool,
         query_params: &HashMap<String, String>,
+        headers: &HeaderMap<HeaderValue>,
         head_only: bool,
         user: Option<String>,
         access_paths: AccessPaths,
         res: &mut Response,
     ) -> Result<()> {
-        if let Some(sort) = query_params.get("sort") {
-            if sort == "name" {
-                paths.sort_by(|v1, v2| v1.sort_by_name(v2))
-            } else if sort == "mtime" {
-                paths.sort_by(|v1, v2| v1.sort_by_mtime(v2))
-            } else if sort == "size" {
-                paths.sort_by(|v1, v2| v1.sort_by_size(v2))
-            }
-            if query_params
-                .get("order")
-                .map(|v| v == "desc")
-                .unwrap_or_default()
-            {
-                paths.reverse()
+        if let Some(filter) = query_params.get("filter") {
+            let patterns: Vec<&str> = filter.split(',').filter(|v| !v.is_empty()).collect();
+            if !patterns.is_empty() {
+                paths.retain(|v| patterns.iter().any(|pattern| glob(pattern, &v.name)));
             }
-        } else {
-            paths.sort_by(|v1, v2| v1.sort_by_name(v2))
+        }
+        let sort_dirs = query_params
+            .get("dirs")
+            .and_then(|v| SortDirs::parse(v))
+            .unwrap_or(self.args.default_sort_dirs);
+        let compare = path_item_comparator(&with_sort_cookie(query_params, headers), sort_dirs);
+        paths.sort_by(&compare);
+        // `?after=cursor` resumes past the entry the cursor was taken from, so entries added or
+        // removed between two pages neither shift nor repeat the remaining ones.
+        if let Some(after) = query_params.get("after") {
+            let Some(cursor) = PageCursor::decode(after) else {
+                status_bad_request(res, "Invalid after cursor");
+                return Ok(());
+            };
+            let cursor = cursor.to_pathitem();
+            paths.retain(|v| compare(v, &cursor) == Ordering::Greater);
+        }
+        let page_limit = query_params
+            .get("limit")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0);
+        let limit = [self.args.listing_limit, page_limit]
+            .into_iter()
+            .flatten()
+            .min();
+        let (truncated, next_cursor) = match limit {
+            Some(limit) if paths.len() > limit => {
+                paths.truncate(limit);
+                (true, paths.last().map(|v| PageCursor::new(v).encode()))
+            }
+            _ => (false, None),
+        };
+        if let Some(next_cursor) = next_cursor.as_ref() {
+            res.headers_mut()
+                .insert("x-next-cursor", HeaderValue::from_str(next_cursor)?);
         }
         if has_query_flag(query_params, "simple") {
             let output = paths
@@ -1174,18 +4163,87 @@ impl Server {
             res.headers_mut()
                 .typed_insert(ContentType::from(mime_guess::mime::TEXT_HTML_UTF_8));
             res.headers_mut()
-                .typed_insert(ContentLength(output.as_bytes().len() as u64));
+                .typed_insert(ContentLength(output.len() as u64));
             *res.body_mut() = body_full(output);
             if head_only {
                 return Ok(());
             }
             return Ok(());
         }
+        // Without the UI, listings are only sent as json.
+        let is_json = has_query_flag(query_params, "json") || self.args.no_ui;
+        if self.args.no_ui && !has_query_flag(query_params, "json") && !accepts_json(headers) {
+            *res.status_mut() = StatusCode::NOT_ACCEPTABLE;
+            *res.body_mut() = body_full("Listings are only sent as application/json");
+            return Ok(());
+        }
+        // JSON keeps the raw timestamps, only the page renders them server side.
+        if !is_json && (self.args.time_format.is_some() || self.args.time_zone.is_some()) {
+            let format = self
+                .args
+                .time_format
+                .as_deref()
+                .unwrap_or(DEFAULT_TIME_FORMAT);
+            let zone = self.args.time_zone.unwrap_or_default();
+            for item in paths.iter_mut() {
+                item.mtime_display = Utc
+                    .timestamp_millis_opt(item.mtime as i64)
+                    .single()
+                    .map(|v| zone.format(v, format));
+            }
+        }
+        if let (true, Some(max_bytes)) = (is_json, self.args.listing_preview_bytes) {
+            for item in paths
+                .iter_mut()
+                .filter(|v| !v.is_dir())
+                .take(LISTING_PREVIEW_MAX_FILES)
+            {
+                item.preview =
+                    read_text_preview(&self.content, &path.join(&item.name), item.size, max_bytes)
+                        .await;
+            }
+        }
+        if is_json && self.args.listing_checksums {
+            // Only hashing takes a slot of --heavy-task-concurrency, cached digests are free
+            let misses = paths.iter().any(|v| {
+                !v.is_dir()
+                    && v.size <= LISTING_CHECKSUM_MAX_SIZE
+                    && self
+                        .cached_sha256_hit(&path.join(&v.name), v.mtime, v.size)
+                        .is_none()
+            });
+            let _permit = match misses {
+                true => match self.heavy_task(res).await {
+                    Some(permit) => permit,
+                    None => return Ok(()),
+                },
+                false => None,
+            };
+            for item in paths.iter_mut().filter(|v| !v.is_dir()) {
+                let digest = match item.size <= LISTING_CHECKSUM_MAX_SIZE {
+                    true => self
+                        .cached_sha256(&path.join(&item.name), item.mtime, item.size)
+                        .await
+                        .ok(),
+                    false => None,
+                };
+                item.sha256 = Some(digest);
+            }
+        }
+        if self.shows_column("mode") {
+            for item in paths.iter_mut() {
+                item.mode = get_file_mtime_and_mode(&path.join(&item.name))
+                    .await
+                    .ok()
+                    .map(|(_, mode)| mode & 0o7777);
+            }
+        }
         let href = format!(
             "/{}",
             normalize_path(path.strip_prefix(&self.args.serve_path)?)
         );
         let readwrite = access_paths.perm().readwrite();
+        let banner = self.args.banner.clone().filter(|_| href == "/");
         let data = IndexData {
             kind: DataKind::Index,
             href,
@@ -1198,25 +4256,80 @@ impl Server {
             auth: self.args.auth.exist(),
             user,
             paths,
+            truncated,
+            next_cursor,
+            banner,
+            columns: (!self.args.listing_columns.is_empty())
+                .then(|| self.args.listing_columns.clone()),
         };
-        let output = if has_query_flag(query_params, "json") {
+        let output = if is_json {
             res.headers_mut()
                 .typed_insert(ContentType::from(mime_guess::mime::APPLICATION_JSON));
-            serde_json::to_string_pretty(&data)?
+            let legacy = query_params
+                .get("legacy")
+                .is_some_and(|v| v.is_empty() || v == "1" || v == "true");
+            if legacy {
+                self.listing_json(&data, "paths", true)?
+            } else {
+                self.listing_json(&ListingEnvelope::new(&data, limit), "entries", true)?
+            }
         } else {
             res.headers_mut()
                 .typed_insert(ContentType::from(mime_guess::mime::TEXT_HTML_UTF_8));
 
-            let index_data = STANDARD.encode(serde_json::to_string(&data)?);
+            let index_data = STANDARD.encode(self.listing_json(&data, "paths", false)?);
             self.html
                 .replace(
                     "__ASSETS_PREFIX__",
                     &format!("{}{}", self.args.uri_prefix, self.assets_prefix),
                 )
                 .replace("__INDEX_DATA__", &index_data)
+                .replace("__OPENGRAPH__", &self.opengraph_meta(path, headers)?)
         };
+        let mut output = Bytes::from(output);
+        #[cfg(feature = "compression-dictionary")]
+        if let Some(dictionary) = self.args.dictionary.as_ref().filter(|_| is_json) {
+            // Accept-Encoding is added below otherwise
+            let vary = match self.args.listing_compress_threshold {
+                Some(_) => "Available-Dictionary",
+                None => "Accept-Encoding, Available-Dictionary",
+            };
+            res.headers_mut()
+                .append(VARY, HeaderValue::from_static(vary));
+            res.headers_mut().insert(
+                "link",
+                HeaderValue::from_str(&format!(
+                    r#"<{}{DICTIONARY_PATH}>; rel="compression-dictionary""#,
+                    self.args.uri_prefix
+                ))?,
+            );
+            if let Some(encoding) = dictionary.negotiate(headers) {
+                output = dictionary.compress(encoding, &output).await?.into();
+                res.headers_mut().insert(
+                    CONTENT_ENCODING,
+                    HeaderValue::from_static(encoding.as_str()),
+                );
+            }
+        }
+        if let Some(threshold) = self.args.listing_compress_threshold {
+            res.headers_mut()
+                .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+            // Listings are small and compress well, so they are compressed in one go.
+            if output.len() as u64 >= threshold
+                && accepts_gzip(headers)
+                && !res.headers().contains_key(CONTENT_ENCODING)
+            {
+                let mut compressed = vec![];
+                GzipEncoder::new(&output[..])
+                    .read_to_end(&mut compressed)
+                    .await?;
+                output = compressed.into();
+                res.headers_mut()
+                    .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+            }
+        }
         res.headers_mut()
-            .typed_insert(ContentLength(output.as_bytes().len() as u64));
+            .typed_insert(ContentLength(output.len() as u64));
         res.headers_mut()
             .typed_insert(CacheControl::new().with_no_cache());
         res.headers_mut().insert(
@@ -1230,14 +4343,146 @@ impl Server {
         Ok(())
     }
 
+    /// Advertise the methods the server accepts anywhere.
+    fn set_allow_headers(&self, res: &mut Response) {
+        self.set_allow_headers_for(res, AllowTarget::Server, true, true);
+    }
+
+    /// Advertise the methods accepted by `target` for a request that may read or write it,
+    /// along with the WebDAV compliance classes unless WebDAV is disabled.
+    fn set_allow_headers_for(
+        &self,
+        res: &mut Response,
+        target: AllowTarget,
+        readable: bool,
+        writable: bool,
+    ) {
+        let webdav = !self.args.disable_webdav;
+        let writable = writable && !self.maintenance.load(atomic::Ordering::SeqCst);
+        let upload = writable && self.args.allow_upload;
+        let delete = writable && self.args.allow_delete;
+        let exists = readable && target != AllowTarget::Missing;
+        // Properties and locks are only kept for files
+        let file = exists && matches!(target, AllowTarget::Server | AllowTarget::File);
+        let methods = [
+            ("GET", exists),
+            ("HEAD", exists),
+            (
+                "PUT",
+                upload
+                    && match target {
+                        AllowTarget::Dir => false,
+                        AllowTarget::File => delete,
+                        _ => true,
+                    },
+            ),
+            ("OPTIONS", true),
+            ("DELETE", delete && target != AllowTarget::Missing),
+            (
+                "PATCH",
+                upload && matches!(target, AllowTarget::Server | AllowTarget::File),
+            ),
+            // mkdir and upload-tree, renames also need delete
+            ("POST", upload),
+            ("PROPFIND", webdav && exists),
+            ("PROPPATCH", webdav && upload && file),
+            (
+                "MKCOL",
+                webdav && upload && matches!(target, AllowTarget::Server | AllowTarget::Missing),
+            ),
+            ("COPY", webdav && upload && exists),
+            ("MOVE", webdav && upload && delete && exists),
+            ("LOCK", webdav && upload && file),
+            ("UNLOCK", webdav && upload && file),
+            ("CHECKAUTH", true),
+            ("LOGOUT", true),
+        ];
+        let allow = methods
+            .iter()
+            .filter(|(_, allowed)| *allowed)
+            .map(|(method, _)| *method)
+            .collect::<Vec<_>>()
+            .join(",");
+        if let Ok(allow) = HeaderValue::from_str(&allow) {
+            res.headers_mut().insert("Allow", allow);
+        }
+        // Locks are only granted so that clients such as Finder agree to write, class 2 is
+        // claimed where they may. Bindings (RFC 5842) aren't supported.
+        if webdav {
+            let classes = if upload { "1, 2, 3" } else { "1, 3" };
+            res.headers_mut()
+                .insert("DAV", HeaderValue::from_static(classes));
+        }
+    }
+
+    /// Whether the user `handle` signed in, by credentials or session, may write `path`, for the
+    /// paths a request touches besides its own.
+    fn can_write(&self, path: &str, user: Option<&str>) -> bool {
+        self.args
+            .auth
+            .user_paths(user)
+            .is_some_and(|v| v.find(path, true).is_some())
+    }
+
+    /// The user signed in by the session cookie of a request, with --auth-session.
+    fn session_user(&self, headers: &HeaderMap<HeaderValue>) -> Option<String> {
+        let secret = self
+            .args
+            .session_secret
+            .as_deref()
+            .filter(|_| self.args.auth_session)?;
+        verify_session(secret, get_cookie(headers, SESSION_COOKIE)?)
+    }
+
+    fn set_session(&self, user: &str, res: &mut Response) -> Result<()> {
+        let Some(secret) = self.args.session_secret.as_deref() else {
+            return Ok(());
+        };
+        let expiry = unix_now()?.as_secs() + self.args.session_ttl;
+        let value = sign_session(secret, user, expiry);
+        self.set_session_cookie(&value, self.args.session_ttl, res)
+    }
+
+    fn clear_session(&self, res: &mut Response) -> Result<()> {
+        self.set_session_cookie("", 0, res)
+    }
+
+    fn set_session_cookie(&self, value: &str, max_age: u64, res: &mut Response) -> Result<()> {
+        let secure = if self.args.tls_cert.is_some() {
+            "; Secure"
+        } else {
+            ""
+        };
+        let cookie = format!(
+            "{SESSION_COOKIE}={value}; Path={}; Max-Age={max_age}; HttpOnly; SameSite=Lax{secure}",
+            self.args.uri_prefix
+        );
+        res.headers_mut()
+            .insert(SET_COOKIE, HeaderValue::from_str(&cookie)?);
+        Ok(())
+    }
+
     fn auth_reject(&self, res: &mut Response) -> Result<()> {
-        set_webdav_headers(res);
+        self.set_allow_headers(res);
 
         www_authenticate(res, &self.args)?;
         *res.status_mut() = StatusCode::UNAUTHORIZED;
         Ok(())
     }
 
+    /// Whether a MIME type is displayed in the browser rather than downloaded, `--inline-types`
+    /// wins over `--attachment-types` and anything matching neither is displayed.
+    fn is_inline_type(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let matches = |types: &[String]| types.iter().any(|v| glob(&v.to_lowercase(), &essence));
+        matches(&self.args.inline_types) || !matches(&self.args.attachment_types)
+    }
+
     async fn is_root_contained(&self, path: &Path) -> bool {
         fs::canonicalize(path)
             .await
@@ -1246,33 +4491,44 @@ impl Server {
             .unwrap_or_default()
     }
 
-    fn extract_dest(&self, req: &Request, res: &mut Response) -> Option<PathBuf> {
+    fn extract_dest(
+        &self,
+        req: &Request,
+        is_dir: bool,
+        user: Option<&str>,
+        res: &mut Response,
+    ) -> Option<PathBuf> {
         let headers = req.headers();
-        let dest_path = match self
-            .extract_destination_header(headers)
-            .and_then(|dest| self.resolve_path(&dest))
-        {
-            Some(dest) => dest,
-            None => {
+        let dest = match self.extract_destination_header(headers) {
+            Some(dest) if decode_uri(&dest).is_some() => dest,
+            _ => {
                 status_bad_request(res, "Invalid Destination");
                 return None;
             }
         };
-
-        let authorization = headers.get(AUTHORIZATION);
-        let guard = self
-            .args
-            .auth
-            .guard(&dest_path, req.method(), authorization, false);
-
-        match guard {
-            (_, Some(_)) => {}
-            _ => {
+        let dest_path = match self.resolve_path(&dest) {
+            Some(dest_path) => dest_path,
+            None => {
                 status_forbid(res);
                 return None;
             }
         };
 
+        if !self.can_write(&dest_path, user) || self.is_denied_dotfile(&dest_path) {
+            status_forbid(res);
+            return None;
+        }
+
+        if self.args.hidden_deny_write && self.is_hidden_path(&dest_path, is_dir) {
+            status_forbid(res);
+            return None;
+        }
+
+        if !is_dir && !self.is_upload_ext_allowed(&dest_path) {
+            status_forbid(res);
+            return None;
+        }
+
         let dest = match self.join_path(&dest_path) {
             Some(dest) => dest,
             None => {
@@ -1285,30 +4541,23 @@ impl Server {
     }
 
     fn extract_destination_header(&self, headers: &HeaderMap<HeaderValue>) -> Option<String> {
-        let dest = headers.get("Destination")?.to_str().ok()?;
-        let uri: Uri = dest.parse().ok()?;
-        Some(uri.path().to_string())
+        let dest = std::str::from_utf8(headers.get("Destination")?.as_bytes()).ok()?;
+        // Some clients send unencoded spaces or unicode, so strip scheme and host by hand
+        // instead of requiring a valid `Uri`.
+        let path = match dest.split_once("://") {
+            Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+            None => dest,
+        };
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        if !path.starts_with('/') {
+            return None;
+        }
+        Some(path.to_string())
     }
 
     fn resolve_path(&self, path: &str) -> Option<String> {
         let path = decode_uri(path)?;
-        let path = path.trim_matches('/');
-        let mut parts = vec![];
-        for comp in Path::new(path).components() {
-            if let Component::Normal(v) = comp {
-                let v = v.to_string_lossy();
-                if cfg!(windows) {
-                    let chars: Vec<char> = v.chars().collect();
-                    if chars.len() == 2 && chars[1] == ':' && chars[0].is_ascii_alphabetic() {
-                        return None;
-                    }
-                }
-                parts.push(v);
-            } else {
-                return None;
-            }
-        }
-        let new_path = parts.join("/");
+        let new_path = normalize_relative_path(&path)?;
         let path_prefix = self.args.path_prefix.as_str();
         if path_prefix.is_empty() {
             return Some(new_path);
@@ -1318,6 +4567,187 @@ impl Server {
             .map(|v| v.trim_matches('/').to_string())
     }
 
+    /// Whether the declared Content-Length exceeds `--max-upload-size`, checked before the body
+    /// is read so that clients sending `Expect: 100-continue` never get to send it.
+    fn is_upload_too_large(&self, headers: &HeaderMap<HeaderValue>) -> bool {
+        let Some(max_upload_size) = self.args.max_upload_size else {
+            return false;
+        };
+        headers
+            .typed_get::<ContentLength>()
+            .is_some_and(|v| v.0 > max_upload_size)
+    }
+
+    /// Pick the language variant of a file to serve, `page.fr.html` for `page.html`, trying the
+    /// languages of Accept-Language by preference, then --default-language.
+    ///
+    /// Returns `None` when the request isn't negotiated, and `Some(None)` when no variant exists
+    /// so that the file itself is served.
+    fn negotiate_language(
+        &self,
+        method: &Method,
+        relative_path: &str,
+        is_entry: bool,
+        headers: &HeaderMap<HeaderValue>,
+    ) -> Option<Option<(PathBuf, String)>> {
+        if !self.args.negotiate_language
+            || is_entry
+            || !matches!(*method, Method::GET | Method::HEAD)
+            || relative_path.is_empty()
+            || relative_path.ends_with('/')
+        {
+            return None;
+        }
+        let (parent, name) = match relative_path.rsplit_once('/') {
+            Some((parent, name)) => (format!("{parent}/"), name),
+            None => (String::new(), relative_path),
+        };
+        let mut langs = accepted_languages(headers);
+        langs.push(self.args.default_language.clone());
+        let mut tried = HashSet::new();
+        for lang in langs {
+            if !tried.insert(lang.clone()) {
+                continue;
+            }
+            let variant = self.args.language_variant.file_name(name, &lang);
+            // Variants go through the same checks as any requested path.
+            let Some(path) = self.join_path(&format!("{parent}{variant}")) else {
+                continue;
+            };
+            if path.is_file() {
+                return Some(Some((path, lang)));
+            }
+        }
+        Some(None)
+    }
+
+    /// The `--hidden` rules applying to a request.
+    fn hidden_rules(&self, access_paths: &AccessPaths) -> &[String] {
+        match access_paths.reveal_hidden() {
+            true => &[],
+            false => &self.args.hidden,
+        }
+    }
+
+    /// Whether any component of `relative_path` matches `--hidden`.
+    fn is_hidden_path(&self, relative_path: &str, is_dir: bool) -> bool {
+        let mut ends = relative_path
+            .match_indices('/')
+            .map(|(i, _)| i)
+            .chain([relative_path.len()])
+            .peekable();
+        while let Some(end) = ends.next() {
+            let is_dir_type = is_dir || ends.peek().is_some();
+            if is_hidden(
+                &self.args.hidden,
+                &relative_path[..end],
+                is_dir_type,
+                self.args.expose_well_known,
+            ) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether `--deny-dotfiles` treats a path as nonexistent.
+    fn is_denied_dotfile(&self, relative_path: &str) -> bool {
+        self.args.deny_dotfiles && is_dotfile_path(relative_path)
+    }
+
+    /// The root that `--deny-dotfiles` paths are relative to, `None` when they are served.
+    fn dotfiles_root(&self) -> Option<PathBuf> {
+        self.args
+            .deny_dotfiles
+            .then(|| self.args.serve_path.clone())
+    }
+
+    /// Whether `--listing-columns` shows a column, name, size and mtime being the default ones.
+    fn shows_column(&self, column: &str) -> bool {
+        match self.args.listing_columns.is_empty() {
+            true => column != "mode",
+            false => self.args.listing_columns.iter().any(|v| v == column),
+        }
+    }
+
+    /// A listing as JSON, without the keys of columns left out by `--listing-columns` in the
+    /// entries under `entries_key`.
+    fn listing_json<T: Serialize>(
+        &self,
+        data: &T,
+        entries_key: &str,
+        pretty: bool,
+    ) -> Result<String> {
+        let hidden: Vec<&str> = [
+            ("size", &["size"][..]),
+            ("mtime", &["mtime", "mtime_display"]),
+        ]
+        .into_iter()
+        .filter(|(column, _)| !self.shows_column(column))
+        .flat_map(|(_, keys)| keys.iter().copied())
+        .collect();
+        // Going through a `Value` sorts the keys, so only when some are removed.
+        let output = match hidden.is_empty() {
+            true if pretty => serde_json::to_string_pretty(data)?,
+            true => serde_json::to_string(data)?,
+            false => {
+                let mut value = serde_json::to_value(data)?;
+                if let Some(paths) = value[entries_key].as_array_mut() {
+                    for item in paths.iter_mut().filter_map(|v| v.as_object_mut()) {
+                        for key in &hidden {
+                            item.remove(*key);
+                        }
+                    }
+                }
+                match pretty {
+                    true => serde_json::to_string_pretty(&value)?,
+                    false => serde_json::to_string(&value)?,
+                }
+            }
+        };
+        Ok(output)
+    }
+
+    /// Where an upload is written before it replaces its target, named after the target so
+    /// that a resumed upload finds it again.
+    fn upload_temp_path(&self, path: &Path) -> PathBuf {
+        match self.args.temp_dir.as_ref() {
+            Some(temp_dir) => {
+                let digest = md5::compute(path.as_os_str().as_encoded_bytes());
+                temp_dir.join(format!("{digest:x}.dufsupload"))
+            }
+            None => append_ext("dufsupload", path.to_path_buf()),
+        }
+    }
+
+    /// The first bytes of a written upload, decrypted when stored with `--encrypt-at-rest`.
+    async fn read_upload_head(&self, path: &Path) -> Result<Vec<u8>> {
+        let mut head = vec![];
+        self.content
+            .open(path, 0, sniff::SNIFF_LEN as u64)
+            .await?
+            .read_to_end(&mut head)
+            .await?;
+        Ok(head)
+    }
+
+    /// Whether `--upload-allow-ext` and `--upload-deny-ext` let a file be written at the path.
+    fn is_upload_ext_allowed(&self, relative_path: &str) -> bool {
+        let allow_ext = &self.args.upload_allow_ext;
+        match Path::new(relative_path).extension() {
+            Some(ext) => {
+                let ext = ext.to_string_lossy().to_lowercase();
+                !self.args.upload_deny_ext.contains(&ext)
+                    && (allow_ext.is_empty() || allow_ext.contains(&ext))
+            }
+            None => allow_ext.is_empty() || self.args.upload_allow_no_ext,
+        }
+    }
+
+    async fn root_file_exists(&self, name: &str) -> bool {
+        fs::metadata(self.args.serve_path.join(name)).await.is_ok()
+    }
+
     fn join_path(&self, path: &str) -> Option<PathBuf> {
         if path.is_empty() {
             return Some(self.args.serve_path.clone());
@@ -1337,25 +4767,40 @@ impl Server {
         access_paths: AccessPaths,
     ) -> Result<Vec<PathItem>> {
         let mut paths: Vec<PathItem> = vec![];
+        let hidden = self.hidden_rules(&access_paths);
         if access_paths.perm().indexonly() {
             for name in access_paths.child_names() {
                 let entry_path = entry_path.join(name);
-                self.add_pathitem(&mut paths, base_path, &entry_path).await;
+                self.add_pathitem(&mut paths, base_path, &entry_path, hidden)
+                    .await;
             }
         } else {
             let mut rd = fs::read_dir(entry_path).await?;
             while let Ok(Some(entry)) = rd.next_entry().await {
                 let entry_path = entry.path();
-                self.add_pathitem(&mut paths, base_path, &entry_path).await;
+                self.add_pathitem(&mut paths, base_path, &entry_path, hidden)
+                    .await;
             }
         }
         Ok(paths)
     }
 
-    async fn add_pathitem(&self, paths: &mut Vec<PathItem>, base_path: &Path, entry_path: &Path) {
-        let base_name = get_file_name(entry_path);
+    async fn add_pathitem(
+        &self,
+        paths: &mut Vec<PathItem>,
+        base_path: &Path,
+        entry_path: &Path,
+        hidden: &[String],
+    ) {
+        let relative_path = relative_to(&self.args.serve_path, entry_path);
         if let Ok(Some(item)) = self.to_pathitem(entry_path, base_path).await {
-            if is_hidden(&self.args.hidden, base_name, item.is_dir()) {
+            if is_hidden(
+                hidden,
+                &relative_path,
+                item.is_dir(),
+                self.args.expose_well_known,
+            ) || is_dotfile(self.dotfiles_root().as_deref(), entry_path)
+            {
                 return;
             }
             paths.push(item);
@@ -1367,11 +4812,19 @@ impl Server {
         let (meta, meta2) = tokio::join!(fs::metadata(&path), fs::symlink_metadata(&path));
         let (meta, meta2) = (meta?, meta2?);
         let is_symlink = meta2.is_symlink();
-        if !self.args.allow_symlink && is_symlink && !self.is_root_contained(path).await {
+        if is_symlink
+            && (self.args.symlink_policy == SymlinkPolicy::Hide
+                || !self.args.allow_symlink && !self.is_root_contained(path).await)
+        {
             return Ok(None);
         }
         let is_dir = meta.is_dir();
-        let path_type = match (is_symlink, is_dir) {
+        let show_symlink = is_symlink && self.args.symlink_policy == SymlinkPolicy::Show;
+        let target = match show_symlink {
+            true => self.symlink_target(path).await,
+            false => None,
+        };
+        let path_type = match (show_symlink, is_dir) {
             (true, true) => PathType::SymlinkDir,
             (false, true) => PathType::Dir,
             (true, false) => PathType::SymlinkFile,
@@ -1387,7 +4840,10 @@ impl Server {
                 }
                 count
             }
-            PathType::File | PathType::SymlinkFile => meta.len(),
+            // A file whose encryption header is broken fails to download, its stored size is shown
+            PathType::File | PathType::SymlinkFile => {
+                self.content.size(path, &meta).await.unwrap_or(meta.len())
+            }
         };
         let rel_path = path.strip_prefix(base_path)?;
         let name = normalize_path(rel_path);
@@ -1396,8 +4852,34 @@ impl Server {
             name,
             mtime,
             size,
+            mtime_display: None,
+            preview: None,
+            sha256: None,
+            mode: None,
+            is_symlink: show_symlink,
+            target,
         }))
     }
+
+    /// The target of a symlink for display, resolved against the folder of the link and shown
+    /// from the served root. It is left out if it points elsewhere, so listings don't reveal the
+    /// layout of the host.
+    async fn symlink_target(&self, path: &Path) -> Option<String> {
+        let target = fs::read_link(path).await.ok()?;
+        // Resolved by the components only, the links on the way are followed when served.
+        let mut resolved = PathBuf::new();
+        for component in path.parent()?.join(target).components() {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::CurDir => {}
+                component => resolved.push(component),
+            }
+        }
+        let rel_path = resolved.strip_prefix(&self.args.serve_path).ok()?;
+        Some(format!("/{}", normalize_path(rel_path)))
+    }
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -1420,6 +4902,167 @@ struct IndexData {
     auth: bool,
     user: Option<String>,
     paths: Vec<PathItem>,
+    truncated: bool,
+    next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    banner: Option<String>,
+    /// The `--listing-columns`, left out when they are the default ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    columns: Option<Vec<String>>,
+}
+
+/// The `?json` listing, `version` is bumped on changes that break clients of the previous one.
+#[derive(Debug, Serialize)]
+struct ListingEnvelope<'a> {
+    kind: &'static str,
+    version: u32,
+    path: &'a str,
+    uri_prefix: &'a str,
+    dir_exists: bool,
+    allow_upload: bool,
+    allow_delete: bool,
+    allow_search: bool,
+    allow_archive: bool,
+    auth: bool,
+    user: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    banner: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    columns: Option<&'a [String]>,
+    entries: &'a [PathItem],
+    page: ListingPage<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListingPage<'a> {
+    /// The most entries of a page, from `?limit` or `--listing-limit`.
+    limit: Option<usize>,
+    truncated: bool,
+    next_cursor: Option<&'a str>,
+}
+
+impl<'a> ListingEnvelope<'a> {
+    const VERSION: u32 = 1;
+
+    fn new(data: &'a IndexData, limit: Option<usize>) -> Self {
+        Self {
+            kind: "listing",
+            version: Self::VERSION,
+            path: &data.href,
+            uri_prefix: &data.uri_prefix,
+            dir_exists: data.dir_exists,
+            allow_upload: data.allow_upload,
+            allow_delete: data.allow_delete,
+            allow_search: data.allow_search,
+            allow_archive: data.allow_archive,
+            auth: data.auth,
+            user: data.user.as_deref(),
+            banner: data.banner.as_deref(),
+            columns: data.columns.as_deref(),
+            entries: &data.paths,
+            page: ListingPage {
+                limit,
+                truncated: data.truncated,
+                next_cursor: data.next_cursor.as_deref(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct UploadTreeResult {
+    entries: Vec<UploadTreeEntry>,
+}
+
+/// The outcome of an entry of a `?upload-tree` archive, `200` for one already there.
+#[derive(Debug, Serialize)]
+struct UploadTreeEntry {
+    path: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl UploadTreeEntry {
+    fn failed(path: &str, status: StatusCode, error: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            status: status.as_u16(),
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BlockManifest {
+    name: String,
+    size: u64,
+    mtime: u64,
+    block_size: u64,
+    blocks: Vec<BlockItem>,
+}
+
+#[derive(Debug, Serialize)]
+struct BlockItem {
+    offset: u64,
+    size: u64,
+    sha256: String,
+}
+
+/// The sort key of the last entry of a listing page.
+#[derive(Debug, Serialize, Deserialize)]
+struct PageCursor {
+    dir: bool,
+    name: String,
+    mtime: u64,
+    size: u64,
+}
+
+impl PageCursor {
+    fn new(item: &PathItem) -> Self {
+        Self {
+            dir: item.is_dir(),
+            name: item.name.clone(),
+            mtime: item.mtime,
+            size: item.size,
+        }
+    }
+
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(value: &str) -> Option<Self> {
+        let data = URL_SAFE_NO_PAD.decode(value).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    fn to_pathitem(&self) -> PathItem {
+        PathItem {
+            path_type: if self.dir {
+                PathType::Dir
+            } else {
+                PathType::File
+            },
+            name: self.name.clone(),
+            mtime: self.mtime,
+            size: self.size,
+            mtime_display: None,
+            preview: None,
+            sha256: None,
+            mode: None,
+            is_symlink: false,
+            target: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameData {
+    from: String,
+    to: String,
+    #[serde(default)]
+    overwrite: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -1434,12 +5077,89 @@ struct EditData {
     editable: bool,
 }
 
+const DAV_NAMESPACE: &str = "DAV:";
+
+/// The resource an `Allow` header describes, `Server` stands for `OPTIONS *`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AllowTarget {
+    Server,
+    Dir,
+    File,
+    Missing,
+}
+
+/// The properties a PROPFIND asks for, an empty body means `allprop`.
+#[derive(Debug, PartialEq)]
+enum PropFind {
+    AllProp,
+    PropName,
+    /// The namespace and local name of each requested property.
+    Prop(Vec<(Option<String>, String)>),
+}
+
+impl PropFind {
+    fn parse(body: &[u8]) -> Result<Self> {
+        if body.iter().all(|v| v.is_ascii_whitespace()) {
+            return Ok(PropFind::AllProp);
+        }
+        let mut depth = 0;
+        let mut parent = String::new();
+        let mut propfind = None;
+        for event in EventReader::new(body) {
+            match event? {
+                XmlEvent::StartElement { name, .. } => {
+                    depth += 1;
+                    let is_dav = name.namespace.as_deref() == Some(DAV_NAMESPACE);
+                    match depth {
+                        1 if !is_dav || name.local_name != "propfind" => {
+                            bail!("expected a propfind element")
+                        }
+                        2 if is_dav => {
+                            propfind = match name.local_name.as_str() {
+                                "allprop" => Some(PropFind::AllProp),
+                                "propname" => Some(PropFind::PropName),
+                                "prop" => Some(PropFind::Prop(vec![])),
+                                // e.g. the `include` of allprop, which names no live props here
+                                _ => propfind,
+                            };
+                            parent = name.local_name;
+                        }
+                        3 if parent == "prop" => {
+                            if let Some(PropFind::Prop(props)) = propfind.as_mut() {
+                                props.push((name.namespace, name.local_name));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                XmlEvent::EndElement { .. } => depth -= 1,
+                _ => {}
+            }
+        }
+        propfind.ok_or_else(|| anyhow!("expected allprop, propname or prop"))
+    }
+}
+
 #[derive(Debug, Serialize, Eq, PartialEq, Ord, PartialOrd)]
 struct PathItem {
     path_type: PathType,
     name: String,
     mtime: u64,
     size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime_display: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preview: Option<String>,
+    /// `Some(None)`, serialized as `null`, for files too large to hash in a listing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<Option<String>>,
+    /// The permission bits, with `--listing-columns` including mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<u16>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_symlink: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
 }
 
 impl PathItem {
@@ -1447,7 +5167,7 @@ impl PathItem {
         self.path_type == PathType::Dir || self.path_type == PathType::SymlinkDir
     }
 
-    pub fn to_dav_xml(&self, prefix: &str) -> String {
+    pub fn to_dav_xml(&self, prefix: &str, propfind: &PropFind) -> String {
         let mtime = match Utc.timestamp_millis_opt(self.mtime as i64) {
             LocalResult::Single(v) => format!("{}", v.format("%a, %d %b %Y %H:%M:%S GMT")),
             _ => String::new(),
@@ -1456,64 +5176,77 @@ impl PathItem {
         if self.is_dir() && !href.ends_with('/') {
             href.push('/');
         }
-        let displayname = escape_str_pcdata(self.base_name());
-        match self.path_type {
-            PathType::Dir | PathType::SymlinkDir => format!(
-                r#"<D:response>
-<D:href>{href}</D:href>
-<D:propstat>
-<D:prop>
-<D:displayname>{displayname}</D:displayname>
-<D:getlastmodified>{mtime}</D:getlastmodified>
-<D:resourcetype><D:collection/></D:resourcetype>
-</D:prop>
-<D:status>HTTP/1.1 200 OK</D:status>
-</D:propstat>
-</D:response>"#
+        let mut live = vec![(
+            "displayname",
+            escape_str_pcdata(self.base_name()).to_string(),
+        )];
+        if !self.is_dir() {
+            live.push(("getcontentlength", self.size.to_string()));
+        }
+        live.push(("getlastmodified", mtime));
+        let resourcetype = if self.is_dir() { "<D:collection/>" } else { "" };
+        live.push(("resourcetype", resourcetype.to_string()));
+
+        let (found, missing) = match propfind {
+            PropFind::AllProp => (
+                live.iter()
+                    .map(|(name, value)| format!("<D:{name}>{value}</D:{name}>"))
+                    .collect(),
+                vec![],
             ),
-            PathType::File | PathType::SymlinkFile => format!(
-                r#"<D:response>
-<D:href>{href}</D:href>
-<D:propstat>
-<D:prop>
-<D:displayname>{displayname}</D:displayname>
-<D:getcontentlength>{}</D:getcontentlength>
-<D:getlastmodified>{mtime}</D:getlastmodified>
-<D:resourcetype></D:resourcetype>
-</D:prop>
-<D:status>HTTP/1.1 200 OK</D:status>
-</D:propstat>
-</D:response>"#,
-                self.size
+            PropFind::PropName => (
+                live.iter()
+                    .map(|(name, _)| format!("<D:{name}/>"))
+                    .collect(),
+                vec![],
             ),
+            PropFind::Prop(props) => {
+                let mut found = vec![];
+                let mut missing = vec![];
+                for (namespace, name) in props {
+                    match live
+                        .iter()
+                        .find(|(v, _)| namespace.as_deref() == Some(DAV_NAMESPACE) && v == name)
+                    {
+                        Some((name, value)) => found.push(format!("<D:{name}>{value}</D:{name}>")),
+                        None => missing.push(match namespace.as_deref() {
+                            Some(DAV_NAMESPACE) => format!("<D:{name}/>"),
+                            Some(namespace) => {
+                                format!(r#"<{name} xmlns="{}"/>"#, escape_str_attribute(namespace))
+                            }
+                            None => format!("<{name}/>"),
+                        }),
+                    }
+                }
+                (found, missing)
+            }
+        };
+
+        let mut output = format!("<D:response>\n<D:href>{href}</D:href>\n");
+        if !found.is_empty() || missing.is_empty() {
+            output.push_str(&propstat(&found, "200 OK"));
+        }
+        if !missing.is_empty() {
+            output.push_str(&propstat(&missing, "404 Not Found"));
         }
+        output.push_str("</D:response>");
+        output
     }
 
     pub fn base_name(&self) -> &str {
-        self.name.split('/').last().unwrap_or_default()
+        self.name.split('/').next_back().unwrap_or_default()
     }
 
     pub fn sort_by_name(&self, other: &Self) -> Ordering {
-        match self.path_type.cmp(&other.path_type) {
-            Ordering::Equal => {
-                alphanumeric_sort::compare_str(self.name.to_lowercase(), other.name.to_lowercase())
-            }
-            v => v,
-        }
+        natural_cmp(&self.name, &other.name)
     }
 
     pub fn sort_by_mtime(&self, other: &Self) -> Ordering {
-        match self.path_type.cmp(&other.path_type) {
-            Ordering::Equal => self.mtime.cmp(&other.mtime),
-            v => v,
-        }
+        self.mtime.cmp(&other.mtime)
     }
 
     pub fn sort_by_size(&self, other: &Self) -> Ordering {
-        match self.path_type.cmp(&other.path_type) {
-            Ordering::Equal => self.size.cmp(&other.size),
-            v => v,
-        }
+        self.size.cmp(&other.size)
     }
 }
 
@@ -1549,6 +5282,11 @@ fn to_timestamp(time: &SystemTime) -> u64 {
         .as_millis() as u64
 }
 
+/// The `/` separated path of `path` from `root`.
+fn relative_to(root: &Path, path: &Path) -> String {
+    normalize_path(path.strip_prefix(root).unwrap_or(path))
+}
+
 fn normalize_path<P: AsRef<Path>>(path: P) -> String {
     let path = path.as_ref().to_str().unwrap_or_default();
     if cfg!(windows) {
@@ -1558,6 +5296,22 @@ fn normalize_path<P: AsRef<Path>>(path: P) -> String {
     }
 }
 
+/// `path` itself for `0`, otherwise `name (n).ext` next to it.
+fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    if n == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path
+        .file_stem()
+        .map(|v| v.to_string_lossy())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem} ({n}).{}", ext.to_string_lossy()),
+        None => format!("{stem} ({n})"),
+    };
+    path.with_file_name(name)
+}
+
 async fn ensure_path_parent(path: &Path) -> Result<()> {
     if let Some(parent) = path.parent() {
         if fs::symlink_metadata(parent).await.is_err() {
@@ -1567,6 +5321,44 @@ async fn ensure_path_parent(path: &Path) -> Result<()> {
     Ok(())
 }
 
+fn remove_conditional_headers(headers: &mut HeaderMap<HeaderValue>) {
+    for name in [
+        IF_MATCH,
+        IF_NONE_MATCH,
+        IF_MODIFIED_SINCE,
+        IF_UNMODIFIED_SINCE,
+        IF_RANGE,
+    ] {
+        headers.remove(name);
+    }
+}
+
+fn disable_caching(res: &mut Response) {
+    res.headers_mut().remove(ETAG);
+    res.headers_mut().remove(LAST_MODIFIED);
+    res.headers_mut()
+        .typed_insert(CacheControl::new().with_no_store());
+}
+
+/// Answer a successful write with `204` and no body for `Prefer: return=minimal`, headers such
+/// as `Location` and `ETag` are kept.
+fn apply_return_minimal(res: &mut Response) {
+    if !matches!(
+        res.status(),
+        StatusCode::OK | StatusCode::CREATED | StatusCode::NO_CONTENT
+    ) {
+        return;
+    }
+    *res.status_mut() = StatusCode::NO_CONTENT;
+    *res.body_mut() = body_full("");
+    res.headers_mut().remove(CONTENT_LENGTH);
+    res.headers_mut().remove(CONTENT_TYPE);
+    res.headers_mut().insert(
+        "preference-applied",
+        HeaderValue::from_static("return=minimal"),
+    );
+}
+
 fn add_cors(res: &mut Response) {
     res.headers_mut()
         .typed_insert(AccessControlAllowOrigin::ANY);
@@ -1586,6 +5378,18 @@ fn add_cors(res: &mut Response) {
     );
 }
 
+fn propstat(props: &[String], status: &str) -> String {
+    let mut output = String::from("<D:propstat>\n<D:prop>\n");
+    for prop in props {
+        output.push_str(prop);
+        output.push('\n');
+    }
+    output.push_str(&format!(
+        "</D:prop>\n<D:status>HTTP/1.1 {status}</D:status>\n</D:propstat>\n"
+    ));
+    output
+}
+
 fn res_multistatus(res: &mut Response, content: &str) {
     *res.status_mut() = StatusCode::MULTI_STATUS;
     res.headers_mut().insert(
@@ -1600,12 +5404,20 @@ fn res_multistatus(res: &mut Response, content: &str) {
     ));
 }
 
+/// Write the files under `dir` as a zip, `strip` is the number of leading components to remove
+/// from entry names and whether entries with too few of them are kept by their file name.
+#[allow(clippy::too_many_arguments)]
 async fn zip_dir<W: AsyncWrite + Unpin>(
     writer: &mut W,
     dir: &Path,
     access_paths: AccessPaths,
+    root: PathBuf,
     hidden: &[String],
+    expose_well_known: bool,
+    dotfiles_root: Option<PathBuf>,
     compression: Compression,
+    strip: (usize, bool),
+    content: ContentReader,
     running: Arc<AtomicBool>,
 ) -> Result<()> {
     let mut writer = ZipFileWriter::with_tokio(writer);
@@ -1621,7 +5433,6 @@ async fn zip_dir<W: AsyncWrite + Unpin>(
                     break;
                 }
                 let entry_path = entry.path();
-                let base_name = get_file_name(entry_path);
                 let file_type = entry.file_type();
                 let mut is_dir_type: bool = file_type.is_dir();
                 if file_type.is_symlink() {
@@ -1634,7 +5445,10 @@ async fn zip_dir<W: AsyncWrite + Unpin>(
                         }
                     }
                 }
-                if is_hidden(&hidden, base_name, is_dir_type) {
+                let relative_path = relative_to(&root, entry_path);
+                if is_hidden(&hidden, &relative_path, is_dir_type, expose_well_known)
+                    || is_dotfile(dotfiles_root.as_deref(), entry_path)
+                {
                     if file_type.is_dir() {
                         it.skip_current_dir();
                     }
@@ -1652,16 +5466,27 @@ async fn zip_dir<W: AsyncWrite + Unpin>(
         paths
     })
     .await?;
+    let mut names = HashSet::new();
     for zip_path in zip_paths.into_iter() {
-        let filename = match zip_path.strip_prefix(dir).ok().and_then(|v| v.to_str()) {
-            Some(v) => v,
-            None => continue,
+        let Some(relative) = zip_path.strip_prefix(dir).ok() else {
+            continue;
+        };
+        let filename = match strip {
+            (0, _) => relative.to_str().map(|v| v.to_string()),
+            (num, keep_shallow) => strip_components(relative, num, keep_shallow),
         };
+        let Some(filename) = filename else {
+            continue;
+        };
+        // Stripping may map several files onto the same name, the first one wins.
+        if strip.0 > 0 && !names.insert(filename.clone()) {
+            continue;
+        }
         let (datetime, mode) = get_file_mtime_and_mode(&zip_path).await?;
         let builder = ZipEntryBuilder::new(filename.into(), compression)
             .unix_permissions(mode)
             .last_modification_date(ZipDateTime::from_chrono(&datetime));
-        let mut file = File::open(&zip_path).await?;
+        let mut file = content.open(&zip_path, 0, u64::MAX).await?;
         let mut file_writer = writer.write_entry_stream(builder).await?.compat_write();
         io::copy(&mut file, &mut file_writer).await?;
         file_writer.into_inner().close().await?;
@@ -1670,15 +5495,112 @@ async fn zip_dir<W: AsyncWrite + Unpin>(
     Ok(())
 }
 
-fn extract_cache_headers(meta: &Metadata) -> Option<(ETag, LastModified)> {
-    let mtime = meta.modified().ok()?;
+/// Drop the first `num` components of `path` like `tar --strip-components`. Paths with no
+/// more than `num` components are skipped, unless `keep_shallow` keeps their file name.
+fn strip_components(path: &Path, num: usize, keep_shallow: bool) -> Option<String> {
+    let parts = path
+        .iter()
+        .map(|v| v.to_str())
+        .collect::<Option<Vec<_>>>()?;
+    let skip = match parts.len() > num {
+        true => num,
+        false if keep_shallow => parts.len().checked_sub(1)?,
+        false => return None,
+    };
+    Some(parts[skip..].join("/"))
+}
+
+fn extract_cache_headers_with_suffix(
+    meta: &Metadata,
+    suffix: &str,
+) -> Option<(ETag, LastModified)> {
+    make_cache_headers(meta.modified().ok()?, meta.len(), suffix)
+}
+
+fn make_cache_headers(mtime: SystemTime, size: u64, suffix: &str) -> Option<(ETag, LastModified)> {
     let timestamp = to_timestamp(&mtime);
-    let size = meta.len();
-    let etag = format!(r#""{timestamp}-{size}""#).parse::<ETag>().ok()?;
+    let etag = format!(r#""{timestamp}-{size}{suffix}""#)
+        .parse::<ETag>()
+        .ok()?;
     let last_modified = LastModified::from(mtime);
     Some((etag, last_modified))
 }
 
+/// Answer `304` when the validators of a file, told apart from its content by `suffix`, match
+/// the conditional headers, otherwise send them with what is derived from the file.
+fn send_not_modified(
+    meta: &Metadata,
+    suffix: &str,
+    headers: &HeaderMap<HeaderValue>,
+    res: &mut Response,
+) -> bool {
+    let Some((etag, last_modified)) = extract_cache_headers_with_suffix(meta, suffix) else {
+        return false;
+    };
+    if let Some(status) =
+        check_preconditions(&Method::GET, headers, Some((&etag, last_modified.into())))
+    {
+        *res.status_mut() = status;
+        return true;
+    }
+    res.headers_mut().typed_insert(last_modified);
+    res.headers_mut().typed_insert(etag);
+    false
+}
+
+/// Whether extracting a `?upload-tree` archive failed on the archive itself.
+fn is_upload_tree_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+    ) || err.get_ref().is_some_and(|v| v.is::<UploadTooLarge>())
+}
+
+fn status_upload_tree_error(res: &mut Response, err: io::Error) -> Result<()> {
+    if err.get_ref().is_some_and(|v| v.is::<UploadTooLarge>()) {
+        status_payload_too_large(res);
+    } else if is_upload_tree_error(&err) {
+        status_bad_request(res, &format!("Invalid tar archive, {err}"));
+    } else {
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Give an extracted file the permissions it was archived with, where there are some.
+async fn set_upload_mode(path: &Path, mode: u32) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = std::fs::Permissions::from_mode(mode);
+        if let Err(err) = fs::set_permissions(path, permissions).await {
+            warn!("Failed to set mode of {}, {err}", path.display());
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+}
+
+/// Keep the response of an upload sent with an `Idempotency-Key` for its retries.
+async fn keep_idempotent_response(res: Response, reservation: &Reservation) -> Response {
+    let (parts, body) = res.into_parts();
+    let body = match body.collect().await {
+        Ok(v) => v.to_bytes(),
+        Err(err) => {
+            let mut res = Response::default();
+            *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            *res.body_mut() = body_full(err.to_string());
+            return res;
+        }
+    };
+    reservation.complete(CachedResponse {
+        status: parts.status,
+        headers: parts.headers.clone(),
+        body: body.clone(),
+    });
+    Response::from_parts(parts, body_full(body))
+}
+
 fn status_forbid(res: &mut Response) {
     *res.status_mut() = StatusCode::FORBIDDEN;
     *res.body_mut() = body_full("Forbidden");
@@ -1689,6 +5611,11 @@ fn status_not_found(res: &mut Response) {
     *res.body_mut() = body_full("Not Found");
 }
 
+fn status_payload_too_large(res: &mut Response) {
+    *res.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+    *res.body_mut() = body_full("Payload Too Large");
+}
+
 fn status_no_content(res: &mut Response) {
     *res.status_mut() = StatusCode::NO_CONTENT;
 }
@@ -1725,29 +5652,254 @@ fn set_content_disposition(res: &mut Response, inline: bool, filename: &str) ->
     Ok(())
 }
 
-fn is_hidden(hidden: &[String], file_name: &str, is_dir_type: bool) -> bool {
-    hidden.iter().any(|v| {
-        if is_dir_type {
-            if let Some(x) = v.strip_suffix('/') {
-                return glob(x, file_name);
+/// The languages of Accept-Language from the most to the least preferred, `fr-ca` followed
+/// by `fr` to fall back on the primary language.
+fn accepted_languages(headers: &HeaderMap<HeaderValue>) -> Vec<String> {
+    let Some(value) = headers.get(ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()) else {
+        return vec![];
+    };
+    let mut langs: Vec<(String, f32)> = value
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let lang = parts.next()?.trim().to_lowercase();
+            let q = parts
+                .find_map(|v| v.trim().strip_prefix("q="))
+                .map(|v| v.parse().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            (q > 0.0 && is_language_tag(&lang)).then_some((lang, q))
+        })
+        .collect();
+    // A stable sort keeps the order of the header between equal weights.
+    langs.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut output = vec![];
+    for (lang, _) in langs {
+        let primary = lang.split('-').next().map(|v| v.to_string());
+        output.push(lang);
+        if let Some(primary) = primary {
+            output.push(primary);
+        }
+    }
+    output
+}
+
+/// Whether chat apps can preview the image, `?format=webp` decodes the same formats.
+fn is_previewable_image(path: &Path) -> bool {
+    mime_guess::from_path(path)
+        .first()
+        .is_some_and(|v| v.type_() == mime_guess::mime::IMAGE && v.subtype() != "svg")
+}
+
+/// Tell whether a path still names the same file, to notice it being rotated.
+#[cfg(unix)]
+fn file_id(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Stream a memory map in slices that borrow its pages instead of copying them.
+fn mmap_body(mmap: memmap2::Mmap) -> BoxBody<Bytes, anyhow::Error> {
+    #[cfg(unix)]
+    let _ = mmap.advise(memmap2::Advice::Sequential);
+    let bytes = Bytes::from_owner(mmap);
+    let len = bytes.len();
+    let chunks = (0..len).step_by(BUF_SIZE).map(move |start| {
+        let end = (start + BUF_SIZE).min(len);
+        Ok(Frame::data(bytes.slice(start..end)))
+    });
+    StreamBody::new(futures_util::stream::iter(chunks)).boxed()
+}
+
+/// Join the components of `path` with `/`, rejecting anything that could escape its base.
+fn normalize_relative_path(path: &str) -> Option<String> {
+    let path = path.trim_matches('/');
+    let mut parts = vec![];
+    for comp in Path::new(path).components() {
+        if let Component::Normal(v) = comp {
+            let v = v.to_string_lossy();
+            if cfg!(windows) {
+                let chars: Vec<char> = v.chars().collect();
+                if chars.len() == 2 && chars[1] == ':' && chars[0].is_ascii_alphabetic() {
+                    return None;
+                }
             }
+            parts.push(v);
+        } else {
+            return None;
+        }
+    }
+    Some(parts.join("/"))
+}
+
+#[derive(Debug)]
+struct UploadTooLarge;
+
+impl std::fmt::Display for UploadTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("upload exceeds the max upload size")
+    }
+}
+
+impl std::error::Error for UploadTooLarge {}
+
+/// Whether `--hidden` hides the entry at `relative_path`. Rules containing a `/` match the
+/// path from the root, the others only the name of the entry.
+fn is_hidden(
+    hidden: &[String],
+    relative_path: &str,
+    is_dir_type: bool,
+    expose_well_known: bool,
+) -> bool {
+    let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    if expose_well_known && is_dir_type && file_name == WELL_KNOWN_DIR {
+        return false;
+    }
+    hidden.iter().any(|v| {
+        let pattern = match v.strip_suffix('/') {
+            Some(_) if !is_dir_type => return false,
+            Some(x) => x,
+            None => v.as_str(),
+        };
+        if pattern.contains('/') {
+            // So that `dir/**` hides the folder itself along with its content.
+            let pattern = pattern.trim_start_matches('/');
+            glob_path(pattern, relative_path)
+                || is_dir_type && glob_path(pattern, &format!("{relative_path}/"))
+        } else {
+            glob(pattern, file_name)
         }
-        glob(v, file_name)
     })
 }
 
-fn set_webdav_headers(res: &mut Response) {
-    res.headers_mut().insert(
-        "Allow",
-        HeaderValue::from_static(
-            "GET,HEAD,PUT,OPTIONS,DELETE,PATCH,PROPFIND,COPY,MOVE,CHECKAUTH,LOGOUT",
-        ),
-    );
-    res.headers_mut()
-        .insert("DAV", HeaderValue::from_static("1, 2, 3"));
+fn is_protected(protect: &[String], root: &Path, path: &Path) -> bool {
+    if is_hidden(protect, &relative_to(root, path), false, false) {
+        return true;
+    }
+    let Some(name) = path.file_name().and_then(|v| v.to_str()) else {
+        return false;
+    };
+    // Markers protect themselves, so that only the operator can lift the protection.
+    name.ends_with(PROTECT_MARKER_EXT)
+        || path
+            .with_file_name(format!(".{name}{PROTECT_MARKER_EXT}"))
+            .exists()
+}
+
+fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime,
+            "application/json" | "application/javascript" | "application/xml"
+        )
+}
+
+/// The decompressed size of a gzip file of `len` bytes, from the ISIZE of its trailer.
+///
+/// ISIZE is the size modulo 4G, it is only trusted while deflate, compressing at most 1032:1,
+/// can't have gone past 4G. The size of the last member only, a file written by `gzip` has one.
+/// The position of `file` is restored.
+async fn gzip_isize(file: &mut fs::File, len: u64) -> io::Result<Option<u64>> {
+    if !(GZIP_MIN_SIZE..=u32::MAX as u64 / 1032).contains(&len) {
+        return Ok(None);
+    }
+    let mut isize = [0u8; 4];
+    file.seek(SeekFrom::End(-4)).await?;
+    file.read_exact(&mut isize).await?;
+    file.seek(SeekFrom::Start(0)).await?;
+    Ok(Some(u32::from_le_bytes(isize) as u64))
+}
+
+/// Find the `<name>.part<N>` files next to `path`, ordered by `N`.
+async fn find_file_parts(path: &Path) -> Result<Vec<(PathBuf, Metadata)>> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(vec![]);
+    };
+    let prefix = format!("{}.part", name.to_string_lossy());
+    let mut parts = vec![];
+    let mut entries = match fs::read_dir(parent).await {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let index = file_name
+            .to_str()
+            .and_then(|v| v.strip_prefix(&prefix))
+            .filter(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|v| v.parse::<u64>().ok());
+        if let Some(index) = index {
+            let meta = fs::metadata(entry.path()).await?;
+            if meta.is_file() {
+                parts.push((index, entry.path(), meta));
+            }
+        }
+    }
+    parts.sort_by_key(|(index, _, _)| *index);
+    Ok(parts
+        .into_iter()
+        .map(|(_, path, meta)| (path, meta))
+        .collect())
+}
+
+/// The first `max_bytes` of a text file as UTF-8, `None` for binaries and large files.
+async fn read_text_preview(
+    content: &ContentReader,
+    path: &Path,
+    size: u64,
+    max_bytes: usize,
+) -> Option<String> {
+    if size > EDITABLE_TEXT_MAX_SIZE {
+        return None;
+    }
+    let mut buffer: Vec<u8> = vec![];
+    content
+        .open(path, 0, max_bytes as u64)
+        .await
+        .ok()?
+        .read_to_end(&mut buffer)
+        .await
+        .ok()?;
+    if buffer.contains(&0) || !content_inspector::inspect(&buffer).is_text() {
+        return None;
+    }
+    match String::from_utf8(buffer) {
+        Ok(v) => Some(v),
+        // The excerpt may end in the middle of a multi-byte character
+        Err(err) if err.utf8_error().error_len().is_none() => {
+            let valid_up_to = err.utf8_error().valid_up_to();
+            let mut buffer = err.into_bytes();
+            buffer.truncate(valid_up_to);
+            String::from_utf8(buffer).ok()
+        }
+        Err(_) => None,
+    }
 }
 
-async fn get_content_type(path: &Path) -> Result<String> {
+/// The type of an HLS/DASH file and whether it is a manifest rather than a segment.
+fn streaming_type(path: &Path) -> Option<(&'static str, bool)> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    STREAMING_TYPES
+        .iter()
+        .find(|(v, ..)| *v == ext)
+        .map(|(_, mime, is_manifest)| (*mime, *is_manifest))
+}
+
+async fn get_content_type(path: &Path, overrides: &BTreeMap<String, String>) -> Result<String> {
+    let ext = path.extension().map(|v| v.to_string_lossy().to_lowercase());
+    if let Some(mime) = ext.and_then(|v| overrides.get(&v)) {
+        return Ok(mime.clone());
+    }
+    if let Some((mime, _)) = streaming_type(path) {
+        return Ok(mime.to_string());
+    }
     let mut buffer: Vec<u8> = vec![];
     fs::File::open(path)
         .await?
@@ -1792,21 +5944,155 @@ fn parse_upload_offset(headers: &HeaderMap<HeaderValue>, size: u64) -> Result<Op
     Ok(Some(start))
 }
 
-async fn sha256_file(path: &Path) -> Result<String> {
-    let mut file = fs::File::open(path).await?;
-    let mut hasher = Sha256::new();
-    let mut buffer = [0u8; 8192];
+/// Reads files as they are served, decrypting the ones stored by `--encrypt-at-rest`.
+#[derive(Debug, Clone, Default)]
+struct ContentReader {
+    #[cfg(feature = "encrypt")]
+    key: Option<encrypt::EncryptionKey>,
+}
+
+impl ContentReader {
+    /// The plaintext size of a file stored encrypted, `None` if it is stored as is.
+    async fn encrypted_size(&self, path: &Path) -> Result<Option<u64>> {
+        #[cfg(feature = "encrypt")]
+        if let Some(key) = self.key.as_ref() {
+            return Ok(encrypt::EncryptedFile::open(key, path)
+                .await?
+                .map(|v| v.size));
+        }
+        #[cfg(not(feature = "encrypt"))]
+        let _ = path;
+        Ok(None)
+    }
+
+    /// The size of a file as it is served.
+    async fn size(&self, path: &Path, meta: &Metadata) -> Result<u64> {
+        if !meta.is_file() {
+            return Ok(meta.len());
+        }
+        Ok(self.encrypted_size(path).await?.unwrap_or(meta.len()))
+    }
+
+    /// Read `len` bytes of a file from `start`, as they are served.
+    async fn open(
+        &self,
+        path: &Path,
+        start: u64,
+        len: u64,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        #[cfg(feature = "encrypt")]
+        if let Some(key) = self.key.as_ref() {
+            if let Some(file) = encrypt::EncryptedFile::open(key, path).await? {
+                return Ok(Box::pin(StreamReader::new(file.read(start, len))));
+            }
+        }
+        let mut file = fs::File::open(path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok(Box::pin(file.take(len)))
+    }
+}
+
+fn is_ndjson_request(
+    query_params: &HashMap<String, String>,
+    headers: &HeaderMap<HeaderValue>,
+) -> bool {
+    query_params.contains_key("ndjson")
+        || headers
+            .get("accept")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.contains("application/x-ndjson"))
+            .unwrap_or_default()
+}
+
+/// The value of the cookie `name` sent with a request.
+fn get_cookie<'a>(headers: &'a HeaderMap<HeaderValue>, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|v| v.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Fill in `?sort` and `?order` from the `dufs_sort=<field>:<order>` cookie set by the UI when
+/// the query has no sort of its own, so that a listing keeps the order a user picked last.
+fn with_sort_cookie<'a>(
+    query_params: &'a HashMap<String, String>,
+    headers: &HeaderMap<HeaderValue>,
+) -> Cow<'a, HashMap<String, String>> {
+    if query_params.contains_key("sort") {
+        return Cow::Borrowed(query_params);
+    }
+    let cookie = get_cookie(headers, "dufs_sort");
+    let Some((sort, order)) = cookie.map(|v| v.split_once(':').unwrap_or((v, "asc"))) else {
+        return Cow::Borrowed(query_params);
+    };
+    if !matches!(sort, "name" | "mtime" | "size") || !matches!(order, "asc" | "desc") {
+        return Cow::Borrowed(query_params);
+    }
+    let mut query_params = query_params.clone();
+    query_params.insert("sort".to_string(), sort.to_string());
+    query_params.insert("order".to_string(), order.to_string());
+    Cow::Owned(query_params)
+}
 
-    loop {
-        let bytes_read = file.read(&mut buffer).await?;
-        if bytes_read == 0 {
-            break;
+/// Order listing entries by `?sort` and `?order`, ties are broken by the exact name so that the
+/// order is total and page cursors always land between two entries.
+fn path_item_comparator(
+    query_params: &HashMap<String, String>,
+    sort_dirs: SortDirs,
+) -> impl Fn(&PathItem, &PathItem) -> Ordering {
+    let sort = query_params.get("sort").cloned().unwrap_or_default();
+    let desc = !sort.is_empty() && query_params.get("order").is_some_and(|v| v == "desc");
+    move |v1, v2| {
+        let ordering = match sort_dirs {
+            SortDirs::First => v1.path_type.cmp(&v2.path_type),
+            SortDirs::Mixed => Ordering::Equal,
+        }
+        .then_with(|| match sort.as_str() {
+            "mtime" => v1.sort_by_mtime(v2),
+            "size" => v1.sort_by_size(v2),
+            _ => v1.sort_by_name(v2),
+        })
+        .then_with(|| v1.name.cmp(&v2.name));
+        if desc {
+            ordering.reverse()
+        } else {
+            ordering
         }
-        hasher.update(&buffer[..bytes_read]);
     }
+}
+
+/// Whether a path is `.well-known` at the serve root or lies below it.
+fn is_well_known_path(relative_path: &str) -> bool {
+    relative_path
+        .strip_prefix(WELL_KNOWN_DIR)
+        .is_some_and(|v| v.is_empty() || v.starts_with('/'))
+}
 
-    let result = hasher.finalize();
-    Ok(format!("{:x}", result))
+/// Whether any component of a path starts with `.`, apart from the `.well-known` at the root.
+fn is_dotfile_path(relative_path: &str) -> bool {
+    let relative_path = match relative_path.strip_prefix(WELL_KNOWN_DIR) {
+        Some(v) if v.is_empty() || v.starts_with('/') => v,
+        _ => relative_path,
+    };
+    relative_path.split('/').any(|v| v.starts_with('.'))
+}
+
+/// Whether `--deny-dotfiles` hides a path below `root`.
+fn is_dotfile(root: Option<&Path>, path: &Path) -> bool {
+    let Some(relative_path) = root.and_then(|v| path.strip_prefix(v).ok()) else {
+        return false;
+    };
+    let relative_path = relative_path.to_string_lossy().replace('\\', "/");
+    is_dotfile_path(&relative_path)
+}
+
+/// Whether a query parameter is set to a true value, `?name`, `?name=1` or `?name=true`.
+fn has_query_bool(query_params: &HashMap<String, String>, name: &str) -> bool {
+    query_params
+        .get(name)
+        .is_some_and(|v| v.is_empty() || v == "1" || v == "true")
 }
 
 fn has_query_flag(query_params: &HashMap<String, String>, name: &str) -> bool {
@@ -0,0 +1,266 @@
+use crate::args::VerifyContentType;
+use crate::utils::glob;
+
+/// The bytes read from the start of an upload to tell its type.
+pub const SNIFF_LEN: usize = 512;
+
+/// Bytes expected at an offset of the content.
+type Magic = (usize, &'static [u8]);
+
+/// Magic bytes at their offsets, and the type of the content they start.
+const SIGNATURES: &[(&[Magic], &str)] = &[
+    (&[(0, b"\x89PNG\r\n\x1a\n")], "image/png"),
+    (&[(0, b"\xff\xd8\xff")], "image/jpeg"),
+    (&[(0, b"GIF87a")], "image/gif"),
+    (&[(0, b"GIF89a")], "image/gif"),
+    (&[(0, b"RIFF"), (8, b"WEBP")], "image/webp"),
+    (&[(0, b"II*\0")], "image/tiff"),
+    (&[(0, b"MM\0*")], "image/tiff"),
+    (&[(4, b"ftypavif")], "image/avif"),
+    (&[(4, b"ftypheic")], "image/heic"),
+    (&[(4, b"ftypheix")], "image/heic"),
+    (&[(4, b"ftypmif1")], "image/heic"),
+    (&[(4, b"ftypM4A ")], "audio/mp4"),
+    (&[(4, b"ftypqt  ")], "video/quicktime"),
+    (&[(4, b"ftyp")], "video/mp4"),
+    (&[(0, b"RIFF"), (8, b"WAVE")], "audio/wav"),
+    (&[(0, b"RIFF"), (8, b"AVI ")], "video/x-msvideo"),
+    (&[(0, b"ID3")], "audio/mpeg"),
+    (&[(0, b"OggS")], "audio/ogg"),
+    (&[(0, b"fLaC")], "audio/flac"),
+    (&[(0, b"\x1a\x45\xdf\xa3")], "video/webm"),
+    (&[(0, b"%PDF-")], "application/pdf"),
+    (&[(0, b"PK\x03\x04")], "application/zip"),
+    (&[(0, b"PK\x05\x06")], "application/zip"),
+    (&[(0, b"\x1f\x8b")], "application/gzip"),
+    (&[(0, b"7z\xbc\xaf\x27\x1c")], "application/x-7z-compressed"),
+    (&[(0, b"Rar!\x1a\x07")], "application/vnd.rar"),
+    (&[(0, b"BZh")], "application/x-bzip2"),
+    (&[(0, b"\xfd7zXZ\0")], "application/x-xz"),
+    (&[(257, b"ustar")], "application/x-tar"),
+    (&[(0, b"\x7fELF")], "application/x-executable"),
+    (&[(0, b"MZ")], "application/x-msdownload"),
+    (&[(0, b"\0asm")], "application/wasm"),
+];
+
+/// Other names clients and `mime_guess` give the sniffed types.
+const ALIASES: &[(&str, &str)] = &[
+    ("image/jpg", "image/jpeg"),
+    ("image/pjpeg", "image/jpeg"),
+    ("image/x-png", "image/png"),
+    ("image/heif", "image/heic"),
+    ("audio/mp3", "audio/mpeg"),
+    ("audio/x-wav", "audio/wav"),
+    ("audio/x-flac", "audio/flac"),
+    ("audio/x-m4a", "audio/mp4"),
+    ("audio/m4a", "audio/mp4"),
+    ("video/avi", "video/x-msvideo"),
+    ("application/x-pdf", "application/pdf"),
+    ("application/x-zip-compressed", "application/zip"),
+    ("application/x-gzip", "application/gzip"),
+    ("application/x-rar-compressed", "application/vnd.rar"),
+    ("application/x-rar", "application/vnd.rar"),
+    ("application/x-bzip", "application/x-bzip2"),
+    ("application/x-msdos-program", "application/x-msdownload"),
+    (
+        "application/vnd.microsoft.portable-executable",
+        "application/x-msdownload",
+    ),
+    ("application/x-elf", "application/x-executable"),
+];
+
+/// What content is, coarse enough that only gross mismatches tell kinds apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Image,
+    Media,
+    Pdf,
+    Archive,
+    Executable,
+}
+
+/// Tell the type of content from its first bytes, `None` for binaries of unknown types.
+pub fn sniff(head: &[u8]) -> Option<&'static str> {
+    let signature = SIGNATURES.iter().find(|(parts, _)| {
+        parts
+            .iter()
+            .all(|(offset, magic)| head.get(*offset..offset + magic.len()) == Some(*magic))
+    });
+    if let Some((_, mime)) = signature {
+        return Some(mime);
+    }
+    if !content_inspector::inspect(head).is_text() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let starts_with = |prefix: &str| {
+        text.get(..prefix.len())
+            .is_some_and(|v| v.eq_ignore_ascii_case(prefix))
+    };
+    Some(if starts_with("<?php") {
+        "application/x-httpd-php"
+    } else if text.starts_with("#!") {
+        "application/x-sh"
+    } else if starts_with("<!doctype html") || starts_with("<html") {
+        "text/html"
+    } else {
+        "text/plain"
+    })
+}
+
+/// Check the head of an upload against the types it was declared with, the `Content-Type` and
+/// the one of its extension, and return the first mismatch as the expected and sniffed types.
+///
+/// Lenient checks only reject content of another kind, e.g. a script named `.jpg`, strict ones
+/// also reject a type sniffing can tell but the content isn't, e.g. a png named `.jpg`.
+/// `allow` lists `expected=sniffed` glob pairs that are never mismatches.
+pub fn find_mismatch(
+    mode: VerifyContentType,
+    allow: &[String],
+    expected: &[String],
+    head: &[u8],
+) -> Option<(String, String)> {
+    if head.is_empty() {
+        return None;
+    }
+    let sniffed = sniff(head).unwrap_or("application/octet-stream");
+    expected
+        .iter()
+        .map(|v| normalize(v))
+        .filter(|expected| !is_unspecific(expected))
+        .find(|expected| {
+            is_mismatch(mode, expected, sniffed)
+                && !allow.iter().any(|pair| match pair.split_once('=') {
+                    Some((e, s)) => glob(e, expected) && glob(s, sniffed),
+                    None => false,
+                })
+        })
+        .map(|expected| (expected, sniffed.to_string()))
+}
+
+/// Types clients send for any content, e.g. `curl --data-binary` sends form data.
+fn is_unspecific(mime: &str) -> bool {
+    matches!(
+        mime,
+        "" | "application/octet-stream" | "application/x-www-form-urlencoded"
+    ) || mime.starts_with("multipart/")
+}
+
+fn is_mismatch(mode: VerifyContentType, expected: &str, sniffed: &str) -> bool {
+    let expected_kind = kind_of(expected);
+    if let (Some(expected_kind), Some(sniffed_kind)) = (expected_kind, kind_of(sniffed)) {
+        if expected_kind != sniffed_kind {
+            return true;
+        }
+    }
+    match mode {
+        VerifyContentType::Lenient => false,
+        VerifyContentType::Strict => {
+            expected_kind != Some(Kind::Text)
+                && expected != sniffed
+                && SIGNATURES.iter().any(|(_, mime)| *mime == expected)
+        }
+    }
+}
+
+fn kind_of(mime: &str) -> Option<Kind> {
+    let (top, sub) = mime.split_once('/')?;
+    let kind = match (top, sub) {
+        ("text", _) => Kind::Text,
+        ("image", "svg+xml") => Kind::Text,
+        ("image", _) => Kind::Image,
+        ("audio" | "video", _) => Kind::Media,
+        ("application", "pdf") => Kind::Pdf,
+        ("application", "json" | "xml" | "javascript" | "x-sh" | "x-httpd-php") => Kind::Text,
+        (
+            "application",
+            "zip" | "gzip" | "x-7z-compressed" | "vnd.rar" | "x-bzip2" | "x-xz" | "x-tar",
+        ) => Kind::Archive,
+        ("application", "x-executable" | "x-msdownload" | "wasm") => Kind::Executable,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Lowercase a type without its parameters, resolving aliases.
+fn normalize(mime: &str) -> String {
+    let mime = mime
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    match ALIASES.iter().find(|(alias, _)| *alias == mime) {
+        Some((_, name)) => name.to_string(),
+        None => mime,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"\0\0\0\x18ftypmp42"), Some("video/mp4"));
+        assert_eq!(
+            sniff(b"\xef\xbb\xbf <?PHP echo 1;"),
+            Some("application/x-httpd-php")
+        );
+        assert_eq!(sniff(b"<!DOCTYPE html><p>"), Some("text/html"));
+        assert_eq!(sniff(b"hello"), Some("text/plain"));
+        assert_eq!(sniff(b"\0\x01\x02\x03"), None);
+    }
+
+    #[test]
+    fn test_sniffed_types_are_known() {
+        for (_, mime) in SIGNATURES {
+            assert!(kind_of(mime).is_some(), "{mime}");
+        }
+        for ext in [
+            "jpg", "png", "gif", "webp", "pdf", "zip", "mp3", "flac", "7z", "rar",
+        ] {
+            let mime = mime_guess::from_ext(ext).first().unwrap();
+            let mime = normalize(mime.essence_str());
+            assert!(SIGNATURES.iter().any(|(_, v)| *v == mime), "{ext} {mime}");
+        }
+    }
+
+    #[test]
+    fn test_find_mismatch() {
+        let lenient = VerifyContentType::Lenient;
+        let strict = VerifyContentType::Strict;
+        let png = b"\x89PNG\r\n\x1a\n\0\0";
+        let php = b"<?php system($_GET['c']);";
+        let jpg = ["image/jpeg".to_string()];
+        assert_eq!(
+            find_mismatch(lenient, &[], &jpg, php),
+            Some(("image/jpeg".into(), "application/x-httpd-php".into()))
+        );
+        assert_eq!(find_mismatch(lenient, &[], &jpg, png), None);
+        assert!(find_mismatch(strict, &[], &jpg, png).is_some());
+        assert_eq!(
+            find_mismatch(strict, &[], &["image/jpg; q=1".into()], b"\xff\xd8\xff\xe0"),
+            None
+        );
+        assert_eq!(
+            find_mismatch(strict, &[], &["text/markdown".into()], php),
+            None
+        );
+        assert_eq!(
+            find_mismatch(strict, &[], &["image/svg+xml".into()], b"<svg>"),
+            None
+        );
+        assert_eq!(
+            find_mismatch(strict, &[], &["application/octet-stream".into()], php),
+            None
+        );
+        let allow = ["image/*=application/x-httpd-php".to_string()];
+        assert_eq!(find_mismatch(lenient, &allow, &jpg, php), None);
+        assert_eq!(find_mismatch(strict, &[], &jpg, b""), None);
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Past this many paths, the expired ones are dropped, and all of them if none is.
+const MAX_ENTRIES: usize = 65536;
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Bumped by each write, so that a stat started before it isn't kept.
+    generation: u64,
+    map: HashMap<PathBuf, (Instant, Metadata)>,
+}
+
+/// The metadata of requested paths, kept for `--stat-cache-ttl` to spare a stat per request.
+///
+/// Writes through dufs drop the whole cache, changes from outside show up once the ttl is over.
+#[derive(Debug)]
+pub struct StatCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+    #[cfg(test)]
+    stats: std::sync::atomic::AtomicU64,
+}
+
+impl StatCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+            #[cfg(test)]
+            stats: Default::default(),
+        }
+    }
+
+    /// The metadata of `path`, only successful stats are kept so that new files show up at once.
+    pub async fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let generation = {
+            let entries = self.entries.lock().unwrap();
+            match entries.map.get(path) {
+                Some((expires, meta)) if *expires > Instant::now() => return Ok(meta.clone()),
+                _ => entries.generation,
+            }
+        };
+        #[cfg(test)]
+        self.stats
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let meta = tokio::fs::metadata(path).await?;
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation == generation {
+            let now = Instant::now();
+            if entries.map.len() >= MAX_ENTRIES {
+                entries.map.retain(|_, (expires, _)| *expires > now);
+                if entries.map.len() >= MAX_ENTRIES {
+                    entries.map.clear();
+                }
+            }
+            entries
+                .map
+                .insert(path.to_path_buf(), (now + self.ttl, meta.clone()));
+        }
+        Ok(meta)
+    }
+
+    /// Forget every path, a write may change the metadata of its parents too.
+    pub fn invalidate(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+        entries.map.clear();
+    }
+
+    /// The stats done so far, the cache missed as many times.
+    #[cfg(test)]
+    pub fn stats(&self) -> u64 {
+        self.stats.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[tokio::test]
+    async fn test_stat_cache() {
+        let tmpdir = TempDir::new().unwrap();
+        let file = tmpdir.child("a.txt");
+        file.write_str("abc").unwrap();
+        let cache = StatCache::new(Duration::from_secs(60));
+        assert_eq!(cache.metadata(file.path()).await.unwrap().len(), 3);
+        file.write_str("abcd").unwrap();
+        assert_eq!(cache.metadata(file.path()).await.unwrap().len(), 3);
+        assert_eq!(cache.stats(), 1);
+
+        cache.invalidate();
+        assert_eq!(cache.metadata(file.path()).await.unwrap().len(), 4);
+        assert_eq!(cache.stats(), 2);
+
+        let missing = tmpdir.child("b.txt");
+        assert!(cache.metadata(missing.path()).await.is_err());
+        missing.write_str("b").unwrap();
+        assert!(cache.metadata(missing.path()).await.is_ok());
+        assert_eq!(cache.stats(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stat_cache_expired() {
+        let tmpdir = TempDir::new().unwrap();
+        let file = tmpdir.child("a.txt");
+        file.write_str("abc").unwrap();
+        let cache = StatCache::new(Duration::ZERO);
+        cache.metadata(file.path()).await.unwrap();
+        file.write_str("abcd").unwrap();
+        assert_eq!(cache.metadata(file.path()).await.unwrap().len(), 4);
+        assert_eq!(cache.stats(), 2);
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures_util::Stream;
+use hyper::body::{Body, Frame, SizeHint};
+use std::{
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::time::Sleep;
+
+use crate::utils::parse_size;
+
+/// A transfer rate in bytes per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate(pub u64);
+
+impl FromStr for Rate {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_size(s).map(Rate).ok_or_else(|| {
+            anyhow!("Invalid rate `{s}`, expected a number of bytes like 512K, 10M or 1G")
+        })
+    }
+}
+
+/// A token bucket refilled at a fixed rate, holding at most one second worth of bytes.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            rate: rate.0 as f64,
+            state: Mutex::new((0.0, Instant::now())),
+        }
+    }
+
+    /// Take `len` bytes from the bucket, returning how long to wait until it is no longer in debt.
+    fn consume(&self, len: usize) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, last) = &mut *state;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * self.rate).min(self.rate);
+        *last = now;
+        *tokens -= len as f64;
+        if *tokens < 0.0 {
+            Duration::from_secs_f64(-*tokens / self.rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// The set of limiters a transfer has to respect, e.g. a per-connection and a global one.
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    limiters: Vec<Arc<RateLimiter>>,
+}
+
+impl Throttle {
+    pub fn new(limiters: impl IntoIterator<Item = Option<Arc<RateLimiter>>>) -> Self {
+        Self {
+            limiters: limiters.into_iter().flatten().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limiters.is_empty()
+    }
+
+    fn consume(&self, len: usize) -> Duration {
+        self.limiters
+            .iter()
+            .map(|v| v.consume(len))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// The download and upload throttles of a single connection.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionThrottle {
+    pub download: Throttle,
+    pub upload: Throttle,
+}
+
+/// Paces a transfer by holding back each chunk until the limiters can afford it.
+struct Pacer<T> {
+    throttle: Throttle,
+    delayed: Option<(Pin<Box<Sleep>>, T)>,
+}
+
+impl<T> Pacer<T> {
+    fn new(throttle: Throttle) -> Self {
+        Self {
+            throttle,
+            delayed: None,
+        }
+    }
+
+    fn poll_paced(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll_inner: impl FnOnce(&mut Context<'_>) -> Poll<Option<T>>,
+        len: impl FnOnce(&T) -> usize,
+    ) -> Poll<Option<T>> {
+        if self.delayed.is_none() {
+            let item = match futures_util::ready!(poll_inner(cx)) {
+                Some(item) => item,
+                None => return Poll::Ready(None),
+            };
+            let wait = self.throttle.consume(len(&item));
+            if wait.is_zero() {
+                return Poll::Ready(Some(item));
+            }
+            self.delayed = Some((Box::pin(tokio::time::sleep(wait)), item));
+        }
+        if let Some((sleep, _)) = self.delayed.as_mut() {
+            futures_util::ready!(sleep.as_mut().poll(cx));
+        }
+        Poll::Ready(self.delayed.take().map(|(_, item)| item))
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// A response body limited to the rate of its throttle.
+    pub struct ThrottledBody<B: Body> {
+        #[pin]
+        inner: B,
+        pacer: Pacer<Result<Frame<B::Data>, B::Error>>,
+    }
+}
+
+impl<B: Body> ThrottledBody<B> {
+    pub fn new(inner: B, throttle: Throttle) -> Self {
+        Self {
+            inner,
+            pacer: Pacer::new(throttle),
+        }
+    }
+}
+
+impl<B: Body<Data = Bytes>> Body for ThrottledBody<B> {
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let inner = this.inner;
+        this.pacer.poll_paced(
+            cx,
+            |cx| inner.poll_frame(cx),
+            |frame| {
+                frame
+                    .as_ref()
+                    .ok()
+                    .and_then(|v| v.data_ref())
+                    .map(|v| v.len())
+                    .unwrap_or_default()
+            },
+        )
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pacer.delayed.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// A request body stream limited to the rate of its throttle.
+    pub struct ThrottledStream<S: Stream> {
+        #[pin]
+        inner: S,
+        pacer: Pacer<S::Item>,
+    }
+}
+
+impl<S: Stream> ThrottledStream<S> {
+    pub fn new(inner: S, throttle: Throttle) -> Self {
+        Self {
+            inner,
+            pacer: Pacer::new(throttle),
+        }
+    }
+}
+
+impl<S, E> Stream for ThrottledStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let inner = this.inner;
+        this.pacer.poll_paced(
+            cx,
+            |cx| inner.poll_next(cx),
+            |item| item.as_ref().map(|v| v.len()).unwrap_or_default(),
+        )
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use std::str::FromStr;
+
+/// Used for listing timestamps when only `--time-zone` is given.
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// The zone listing timestamps are rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Zone {
+    #[default]
+    Utc,
+    Local,
+    Fixed(FixedOffset),
+    #[cfg(feature = "tz")]
+    Named(chrono_tz::Tz),
+}
+
+impl Zone {
+    pub fn format(&self, time: DateTime<Utc>, format: &str) -> String {
+        match self {
+            Zone::Utc => time.format(format).to_string(),
+            Zone::Local => time.with_timezone(&Local).format(format).to_string(),
+            Zone::Fixed(offset) => time.with_timezone(offset).format(format).to_string(),
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => time.with_timezone(tz).format(format).to_string(),
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("utc") {
+            return Ok(Zone::Utc);
+        }
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+        if let Ok(offset) = s.parse::<FixedOffset>() {
+            return Ok(Zone::Fixed(offset));
+        }
+        #[cfg(feature = "tz")]
+        if let Ok(tz) = s.parse::<chrono_tz::Tz>() {
+            return Ok(Zone::Named(tz));
+        }
+        let expected = if cfg!(feature = "tz") {
+            "UTC, local, an offset like +08:00 or a name like Europe/Paris"
+        } else {
+            "UTC, local or an offset like +08:00"
+        };
+        Err(anyhow!("Invalid time zone `{s}`, expected {expected}"))
+    }
+}
+
+/// Reject strftime formats chrono can't render, which would otherwise panic while formatting.
+pub fn validate_time_format(format: &str) -> Result<()> {
+    if StrftimeItems::new(format).any(|v| matches!(v, Item::Error)) {
+        bail!("Invalid time format `{format}`");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_zone_format() {
+        let time = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let zone: Zone = "+08:00".parse().unwrap();
+        assert_eq!(
+            zone.format(time, "%Y-%m-%d %H:%M %z"),
+            "2023-11-15 06:13 +0800"
+        );
+        let zone: Zone = "UTC".parse().unwrap();
+        assert_eq!(zone.format(time, DEFAULT_TIME_FORMAT), "2023-11-14 22:13");
+        assert!("Mars/Olympus".parse::<Zone>().is_err());
+        assert!(validate_time_format("%d/%m/%Y").is_ok());
+        assert!(validate_time_format("%Q").is_err());
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn test_named_zone_format() {
+        let time = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let zone: Zone = "Asia/Tokyo".parse().unwrap();
+        assert_eq!(zone.format(time, DEFAULT_TIME_FORMAT), "2023-11-15 07:13");
+    }
+}
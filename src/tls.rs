@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tokio_rustls::rustls::{
+    crypto::ring::{sign::any_supported_type, Ticketer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    Error, InconsistentKeys, ServerConfig,
+};
+
+use crate::args::Args;
+use crate::utils::{load_certs, load_private_key};
+
+/// Build the TLS config of `--tls-cert` and `--tls-key`, `None` when serving plain HTTP.
+///
+/// Sessions resume through tickets, whose key the ticketer rotates every 6 hours.
+pub fn build_tls_config(args: &Args) -> Result<Option<(ServerConfig, Arc<StapledCert>)>> {
+    let (Some(cert_file), Some(key_file)) = (&args.tls_cert, &args.tls_key) else {
+        return Ok(None);
+    };
+    let certs = load_certs(cert_file)?;
+    let key = load_private_key(key_file)?;
+    let key = any_supported_type(&key)?;
+    let cert = CertifiedKey::new(certs, key);
+    match cert.keys_match() {
+        Ok(()) | Err(Error::InconsistentKeys(InconsistentKeys::Unknown)) => {}
+        Err(err) => return Err(err.into()),
+    }
+    let cert = Arc::new(StapledCert {
+        cert: RwLock::new(Arc::new(cert)),
+    });
+    if let Some(ocsp_file) = &args.tls_ocsp_file {
+        cert.staple(ocsp_file)?;
+    }
+
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_cert_resolver(cert.clone());
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    config.ticketer = Ticketer::new()?;
+    Ok(Some((config, cert)))
+}
+
+/// The served certificate, along with the OCSP response stapled to it.
+#[derive(Debug)]
+pub struct StapledCert {
+    cert: RwLock<Arc<CertifiedKey>>,
+}
+
+impl StapledCert {
+    /// Staple the DER-encoded OCSP response of a file, replacing the previous one.
+    pub fn staple(&self, ocsp_file: &Path) -> Result<()> {
+        let ocsp = std::fs::read(ocsp_file)
+            .with_context(|| format!("Failed to access `{}`", ocsp_file.display()))?;
+        let mut cert = self.cert.write().unwrap();
+        let mut stapled = CertifiedKey::clone(&cert);
+        stapled.ocsp = (!ocsp.is_empty()).then_some(ocsp);
+        *cert = Arc::new(stapled);
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for StapledCert {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.cert.read().unwrap().clone())
+    }
+}
@@ -0,0 +1,356 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+const BLOCK_SIZE: u64 = 512;
+/// The largest pax or GNU long name header read, they only hold names and a few attributes.
+const EXTENSION_MAX_SIZE: u64 = 65536;
+
+/// What a tar entry creates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    /// Links, devices and fifos, never extracted.
+    Other,
+}
+
+/// The header of a tar entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TarEntry {
+    /// The name as archived, see `normalize_entry_path` for the one to extract to.
+    pub path: String,
+    pub kind: EntryKind,
+    pub mode: u32,
+    pub size: u64,
+    /// Seconds since the epoch.
+    pub mtime: u64,
+}
+
+/// Read a tar stream one entry at a time, the content of the current entry is read from the
+/// reader itself and whatever is left of it is skipped by `next_entry`.
+///
+/// Reads ustar, pax and GNU archives, names from pax `path` records and GNU long names included.
+pub struct TarReader<R> {
+    reader: R,
+    remaining: u64,
+    padding: u64,
+}
+
+impl<R: AsyncRead + Unpin> TarReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            remaining: 0,
+            padding: 0,
+        }
+    }
+
+    /// The next entry, `None` past the end of the archive.
+    pub async fn next_entry(&mut self) -> io::Result<Option<TarEntry>> {
+        self.skip(self.remaining + self.padding).await?;
+        self.remaining = 0;
+        self.padding = 0;
+        let mut long_path = None;
+        let mut pax_path = None;
+        let mut pax_size = None;
+        loop {
+            let mut block = [0; BLOCK_SIZE as usize];
+            if !self.read_block(&mut block).await? || block.iter().all(|v| *v == 0) {
+                return Ok(None);
+            }
+            if !has_valid_checksum(&block) {
+                return Err(invalid_data("Invalid tar header checksum"));
+            }
+            let size = parse_number(&block[124..136])?;
+            let typeflag = block[156];
+            if matches!(typeflag, b'x' | b'g' | b'L') {
+                if size > EXTENSION_MAX_SIZE {
+                    return Err(invalid_data("Tar extended header too large"));
+                }
+                let mut data = vec![0; size as usize];
+                self.reader.read_exact(&mut data).await?;
+                self.skip(padding(size)).await?;
+                match typeflag {
+                    b'x' => {
+                        for (key, value) in parse_pax_records(&data)? {
+                            match key {
+                                "path" => pax_path = Some(value.to_string()),
+                                "size" => {
+                                    pax_size = Some(
+                                        value
+                                            .parse()
+                                            .map_err(|_| invalid_data("Invalid pax size record"))?,
+                                    )
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    b'L' => long_path = Some(cstr(&data)),
+                    _ => {}
+                }
+                continue;
+            }
+            let path = match pax_path.or(long_path) {
+                Some(path) => path,
+                None => header_path(&block),
+            };
+            let kind = match typeflag {
+                b'0' | b'\0' | b'7' => EntryKind::File,
+                b'5' => EntryKind::Dir,
+                _ => EntryKind::Other,
+            };
+            let size = pax_size.unwrap_or(size);
+            // Links, devices, fifos and folders have no content, whatever their size says.
+            let content = match typeflag {
+                b'0' | b'\0' | b'7' => size,
+                b'1' | b'2' | b'3' | b'4' | b'5' | b'6' => 0,
+                _ => size,
+            };
+            self.remaining = content;
+            self.padding = padding(content);
+            return Ok(Some(TarEntry {
+                path,
+                kind,
+                mode: parse_number(&block[100..108])? as u32,
+                size: content,
+                mtime: parse_number(&block[136..148])?,
+            }));
+        }
+    }
+
+    /// Read a whole block, `false` if the stream ended cleanly before it.
+    async fn read_block(&mut self, block: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < block.len() {
+            let n = self.reader.read(&mut block[filled..]).await?;
+            if n == 0 {
+                if filled == 0 {
+                    return Ok(false);
+                }
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            filled += n;
+        }
+        Ok(true)
+    }
+
+    async fn skip(&mut self, len: u64) -> io::Result<()> {
+        let skipped =
+            tokio::io::copy(&mut (&mut self.reader).take(len), &mut tokio::io::sink()).await?;
+        if skipped < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TarReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.remaining == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let max = buf
+            .remaining()
+            .min(this.remaining.min(usize::MAX as u64) as usize);
+        let mut limited = buf.take(max);
+        ready!(Pin::new(&mut this.reader).poll_read(cx, &mut limited))?;
+        let n = limited.filled().len();
+        if n == 0 {
+            return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+        }
+        // SAFETY: `limited` filled the first `n` bytes of the unfilled part of `buf`.
+        unsafe { buf.assume_init(n) };
+        buf.advance(n);
+        this.remaining -= n as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The relative path to extract an entry to, `None` if it would escape the destination.
+pub fn normalize_entry_path(path: &str) -> Option<String> {
+    let mut parts = vec![];
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            _ if part.len() == 2
+                && part.ends_with(':')
+                && part.starts_with(|c: char| c.is_ascii_alphabetic()) =>
+            {
+                return None
+            }
+            _ => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn header_path(block: &[u8]) -> String {
+    let name = cstr(&block[..100]);
+    // GNU archives have other fields where ustar has the prefix.
+    let prefix = match &block[257..263] == b"ustar\0" {
+        true => cstr(&block[345..500]),
+        false => String::new(),
+    };
+    match prefix.is_empty() {
+        true => name,
+        false => format!("{prefix}/{name}"),
+    }
+}
+
+/// Octal digits padded with spaces or NULs, or GNU base-256 when the high bit is set.
+fn parse_number(field: &[u8]) -> io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        return field[1..]
+            .iter()
+            .try_fold(u64::from(field[0] & 0x7f), |acc, v| {
+                acc.checked_mul(256).map(|acc| acc | u64::from(*v))
+            })
+            .ok_or_else(|| invalid_data("Tar number too large"));
+    }
+    let digits = String::from_utf8_lossy(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| invalid_data("Invalid tar number"))
+}
+
+fn has_valid_checksum(block: &[u8]) -> bool {
+    let Ok(expected) = parse_number(&block[148..156]) else {
+        return false;
+    };
+    let sum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, v)| match (148..156).contains(&i) {
+            true => u64::from(b' '),
+            false => u64::from(*v),
+        })
+        .sum();
+    sum == expected
+}
+
+/// Parse `<len> <key>=<value>\n` records.
+fn parse_pax_records(data: &[u8]) -> io::Result<Vec<(&str, &str)>> {
+    let mut records = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|v| *v == b' ')
+            .ok_or_else(|| invalid_data("Invalid pax record"))?;
+        let len: usize = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > space + 1 && *v <= rest.len())
+            .ok_or_else(|| invalid_data("Invalid pax record"))?;
+        let record = std::str::from_utf8(&rest[space + 1..len - 1])
+            .map_err(|_| invalid_data("Invalid pax record"))?;
+        if let Some((key, value)) = record.split_once('=') {
+            records.push((key, value));
+        }
+        rest = &rest[len..];
+    }
+    Ok(records)
+}
+
+fn cstr(data: &[u8]) -> String {
+    let end = data.iter().position(|v| *v == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(path: &str, typeflag: u8, size: u64) -> Vec<u8> {
+        let mut block = vec![0; BLOCK_SIZE as usize];
+        block[..path.len()].copy_from_slice(path.as_bytes());
+        block[100..107].copy_from_slice(b"0000644");
+        block[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+        block[136..147].copy_from_slice(b"14524770400");
+        block[156] = typeflag;
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[148..156].copy_from_slice(b"        ");
+        let sum: u32 = block.iter().map(|v| *v as u32).sum();
+        block[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+        block
+    }
+
+    fn archive(entries: &[(&str, u8, &[u8])]) -> Vec<u8> {
+        let mut data = vec![];
+        for (path, typeflag, content) in entries {
+            data.extend(header(path, *typeflag, content.len() as u64));
+            data.extend(*content);
+            data.resize(data.len() + padding(content.len() as u64) as usize, 0);
+        }
+        data.resize(data.len() + 2 * BLOCK_SIZE as usize, 0);
+        data
+    }
+
+    #[tokio::test]
+    async fn test_read_entries() {
+        let pax = b"18 path=long/name\n";
+        let data = archive(&[
+            ("dir/", b'5', b""),
+            ("dir/a.txt", b'0', b"hello"),
+            ("PaxHeader", b'x', pax),
+            ("short", b'0', b"world"),
+        ]);
+        let mut reader = TarReader::new(data.as_slice());
+        let entry = reader.next_entry().await.unwrap().unwrap();
+        assert_eq!((entry.path.as_str(), entry.kind), ("dir/", EntryKind::Dir));
+        assert_eq!(entry.mode, 0o644);
+        assert_eq!(entry.mtime, 1_700_000_000);
+        let entry = reader.next_entry().await.unwrap().unwrap();
+        assert_eq!((entry.path.as_str(), entry.size), ("dir/a.txt", 5));
+        // The content left unread is skipped
+        let entry = reader.next_entry().await.unwrap().unwrap();
+        assert_eq!(entry.path, "long/name");
+        let mut content = String::new();
+        reader.read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, "world");
+        assert_eq!(reader.next_entry().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_invalid() {
+        let mut data = archive(&[("a.txt", b'0', b"hello")]);
+        data[0] = b'b';
+        let mut reader = TarReader::new(data.as_slice());
+        assert!(reader.next_entry().await.is_err());
+
+        let data = archive(&[("a.txt", b'0', b"hello")]);
+        let mut reader = TarReader::new(&data[..BLOCK_SIZE as usize + 2]);
+        reader.next_entry().await.unwrap().unwrap();
+        let mut content = vec![];
+        assert!(reader.read_to_end(&mut content).await.is_err());
+    }
+
+    #[test]
+    fn test_normalize_entry_path() {
+        assert_eq!(normalize_entry_path("./a//b/").unwrap(), "a/b");
+        assert_eq!(normalize_entry_path("/etc/passwd").unwrap(), "etc/passwd");
+        assert_eq!(normalize_entry_path("./").unwrap(), "");
+        assert_eq!(normalize_entry_path("a/../../b"), None);
+        assert_eq!(normalize_entry_path("..\\b"), None);
+        assert_eq!(normalize_entry_path("C:/b"), None);
+    }
+}
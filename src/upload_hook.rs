@@ -0,0 +1,145 @@
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::str::FromStr;
+use tokio::process::Command;
+
+const CMD_SPECIAL_CHARS: [char; 8] = ['&', '|', '<', '>', '^', '%', '!', '"'];
+
+/// A command spawned after an upload has been finalized.
+///
+/// The placeholders `{path}`, `{name}` and `{size}` are substituted with the uploaded file's
+/// values. Unless `shell` is set, the command is split into a program and its arguments up front
+/// and the values are substituted per argument, so they can never be interpreted by a shell. With
+/// `shell`, the placeholders become the positional parameters `"$1"`, `"$2"` and `"$3"` of the
+/// `sh -c` script, which the shell expands without parsing them again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadHook {
+    command: String,
+    argv: Vec<String>,
+    shell: bool,
+}
+
+impl UploadHook {
+    pub fn new(command: &str, shell: bool) -> Result<Self> {
+        let mut hook: Self = command.parse()?;
+        hook.shell = shell;
+        Ok(hook)
+    }
+
+    pub fn spawn(&self, path: &Path, size: u64) {
+        let name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let path = path.display().to_string();
+        let render = |v: &str| {
+            v.replace("{path}", &path)
+                .replace("{name}", &name)
+                .replace("{size}", &size.to_string())
+        };
+        let mut command = if self.shell && cfg!(windows) {
+            // cmd has no positional parameters, and expands variables before parsing the line
+            if [&path, &name].iter().any(|v| v.contains(CMD_SPECIAL_CHARS)) {
+                error!("Refused to run upload hook for {path}, its name has cmd special chars");
+                return;
+            }
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(render(&self.command));
+            command
+        } else if self.shell {
+            let script = self
+                .command
+                .replace("{path}", "\"$1\"")
+                .replace("{name}", "\"$2\"")
+                .replace("{size}", "\"$3\"");
+            let mut command = Command::new("sh");
+            command
+                .arg("-c")
+                .arg(script)
+                .arg("dufs")
+                .args([&path, &name, &size.to_string()]);
+            command
+        } else {
+            let mut command = Command::new(&self.argv[0]);
+            command.args(self.argv[1..].iter().map(|v| render(v)));
+            command
+        };
+        let program = self.argv[0].clone();
+        tokio::spawn(async move {
+            match command.status().await {
+                Ok(status) if status.success() => {}
+                Ok(status) => warn!("Upload hook `{program}` for {path} exited with {status}"),
+                Err(err) => error!("Failed to run upload hook `{program}` for {path}, {err}"),
+            }
+        });
+    }
+}
+
+impl FromStr for UploadHook {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let argv = split_command(s)?;
+        if argv.is_empty() {
+            bail!("Invalid upload hook, empty command");
+        }
+        Ok(Self {
+            command: s.to_string(),
+            argv,
+            shell: false,
+        })
+    }
+}
+
+/// Split a command line into words, honoring single and double quotes.
+pub fn split_command(s: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in s.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        bail!("Invalid upload hook `{s}`, unterminated quote");
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command("convert {path} -resize 50% '{path}.thumb'").unwrap(),
+            ["convert", "{path}", "-resize", "50%", "{path}.thumb"]
+        );
+        assert_eq!(
+            split_command(r#"echo "a b" c''d"#).unwrap(),
+            ["echo", "a b", "cd"]
+        );
+        assert!(split_command("echo 'abc").is_err());
+        assert!("  ".parse::<UploadHook>().is_err());
+    }
+}
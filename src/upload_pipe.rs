@@ -0,0 +1,100 @@
+use anyhow::{anyhow, bail, Result};
+use hyper::StatusCode;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{self, AsyncRead};
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use crate::upload_hook::split_command;
+
+/// A command that the body of a `PUT` to its path is streamed into, instead of a file.
+///
+/// The command is split into a program and its arguments, it never runs through a shell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadPipe {
+    argv: Vec<String>,
+}
+
+impl UploadPipe {
+    /// Parse a `path=command` mapping into the relative path it serves and its pipe.
+    pub fn parse_mapping(mapping: &str) -> Result<(String, Self)> {
+        let (path, command) = mapping
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid pipe `{mapping}`, expected path=command"))?;
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            bail!("Invalid pipe `{mapping}`, the path must not be the root");
+        }
+        Ok((path.to_string(), command.parse()?))
+    }
+
+    /// Stream `body` into the stdin of the command, then map how it exited to a status.
+    ///
+    /// The command is killed once `limit` elapses, and `504` returned.
+    pub async fn run<R: AsyncRead + Unpin>(
+        &self,
+        body: &mut R,
+        limit: Duration,
+    ) -> Result<StatusCode> {
+        let mut child = Command::new(&self.argv[0])
+            .args(&self.argv[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| anyhow!("Failed to run pipe `{}`, {err}", self.argv[0]))?;
+        let mut stdin = child.stdin.take().unwrap();
+        let program = &self.argv[0];
+        let ret = timeout(limit, async {
+            match io::copy(body, &mut stdin).await {
+                // The command is done reading, its exit status tells whether that's fine.
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+                Err(err) => return Err(err),
+                Ok(_) => {}
+            }
+            drop(stdin);
+            child.wait().await
+        })
+        .await;
+        match ret {
+            Err(_) => {
+                warn!("Pipe `{program}` timed out");
+                Ok(StatusCode::GATEWAY_TIMEOUT)
+            }
+            Ok(Err(err)) => Err(err.into()),
+            Ok(Ok(status)) if status.success() => Ok(StatusCode::NO_CONTENT),
+            Ok(Ok(status)) => {
+                warn!("Pipe `{program}` exited with {status}");
+                Ok(StatusCode::BAD_GATEWAY)
+            }
+        }
+    }
+}
+
+impl FromStr for UploadPipe {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let argv = split_command(s)?;
+        if argv.is_empty() {
+            bail!("Invalid pipe, empty command");
+        }
+        Ok(Self { argv })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mapping() {
+        let (path, pipe) = UploadPipe::parse_mapping("/ingest/=tar -x -C '/my data'").unwrap();
+        assert_eq!(path, "ingest");
+        assert_eq!(pipe.argv, ["tar", "-x", "-C", "/my data"]);
+        assert!(UploadPipe::parse_mapping("/ingest").is_err());
+        assert!(UploadPipe::parse_mapping("/=cat").is_err());
+        assert!(UploadPipe::parse_mapping("/ingest= ").is_err());
+    }
+}
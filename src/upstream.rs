@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Result};
+use http_body_util::BodyExt;
+use hyper::{
+    header::{HeaderName, HeaderValue, AUTHORIZATION, CONNECTION, COOKIE, HOST, UPGRADE},
+    HeaderMap, StatusCode, Uri,
+};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpStream;
+
+use crate::http_utils::body_full;
+use crate::server::{Request, Response};
+
+/// Headers describing a single connection, which a proxy must not pass along.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// An HTTP app that the requests below a path are forwarded to, with `--proxy`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upstream {
+    path: String,
+    authority: String,
+    base_path: String,
+}
+
+impl Upstream {
+    /// Parse a `path=url` mapping, e.g. `/api=http://127.0.0.1:8080`.
+    pub fn parse_mapping(mapping: &str) -> Result<Self> {
+        let (path, url) = mapping
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid proxy `{mapping}`, expected path=url"))?;
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            bail!("Invalid proxy `{mapping}`, the path must not be the root");
+        }
+        let uri: Uri = url
+            .parse()
+            .map_err(|_| anyhow!("Invalid proxy `{mapping}`, bad url"))?;
+        if uri.scheme_str() != Some("http") {
+            bail!("Invalid proxy `{mapping}`, only http:// upstreams are supported");
+        }
+        let authority = uri
+            .authority()
+            .ok_or_else(|| anyhow!("Invalid proxy `{mapping}`, no host"))?;
+        let authority = match authority.port() {
+            Some(_) => authority.to_string(),
+            None => format!("{authority}:80"),
+        };
+        Ok(Self {
+            path: path.to_string(),
+            authority,
+            base_path: uri.path().trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Whether the request path relative to the root is forwarded to this upstream.
+    pub fn matches(&self, relative_path: &str) -> bool {
+        relative_path
+            .strip_prefix(&self.path)
+            .is_some_and(|v| v.is_empty() || v.starts_with('/'))
+    }
+
+    /// Forward a request, then stream the response back. A `101` hands the connection over,
+    /// so that WebSockets run between the client and the upstream.
+    ///
+    /// The credentials meant for dufs are kept from the upstream unless `pass_credentials`.
+    pub async fn forward(&self, mut req: Request, pass_credentials: bool) -> Result<Response> {
+        let stream = match TcpStream::connect(&self.authority).await {
+            Ok(v) => v,
+            Err(err) => {
+                warn!("Failed to connect upstream `{}`, {err}", self.authority);
+                return Ok(bad_gateway());
+            }
+        };
+        let (mut sender, conn) =
+            hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(async move {
+            if let Err(err) = conn.with_upgrades().await {
+                debug!("Upstream connection failed, {err}");
+            }
+        });
+
+        let upgrade = req.headers().get(UPGRADE).cloned();
+        let client_upgrade = upgrade.is_some().then(|| hyper::upgrade::on(&mut req));
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map(|v| v.as_str())
+            .unwrap_or("/");
+        let uri: Uri = format!("{}{path_and_query}", self.base_path).parse()?;
+        let (mut parts, body) = req.into_parts();
+        let host = parts.headers.get(HOST).cloned();
+        remove_hop_by_hop_headers(&mut parts.headers);
+        if !pass_credentials {
+            remove_dufs_credentials(&mut parts.headers);
+        }
+        parts.headers.insert(HOST, self.authority.parse()?);
+        if let Some(host) = host {
+            parts.headers.insert("x-forwarded-host", host);
+        }
+        if let Some(upgrade) = upgrade {
+            parts.headers.insert(CONNECTION, "upgrade".parse()?);
+            parts.headers.insert(UPGRADE, upgrade);
+        }
+        parts.uri = uri;
+        parts.version = hyper::Version::HTTP_11;
+        let mut upstream_res = match sender
+            .send_request(hyper::Request::from_parts(parts, body))
+            .await
+        {
+            Ok(v) => v,
+            Err(err) => {
+                warn!("Failed to proxy to upstream `{}`, {err}", self.authority);
+                return Ok(bad_gateway());
+            }
+        };
+
+        if upstream_res.status() == StatusCode::SWITCHING_PROTOCOLS {
+            let Some(client_upgrade) = client_upgrade else {
+                return Ok(bad_gateway());
+            };
+            let upstream_upgrade = hyper::upgrade::on(&mut upstream_res);
+            tokio::spawn(async move {
+                match tokio::try_join!(client_upgrade, upstream_upgrade) {
+                    Ok((client, upstream)) => {
+                        let _ = tokio::io::copy_bidirectional(
+                            &mut TokioIo::new(client),
+                            &mut TokioIo::new(upstream),
+                        )
+                        .await;
+                    }
+                    Err(err) => debug!("Failed to upgrade the proxied connection, {err}"),
+                }
+            });
+            let (parts, _) = upstream_res.into_parts();
+            return Ok(Response::from_parts(parts, body_full("")));
+        }
+
+        let (mut parts, body) = upstream_res.into_parts();
+        remove_hop_by_hop_headers(&mut parts.headers);
+        Ok(Response::from_parts(
+            parts,
+            body.map_err(|err| anyhow!("{err}")).boxed(),
+        ))
+    }
+}
+
+fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    // Connection may name further headers to drop.
+    let named: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|v| v.trim().parse().ok())
+        .collect();
+    for name in named {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(*name);
+    }
+}
+
+/// Drop the Authorization header and the `dufs_` cookies, those of the upstream are kept.
+fn remove_dufs_credentials(headers: &mut HeaderMap) {
+    headers.remove(AUTHORIZATION);
+    let cookies: Vec<String> = headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty() && !v.starts_with("dufs_"))
+        .map(|v| v.to_string())
+        .collect();
+    headers.remove(COOKIE);
+    if !cookies.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&cookies.join("; ")) {
+            headers.insert(COOKIE, value);
+        }
+    }
+}
+
+fn bad_gateway() -> Response {
+    let mut res = Response::new(body_full("Bad Gateway"));
+    *res.status_mut() = StatusCode::BAD_GATEWAY;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mapping() {
+        let upstream = Upstream::parse_mapping("/api/=http://127.0.0.1:8080/v1/").unwrap();
+        assert_eq!(upstream.path, "api");
+        assert_eq!(upstream.authority, "127.0.0.1:8080");
+        assert_eq!(upstream.base_path, "/v1");
+        let upstream = Upstream::parse_mapping("/api=http://localhost").unwrap();
+        assert_eq!(upstream.authority, "localhost:80");
+        assert_eq!(upstream.base_path, "");
+        assert!(Upstream::parse_mapping("/=http://localhost").is_err());
+        assert!(Upstream::parse_mapping("/api=https://localhost").is_err());
+        assert!(Upstream::parse_mapping("/api").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let upstream = Upstream::parse_mapping("/api=http://localhost").unwrap();
+        assert!(upstream.matches("api"));
+        assert!(upstream.matches("api/users"));
+        assert!(!upstream.matches("apis"));
+        assert!(!upstream.matches("dir/api"));
+    }
+
+    #[test]
+    fn test_remove_dufs_credentials() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Basic dXNlcjpwYXNz"),
+        );
+        headers.append(COOKIE, HeaderValue::from_static("dufs_session=abc; app=1"));
+        headers.append(COOKIE, HeaderValue::from_static("dufs_sort=name:asc"));
+        remove_dufs_credentials(&mut headers);
+        assert!(headers.get(AUTHORIZATION).is_none());
+        assert_eq!(
+            headers.get_all(COOKIE).iter().collect::<Vec<_>>(),
+            ["app=1"]
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_static("dufs_session=abc"));
+        remove_dufs_credentials(&mut headers);
+        assert!(headers.get(COOKIE).is_none());
+    }
+}
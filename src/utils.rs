@@ -4,7 +4,9 @@ use chrono::{DateTime, Utc};
 use rustls_pki_types::{CertificateDer, PrivateKeyDer};
 use std::{
     borrow::Cow,
+    cmp::Ordering,
     ffi::{OsStr, OsString},
+    io,
     path::{Path, PathBuf},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
@@ -15,12 +17,16 @@ pub fn unix_now() -> Result<Duration> {
         .with_context(|| "Invalid system time")
 }
 
+/// Percent-encode each segment of a path, keeping the `/` between them.
+///
+/// Only unreserved characters are kept, so names with `#`, `?`, `%` or spaces make valid
+/// hrefs that `decode_uri` turns back into the path.
 pub fn encode_uri(v: &str) -> String {
     let parts: Vec<_> = v.split('/').map(urlencoding::encode).collect();
     parts.join("/")
 }
 
-pub fn decode_uri(v: &str) -> Option<Cow<str>> {
+pub fn decode_uri(v: &str) -> Option<Cow<'_, str>> {
     percent_encoding::percent_decode(v.as_bytes())
         .decode_utf8()
         .ok()
@@ -61,6 +67,19 @@ pub fn glob(pattern: &str, target: &str) -> bool {
     pat.matches(target)
 }
 
+/// Match a relative path, `*` and `?` stop at `/` while `**` spans any number of folders.
+pub fn glob_path(pattern: &str, path: &str) -> bool {
+    let pat = match ::glob::Pattern::new(pattern) {
+        Ok(pat) => pat,
+        Err(_) => return false,
+    };
+    let options = ::glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    pat.matches_with(path, options)
+}
+
 // Load public certificate from file.
 #[cfg(feature = "tls")]
 pub fn load_certs<T: AsRef<Path>>(filename: T) -> Result<Vec<CertificateDer<'static>>> {
@@ -106,33 +125,83 @@ pub fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
     if unit != "bytes" || range.contains(',') {
         return None;
     }
+    parse_range_spec(range, size)
+}
+
+/// The ranges of a request served as separate parts.
+const MAX_RANGES: usize = 50;
+
+/// Parse a `bytes=` header that may hold several ranges, the unsatisfiable ones are dropped.
+/// The result is sorted with overlapping and adjacent ranges merged, `None` when it is empty.
+pub fn parse_ranges(range: &str, size: u64) -> Option<Vec<(u64, u64)>> {
+    let (unit, range) = range.split_once('=')?;
+    // Whitespace is only allowed around the commas of the list.
+    if unit != "bytes" || range.starts_with([' ', '\t']) {
+        return None;
+    }
+    let ranges = range
+        .split(',')
+        .map(|v| check_range_spec(v.trim(), size))
+        .collect::<Option<Vec<_>>>()?;
+    let mut ranges = coalesce_ranges(ranges.into_iter().flatten().collect());
+    // Past this many, the parts would mostly be overhead, so the span of them is sent at once.
+    if ranges.len() > MAX_RANGES {
+        let (start, end) = (ranges[0].0, ranges[ranges.len() - 1].1);
+        ranges = vec![(start, end)];
+    }
+    (!ranges.is_empty()).then_some(ranges)
+}
+
+pub fn coalesce_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut output: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match output.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => output.push((start, end)),
+        }
+    }
+    output
+}
+
+fn parse_range_spec(range: &str, size: u64) -> Option<(u64, u64)> {
+    check_range_spec(range, size)?
+}
+
+/// `None` when the range is invalid, `Some(None)` when it is valid but unsatisfiable.
+fn check_range_spec(range: &str, size: u64) -> Option<Option<(u64, u64)>> {
     let (start, end) = range.split_once('-')?;
     if start.is_empty() {
-        let offset = end.parse::<u64>().ok()?;
-        if offset <= size {
-            Some((size - offset, size - 1))
+        let offset = parse_range_pos(end)?;
+        if offset <= size && offset > 0 {
+            Some(Some((size - offset, size - 1)))
         } else {
-            None
+            Some(None)
         }
     } else {
-        let start = start.parse::<u64>().ok()?;
-        if start < size {
-            if end.is_empty() {
-                Some((start, size - 1))
-            } else {
-                let end = end.parse::<u64>().ok()?;
-                if end < size {
-                    Some((start, end))
-                } else {
-                    None
-                }
-            }
-        } else {
+        let start = parse_range_pos(start)?;
+        if end.is_empty() {
+            return Some((start < size).then(|| (start, size - 1)));
+        }
+        let end = parse_range_pos(end)?;
+        if start > end {
             None
+        } else if end < size {
+            Some(Some((start, end)))
+        } else {
+            Some(None)
         }
     }
 }
 
+/// Unlike `u64::from_str`, reject signs since a position is `1*DIGIT`.
+fn parse_range_pos(pos: &str) -> Option<u64> {
+    if !pos.bytes().all(|v| v.is_ascii_digit()) {
+        return None;
+    }
+    pos.parse().ok()
+}
+
 /// Source: https://internals.rust-lang.org/t/pathbuf-has-set-extension-but-no-add-extension-cannot-cleanly-turn-tar-to-tar-gz/14187/11
 /// Returns a path with a new dotted extension component appended to the end.
 /// Note: does not check if the path is a file or directory; you should do that.
@@ -153,10 +222,164 @@ pub fn append_ext(ext: impl AsRef<OsStr>, path: PathBuf) -> PathBuf {
     os_string.into()
 }
 
+/// Move a file, copying it when `dest` is on another filesystem.
+///
+/// The copy is written next to `dest` then renamed, so `dest` never shows up partly written.
+pub async fn move_file(src: &Path, dest: &Path) -> io::Result<()> {
+    match tokio::fs::rename(src, dest).await {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            let temp = append_ext("dufsupload", dest.to_path_buf());
+            let ret = async {
+                tokio::fs::copy(src, &temp).await?;
+                tokio::fs::rename(&temp, dest).await
+            }
+            .await;
+            if ret.is_err() {
+                let _ = tokio::fs::remove_file(&temp).await;
+            }
+            ret?;
+            tokio::fs::remove_file(src).await
+        }
+        ret => ret,
+    }
+}
+
+/// Parse a positive number of bytes with an optional `K`, `M` or `G` suffix, e.g. `1.5M`.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let value = s.trim();
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&value[..i], c.to_ascii_uppercase()),
+        _ => (value, 'B'),
+    };
+    let multiplier = match unit {
+        'B' => 1,
+        'K' => 1 << 10,
+        'M' => 1 << 20,
+        'G' => 1 << 30,
+        _ => return None,
+    };
+    let number: f64 = number.parse().ok()?;
+    let size = (number * multiplier as f64) as u64;
+    if !number.is_finite() || size == 0 {
+        return None;
+    }
+    Some(size)
+}
+
+/// Map the byte range `start..=end` of a concatenation of parts with the given sizes to
+/// `(part index, offset in part, length)` segments.
+pub fn split_range(sizes: &[u64], start: u64, end: u64) -> Vec<(usize, u64, u64)> {
+    let mut segments = vec![];
+    let mut part_start = 0;
+    for (index, size) in sizes.iter().enumerate() {
+        let part_end = part_start + size;
+        if *size > 0 && start < part_end && end >= part_start {
+            let offset = start.saturating_sub(part_start);
+            let len = end.min(part_end - 1) + 1 - (part_start + offset);
+            segments.push((index, offset, len));
+        }
+        part_start = part_end;
+    }
+    segments
+}
+
+/// Compare names the way people read them, `file2` before `file10`.
+///
+/// Names are split into runs of ASCII digits and of other characters. Digit runs compare by
+/// their value, other characters by their Unicode lowercase. Names equal that way are told
+/// apart by fewer leading zeros first, `file2` before `file02`, then by their code points,
+/// `File` before `file`, so that the order is total and the same on every listing.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+    let mut zeros = Ordering::Equal;
+    loop {
+        let ordering = match (x.peek().copied(), y.peek().copied()) {
+            (None, None) => break,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(c1), Some(c2)) if c1.is_ascii_digit() && c2.is_ascii_digit() => {
+                let take_digits = |it: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = it.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (n1, n2) = (take_digits(&mut x), take_digits(&mut y));
+                let (v1, v2) = (n1.trim_start_matches('0'), n2.trim_start_matches('0'));
+                if zeros == Ordering::Equal {
+                    zeros = n1.len().cmp(&n2.len());
+                }
+                v1.len().cmp(&v2.len()).then_with(|| v1.cmp(v2))
+            }
+            (Some(c1), Some(c2)) => {
+                x.next();
+                y.next();
+                c1.to_lowercase().cmp(c2.to_lowercase())
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    zeros.then_with(|| a.cmp(b))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_encode_uri() {
+        assert_eq!(encode_uri("/dir/a#b?c.txt"), "/dir/a%23b%3Fc.txt");
+        assert_eq!(encode_uri("my dir/100%.txt"), "my%20dir/100%25.txt");
+        assert_eq!(
+            encode_uri("日本/ü&=+;.txt"),
+            "%E6%97%A5%E6%9C%AC/%C3%BC%26%3D%2B%3B.txt"
+        );
+        for path in [
+            "/a#b.txt",
+            "/a?b.txt",
+            "/a b/c.txt",
+            "/100%.txt",
+            "/%23#?%3F/",
+        ] {
+            assert_eq!(decode_uri(&encode_uri(path)).unwrap(), path);
+        }
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file2"), Ordering::Greater);
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(natural_cmp("a2b", "a2c"), Ordering::Less);
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("file", "file"), Ordering::Equal);
+        // Case and leading zeros only break ties
+        assert_eq!(natural_cmp("B", "a"), Ordering::Greater);
+        assert_eq!(natural_cmp("File", "file"), Ordering::Less);
+        assert_eq!(natural_cmp("file02", "file2"), Ordering::Greater);
+        assert_eq!(natural_cmp("file02", "file3"), Ordering::Less);
+        assert_eq!(natural_cmp("Äpfel", "äpfel2"), Ordering::Less);
+        assert_eq!(natural_cmp("émile", "Zoé"), Ordering::Greater);
+
+        let mut names = vec![
+            "file10", "File2", "file02", "file2", "file1", "file", "Émile", "émile",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["file", "file1", "File2", "file2", "file02", "file10", "Émile", "émile"]
+        );
+        names.reverse();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["file", "file1", "File2", "file2", "file02", "file10", "Émile", "émile"]
+        );
+    }
+
     #[test]
     fn test_glob_key() {
         assert!(glob("", ""));
@@ -181,6 +404,17 @@ mod tests {
         assert!(!glob("*/", "abc"));
     }
 
+    #[test]
+    fn test_glob_path() {
+        assert!(glob_path("logs/**/*.tmp", "logs/a.tmp"));
+        assert!(glob_path("logs/**/*.tmp", "logs/a/b/c.tmp"));
+        assert!(!glob_path("logs/**/*.tmp", "other/logs/a.tmp"));
+        assert!(!glob_path("logs/*.tmp", "logs/a/b.tmp"));
+        assert!(glob_path("**/node_modules/**", "a/node_modules/b"));
+        assert!(glob_path("**/node_modules/**", "node_modules/b/c"));
+        assert!(!glob_path("**/node_modules/**", "a/node_modules_b/c"));
+    }
+
     #[test]
     fn test_parse_range() {
         assert_eq!(parse_range("bytes=0-499", 500), Some((0, 499)));
@@ -191,5 +425,92 @@ mod tests {
         assert_eq!(parse_range("bytes=500-", 500), None);
         assert_eq!(parse_range("bytes=-501", 500), None);
         assert_eq!(parse_range("bytes=0-500", 500), None);
+        assert_eq!(parse_range("bytes=10-5", 500), None);
+        assert_eq!(parse_range("bytes=0-10,20-30", 500), None);
+    }
+
+    #[test]
+    fn test_parse_range_malformed() {
+        for range in [
+            "",
+            "bytes",
+            "bytes=",
+            "bytes=-",
+            "bytes=--",
+            "bytes=-0",
+            "bytes=abc-def",
+            "bytes=0-def",
+            "bytes= 0-10",
+            "bytes=0 -10",
+            "bytes=+0-10",
+            "bytes=0-+10",
+            "bytes=--10",
+            "bytes=0-1-2",
+            "bytes=0-18446744073709551616",
+            "bytes=18446744073709551616-",
+            "bytes=-18446744073709551616",
+            "bytes=10-5",
+            "items=0-10",
+            "BYTES=0-10",
+        ] {
+            assert_eq!(parse_range(range, 500), None, "{range}");
+            assert_eq!(parse_ranges(range, 500), None, "{range}");
+        }
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("bytes=-1", 0), None);
+        assert_eq!(
+            parse_range("bytes=0-18446744073709551614", u64::MAX),
+            Some((0, u64::MAX - 1))
+        );
+        assert_eq!(parse_ranges("bytes=0-10,,20-30", 500), None);
+        assert_eq!(parse_ranges("bytes=0-10,", 500), None);
+        assert_eq!(parse_ranges("bytes=0-10,abc", 500), None);
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(
+            parse_ranges("bytes=0-100,50-150", 500),
+            Some(vec![(0, 150)])
+        );
+        assert_eq!(
+            parse_ranges("bytes=0-10,100-110", 500),
+            Some(vec![(0, 10), (100, 110)])
+        );
+        assert_eq!(
+            parse_ranges("bytes=100-110, 0-10, 11-20", 500),
+            Some(vec![(0, 20), (100, 110)])
+        );
+        assert_eq!(parse_ranges("bytes=-100,0-", 500), Some(vec![(0, 499)]));
+        assert_eq!(
+            parse_ranges("bytes=0-10,600-700,-0", 500),
+            Some(vec![(0, 10)])
+        );
+        assert_eq!(parse_ranges("bytes=600-700,500-", 500), None);
+        assert_eq!(parse_ranges("bytes=0-10,20-10", 500), None);
+        assert_eq!(parse_ranges("items=0-10", 500), None);
+
+        let many = (0..60).map(|v| format!("{}-{}", v * 4, v * 4 + 1));
+        let many = format!("bytes={}", many.collect::<Vec<_>>().join(","));
+        assert_eq!(parse_ranges(&many, 500), Some(vec![(0, 237)]));
+    }
+
+    #[test]
+    fn test_split_range() {
+        assert_eq!(split_range(&[3, 4], 0, 6), [(0, 0, 3), (1, 0, 4)]);
+        assert_eq!(split_range(&[3, 4], 1, 4), [(0, 1, 2), (1, 0, 2)]);
+        assert_eq!(split_range(&[3, 4], 3, 5), [(1, 0, 3)]);
+        assert_eq!(split_range(&[3, 0, 4], 2, 3), [(0, 2, 1), (2, 0, 1)]);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100"), Some(100));
+        assert_eq!(parse_size("512K"), Some(512 * 1024));
+        assert_eq!(parse_size("10m"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Some(3 << 29));
+        assert_eq!(parse_size("0"), None);
+        assert_eq!(parse_size("10X"), None);
+        assert_eq!(parse_size("M"), None);
     }
 }
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, bail, Result};
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::Bytes,
+    header::{CONTENT_LENGTH, CONTENT_TYPE, HOST, USER_AGENT},
+    Method, Request, Uri,
+};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    sync::mpsc,
+    time::timeout,
+};
+
+use crate::auth::hmac_sha256;
+
+/// The events waiting to be sent, past which new ones are dropped.
+const QUEUE_SIZE: usize = 1024;
+/// Attempts to deliver an event, waiting twice as long after each failure.
+const MAX_ATTEMPTS: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+const SIGNATURE_HEADER: &str = "x-dufs-signature";
+
+/// The URL events are POSTed to, with `--webhook-url`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookTarget {
+    uri: Uri,
+    tls: bool,
+    host: String,
+    port: u16,
+    secret: Option<String>,
+}
+
+impl WebhookTarget {
+    pub fn new(url: &str, secret: Option<String>) -> Result<Self> {
+        let uri: Uri = url
+            .parse()
+            .map_err(|_| anyhow!("Invalid webhook url `{url}`"))?;
+        let tls = match uri.scheme_str() {
+            Some("http") => false,
+            #[cfg(feature = "tls")]
+            Some("https") => true,
+            #[cfg(not(feature = "tls"))]
+            Some("https") => bail!("Invalid webhook url `{url}`, https requires the tls feature"),
+            _ => bail!("Invalid webhook url `{url}`, expected http:// or https://"),
+        };
+        let host = uri
+            .host()
+            .ok_or_else(|| anyhow!("Invalid webhook url `{url}`, no host"))?
+            .trim_matches(['[', ']'])
+            .to_string();
+        let port = uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+        Ok(Self {
+            uri,
+            tls,
+            host,
+            port,
+            secret,
+        })
+    }
+}
+
+/// A change of the served directory, sent as JSON.
+#[derive(Debug, Serialize)]
+pub struct WebhookEvent {
+    pub event: &'static str,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Seconds since the epoch.
+    pub time: u64,
+}
+
+/// Sends events in the background, one at a time, so a slow receiver never holds up a request.
+pub struct Webhook {
+    tx: mpsc::Sender<WebhookEvent>,
+}
+
+impl Webhook {
+    pub fn spawn(target: WebhookTarget) -> Self {
+        let (tx, mut rx) = mpsc::channel::<WebhookEvent>(QUEUE_SIZE);
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let body = match serde_json::to_vec(&event) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        error!("Failed to serialize webhook event, {err}");
+                        continue;
+                    }
+                };
+                let mut delay = RETRY_DELAY;
+                for attempt in 1..=MAX_ATTEMPTS {
+                    match timeout(SEND_TIMEOUT, send(&target, &body)).await {
+                        Ok(Ok(())) => break,
+                        Ok(Err(err)) => warn!("Failed to send webhook for {}, {err}", event.path),
+                        Err(_) => warn!("Failed to send webhook for {}, timed out", event.path),
+                    }
+                    if attempt < MAX_ATTEMPTS {
+                        tokio::time::sleep(delay).await;
+                        delay *= 2;
+                    }
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    pub fn notify(&self, event: WebhookEvent) {
+        if self.tx.try_send(event).is_err() {
+            warn!("Webhook queue is full, dropping an event");
+        }
+    }
+}
+
+async fn send(target: &WebhookTarget, body: &[u8]) -> Result<()> {
+    let stream = TcpStream::connect((target.host.as_str(), target.port)).await?;
+    if target.tls {
+        #[cfg(feature = "tls")]
+        {
+            use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let config = ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            let server_name = rustls_pki_types::ServerName::try_from(target.host.clone())?;
+            let stream = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config))
+                .connect(server_name, stream)
+                .await?;
+            return post(target, stream, body).await;
+        }
+    }
+    post(target, stream, body).await
+}
+
+async fn post<T>(target: &WebhookTarget, io: T, body: &[u8]) -> Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(io)).await?;
+    tokio::spawn(async move {
+        if let Err(err) = conn.await {
+            debug!("Webhook connection failed, {err}");
+        }
+    });
+    let path_and_query = target
+        .uri
+        .path_and_query()
+        .map(|v| v.as_str())
+        .unwrap_or("/");
+    let mut req = Request::builder()
+        .method(Method::POST)
+        .uri(path_and_query)
+        .header(
+            HOST,
+            target
+                .uri
+                .authority()
+                .map(|v| v.as_str())
+                .unwrap_or_default(),
+        )
+        .header(USER_AGENT, concat!("dufs/", env!("CARGO_PKG_VERSION")))
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, body.len());
+    if let Some(secret) = target.secret.as_ref() {
+        req = req.header(
+            SIGNATURE_HEADER,
+            format!("sha256={}", hmac_sha256(secret.as_bytes(), body)),
+        );
+    }
+    let req = req.body(Full::new(Bytes::copy_from_slice(body)))?;
+    let res = sender.send_request(req).await?;
+    let status = res.status();
+    // Read the body so the receiver isn't cut off mid-response.
+    let _ = res.into_body().collect().await;
+    if !status.is_success() {
+        bail!("the receiver answered {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_target() {
+        let target = WebhookTarget::new("http://127.0.0.1:9000/hook?x=1", None).unwrap();
+        assert!(!target.tls);
+        assert_eq!(target.host, "127.0.0.1");
+        assert_eq!(target.port, 9000);
+        let target = WebhookTarget::new("http://[::1]/hook", None).unwrap();
+        assert_eq!(target.host, "::1");
+        assert_eq!(target.port, 80);
+        assert!(WebhookTarget::new("ftp://example.com", None).is_err());
+        assert!(WebhookTarget::new("/hook", None).is_err());
+    }
+}
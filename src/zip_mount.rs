@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use async_zip::{base::read::seek::ZipFileReader, ZipFile};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::File,
+    io::{self, AsyncRead, AsyncReadExt, BufReader},
+};
+use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+
+/// An entry of a mounted archive, folders only implied by the names of their files included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZipMountEntry {
+    /// The index of the entry in the archive, `None` for implied folders.
+    index: Option<usize>,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Milliseconds since the epoch.
+    pub mtime: u64,
+}
+
+/// A zip archive served as a read-only folder.
+///
+/// The central directory is indexed once, then each read reopens the archive and
+/// decompresses the single entry it needs.
+pub struct ZipMount {
+    path: PathBuf,
+    file: ZipFile,
+    entries: BTreeMap<String, ZipMountEntry>,
+}
+
+impl ZipMount {
+    pub async fn open(path: &Path) -> Result<Self> {
+        let reader = BufReader::new(File::open(path).await?);
+        let reader = ZipFileReader::with_tokio(reader)
+            .await
+            .map_err(|err| anyhow!("Failed to read archive `{}`, {err}", path.display()))?;
+        let file = reader.file().clone();
+        let mut entries = BTreeMap::new();
+        entries.insert(String::new(), implied_dir(0));
+        for (index, entry) in file.entries().iter().enumerate() {
+            let Some(name) = entry
+                .filename()
+                .as_str()
+                .ok()
+                .and_then(normalize_entry_name)
+            else {
+                warn!("Skip archive entry `{:?}`", entry.filename().as_bytes());
+                continue;
+            };
+            if name.is_empty() {
+                continue;
+            }
+            let mtime = entry
+                .last_modification_date()
+                .as_chrono()
+                .single()
+                .map(|v| v.timestamp_millis().max(0) as u64)
+                .unwrap_or_default();
+            let mut parent = name.as_str();
+            while let Some((v, _)) = parent.rsplit_once('/') {
+                parent = v;
+                entries
+                    .entry(parent.to_string())
+                    .or_insert_with(|| implied_dir(mtime));
+            }
+            let is_dir = entry.dir().unwrap_or_default();
+            entries.insert(
+                name,
+                ZipMountEntry {
+                    index: (!is_dir).then_some(index),
+                    is_dir,
+                    size: if is_dir { 0 } else { entry.uncompressed_size() },
+                    mtime,
+                },
+            );
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            entries,
+        })
+    }
+
+    /// The entry at the relative `name`, `""` being the root of the archive.
+    pub fn get(&self, name: &str) -> Option<&ZipMountEntry> {
+        self.entries.get(name)
+    }
+
+    /// The direct children of the folder `dir`, folders sized by their number of children.
+    pub fn list(&self, dir: &str) -> Vec<(&str, ZipMountEntry)> {
+        let children = |dir: &str| {
+            let prefix = match dir {
+                "" => String::new(),
+                _ => format!("{dir}/"),
+            };
+            self.entries
+                .range(prefix.clone()..)
+                .skip_while(|(name, _)| name.is_empty())
+                .take_while(move |(name, _)| name.starts_with(&prefix))
+                .filter(move |(name, _)| !name[dir.len()..].trim_start_matches('/').contains('/'))
+                .map(|(name, entry)| (name.as_str(), entry))
+                .collect::<Vec<_>>()
+        };
+        children(dir)
+            .into_iter()
+            .map(|(name, entry)| {
+                let mut entry = entry.clone();
+                if entry.is_dir {
+                    entry.size = children(name).len() as u64;
+                }
+                (name.rsplit('/').next().unwrap_or(name), entry)
+            })
+            .collect()
+    }
+
+    /// Read `len` decompressed bytes of a file entry, starting at `start`.
+    ///
+    /// Compressed entries can't seek, the bytes before `start` are decompressed and skipped.
+    pub async fn read(
+        &self,
+        entry: &ZipMountEntry,
+        start: u64,
+        len: u64,
+    ) -> Result<impl AsyncRead + Send + Unpin + 'static> {
+        let index = entry
+            .index
+            .ok_or_else(|| anyhow!("Not a file of the archive"))?;
+        let reader = BufReader::new(File::open(&self.path).await?).compat();
+        let reader = ZipFileReader::from_raw_parts(reader, self.file.clone());
+        let mut reader = reader.into_entry(index).await?.compat();
+        io::copy(&mut (&mut reader).take(start), &mut io::sink()).await?;
+        Ok(reader.take(len))
+    }
+}
+
+fn implied_dir(mtime: u64) -> ZipMountEntry {
+    ZipMountEntry {
+        index: None,
+        is_dir: true,
+        size: 0,
+        mtime,
+    }
+}
+
+/// Normalize the name of an archive entry, rejecting those which escape the archive.
+fn normalize_entry_name(name: &str) -> Option<String> {
+    let mut parts = vec![];
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            _ => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+pub fn is_zip_path(path: &Path) -> bool {
+    path.extension()
+        .map(|v| v.eq_ignore_ascii_case("zip"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_entry_name() {
+        assert_eq!(normalize_entry_name("a/b.txt").unwrap(), "a/b.txt");
+        assert_eq!(normalize_entry_name("./a//b/").unwrap(), "a/b");
+        assert_eq!(normalize_entry_name("/a\\b").unwrap(), "a/b");
+        assert!(normalize_entry_name("a/../../b").is_none());
+    }
+}
@@ -0,0 +1,71 @@
+mod fixtures;
+mod utils;
+
+use async_zip::base::read::mem::ZipFileReader;
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+fn zip_entry_names(data: Vec<u8>) -> Result<Vec<String>, Error> {
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let reader = runtime.block_on(ZipFileReader::new(data))?;
+    let mut names: Vec<String> = reader
+        .file()
+        .entries()
+        .iter()
+        .map(|v| v.filename().as_str().map(|v| v.to_string()))
+        .collect::<Result<_, _>>()?;
+    names.sort();
+    Ok(names)
+}
+
+fn create_nested(server: &TestServer) -> Result<(), Error> {
+    let dir = server.path().join("nested");
+    std::fs::create_dir_all(dir.join("a/b/c"))?;
+    std::fs::write(dir.join("a/b/c/file.txt"), "file")?;
+    std::fs::write(dir.join("a/b/other.txt"), "other")?;
+    std::fs::write(dir.join("a/shallow.txt"), "shallow")?;
+    std::fs::write(dir.join("top.txt"), "top")?;
+    Ok(())
+}
+
+#[rstest]
+fn zip_strip_components(
+    #[with(&["--allow-archive", "--archive-strip-components", "2"])] server: TestServer,
+) -> Result<(), Error> {
+    create_nested(&server)?;
+    let resp = reqwest::blocking::get(format!("{}nested?zip", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let names = zip_entry_names(resp.bytes()?.to_vec())?;
+    assert_eq!(names, ["c/file.txt", "other.txt"]);
+    Ok(())
+}
+
+#[rstest]
+fn zip_strip_components_keep_shallow(
+    #[with(&["--allow-archive", "--archive-strip-components", "2", "--archive-keep-shallow"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    create_nested(&server)?;
+    let resp = reqwest::blocking::get(format!("{}nested?zip", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let names = zip_entry_names(resp.bytes()?.to_vec())?;
+    assert_eq!(names, ["c/file.txt", "other.txt", "shallow.txt", "top.txt"]);
+    Ok(())
+}
+
+#[rstest]
+fn zip_without_strip(#[with(&["--allow-archive"])] server: TestServer) -> Result<(), Error> {
+    create_nested(&server)?;
+    let resp = reqwest::blocking::get(format!("{}nested?zip", server.url()))?;
+    let names = zip_entry_names(resp.bytes()?.to_vec())?;
+    assert_eq!(
+        names,
+        [
+            "a/b/c/file.txt",
+            "a/b/other.txt",
+            "a/shallow.txt",
+            "top.txt"
+        ]
+    );
+    Ok(())
+}
@@ -3,8 +3,11 @@
 mod fixtures;
 mod utils;
 
-use fixtures::{server, Error, TestServer};
+use assert_cmd::prelude::*;
+use assert_fs::fixture::TempDir;
+use fixtures::{port, server, tmpdir, wait_for_port, Error, TestServer};
 use rstest::rstest;
+use std::process::{Command, Stdio};
 
 #[rstest]
 fn path_prefix_index(#[with(&["--path-prefix", "xyz"])] server: TestServer) -> Result<(), Error> {
@@ -30,3 +33,112 @@ fn path_prefix_propfind(
     assert!(text.contains("<D:href>/xyz/</D:href>"));
     Ok(())
 }
+
+#[rstest]
+#[case(server(&["--root-redirect", "/my folder"]), 302)]
+#[case(server(&["--root-redirect", "/my folder", "--root-redirect-permanent"]), 301)]
+fn root_redirect(#[case] server: TestServer, #[case] status: u16) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let resp = client.get(server.url()).send()?;
+    assert_eq!(resp.status(), status);
+    assert_eq!(resp.headers().get("location").unwrap(), "/my%20folder");
+    let resp = client.get(format!("{}index.html", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
+
+#[rstest]
+fn redirect_slash(#[with(&["--path-prefix", "xyz"])] server: TestServer) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let url = format!("http://localhost:{}", server.port());
+    let resp = client.get(format!("{url}/xyz/dir1?json")).send()?;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("location").unwrap(), "/xyz/dir1/?json");
+    let resp = client.get(format!("{url}/xyz/index.html/")).send()?;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("location").unwrap(), "/xyz/index.html");
+    let resp = client.get(format!("{url}/xyz")).send()?;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("location").unwrap(), "/xyz/");
+    let resp = client.get(format!("{url}/xyz/dir1/")).send()?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
+
+#[rstest]
+fn no_redirect_slash(#[with(&["--no-redirect-slash"])] server: TestServer) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let resp = client.get(format!("{}dir1", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    let resp = client.get(format!("{}index.html/", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
+
+#[rstest]
+fn create_serve_dir(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let serve_path = tmpdir.path().join("volume/share");
+    let mut command = Command::cargo_bin("dufs")?;
+    command
+        .arg(&serve_path)
+        .arg("-p")
+        .arg(port.to_string())
+        .args(["-A", "--create-serve-dir"]);
+    #[cfg(unix)]
+    command.args(["--create-serve-dir-mode", "700"]);
+    let mut child = command.stdout(Stdio::null()).spawn()?;
+    wait_for_port(port);
+    assert!(serve_path.is_dir());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &std::path::Path| -> Result<u32, Error> {
+            Ok(path.metadata()?.permissions().mode() & 0o7777)
+        };
+        assert_eq!(mode(&serve_path)?, 0o700);
+        assert_eq!(mode(serve_path.parent().unwrap())?, 0o700);
+    }
+
+    let url = format!("http://localhost:{port}/");
+    let resp = fetch!(b"PUT", format!("{url}file1")).body("abc").send()?;
+    assert_eq!(resp.status(), 201);
+    let resp = reqwest::blocking::get(format!("{url}file1"))?;
+    assert_eq!(resp.text()?, "abc");
+    assert!(serve_path.join("file1").exists());
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn missing_serve_dir(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let serve_path = tmpdir.path().join("missing");
+    Command::cargo_bin("dufs")?
+        .arg(&serve_path)
+        .arg("-p")
+        .arg(port.to_string())
+        .assert()
+        .stderr(predicates::str::contains("use --create-serve-dir"))
+        .failure();
+    assert!(!serve_path.exists());
+    Ok(())
+}
+
+#[rstest]
+#[case("1K")]
+#[case("32M")]
+#[case("abc")]
+fn invalid_read_buffer_size(#[case] size: &str) -> Result<(), Error> {
+    Command::cargo_bin("dufs")?
+        .args(["--read-buffer-size", size])
+        .assert()
+        .stderr(predicates::str::contains("read-buffer-size"))
+        .failure();
+    Ok(())
+}
@@ -124,3 +124,98 @@ fn assets_override(tmpdir: TempDir, port: u16) -> Result<(), Error> {
     child.kill()?;
     Ok(())
 }
+
+#[rstest]
+fn assets_template_vars(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let assets_dir = tmpdir.join("custom-assets");
+    std::fs::create_dir(&assets_dir)?;
+    std::fs::write(
+        assets_dir.join("index.html"),
+        r#"<title>__TITLE__</title><img src="__LOGO__">__INDEX_DATA__"#,
+    )?;
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--assets-dir")
+        .arg(&assets_dir)
+        .args(["--template-var", "title=My <Files>"])
+        .args(["--template-var", "logo=/logo.png"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    wait_for_port(port);
+
+    let resp = reqwest::blocking::get(format!("http://localhost:{port}"))?;
+    assert!(resp
+        .text()?
+        .starts_with(r#"<title>My &lt;Files&gt;</title><img src="/logo.png">"#));
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn assets_template_fallback(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let assets_dir = tmpdir.join("custom-assets");
+    std::fs::create_dir(&assets_dir)?;
+    std::fs::write(assets_dir.join("index.html"), "<title>__TITLE__</title>")?;
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--assets")
+        .arg(&assets_dir)
+        .args(["--template-var", "title=Files"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    wait_for_port(port);
+
+    let url = format!("http://localhost:{port}");
+    let resp = reqwest::blocking::get(&url)?;
+    assert_resp_paths!(resp);
+    let resp = reqwest::blocking::get(format!(
+        "{url}/__dufs_v{}__/index.js",
+        env!("CARGO_PKG_VERSION")
+    ))?;
+    assert_eq!(resp.status(), 200);
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn no_ui(#[with(&["--no-ui"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"GET", server.url())
+        .header("accept", "text/html")
+        .send()?;
+    assert_eq!(resp.status(), 406);
+
+    let resp = fetch!(b"GET", server.url())
+        .header("accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    let json: serde_json::Value = serde_json::from_str(&resp.text()?)?;
+    assert_eq!(json["kind"], "listing");
+
+    let resp = fetch!(b"GET", format!("{}?json", server.url()))
+        .header("accept", "text/html")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+
+    let ver = env!("CARGO_PKG_VERSION");
+    for asset in ["index.js", "index.css", "favicon.ico"] {
+        let resp = reqwest::blocking::get(format!("{}__dufs_v{ver}__/{asset}", server.url()))?;
+        assert_eq!(resp.status(), 404);
+    }
+    let resp = reqwest::blocking::get(format!("{}index.html?edit", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
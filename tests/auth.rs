@@ -98,6 +98,27 @@ fn auth_and_public(
     Ok(())
 }
 
+#[rstest]
+fn auth_public_read_private_write(
+    #[with(&["-a", "user:pass@/:rw", "-a", "@/", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_eq!(resp.status(), 200);
+    let resp = fetch!(b"PUT", &url).body(b"abc".to_vec()).send()?;
+    assert_eq!(resp.status(), 401);
+    let resp = fetch!(b"DELETE", &url).send()?;
+    assert_eq!(resp.status(), 401);
+    let resp = fetch!(b"PUT", &url)
+        .body(b"abc".to_vec())
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_eq!(resp.text()?, "abc");
+    Ok(())
+}
+
 #[rstest]
 fn auth_skip(#[with(&["--auth", "@/"])] server: TestServer) -> Result<(), Error> {
     let resp = reqwest::blocking::get(server.url())?;
@@ -281,6 +302,24 @@ fn auth_partial_index(
     Ok(())
 }
 
+#[rstest]
+fn auth_dir_entry(
+    #[with(&["--auth", "user:pass@/dir1:ro", "--auth", "@/dir2", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}dir2?entry=../dir1/index.html", server.url());
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_eq!(resp.status(), 400);
+    let url = format!("{}?entry=dir1/index.html", server.url());
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_eq!(resp.status(), 401);
+    let resp = send_with_digest_auth(fetch!(b"GET", &url), "user", "pass")?;
+    assert_eq!(resp.status(), 200);
+    let url = format!("{}?entry=dir2/index.html", server.url());
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
+
 #[rstest]
 fn no_auth_propfind_dir(
     #[with(&["--auth", "admin:admin@/:rw", "--auth", "@/dir-assets", "-A"])] server: TestServer,
@@ -339,3 +378,145 @@ fn auth_precedence(
 
     Ok(())
 }
+
+#[rstest]
+fn auth_well_known_no_auth(
+    #[with(&["--auth", "user:pass@/:rw", "-A", "--well-known-no-auth"])] server: TestServer,
+) -> Result<(), Error> {
+    let dir = server.path().join(".well-known/acme-challenge");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("token"), b"token.thumbprint")?;
+    let url = format!("{}.well-known/acme-challenge/token", server.url());
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "token.thumbprint");
+    let resp = fetch!(b"PUT", &url).body(b"abc".to_vec()).send()?;
+    assert_eq!(resp.status(), 401);
+    let resp = reqwest::blocking::get(format!("{}.well-knownx", server.url()))?;
+    assert_eq!(resp.status(), 401);
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(resp.status(), 401);
+    Ok(())
+}
+
+#[rstest]
+fn auth_options_allow(
+    #[with(&["--auth", "user:pass@/dir1:rw,/dir2:ro", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let allow = |path: &str, auth: bool| -> Result<String, Error> {
+        let mut req = fetch!(b"OPTIONS", format!("{}{path}", server.url()));
+        if auth {
+            req = req.basic_auth("user", Some("pass"));
+        }
+        let resp = req.send()?;
+        assert_eq!(resp.status(), 200);
+        Ok(resp.headers().get("allow").unwrap().to_str()?.to_string())
+    };
+    assert_eq!(
+        allow("dir1/index.html", true)?,
+        "GET,HEAD,PUT,OPTIONS,DELETE,PATCH,POST,PROPFIND,PROPPATCH,COPY,MOVE,LOCK,UNLOCK,CHECKAUTH,LOGOUT"
+    );
+    assert_eq!(
+        allow("dir2/index.html", true)?,
+        "GET,HEAD,OPTIONS,PROPFIND,CHECKAUTH,LOGOUT"
+    );
+    assert_eq!(allow("dir1/index.html", false)?, "OPTIONS,CHECKAUTH,LOGOUT");
+    Ok(())
+}
+
+#[rstest]
+fn auth_session(
+    #[with(&["--auth", "user:pass@/:rw", "--auth-session", "--session-secret", "s3cret"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"GET", &url)
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    let set_cookie = resp.headers().get("set-cookie").unwrap().to_str()?;
+    assert!(set_cookie.contains("HttpOnly"), "{set_cookie}");
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+    assert!(cookie.starts_with("dufs_session="));
+
+    let resp = fetch!(b"GET", &url).header("cookie", &cookie).send()?;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get("set-cookie").is_none());
+
+    let forged = format!("{cookie}x");
+    let resp = fetch!(b"GET", &url).header("cookie", &forged).send()?;
+    assert_eq!(resp.status(), 401);
+
+    let resp = fetch!(b"GET", format!("{}__dufs__/logout", server.url()))
+        .header("cookie", &cookie)
+        .send()?;
+    assert_eq!(resp.status(), 401);
+    let set_cookie = resp.headers().get("set-cookie").unwrap().to_str()?;
+    assert!(set_cookie.starts_with("dufs_session=;"), "{set_cookie}");
+    assert!(set_cookie.contains("Max-Age=0"), "{set_cookie}");
+    Ok(())
+}
+
+#[rstest]
+fn auth_session_expired(
+    #[with(&["--auth", "user:pass@/:rw", "--auth-session", "--session-ttl", "1"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"GET", &url)
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    let cookie = resp.headers().get("set-cookie").unwrap().to_str()?;
+    let cookie = cookie.split(';').next().unwrap().to_string();
+    let resp = fetch!(b"GET", &url).header("cookie", &cookie).send()?;
+    assert_eq!(resp.status(), 200);
+
+    std::thread::sleep(std::time::Duration::from_millis(2100));
+    let resp = fetch!(b"GET", &url).header("cookie", &cookie).send()?;
+    assert_eq!(resp.status(), 401);
+    Ok(())
+}
+
+#[rstest]
+fn auth_session_writes(
+    #[with(&["--auth", "user:pass@/dir1:rw,/dir2", "--auth-session", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}dir1/", server.url()))
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    let cookie = resp.headers().get("set-cookie").unwrap().to_str()?;
+    let cookie = cookie.split(';').next().unwrap().to_string();
+
+    let resp = fetch!(b"MOVE", format!("{}dir1/test.txt", server.url()))
+        .header("cookie", &cookie)
+        .header("Destination", format!("{}dir1/moved.txt", server.url()))
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    let resp = fetch!(
+        b"POST",
+        format!("{}dir1/moved.txt?rename=renamed.txt", server.url())
+    )
+    .header("cookie", &cookie)
+    .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("dir1/renamed.txt").exists());
+    let resp = fetch!(b"POST", format!("{}dir1/new?mkdir", server.url()))
+        .header("cookie", &cookie)
+        .send()?;
+    assert_eq!(resp.status(), 201);
+
+    // The destination is still checked against the paths of the user
+    let resp = fetch!(b"MOVE", format!("{}dir1/renamed.txt", server.url()))
+        .header("cookie", &cookie)
+        .header("Destination", format!("{}dir2/moved.txt", server.url()))
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(
+        b"POST",
+        format!("{}dir1/renamed.txt?rename=/dir2/x.txt", server.url())
+    )
+    .header("cookie", &cookie)
+    .send()?;
+    assert_eq!(resp.status(), 403);
+    Ok(())
+}
@@ -4,8 +4,8 @@ mod utils;
 use chrono::{DateTime, Duration};
 use fixtures::{server, Error, TestServer};
 use reqwest::header::{
-    HeaderName, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE,
-    LAST_MODIFIED,
+    HeaderName, CACHE_CONTROL, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    IF_UNMODIFIED_SINCE, LAST_MODIFIED,
 };
 use reqwest::StatusCode;
 use rstest::rstest;
@@ -78,3 +78,97 @@ fn get_file_with_etag_match(
     assert_eq!(resp.status(), expected_code);
     Ok(())
 }
+
+#[rstest]
+#[case(IF_MATCH, same_etag, StatusCode::CREATED)]
+#[case(IF_MATCH, different_etag, StatusCode::PRECONDITION_FAILED)]
+#[case(IF_NONE_MATCH, same_etag, StatusCode::PRECONDITION_FAILED)]
+fn put_file_with_etag_match(
+    #[case] header_condition: HeaderName,
+    #[case] etag_modifier: fn(&str) -> String,
+    #[case] expected_code: StatusCode,
+    #[with(&["-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"HEAD", format!("{}index.html", server.url())).send()?;
+
+    let etag = resp
+        .headers()
+        .get(ETAG)
+        .and_then(|h| h.to_str().ok())
+        .expect("Received no valid etag header");
+
+    let resp = fetch!(b"PUT", format!("{}index.html", server.url()))
+        .header(header_condition, etag_modifier(etag))
+        .body("abc")
+        .send()?;
+
+    assert_eq!(resp.status(), expected_code);
+    Ok(())
+}
+
+#[rstest]
+#[case("index.html")]
+#[case("dir1/")]
+#[case(&format!("__dufs_v{}__/index.js", env!("CARGO_PKG_VERSION")))]
+fn no_cache(#[case] path: &str, #[with(&["--no-cache"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}{path}", server.url())).send()?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+    assert!(resp.headers().get(ETAG).is_none());
+    assert!(resp.headers().get(LAST_MODIFIED).is_none());
+
+    let resp = fetch!(b"GET", format!("{}{path}", server.url()))
+        .header(IF_NONE_MATCH, "*")
+        .header(IF_MODIFIED_SINCE, "Fri, 01 Jan 2100 00:00:00 GMT")
+        .send()?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    Ok(())
+}
+
+#[rstest]
+fn etag_strong(#[with(&["--etag", "strong"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let etag = |resp: &reqwest::blocking::Response| {
+        resp.headers()
+            .get(ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned()
+    };
+    let resp = fetch!(b"GET", &url).send()?;
+    let strong = etag(&resp);
+    // The SHA-256 of "This is index.html"
+    assert!(strong.starts_with(r#""c8dd395e3202674b"#));
+
+    // A touch leaves the ETag alone
+    let file = std::fs::File::options()
+        .write(true)
+        .open(server.path().join("index.html"))?;
+    file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(3600))?;
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_eq!(etag(&resp), strong);
+    let resp = fetch!(b"GET", &url).header(IF_NONE_MATCH, &strong).send()?;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    // While new content gets a new one
+    std::fs::write(server.path().join("index.html"), "changed")?;
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_ne!(etag(&resp), strong);
+    Ok(())
+}
+
+#[rstest]
+fn stat_cache_invalidated_by_writes(
+    #[with(&["-A", "--stat-cache-ttl", "60"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}test.txt", server.url());
+    assert_eq!(reqwest::blocking::get(&url)?.status(), 200);
+    assert_eq!(fetch!(b"DELETE", &url).send()?.status(), 204);
+    assert_eq!(reqwest::blocking::get(&url)?.status(), 404);
+
+    let resp = fetch!(b"PUT", &url).body(b"abc".to_vec()).send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(reqwest::blocking::get(&url)?.text()?, "abc");
+    Ok(())
+}
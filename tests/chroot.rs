@@ -0,0 +1,76 @@
+#![cfg(unix)]
+
+mod fixtures;
+mod utils;
+
+use assert_cmd::prelude::*;
+use assert_fs::fixture::TempDir;
+use fixtures::{port, tmpdir, wait_for_port, Error};
+use rstest::rstest;
+use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+use std::process::{Command, Stdio};
+
+#[rstest]
+fn chroot_confines_serving(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    // chroot and setuid need root, skip elsewhere
+    if unsafe { libc::geteuid() } != 0 {
+        return Ok(());
+    }
+    let outside = TempDir::new()?;
+    std::fs::write(outside.path().join("secret.txt"), "secret")?;
+    symlink(
+        outside.path().join("secret.txt"),
+        tmpdir.path().join("link"),
+    )?;
+    std::fs::set_permissions(tmpdir.path(), std::fs::Permissions::from_mode(0o777))?;
+
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args(["-A", "--allow-symlink", "--chroot", "--user", "nobody"])
+        .stdout(Stdio::null())
+        .spawn()?;
+    wait_for_port(port);
+
+    let url = format!("http://localhost:{port}/");
+    let resp = reqwest::blocking::get(format!("{url}index.html"))?;
+    assert_eq!(resp.status(), 200);
+    let resp = reqwest::blocking::get(format!("{url}link"))?;
+    assert_eq!(resp.status(), 404);
+
+    let resp = fetch!(b"PUT", format!("{url}upload.txt"))
+        .body("abc")
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert_ne!(tmpdir.path().join("upload.txt").metadata()?.uid(), 0);
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn symlink_escapes_without_chroot(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let outside = TempDir::new()?;
+    std::fs::write(outside.path().join("secret.txt"), "secret")?;
+    symlink(
+        outside.path().join("secret.txt"),
+        tmpdir.path().join("link"),
+    )?;
+
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--allow-symlink")
+        .stdout(Stdio::null())
+        .spawn()?;
+    wait_for_port(port);
+
+    let resp = reqwest::blocking::get(format!("http://localhost:{port}/link"))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "secret");
+
+    child.kill()?;
+    Ok(())
+}
@@ -0,0 +1,89 @@
+#![cfg(unix)]
+
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixListener;
+
+const EICAR: &str = r"X5O!P%@AP[4\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+/// Spawn a fake clamd which flags streams containing the EICAR test string.
+fn mock_clamd(path: &str) {
+    let listener = UnixListener::bind(path).unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut command = [0u8; 10];
+            stream.read_exact(&mut command).unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut data = vec![];
+            loop {
+                let mut len = [0u8; 4];
+                stream.read_exact(&mut len).unwrap();
+                let len = u32::from_be_bytes(len) as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; len];
+                stream.read_exact(&mut chunk).unwrap();
+                data.extend(chunk);
+            }
+            let reply: &[u8] = if String::from_utf8_lossy(&data).contains(EICAR) {
+                b"stream: Eicar-Signature FOUND\0"
+            } else {
+                b"stream: OK\0"
+            };
+            stream.write_all(reply).unwrap();
+        }
+    });
+}
+
+#[rstest]
+fn upload_scanned_by_clamav() -> Result<(), Error> {
+    let socket_dir = assert_fs::TempDir::new()?;
+    let socket = socket_dir.path().join("clamd.sock").display().to_string();
+    mock_clamd(&socket);
+    let server: TestServer = server(&["--allow-upload", "--clamav-socket", &socket]);
+
+    let url = format!("{}eicar.txt", server.url());
+    let resp = fetch!(b"PUT", &url).body(EICAR).send()?;
+    assert_eq!(resp.status(), 422);
+    assert_eq!(resp.text()?, "Eicar-Signature");
+    assert!(!server.path().join("eicar.txt").exists());
+    assert!(!server.path().join("eicar.txt.dufsupload").exists());
+
+    let url = format!("{}clean.txt", server.url());
+    let resp = fetch!(b"PUT", &url).body("hello").send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("clean.txt").exists());
+    Ok(())
+}
+
+#[cfg(feature = "encrypt")]
+#[rstest]
+fn encrypted_upload_scanned_by_clamav() -> Result<(), Error> {
+    let socket_dir = assert_fs::TempDir::new()?;
+    let socket = socket_dir.path().join("clamd.sock").display().to_string();
+    mock_clamd(&socket);
+    let key_file = socket_dir.path().join("key");
+    std::fs::write(&key_file, "42".repeat(32))?;
+    let key_file = key_file.display().to_string();
+    let server: TestServer = server(&[
+        "--allow-upload",
+        "--clamav-socket",
+        &socket,
+        "--encrypt-at-rest",
+        "--encryption-key-file",
+        &key_file,
+    ]);
+
+    let url = format!("{}eicar.txt", server.url());
+    let resp = fetch!(b"PUT", &url).body(EICAR).send()?;
+    assert_eq!(resp.status(), 422);
+    assert_eq!(resp.text()?, "Eicar-Signature");
+    assert!(!server.path().join("eicar.txt").exists());
+    Ok(())
+}
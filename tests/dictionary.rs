@@ -0,0 +1,111 @@
+#![cfg(feature = "compression-dictionary")]
+
+mod fixtures;
+mod utils;
+
+use assert_fs::fixture::TempDir;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use fixtures::{server, tmpdir, Error};
+use rstest::rstest;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+
+#[rstest]
+fn dictionary_compressed_listing(tmpdir: TempDir) -> Result<(), Error> {
+    let dictionary_file = tmpdir.path().join("listing.dict");
+    let dictionary = br#"{"href":"/","uri_prefix":"/","allow_upload":false,"paths":[{"path_type":"File","name":"","mtime":,"size":}]}"#.repeat(4);
+    std::fs::write(&dictionary_file, &dictionary)?;
+    let server = server(&[
+        "--compression-dictionary",
+        dictionary_file.to_str().unwrap(),
+    ]);
+
+    let resp = reqwest::blocking::get(format!("{}__dufs__/dictionary", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("use-as-dictionary").unwrap(),
+        r#"match="/*""#
+    );
+    assert_eq!(resp.bytes()?.as_ref(), dictionary.as_slice());
+
+    let url = format!("{}?json", server.url());
+    let plain = fetch!(b"GET", &url)
+        .header("accept-encoding", "gzip, dcb, dcz")
+        .send()?;
+    assert!(!plain.headers().contains_key("content-encoding"));
+    assert_eq!(
+        plain.headers().get("vary").unwrap(),
+        "Accept-Encoding, Available-Dictionary"
+    );
+    assert_eq!(
+        plain.headers().get("link").unwrap(),
+        r#"</__dufs__/dictionary>; rel="compression-dictionary""#
+    );
+    let plain = plain.bytes()?;
+
+    let hash: [u8; 32] = Sha256::digest(&dictionary).into();
+    let available = format!(":{}:", STANDARD.encode(hash));
+    for encoding in ["dcz", "dcb"] {
+        let resp = fetch!(b"GET", &url)
+            .header("accept-encoding", format!("gzip, {encoding}"))
+            .header("available-dictionary", &available)
+            .send()?;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get("content-encoding").unwrap(), encoding);
+        let body = resp.bytes()?;
+        let (magic, rest) = body.split_at(if encoding == "dcz" { 8 } else { 4 });
+        assert_eq!(
+            magic[..4],
+            if encoding == "dcz" {
+                [0x5e, 0x2a, 0x4d, 0x18]
+            } else {
+                [0xff, 0x44, 0x43, 0x42]
+            }
+        );
+        assert_eq!(&rest[..32], hash.as_slice());
+        let mut text = vec![];
+        if encoding == "dcz" {
+            zstd::stream::Decoder::with_dictionary(&rest[32..], &dictionary)?
+                .read_to_end(&mut text)?;
+        } else {
+            brotli::Decompressor::new_with_custom_dict(
+                &rest[32..],
+                4096,
+                dictionary.clone().into(),
+            )
+            .read_to_end(&mut text)?;
+        }
+        assert_eq!(text, plain.as_ref());
+    }
+
+    // Another dictionary gets the listing as is
+    let resp = fetch!(b"GET", &url)
+        .header("accept-encoding", "dcz")
+        .header(
+            "available-dictionary",
+            format!(":{}:", STANDARD.encode([0u8; 32])),
+        )
+        .send()?;
+    assert!(!resp.headers().contains_key("content-encoding"));
+    Ok(())
+}
+
+#[rstest]
+fn dictionary_falls_back_to_gzip(tmpdir: TempDir) -> Result<(), Error> {
+    let dictionary_file = tmpdir.path().join("listing.dict");
+    std::fs::write(&dictionary_file, r#"{"paths":[]}"#)?;
+    let server = server(&[
+        "--compression-dictionary",
+        dictionary_file.to_str().unwrap(),
+        "--listing-compress-threshold",
+        "1",
+    ]);
+    let resp = fetch!(b"GET", format!("{}?json", server.url()))
+        .header("accept-encoding", "gzip, dcz")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-encoding").unwrap(), "gzip");
+    let vary: Vec<_> = resp.headers().get_all("vary").iter().collect();
+    assert_eq!(vary, ["Available-Dictionary", "Accept-Encoding"]);
+    Ok(())
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "embed")]
+
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+#[rstest]
+fn get_embedded_file(
+    #[with(&["--serve-embedded", "--allow-all"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}css/style.css", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/css; charset=UTF-8"
+    );
+    assert_eq!(resp.headers().get("accept-ranges").unwrap(), "bytes");
+    assert_eq!(resp.text()?, "body { color: #333; }\n");
+
+    let resp = fetch!(b"GET", format!("{}css/style.css", server.url()))
+        .header("range", "bytes=0-3")
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(resp.headers().get("content-range").unwrap(), "bytes 0-3/22");
+    assert_eq!(resp.text()?, "body");
+    Ok(())
+}
+
+#[rstest]
+fn list_embedded_dir(
+    #[with(&["--serve-embedded", "--allow-all"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?simple", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "css/\nindex.html\n");
+    Ok(())
+}
+
+#[rstest]
+fn embedded_is_read_only(
+    #[with(&["--serve-embedded", "--allow-all"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"PUT", &url).body("abc").send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"DELETE", &url).send()?;
+    assert_eq!(resp.status(), 403);
+    Ok(())
+}
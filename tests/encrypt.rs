@@ -0,0 +1,167 @@
+#![cfg(feature = "encrypt")]
+
+mod fixtures;
+mod utils;
+
+use assert_fs::fixture::TempDir;
+use async_zip::base::read::mem::ZipFileReader;
+use fixtures::{server, tmpdir, Error, TestServer};
+use rstest::rstest;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+fn encrypted_server(tmpdir: &TempDir, args: &[&str]) -> TestServer {
+    let key_file = tmpdir.path().join("dufs.key");
+    std::fs::write(&key_file, "42".repeat(32)).unwrap();
+    let mut args = args.to_vec();
+    args.extend([
+        "-A",
+        "--encrypt-at-rest",
+        "--encryption-key-file",
+        key_file.to_str().unwrap(),
+    ]);
+    server(&args)
+}
+
+#[rstest]
+fn encrypt_at_rest_round_trip(tmpdir: TempDir) -> Result<(), Error> {
+    let server = encrypted_server(&tmpdir, &[]);
+    // Spans a few chunks of the encryption
+    let data: Vec<u8> = (0..200_000u32).flat_map(|v| v.to_le_bytes()).collect();
+    let url = format!("{}secret.bin", server.url());
+    let resp = fetch!(b"PUT", &url).body(data.clone()).send()?;
+    assert_eq!(resp.status(), 201);
+
+    let stored = std::fs::read(server.path().join("secret.bin"))?;
+    assert_ne!(stored, data);
+    assert!(!stored.windows(64).any(|v| v == &data[4000..4064]));
+
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-length").unwrap(),
+        &data.len().to_string()
+    );
+    assert_eq!(resp.bytes()?.as_ref(), data.as_slice());
+
+    let resp = fetch!(b"GET", &url)
+        .header("range", "bytes=100000-300000")
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(
+        resp.headers().get("content-range").unwrap(),
+        "bytes 100000-300000/800000"
+    );
+    assert_eq!(resp.bytes()?.as_ref(), &data[100000..=300000]);
+
+    // Files stored before are served as they are
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(resp.text()?, "This is index.html");
+    Ok(())
+}
+
+#[rstest]
+fn encrypt_at_rest_checksums(tmpdir: TempDir) -> Result<(), Error> {
+    let server = encrypted_server(
+        &tmpdir,
+        &[
+            "--etag",
+            "strong",
+            "--listing-checksums",
+            "--block-size",
+            "300000",
+        ],
+    );
+    let data: Vec<u8> = (0..200_000u32).flat_map(|v| v.to_le_bytes()).collect();
+    let sha256 = |data: &[u8]| format!("{:x}", Sha256::digest(data));
+    let url = format!("{}dir1/secret.bin", server.url());
+    let resp = fetch!(b"PUT", &url).body(data.clone()).send()?;
+    assert_eq!(resp.status(), 201);
+
+    let resp = reqwest::blocking::get(format!("{url}?hash"))?;
+    assert_eq!(resp.text()?, sha256(&data));
+    let resp = reqwest::blocking::get(format!("{url}?checksum=sha256&range=bytes=100-199"))?;
+    assert_eq!(
+        resp.headers().get("x-checksum-range").unwrap(),
+        "bytes 100-199/800000"
+    );
+    assert_eq!(resp.text()?, sha256(&data[100..200]));
+
+    let resp = reqwest::blocking::get(&url)?;
+    let etag = format!(r#""{}""#, sha256(&data));
+    assert_eq!(resp.headers().get("etag").unwrap(), etag.as_str());
+
+    let manifest: Value =
+        serde_json::from_str(&reqwest::blocking::get(format!("{url}?manifest"))?.text()?)?;
+    assert_eq!(manifest["size"], 800000);
+    let blocks = manifest["blocks"].as_array().unwrap();
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(blocks[2]["offset"], 600000);
+    assert_eq!(blocks[2]["size"], 200000);
+    assert_eq!(blocks[2]["sha256"], sha256(&data[600000..]));
+
+    let resp = reqwest::blocking::get(format!("{}dir1/?checksums=sha256", server.url()))?;
+    let sums = resp.text()?;
+    assert!(
+        sums.contains(&format!("{}  secret.bin\n", sha256(&data))),
+        "{sums}"
+    );
+    Ok(())
+}
+
+#[rstest]
+fn encrypt_at_rest_sizes(tmpdir: TempDir) -> Result<(), Error> {
+    let server = encrypted_server(&tmpdir, &[]);
+    let data = "secret ".repeat(10000);
+    let url = format!("{}dir1/secret.txt", server.url());
+    let resp = fetch!(b"PUT", &url).body(data.clone()).send()?;
+    assert_eq!(resp.status(), 201);
+
+    let listing: Value = serde_json::from_str(
+        &reqwest::blocking::get(format!("{}dir1/?json", server.url()))?.text()?,
+    )?;
+    let item = listing["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|v| v["name"] == "secret.txt")
+        .unwrap();
+    assert_eq!(item["size"], data.len());
+
+    let resp = fetch!(b"PROPFIND", &url).send()?;
+    assert_eq!(resp.status(), 207);
+    let body = resp.text()?;
+    assert!(
+        body.contains(&format!(
+            "<D:getcontentlength>{}</D:getcontentlength>",
+            data.len()
+        )),
+        "{body}"
+    );
+
+    let resp = reqwest::blocking::get(format!("{}dir1?zip", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let zip = resp.bytes()?.to_vec();
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let contents = runtime.block_on(async {
+        let reader = ZipFileReader::new(zip).await?;
+        let index = reader
+            .file()
+            .entries()
+            .iter()
+            .position(|v| v.filename().as_str().ok() == Some("secret.txt"))
+            .unwrap();
+        let mut contents = String::new();
+        reader
+            .reader_with_entry(index)
+            .await?
+            .read_to_string_checked(&mut contents)
+            .await?;
+        Ok::<_, Error>(contents)
+    })?;
+    assert_eq!(contents, data);
+
+    let resp = reqwest::blocking::get(format!("{url}?follow"))?;
+    assert_eq!(resp.status(), 409);
+    Ok(())
+}
@@ -0,0 +1,89 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::time::{Duration, SystemTime};
+use xml::reader::{EventReader, XmlEvent};
+
+/// Parse a feed, returning the titles and links of its items in order.
+fn parse_feed(body: &str, item_tag: &str) -> Result<(Vec<String>, Vec<String>), Error> {
+    let (mut titles, mut links) = (vec![], vec![]);
+    let (mut in_item, mut current) = (false, String::new());
+    for event in EventReader::from_str(body) {
+        match event? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                if name.local_name == item_tag {
+                    in_item = true;
+                }
+                if in_item && name.local_name == "link" {
+                    if let Some(href) = attributes.iter().find(|v| v.name.local_name == "href") {
+                        links.push(href.value.clone());
+                    }
+                }
+                current = name.local_name;
+            }
+            XmlEvent::Characters(text) if in_item => match current.as_str() {
+                "title" => titles.push(text),
+                "link" => links.push(text),
+                _ => {}
+            },
+            XmlEvent::EndElement { name } if name.local_name == item_tag => in_item = false,
+            _ => {}
+        }
+    }
+    Ok((titles, links))
+}
+
+fn setup_files(server: &TestServer) -> Result<(), Error> {
+    let dir = server.path().join("releases");
+    std::fs::create_dir_all(dir.join("sub"))?;
+    let now = SystemTime::now();
+    for (name, age) in [("old.txt", 300), ("new & shiny.txt", 10), ("mid.txt", 100)] {
+        let path = dir.join(name);
+        std::fs::write(&path, name)?;
+        let mtime = filetime::FileTime::from_system_time(now - Duration::from_secs(age));
+        filetime::set_file_mtime(&path, mtime)?;
+    }
+    Ok(())
+}
+
+#[rstest]
+fn feed_atom(#[with(&["--hidden", ".*"])] server: TestServer) -> Result<(), Error> {
+    setup_files(&server)?;
+    std::fs::write(server.path().join("releases/.hidden"), b"hidden")?;
+    let resp = reqwest::blocking::get(format!("{}releases?feed=atom", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/atom+xml; charset=utf-8"
+    );
+    let body = resp.text()?;
+    let (titles, links) = parse_feed(&body, "entry")?;
+    assert_eq!(titles, ["new & shiny.txt", "mid.txt", "old.txt"]);
+    assert_eq!(
+        links[0],
+        format!("{}releases/new%20%26%20shiny.txt", server.url())
+    );
+    Ok(())
+}
+
+#[rstest]
+fn feed_rss(server: TestServer) -> Result<(), Error> {
+    setup_files(&server)?;
+    let resp = reqwest::blocking::get(format!("{}releases?feed=rss", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let (titles, links) = parse_feed(&resp.text()?, "item")?;
+    assert_eq!(titles, ["new & shiny.txt", "mid.txt", "old.txt"]);
+    assert_eq!(links[1], format!("{}releases/mid.txt", server.url()));
+    Ok(())
+}
+
+#[rstest]
+fn feed_invalid(server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}dir1?feed=json", server.url()))?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+}
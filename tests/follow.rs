@@ -0,0 +1,47 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+
+/// Read from the response until `len` bytes arrived.
+fn read_exact_bytes(resp: &mut impl Read, len: usize) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; len];
+    resp.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[rstest]
+fn follow_streams_appended_bytes(server: TestServer) -> Result<(), Error> {
+    let path = server.path().join("app.log");
+    std::fs::write(&path, "line 1\n")?;
+    let mut resp = reqwest::blocking::get(format!("{}app.log?follow=1", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
+    assert!(resp.headers().get("content-length").is_none());
+    assert_eq!(read_exact_bytes(&mut resp, 7)?, b"line 1\n");
+
+    let mut file = OpenOptions::new().append(true).open(&path)?;
+    file.write_all(b"line 2\n")?;
+    assert_eq!(read_exact_bytes(&mut resp, 7)?, b"line 2\n");
+
+    // Truncated by a log rotation, the new content is streamed from its start
+    std::fs::write(&path, "new\n")?;
+    assert_eq!(read_exact_bytes(&mut resp, 4)?, b"new\n");
+    Ok(())
+}
+
+#[rstest]
+fn follow_timeout(server: TestServer) -> Result<(), Error> {
+    let path = server.path().join("app.log");
+    std::fs::write(&path, "line 1\n")?;
+    let resp = reqwest::blocking::get(format!("{}app.log?follow=1&timeout=1", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "line 1\n");
+
+    let resp = reqwest::blocking::get(format!("{}app.log?follow=1&timeout=x", server.url()))?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+}
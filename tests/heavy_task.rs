@@ -0,0 +1,122 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::io::Read;
+use std::time::Duration;
+
+/// Start downloading an archive large enough to stall on the unread response, which keeps its
+/// heavy task running until the response is dropped.
+fn start_large_zip(server: &TestServer) -> Result<reqwest::blocking::Response, Error> {
+    let dir = server.path().join("large");
+    std::fs::create_dir_all(&dir)?;
+    // Incompressible, so that the archive is as large as the file.
+    let mut state = 0x2545f4914f6cdd1du64;
+    let data: Vec<u8> = (0..32 * 1024 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    std::fs::write(dir.join("data.bin"), data)?;
+    let mut resp = reqwest::blocking::get(format!("{}large?zip", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let mut buf = [0; 4];
+    resp.read_exact(&mut buf)?;
+    Ok(resp)
+}
+
+#[rstest]
+fn heavy_task_reject(
+    #[with(&["--allow-archive", "--heavy-task-concurrency", "1", "--heavy-task-policy", "reject"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let zip = start_large_zip(&server)?;
+    let resp = reqwest::blocking::get(format!("{}index.html?hash", server.url()))?;
+    assert_eq!(resp.status(), 503);
+    assert!(resp.headers().get("retry-after").is_some());
+
+    // Whole files are served meanwhile
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(resp.status(), 200);
+
+    drop(zip);
+    let mut status = 0;
+    for _ in 0..50 {
+        let resp = reqwest::blocking::get(format!("{}index.html?hash", server.url()))?;
+        status = resp.status().as_u16();
+        if status == 200 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(status, 200);
+    Ok(())
+}
+
+#[rstest]
+fn heavy_task_queue(
+    #[with(&["--allow-archive", "--heavy-task-concurrency", "1"])] server: TestServer,
+) -> Result<(), Error> {
+    let zip = start_large_zip(&server)?;
+    let url = server.url();
+    let handles: Vec<_> = (0..3)
+        .map(|_| {
+            let url = url.clone();
+            std::thread::spawn(move || {
+                reqwest::blocking::get(format!("{url}index.html?hash")).map(|v| v.status())
+            })
+        })
+        .collect();
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(handles.iter().all(|v| !v.is_finished()));
+
+    drop(zip);
+    for handle in handles {
+        assert_eq!(handle.join().unwrap()?, 200);
+    }
+    Ok(())
+}
+
+#[rstest]
+fn heavy_task_reject_hashing(
+    #[with(&[
+        "--allow-archive",
+        "--heavy-task-concurrency",
+        "1",
+        "--heavy-task-policy",
+        "reject",
+        "--listing-checksums",
+        "--etag",
+        "strong",
+    ])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let zip = start_large_zip(&server)?;
+    let resp = reqwest::blocking::get(format!("{}?checksums=sha256", server.url()))?;
+    assert_eq!(resp.status(), 503);
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    assert_eq!(resp.status(), 503);
+
+    // Files are still served, with the ETag they would get without --etag strong
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let etag = resp.headers().get("etag").unwrap().to_str()?.to_owned();
+    assert!(etag.contains('-'));
+
+    drop(zip);
+    let mut status = 0;
+    for _ in 0..50 {
+        let resp = reqwest::blocking::get(format!("{}?checksums=sha256", server.url()))?;
+        status = resp.status().as_u16();
+        if status == 200 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(status, 200);
+    Ok(())
+}
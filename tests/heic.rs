@@ -0,0 +1,45 @@
+#![cfg(feature = "heic")]
+
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+const SAMPLE_HEIC: &[u8] = include_bytes!("data/sample.heic");
+
+#[rstest]
+fn upload_heic_as_jpeg(#[with(&["-A", "--convert-heic"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}photo.heic", server.url()))
+        .body(SAMPLE_HEIC)
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(!server.path().join("photo.heic").exists());
+    let converted = image::open(server.path().join("photo.jpg"))?;
+    assert_eq!((converted.width(), converted.height()), (64, 64));
+    Ok(())
+}
+
+#[rstest]
+fn upload_heic_keep_original(
+    #[with(&["-A", "--convert-heic", "--keep-heic"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}photo.HEIF", server.url()))
+        .body(SAMPLE_HEIC)
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("photo.HEIF").exists());
+    image::open(server.path().join("photo.jpg"))?;
+    Ok(())
+}
+
+#[rstest]
+fn upload_invalid_heic(#[with(&["-A", "--convert-heic"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}broken.heic", server.url()))
+        .body(b"not a heic".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("broken.heic").exists());
+    assert!(!server.path().join("broken.jpg").exists());
+    Ok(())
+}
@@ -70,3 +70,196 @@ fn hidden_dir_only(
     assert_eq!(paths.len(), count);
     Ok(())
 }
+
+#[rstest]
+#[case(server(&["-A", "--hidden", ".*"] as &[&str]), 201)]
+#[case(server(&["-A", "--hidden", ".*", "--hidden-deny-write"]), 403)]
+fn hidden_deny_write_upload(#[case] server: TestServer, #[case] status: u16) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}.env", server.url()))
+        .body(b"SECRET=1".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), status);
+    let resp = fetch!(b"PUT", format!("{}.git/config2", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), status);
+    Ok(())
+}
+
+#[rstest]
+fn hidden_deny_write_create(
+    #[with(&["-A", "--hidden", ".*,secret/", "--hidden-deny-write"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"MKCOL", format!("{}secret", server.url())).send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"PUT", format!("{}secret", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    let resp = fetch!(b"MOVE", format!("{}index.html", server.url()))
+        .header("Destination", format!("{}.index.html", server.url()))
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"PUT", format!("{}dir1/file.txt", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&["--hidden", ".*"]), false)]
+#[case(server(&["--hidden", ".*", "--expose-well-known"]), true)]
+fn hidden_well_known(#[case] server: TestServer, #[case] listed: bool) -> Result<(), Error> {
+    let dir = server.path().join(".well-known/acme-challenge");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("token"), b"token.thumbprint")?;
+    let resp = reqwest::blocking::get(format!("{}.well-known/acme-challenge/token", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "token.thumbprint");
+
+    let resp = reqwest::blocking::get(server.url())?;
+    let paths = utils::retrieve_index_paths(&resp.text()?);
+    assert_eq!(paths.contains(".well-known/"), listed);
+    assert!(!paths.contains(".git/"));
+    Ok(())
+}
+
+#[rstest]
+fn deny_dotfiles(#[with(&["--deny-dotfiles"])] server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join(".env"), b"SECRET=1")?;
+    let dir = server.path().join(".well-known");
+    std::fs::create_dir_all(dir.join(".hidden"))?;
+    std::fs::write(dir.join("x"), b"x")?;
+    std::fs::write(dir.join(".hidden/x"), b"x")?;
+
+    let resp = reqwest::blocking::get(format!("{}.env", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    let resp = reqwest::blocking::get(format!("{}.git/index.html", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    let resp = reqwest::blocking::get(format!("{}.well-known/.hidden/x", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let resp = reqwest::blocking::get(format!("{}.well-known/x", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "x");
+
+    let resp = reqwest::blocking::get(server.url())?;
+    let paths = utils::retrieve_index_paths(&resp.text()?);
+    assert!(paths.contains("index.html"));
+    assert!(paths.contains(".well-known/"));
+    assert!(!paths.contains(".git/"));
+    assert!(!paths.contains(".env"));
+    Ok(())
+}
+
+#[rstest]
+fn deny_dotfiles_writes(
+    #[with(&["-A", "--deny-dotfiles"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join(".env"), b"SECRET=1")?;
+
+    let resp = fetch!(b"POST", server.url())
+        .body(r#"{"from":".env","to":"env.txt"}"#)
+        .send()?;
+    assert_eq!(resp.status(), 404);
+    let resp = fetch!(b"POST", format!("{}test.txt?rename=.env2", server.url())).send()?;
+    assert_eq!(resp.status(), 403);
+    for method in [b"MOVE".as_slice(), b"COPY"] {
+        let resp = reqwest::blocking::Client::new()
+            .request(
+                reqwest::Method::from_bytes(method)?,
+                format!("{}test.txt", server.url()),
+            )
+            .header("Destination", format!("{}.config/test.txt", server.url()))
+            .send()?;
+        assert_eq!(resp.status(), 403);
+    }
+    assert!(server.path().join(".env").exists());
+    assert!(!server.path().join("env.txt").exists());
+    assert!(!server.path().join(".env2").exists());
+    assert!(!server.path().join(".config").exists());
+    Ok(())
+}
+
+fn list(server: &TestServer, dir: &str) -> Result<Vec<String>, Error> {
+    let resp = reqwest::blocking::get(format!("{}{}", server.url(), dir))?;
+    assert_eq!(resp.status(), 200);
+    let mut paths: Vec<String> = utils::retrieve_index_paths(&resp.text()?)
+        .into_iter()
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+#[rstest]
+fn hidden_path_glob(
+    #[with(&["--hidden", "logs/**/*.tmp,**/node_modules/**"])] server: TestServer,
+) -> Result<(), Error> {
+    for path in [
+        "logs/a.tmp",
+        "logs/keep.txt",
+        "logs/x/b.tmp",
+        "other/logs/c.tmp",
+        "node_modules/m.js",
+        "dir1/node_modules/m.js",
+        "dir1/node_modules_b/m.js",
+    ] {
+        let path = server.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, b"x")?;
+    }
+    assert_eq!(list(&server, "logs/")?, ["keep.txt", "x/"]);
+    assert_eq!(list(&server, "logs/x/")?, [] as [&str; 0]);
+    assert_eq!(list(&server, "other/logs/")?, ["c.tmp"]);
+    assert!(!list(&server, "")?.contains(&"node_modules/".to_string()));
+    let paths = list(&server, "dir1/")?;
+    assert!(!paths.contains(&"node_modules/".to_string()));
+    assert!(paths.contains(&"node_modules_b/".to_string()));
+    Ok(())
+}
+
+#[rstest]
+fn hidden_path_glob_search(
+    #[with(&["--allow-search", "--hidden", "dir1/*.html"])] server: TestServer,
+) -> Result<(), Error> {
+    assert!(!list(&server, "dir1/")?.contains(&"index.html".to_string()));
+    assert!(list(&server, "dir2/")?.contains(&"index.html".to_string()));
+    assert!(list(&server, "")?.contains(&"index.html".to_string()));
+
+    let resp = reqwest::blocking::get(format!("{}?q=index.html", server.url()))?;
+    let paths = utils::retrieve_index_paths(&resp.text()?);
+    assert!(!paths.contains("dir1/index.html"));
+    assert!(paths.contains("dir2/index.html"));
+    Ok(())
+}
+
+#[rstest]
+fn serve_hidden_with_auth(
+    #[with(&["--auth", "user:pass@/:rw", "--auth", "@/", "--hidden", ".git,index.html", "--serve-hidden-with-auth"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let paths = list(&server, "")?;
+    assert!(paths.contains(&"dir1/".to_string()));
+    assert!(!paths.contains(&"index.html".to_string()));
+    assert!(!paths.contains(&".git/".to_string()));
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    let resp = reqwest::blocking::get(format!("{}.git/index.html", server.url()))?;
+    assert_eq!(resp.status(), 404);
+
+    let resp = fetch!(b"GET", server.url())
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    let paths = utils::retrieve_index_paths(&resp.text()?);
+    assert!(paths.contains("index.html"));
+    assert!(paths.contains(".git/"));
+    let resp = fetch!(b"GET", format!("{}index.html", server.url()))
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "This is index.html");
+    Ok(())
+}
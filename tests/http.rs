@@ -1,9 +1,12 @@
 mod fixtures;
 mod utils;
 
-use fixtures::{server, Error, TestServer, BIN_FILE};
+use fixtures::{server, Error, TestServer, BIN_FILE, DIR_ASSETS};
 use rstest::rstest;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::net::TcpStream;
 use utils::retrieve_edit_file;
 
 #[rstest]
@@ -65,7 +68,267 @@ fn get_dir_json(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
         "application/json"
     );
     let json: Value = serde_json::from_str(&resp.text().unwrap()).unwrap();
-    assert!(json["paths"].as_array().is_some());
+    assert!(json["entries"].as_array().is_some());
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_json_envelope(server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}dir1/?json&limit=1", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    assert_eq!(json["kind"], "listing");
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["path"], "/dir1");
+    assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+    assert_eq!(json["page"]["limit"], 1);
+    assert_eq!(json["page"]["truncated"], true);
+    assert!(json["page"]["next_cursor"].is_string());
+    assert!(json.get("paths").is_none());
+
+    let resp = reqwest::blocking::get(format!("{}dir1/?json&limit=1&legacy=1", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    assert_eq!(json["kind"], "Index");
+    assert_eq!(json["href"], "/dir1");
+    assert_eq!(json["paths"].as_array().unwrap().len(), 1);
+    assert_eq!(json["truncated"], true);
+    assert!(json.get("entries").is_none());
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_json_columns(
+    #[with(&["--listing-columns", "name,mtime,mode"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    assert_eq!(
+        json["columns"],
+        serde_json::json!(["name", "mtime", "mode"])
+    );
+    let paths = json["entries"].as_array().unwrap();
+    assert!(!paths.is_empty());
+    for item in paths {
+        assert!(item.get("size").is_none());
+        assert!(item["mtime"].is_u64());
+        assert!(item["mode"].as_u64().unwrap() <= 0o7777);
+    }
+
+    let resp = reqwest::blocking::get(server.url())?;
+    assert_resp_paths!(resp);
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_json_default_columns(server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    assert!(json.get("columns").is_none());
+    let item = &json["entries"][0];
+    assert!(item["size"].is_u64());
+    assert!(item.get("mode").is_none());
+    Ok(())
+}
+
+#[rstest]
+#[case("*.pdf", &["a.pdf", "c.pdf"])]
+#[case("*.pdf,*.jpg", &["a.pdf", "b.jpg", "c.pdf"])]
+#[case("*.pdf,,img*", &["a.pdf", "c.pdf", "img/"])]
+fn get_dir_filter(
+    #[case] filter: &str,
+    #[case] expected: &[&str],
+    server: TestServer,
+) -> Result<(), Error> {
+    let dir = server.path().join("mixed");
+    std::fs::create_dir_all(dir.join("img"))?;
+    for name in ["a.pdf", "b.jpg", "c.pdf", "d.txt"] {
+        std::fs::write(dir.join(name), name)?;
+    }
+    let resp = reqwest::blocking::get(format!("{}mixed/?filter={filter}", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let paths = utils::retrieve_index_paths(&resp.text()?);
+    let mut paths: Vec<String> = paths.into_iter().collect();
+    paths.sort();
+    assert_eq!(paths, expected);
+
+    let resp = reqwest::blocking::get(format!("{}mixed/?json&filter={filter}", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    let mut names: Vec<&str> = json["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["name"].as_str().unwrap())
+        .collect();
+    names.sort();
+    let expected: Vec<&str> = expected.iter().map(|v| v.trim_end_matches('/')).collect();
+    assert_eq!(names, expected);
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_ndjson(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    let count = json["entries"].as_array().unwrap().len();
+
+    let resp = reqwest::blocking::get(format!("{}?ndjson", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/x-ndjson"
+    );
+    let text = resp.text()?;
+    let mut lines = 0;
+    for line in text.lines() {
+        let item: Value = serde_json::from_str(line)?;
+        assert!(item["name"].as_str().is_some());
+        lines += 1;
+    }
+    assert_eq!(lines, count);
+
+    let resp = fetch!(b"GET", server.url())
+        .header("accept", "application/x-ndjson")
+        .send()?;
+    assert_eq!(resp.text()?.lines().count(), count);
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_time_zone(
+    #[with(&["--time-zone", "+08:00", "--time-format", "%Y-%m-%d %H:%M:%S %z"])] server: TestServer,
+) -> Result<(), Error> {
+    let file = std::fs::File::create(server.path().join("stamp.txt"))?;
+    file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000))?;
+    let find_stamp = |json: &Value| {
+        json["paths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|v| v["name"] == "stamp.txt")
+            .cloned()
+            .unwrap()
+    };
+
+    let resp = reqwest::blocking::get(server.url())?;
+    let json = utils::retrieve_json(&resp.text()?).unwrap();
+    let stamp = find_stamp(&json);
+    assert_eq!(stamp["mtime_display"], "2023-11-15 06:13:20 +0800");
+
+    let resp = reqwest::blocking::get(format!("{}?json&legacy=1", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    let stamp = find_stamp(&json);
+    assert_eq!(stamp["mtime"], 1_700_000_000_000u64);
+    assert!(stamp.get("mtime_display").is_none());
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_listing_limit(
+    #[with(&["-A", "--listing-limit", "3"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    let paths = json["entries"].as_array().unwrap();
+    assert_eq!(paths.len(), 3);
+    assert!(paths.iter().all(|v| v["path_type"] == "Dir"));
+    assert_eq!(json["page"]["truncated"], true);
+
+    let resp = reqwest::blocking::get(format!("{}?json&sort=name&order=desc", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    let paths = json["entries"].as_array().unwrap();
+    assert_eq!(paths.len(), 3);
+    assert!(paths.iter().all(|v| v["path_type"] == "File"));
+
+    let resp = reqwest::blocking::get(format!("{}{}?json", server.url(), DIR_ASSETS))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+    assert_eq!(json["page"]["truncated"], false);
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_listing_preview(
+    #[with(&["--listing-preview-bytes", "7"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    let paths = json["entries"].as_array().unwrap();
+    let find = |name: &str| paths.iter().find(|v| v["name"] == name).unwrap();
+    assert_eq!(find("index.html")["preview"], "This is");
+    assert!(find(BIN_FILE).get("preview").is_none());
+    assert!(find("dir1").get("preview").is_none());
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_listing_checksums(
+    #[with(&["--listing-checksums"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(
+        server.path().join("large.bin"),
+        vec![0u8; 16 * 1024 * 1024 + 1],
+    )?;
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    let paths = json["entries"].as_array().unwrap();
+    let find = |name: &str| paths.iter().find(|v| v["name"] == name).unwrap();
+    let expect = format!("{:x}", Sha256::digest(b"This is index.html"));
+    assert_eq!(find("index.html")["sha256"], expect);
+    assert!(find("large.bin")["sha256"].is_null());
+    assert!(find("large.bin").get("sha256").is_some());
+    assert!(find("dir1").get("sha256").is_none());
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_banner(#[with(&["--banner", "Be <nice>"])] server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url())?;
+    let json = utils::retrieve_json(&resp.text()?).unwrap();
+    assert_eq!(json["banner"], "Be <nice>");
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    assert_eq!(json["banner"], "Be <nice>");
+    let resp = reqwest::blocking::get(format!("{}dir1/?json", server.url()))?;
+    let json: Value = serde_json::from_str(&resp.text()?)?;
+    assert!(json.get("banner").is_none());
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_pagination_cursor(server: TestServer) -> Result<(), Error> {
+    let dir = server.path().join("pages");
+    std::fs::create_dir_all(&dir)?;
+    for name in ["a", "c", "e", "g", "i"] {
+        std::fs::write(dir.join(name), name)?;
+    }
+    let mut seen = vec![];
+    let mut after: Option<String> = None;
+    for page in 0.. {
+        let mut url = format!("{}pages?json&limit=2", server.url());
+        if let Some(after) = after.as_ref() {
+            url.push_str(&format!("&after={after}"));
+        }
+        let resp = reqwest::blocking::get(url)?;
+        assert_eq!(resp.status(), 200);
+        let json: Value = serde_json::from_str(&resp.text()?)?;
+        for item in json["entries"].as_array().unwrap() {
+            seen.push(item["name"].as_str().unwrap().to_string());
+        }
+        if page == 0 {
+            // One entry lands before the cursor and one after it
+            std::fs::write(dir.join("b"), "b")?;
+            std::fs::write(dir.join("f"), "f")?;
+        }
+        match json["page"]["next_cursor"].as_str() {
+            Some(cursor) => after = Some(cursor.to_string()),
+            None => break,
+        }
+    }
+    assert_eq!(seen, ["a", "c", "e", "f", "g", "i"]);
+
+    let resp = reqwest::blocking::get(format!("{}pages?json&after=%%%", server.url()))?;
+    assert_eq!(resp.status(), 400);
     Ok(())
 }
 
@@ -137,6 +400,52 @@ fn get_dir_search4(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn get_dir_search_regex(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!(
+        "{}?q={}&regex=1&simple",
+        server.url(),
+        urlencoding::encode(r"^test\.(txt|html)$")
+    ))?;
+    assert_eq!(resp.status(), 200);
+    let text = resp.text()?;
+    let names: Vec<&str> = text.lines().collect();
+    assert!(names.contains(&"test.txt"));
+    assert!(names.contains(&"test.html"));
+    assert!(names.iter().all(|v| {
+        let name = v.rsplit('/').next().unwrap();
+        name == "test.txt" || name == "test.html"
+    }));
+
+    // Without the flag the query is a plain substring
+    let resp = reqwest::blocking::get(format!("{}?q={}&simple", server.url(), "^test"))?;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.text()?.is_empty());
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_search_regex_hidden(
+    #[with(&["-A", "--hidden", "test.html"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?q=html$&regex=1&simple", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    let text = resp.text()?;
+    assert!(text.lines().any(|v| v == "index.html"));
+    assert!(!text.lines().any(|v| v.ends_with("test.html")));
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_search_regex_invalid(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}?q=test(&regex=1", server.url()))?;
+    assert_eq!(resp.status(), 400);
+    let resp =
+        reqwest::blocking::get(format!("{}?q=a{{100}}{{100}}{{100}}&regex=1", server.url()))?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+}
+
 #[rstest]
 fn head_dir_search(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     let resp = fetch!(b"HEAD", format!("{}?q={}", server.url(), "test.html")).send()?;
@@ -189,6 +498,271 @@ fn head_file(server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn get_file_gzip(#[with(&["--gzip"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"GET", &url)
+        .header("accept-encoding", "gzip, br")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-encoding").unwrap(), "gzip");
+    assert_eq!(resp.headers().get("vary").unwrap(), "Accept-Encoding");
+    assert_eq!(resp.headers().get("accept-ranges").unwrap(), "none");
+    assert!(!resp.headers().contains_key("content-length"));
+    let etag = resp.headers().get("etag").unwrap().clone();
+    assert!(resp.bytes()?.starts_with(&[0x1f, 0x8b]));
+
+    let resp = fetch!(b"GET", &url)
+        .header("accept-encoding", "gzip")
+        .header("if-none-match", etag)
+        .send()?;
+    assert_eq!(resp.status(), 304);
+    assert_eq!(resp.headers().get("vary").unwrap(), "Accept-Encoding");
+
+    let resp = fetch!(b"GET", &url).send()?;
+    assert_eq!(resp.status(), 200);
+    assert!(!resp.headers().contains_key("content-encoding"));
+    assert_eq!(resp.headers().get("vary").unwrap(), "Accept-Encoding");
+    assert_eq!(resp.headers().get("accept-ranges").unwrap(), "bytes");
+
+    let resp = fetch!(b"GET", &url)
+        .header("accept-encoding", "gzip")
+        .header("range", "bytes=0-1")
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert!(!resp.headers().contains_key("content-encoding"));
+    assert_eq!(resp.text()?, "Th");
+    Ok(())
+}
+
+#[rstest]
+fn get_binary_file_not_gzipped(#[with(&["--gzip"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}{BIN_FILE}", server.url()))
+        .header("accept-encoding", "gzip")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert!(!resp.headers().contains_key("content-encoding"));
+    assert!(!resp.headers().contains_key("vary"));
+    assert_eq!(resp.headers().get("accept-ranges").unwrap(), "bytes");
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_gzip(
+    #[with(&["--listing-compress-threshold", "1k"])] server: TestServer,
+) -> Result<(), Error> {
+    for i in 0..100 {
+        std::fs::write(server.path().join(format!("file{i}.txt")), "")?;
+    }
+    let url = format!("{}?json", server.url());
+    let plain = fetch!(b"GET", &url).send()?;
+    assert_eq!(plain.status(), 200);
+    assert!(!plain.headers().contains_key("content-encoding"));
+    assert_eq!(plain.headers().get("vary").unwrap(), "Accept-Encoding");
+    let plain = plain.text()?;
+
+    let resp = fetch!(b"GET", &url)
+        .header("accept-encoding", "gzip")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-encoding").unwrap(), "gzip");
+    assert_eq!(resp.headers().get("vary").unwrap(), "Accept-Encoding");
+    let compressed = resp.bytes()?;
+    assert!(compressed.len() < plain.len());
+    let mut text = String::new();
+    std::io::Read::read_to_string(
+        &mut flate2::read::GzDecoder::new(&compressed[..]),
+        &mut text,
+    )?;
+    assert_eq!(text, plain);
+
+    let resp = fetch!(b"GET", server.url())
+        .header("accept-encoding", "gzip")
+        .send()?;
+    assert_eq!(resp.headers().get("content-encoding").unwrap(), "gzip");
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_small_not_gzipped(
+    #[with(&["--listing-compress-threshold", "1M"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}?json", server.url()))
+        .header("accept-encoding", "gzip")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert!(!resp.headers().contains_key("content-encoding"));
+    assert_eq!(resp.headers().get("vary").unwrap(), "Accept-Encoding");
+    Ok(())
+}
+
+#[rstest]
+fn get_file_max_header_size(
+    #[with(&["--max-header-size", "16K"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"GET", &url)
+        .header("cookie", "a".repeat(4096))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    let resp = fetch!(b"GET", &url)
+        .header("cookie", "a".repeat(32768))
+        .send()?;
+    assert_eq!(resp.status(), 431);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_max_request_line_length(
+    #[with(&["--max-request-line-length", "100"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(
+        b"GET",
+        format!("{}index.html?{}", server.url(), "a".repeat(80))
+    )
+    .send()?;
+    assert_eq!(resp.status(), 200);
+    let resp = fetch!(
+        b"GET",
+        format!("{}index.html?{}", server.url(), "a".repeat(100))
+    )
+    .send()?;
+    assert_eq!(resp.status(), 414);
+    let resp = fetch!(b"GET", format!("{}{}", server.url(), "a".repeat(100))).send()?;
+    assert_eq!(resp.status(), 414);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_zero_copy(#[with(&["--zero-copy"])] server: TestServer) -> Result<(), Error> {
+    let data: Vec<u8> = (0..200_003u32).map(|v| (v % 251) as u8).collect();
+    std::fs::write(server.path().join("big.bin"), &data)?;
+    let resp = reqwest::blocking::get(format!("{}big.bin", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-length").unwrap(),
+        &data.len().to_string()
+    );
+    assert_eq!(resp.bytes()?.as_ref(), data.as_slice());
+
+    std::fs::write(server.path().join("empty.bin"), b"")?;
+    let resp = reqwest::blocking::get(format!("{}empty.bin", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.bytes()?.is_empty());
+
+    let resp = fetch!(b"GET", format!("{}big.bin", server.url()))
+        .header("range", "bytes=100000-100009")
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(resp.bytes()?.as_ref(), &data[100000..100010]);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_coalesce_downloads(
+    #[with(&["--coalesce-downloads"])] server: TestServer,
+) -> Result<(), Error> {
+    let data: Vec<u8> = (0..500_003u32).map(|v| (v % 251) as u8).collect();
+    std::fs::write(server.path().join("big.bin"), &data)?;
+    let url = format!("{}big.bin", server.url());
+    let bodies = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| scope.spawn(|| reqwest::blocking::get(&url).and_then(|v| v.bytes())))
+            .collect();
+        handles
+            .into_iter()
+            .map(|v| v.join().unwrap())
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    assert!(bodies.iter().all(|v| v.as_ref() == data.as_slice()));
+
+    let resp = fetch!(b"GET", &url)
+        .header("range", "bytes=100000-100009")
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(resp.bytes()?.as_ref(), &data[100000..100010]);
+    Ok(())
+}
+
+#[rstest]
+fn get_dir_checksums(#[with(&["--hidden", "*.tmp"])] server: TestServer) -> Result<(), Error> {
+    let dir = server.path().join("sums");
+    std::fs::create_dir_all(dir.join("sub"))?;
+    std::fs::write(dir.join("b.tar.gz"), b"release b")?;
+    std::fs::write(dir.join("a.zip"), b"release a")?;
+    std::fs::write(dir.join(".hidden"), b"hidden")?;
+    std::fs::write(dir.join("partial.tmp"), b"partial")?;
+    let url = format!("{}sums?checksums=sha256", server.url());
+    let expect = format!(
+        "{:x}  a.zip\n{:x}  b.tar.gz\n",
+        Sha256::digest(b"release a"),
+        Sha256::digest(b"release b")
+    );
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, expect);
+    // Served again from the digest cache
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.text()?, expect);
+
+    let resp = reqwest::blocking::get(format!("{}sums?checksums=md5", server.url()))?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+}
+
+#[rstest]
+fn hash_file_range(server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!(
+        "{}index.html?checksum=sha256&range=bytes=5-10",
+        server.url()
+    ))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("x-checksum-range").unwrap(),
+        "bytes 5-10/18"
+    );
+    let expect = format!("{:x}", Sha256::digest(&b"This is index.html"[5..=10]));
+    assert_eq!(resp.text()?, expect);
+
+    let resp = reqwest::blocking::get(format!("{}index.html?hash&range=bytes=18-", server.url()))?;
+    assert_eq!(resp.status(), 416);
+    assert_eq!(resp.headers().get("content-range").unwrap(), "bytes */18");
+
+    let resp = reqwest::blocking::get(format!("{}index.html?checksum=md5", server.url()))?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_manifest(#[with(&["--block-size", "5"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = reqwest::blocking::get(format!("{url}?manifest=1"))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    let manifest: Value = serde_json::from_str(&resp.text()?)?;
+    assert_eq!(manifest["size"], 18);
+    assert_eq!(manifest["block_size"], 5);
+    let blocks = manifest["blocks"].as_array().unwrap();
+    assert_eq!(blocks.len(), 4);
+    assert_eq!(blocks[3]["size"], 3);
+    let client = reqwest::blocking::Client::new();
+    for block in blocks {
+        let start = block["offset"].as_u64().unwrap();
+        let end = start + block["size"].as_u64().unwrap() - 1;
+        let resp = client
+            .get(&url)
+            .header("range", format!("bytes={start}-{end}"))
+            .send()?;
+        assert_eq!(resp.status(), 206);
+        let expect = format!("{:x}", Sha256::digest(resp.bytes()?));
+        assert_eq!(block["sha256"], expect);
+    }
+    Ok(())
+}
+
 #[rstest]
 fn hash_file(server: TestServer) -> Result<(), Error> {
     let resp = reqwest::blocking::get(format!("{}index.html?hash", server.url()))?;
@@ -204,6 +778,46 @@ fn hash_file(server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn hash_file_not_modified(server: TestServer) -> Result<(), Error> {
+    let path = server.path().join("polled.txt");
+    std::fs::write(&path, "abc")?;
+    let url = format!("{}polled.txt?hash", server.url());
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    let etag = resp.headers().get("etag").unwrap().clone();
+    let last_modified = resp.headers().get("last-modified").unwrap().clone();
+    assert_eq!(resp.text()?, format!("{:x}", Sha256::digest(b"abc")));
+
+    // The digest of a range has validators of its own
+    let resp = fetch!(b"GET", format!("{url}&range=bytes=0-1"))
+        .header("if-none-match", &etag)
+        .send()?;
+    assert_eq!(resp.status(), 200);
+
+    let resp = fetch!(b"GET", &url).header("if-none-match", &etag).send()?;
+    assert_eq!(resp.status(), 304);
+    let resp = fetch!(b"GET", &url)
+        .header("if-modified-since", &last_modified)
+        .send()?;
+    assert_eq!(resp.status(), 304);
+
+    std::fs::write(&path, "abcd")?;
+    std::fs::File::options()
+        .write(true)
+        .open(&path)?
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))?;
+    let resp = fetch!(b"GET", &url).header("if-none-match", &etag).send()?;
+    assert_eq!(resp.status(), 200);
+    assert_ne!(resp.headers().get("etag").unwrap(), &etag);
+    assert_eq!(resp.text()?, format!("{:x}", Sha256::digest(b"abcd")));
+    let resp = fetch!(b"GET", &url)
+        .header("if-modified-since", &last_modified)
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
+
 #[rstest]
 fn get_file_404(server: TestServer) -> Result<(), Error> {
     let resp = reqwest::blocking::get(format!("{}404", server.url()))?;
@@ -222,6 +836,51 @@ fn get_file_emoji_path(server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn get_file_disposition_policy(server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join("doc.pdf"), b"%PDF-1.4")?;
+    std::fs::write(server.path().join("pkg.zip"), b"PK")?;
+    let disposition = |name: &str| -> Result<String, Error> {
+        let resp = reqwest::blocking::get(format!("{}{name}", server.url()))?;
+        assert_eq!(resp.status(), 200);
+        Ok(resp.headers()["content-disposition"].to_str()?.to_string())
+    };
+    assert_eq!(disposition("doc.pdf")?, "inline; filename=\"doc.pdf\"");
+    assert_eq!(disposition("pkg.zip")?, "attachment; filename=\"pkg.zip\"");
+    assert_eq!(
+        disposition("pkg.zip?inline")?,
+        "inline; filename=\"pkg.zip\""
+    );
+    assert_eq!(
+        disposition("doc.pdf?download")?,
+        "attachment; filename=\"doc.pdf\""
+    );
+    Ok(())
+}
+
+#[rstest]
+fn get_file_disposition_custom_types(
+    #[with(&["--attachment-types", "application/*", "--inline-types", "application/pdf"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("doc.pdf"), b"%PDF-1.4")?;
+    let resp = reqwest::blocking::get(format!("{}doc.pdf", server.url()))?;
+    assert_eq!(
+        resp.headers()["content-disposition"],
+        "inline; filename=\"doc.pdf\""
+    );
+    let resp = reqwest::blocking::get(format!("{}index.html", server.url()))?;
+    assert_eq!(
+        resp.headers()["content-disposition"],
+        "inline; filename=\"index.html\""
+    );
+    let resp = reqwest::blocking::get(format!("{}{BIN_FILE}", server.url()))?;
+    assert!(resp.headers()["content-disposition"]
+        .as_bytes()
+        .starts_with(b"attachment;"));
+    Ok(())
+}
+
 #[cfg(not(target_os = "windows"))]
 #[rstest]
 fn get_file_newline_path(server: TestServer) -> Result<(), Error> {
@@ -252,6 +911,28 @@ fn get_file_edit_bin(server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn get_file_view_opengraph(server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join("pic.png"), b"")?;
+    let resp = fetch!(b"GET", format!("{}pic.png?view", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    let body = resp.text()?;
+    assert!(body.contains(r#"<meta property="og:title" content="pic.png" />"#));
+    let expect = format!(
+        r#"<meta property="og:image" content="{}pic.png" />"#,
+        server.url()
+    );
+    assert!(body.contains(&expect));
+
+    let resp = fetch!(b"GET", format!("{}index.html?view", server.url())).send()?;
+    assert!(!resp.text()?.contains("og:image"));
+    let resp = fetch!(b"GET", format!("{}dir1/", server.url())).send()?;
+    assert!(resp
+        .text()?
+        .contains(r#"<meta property="og:title" content="dir1" />"#));
+    Ok(())
+}
+
 #[rstest]
 fn head_file_404(server: TestServer) -> Result<(), Error> {
     let resp = fetch!(b"HEAD", format!("{}404", server.url())).send()?;
@@ -260,14 +941,69 @@ fn head_file_404(server: TestServer) -> Result<(), Error> {
 }
 
 #[rstest]
-fn options_dir(server: TestServer) -> Result<(), Error> {
+fn options_dir(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     let resp = fetch!(b"OPTIONS", format!("{}index.html", server.url())).send()?;
     assert_eq!(resp.status(), 200);
     assert_eq!(
         resp.headers().get("allow").unwrap(),
-        "GET,HEAD,PUT,OPTIONS,DELETE,PATCH,PROPFIND,COPY,MOVE,CHECKAUTH,LOGOUT"
+        "GET,HEAD,PUT,OPTIONS,DELETE,PATCH,POST,PROPFIND,PROPPATCH,COPY,MOVE,LOCK,UNLOCK,CHECKAUTH,LOGOUT"
     );
     assert_eq!(resp.headers().get("dav").unwrap(), "1, 2, 3");
+    let resp = fetch!(b"OPTIONS", format!("{}file-missing", server.url())).send()?;
+    assert_eq!(
+        resp.headers().get("allow").unwrap(),
+        "PUT,OPTIONS,POST,MKCOL,CHECKAUTH,LOGOUT"
+    );
+    Ok(())
+}
+
+#[rstest]
+fn options_readonly(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"OPTIONS", format!("{}index.html", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("allow").unwrap(),
+        "GET,HEAD,OPTIONS,PROPFIND,CHECKAUTH,LOGOUT"
+    );
+    assert_eq!(resp.headers().get("dav").unwrap(), "1, 3");
+    let resp = fetch!(b"OPTIONS", format!("{}file-missing", server.url())).send()?;
+    assert_eq!(
+        resp.headers().get("allow").unwrap(),
+        "OPTIONS,CHECKAUTH,LOGOUT"
+    );
+    Ok(())
+}
+
+#[rstest]
+fn options_resource(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"OPTIONS", format!("{}dir1/", server.url())).send()?;
+    assert_eq!(
+        resp.headers().get("allow").unwrap(),
+        "GET,HEAD,OPTIONS,DELETE,POST,PROPFIND,COPY,MOVE,CHECKAUTH,LOGOUT"
+    );
+    let resp = fetch!(b"OPTIONS", format!("{}file-missing", server.url())).send()?;
+    assert_eq!(
+        resp.headers().get("allow").unwrap(),
+        "PUT,OPTIONS,POST,MKCOL,CHECKAUTH,LOGOUT"
+    );
+    Ok(())
+}
+
+#[rstest]
+fn options_server(#[with(&["--allow-upload"])] server: TestServer) -> Result<(), Error> {
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port()))?;
+    write!(
+        stream,
+        "OPTIONS * HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp)?;
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert!(
+        resp.contains("allow: GET,HEAD,PUT,OPTIONS,PATCH,POST,PROPFIND,PROPPATCH,MKCOL,COPY,LOCK,UNLOCK,CHECKAUTH,LOGOUT\r\n"),
+        "{resp}"
+    );
+    assert!(resp.contains("dav: 1, 2, 3\r\n"), "{resp}");
     Ok(())
 }
 
@@ -281,6 +1017,189 @@ fn put_file(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+#[case("x-oc-mtime")]
+#[case("x-dufs-mtime")]
+fn put_file_mtime(#[with(&["-A"])] server: TestServer, #[case] header: &str) -> Result<(), Error> {
+    let url = format!("{}file1", server.url());
+    let resp = fetch!(b"PUT", &url)
+        .header(header, "1600000000")
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.headers().get("x-oc-mtime").unwrap(), "accepted");
+    let mtime = std::fs::metadata(server.path().join("file1"))?.modified()?;
+    assert_eq!(
+        mtime,
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000)
+    );
+    Ok(())
+}
+
+#[rstest]
+fn put_file_if_none_match(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}file1", server.url());
+    let resp = fetch!(b"PUT", &url)
+        .header("if-none-match", "*")
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    let resp = fetch!(b"PUT", &url)
+        .header("if-none-match", "*")
+        .body(b"def".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 412);
+    assert_eq!(std::fs::read_to_string(server.path().join("file1"))?, "abc");
+    assert!(!server.path().join("file1.dufsupload").exists());
+    Ok(())
+}
+
+#[rstest]
+fn put_file_return_minimal(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}file1", server.url());
+    let resp = fetch!(b"PUT", &url)
+        .header("prefer", "return=minimal")
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    assert_eq!(
+        resp.headers().get("preference-applied").unwrap(),
+        "return=minimal"
+    );
+    assert!(resp.headers().contains_key("location"));
+    assert_eq!(resp.text()?, "");
+    assert_eq!(std::fs::read_to_string(server.path().join("file1"))?, "abc");
+
+    let resp = fetch!(b"MKCOL", format!("{}newdir", server.url()))
+        .header("prefer", "return=minimal")
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(resp.headers().contains_key("preference-applied"));
+
+    let resp = fetch!(b"PUT", &url).body(b"def".to_vec()).send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(!resp.headers().contains_key("preference-applied"));
+    Ok(())
+}
+
+#[rstest]
+fn put_file_if_match(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"PUT", &url)
+        .header("if-match", r#""0-0""#)
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 412);
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("index.html"))?,
+        "This is index.html"
+    );
+    let etag = reqwest::blocking::get(&url)?
+        .headers()
+        .get("etag")
+        .unwrap()
+        .clone();
+    let resp = fetch!(b"PUT", &url)
+        .header("if-match", etag)
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("index.html"))?,
+        "abc"
+    );
+    let resp = fetch!(b"PUT", format!("{}missing", server.url()))
+        .header("if-match", "*")
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 412);
+    Ok(())
+}
+
+#[rstest]
+fn put_file_location(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}dir1/file 1", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.headers().get("location").unwrap(), "/dir1/file%201");
+    let resp = fetch!(b"POST", format!("{}dir1/file 1?rename=file2", server.url())).send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.headers().get("location").unwrap(), "/dir1/file2");
+    let resp = fetch!(b"POST", format!("{}dir1/sub?mkdir", server.url())).send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.headers().get("location").unwrap(), "/dir1/sub/");
+    Ok(())
+}
+
+#[rstest]
+fn put_file_ingest(
+    #[with(&["-A", "--ingest", "/ingest/"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}ingest/sensor.log", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    let date = chrono::Utc::now().format("%Y/%m/%d").to_string();
+    assert_eq!(
+        resp.headers().get("location").unwrap(),
+        &format!("/ingest/{date}/sensor.log")
+    );
+    let path = server.path().join("ingest").join(&date).join("sensor.log");
+    assert_eq!(std::fs::read(path)?, b"abc");
+
+    // Only files PUT inside of it are routed
+    let resp = fetch!(b"PUT", format!("{}ingested.log", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.headers().get("location").unwrap(), "/ingested.log");
+    Ok(())
+}
+
+#[rstest]
+fn put_file_ingest_layout(
+    #[with(&["-A", "--ingest", "ingest", "--ingest-layout", "%Y-%m"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}ingest/sub/sensor.log", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    let month = chrono::Utc::now().format("%Y-%m").to_string();
+    assert_eq!(
+        resp.headers().get("location").unwrap(),
+        &format!("/ingest/{month}/sub/sensor.log")
+    );
+    Ok(())
+}
+
+#[rstest]
+fn put_file_no_overwrite(
+    #[with(&["--allow-upload", "--no-overwrite"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"PUT", &url).body(b"abc".to_vec()).send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(
+        resp.headers().get("location").unwrap(),
+        "/index%20%281%29.html"
+    );
+    let resp = fetch!(b"PUT", &url).body(b"def".to_vec()).send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(
+        resp.headers().get("location").unwrap(),
+        "/index%20%282%29.html"
+    );
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("index.html"))?,
+        "This is index.html"
+    );
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("index (2).html"))?,
+        "def"
+    );
+    Ok(())
+}
+
 #[rstest]
 fn put_file_create_dir(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     let url = format!("{}xyz/file1", server.url());
@@ -309,6 +1228,53 @@ fn delete_file(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn delete_empty_dir(#[with(&["--allow-delete"])] server: TestServer) -> Result<(), Error> {
+    std::fs::create_dir(server.path().join("empty"))?;
+    let resp = fetch!(b"DELETE", format!("{}empty", server.url())).send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(!server.path().join("empty").exists());
+    Ok(())
+}
+
+#[rstest]
+fn delete_dir_not_empty(#[with(&["--allow-delete"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"DELETE", format!("{}dir1?recursive=0", server.url())).send()?;
+    assert_eq!(resp.status(), 409);
+    let resp = fetch!(b"DELETE", format!("{}dir1", server.url()))
+        .header("Depth", "0")
+        .send()?;
+    assert_eq!(resp.status(), 409);
+    assert!(server.path().join("dir1/index.html").exists());
+    let resp = fetch!(b"DELETE", format!("{}dir1", server.url())).send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(!server.path().join("dir1").exists());
+    let resp = fetch!(b"DELETE", format!("{}dir2?recursive=1", server.url())).send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(!server.path().join("dir2").exists());
+    let resp = fetch!(b"DELETE", format!("{}dir3", server.url()))
+        .header("Depth", "infinity")
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(!server.path().join("dir3").exists());
+    Ok(())
+}
+
+#[rstest]
+fn delete_dir_recursive_not_allowed(
+    #[with(&["-A", "--no-recursive-delete"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"DELETE", format!("{}dir1", server.url())).send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"DELETE", format!("{}dir1?recursive=0", server.url())).send()?;
+    assert_eq!(resp.status(), 409);
+    assert!(server.path().join("dir1/index.html").exists());
+    std::fs::create_dir(server.path().join("empty"))?;
+    let resp = fetch!(b"DELETE", format!("{}empty", server.url())).send()?;
+    assert_eq!(resp.status(), 204);
+    Ok(())
+}
+
 #[rstest]
 fn delete_file_404(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     let resp = fetch!(b"DELETE", format!("{}file1", server.url())).send()?;
@@ -346,6 +1312,129 @@ fn get_file_content_type(server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn get_file_parts(#[with(&["--join-parts"])] server: TestServer) -> Result<(), Error> {
+    for (name, contents) in [("part0", "abc"), ("part10", "hi"), ("part2", "defg")] {
+        std::fs::write(server.path().join(format!("joined.txt.{name}")), contents)?;
+    }
+    let url = format!("{}joined.txt", server.url());
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/plain");
+    assert_eq!(resp.headers().get("content-length").unwrap(), "9");
+    assert_eq!(resp.text()?, "abcdefghi");
+
+    let resp = fetch!(b"GET", &url).header("range", "bytes=1-4").send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(resp.headers().get("content-range").unwrap(), "bytes 1-4/9");
+    assert_eq!(resp.text()?, "bcde");
+
+    let resp = fetch!(b"GET", &url).header("range", "bytes=9-").send()?;
+    assert_eq!(resp.status(), 416);
+    assert_eq!(resp.headers().get("content-range").unwrap(), "bytes */9");
+    Ok(())
+}
+
+#[rstest]
+fn get_file_gunzip(#[with(&["--gunzip"])] server: TestServer) -> Result<(), Error> {
+    let data = "hello gunzip\n".repeat(100);
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data.as_bytes())?;
+    let gz = encoder.finish()?;
+    std::fs::write(server.path().join("notes.txt.gz"), &gz)?;
+    let url = format!("{}notes.txt", server.url());
+
+    let resp = fetch!(b"HEAD", &url).send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/plain");
+    assert_eq!(
+        resp.headers().get("content-length").unwrap(),
+        &data.len().to_string()
+    );
+    assert!(resp.headers().get("content-encoding").is_none());
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.text()?, data);
+
+    let resp = fetch!(b"GET", &url)
+        .header("accept-encoding", "gzip")
+        .send()?;
+    assert_eq!(resp.headers().get("content-encoding").unwrap(), "gzip");
+    assert_eq!(
+        resp.headers().get("content-length").unwrap(),
+        &gz.len().to_string()
+    );
+    assert_eq!(resp.bytes()?, gz);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_gunzip_disabled(server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join("notes.txt.gz"), b"")?;
+    let resp = reqwest::blocking::get(format!("{}notes.txt", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_parts_disabled(server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join("joined.txt.part0"), "abc")?;
+    let resp = reqwest::blocking::get(format!("{}joined.txt", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_content_type_override(
+    #[with(&["--mime", ".TS=application/typescript"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("main.ts"), "let a = 1;")?;
+    let resp = reqwest::blocking::get(format!("{}main.ts", server.url()))?;
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/typescript"
+    );
+    let resp = reqwest::blocking::get(format!("{}content-types/bin.tar", server.url()))?;
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/x-tar"
+    );
+    Ok(())
+}
+
+#[rstest]
+#[case("live.m3u8", "application/vnd.apple.mpegurl", "no-cache")]
+#[case("live.mpd", "application/dash+xml", "no-cache")]
+#[case("seg1.ts", "video/mp2t", "public, max-age=86400")]
+#[case("seg1.m4s", "video/iso.segment", "public, max-age=86400")]
+fn get_file_streaming(
+    #[case] name: &str,
+    #[case] content_type: &str,
+    #[case] cache_control: &str,
+    server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join(name), "#EXTM3U")?;
+    let resp = reqwest::blocking::get(format!("{}{name}", server.url()))?;
+    assert_eq!(resp.headers().get("content-type").unwrap(), content_type);
+    assert_eq!(resp.headers().get("cache-control").unwrap(), cache_control);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_streaming_override(
+    #[with(&["--mime", ".ts=application/typescript", "--no-streaming-cache"])] server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("main.ts"), "let a = 1;")?;
+    std::fs::write(server.path().join("live.m3u8"), "#EXTM3U")?;
+    let resp = reqwest::blocking::get(format!("{}main.ts", server.url()))?;
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/typescript"
+    );
+    let resp = reqwest::blocking::get(format!("{}live.m3u8", server.url()))?;
+    assert!(resp.headers().get("cache-control").is_none());
+    Ok(())
+}
+
 // #[rstest]
 // fn resumable_upload(#[with(&["--allow-upload"])] server: TestServer) -> Result<(), Error> {
 //     let url = format!("{}file1", server.url());
@@ -361,3 +1450,40 @@ fn get_file_content_type(server: TestServer) -> Result<(), Error> {
 //     assert_eq!(resp.text().unwrap(), "abc123");
 //     Ok(())
 // }
+
+#[rstest]
+#[case("a#b.txt")]
+#[case("a?b.txt")]
+#[case("a b.txt")]
+#[case("100%.txt")]
+fn encoded_links(#[with(&["-A"])] server: TestServer, #[case] name: &str) -> Result<(), Error> {
+    let dir = "we#ird ?dir%";
+    let href = format!("/{}", utils::encode_uri(&format!("{dir}/{name}")));
+    let url = |href: &str| format!("{}{}", server.url(), &href[1..]);
+    let decode = |href: &str| urlencoding::decode(href).unwrap().into_owned();
+    assert!(!href.contains(['#', '?', ' ']));
+    assert_eq!(decode(&href), format!("/{dir}/{name}"));
+
+    let resp = fetch!(b"PUT", url(&href)).body(b"abc".to_vec()).send()?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.headers().get("location").unwrap().to_str()?, href);
+    assert_eq!(reqwest::blocking::get(url(&href))?.text()?, "abc");
+
+    // The redirect to the trailing slash keeps the name encoded
+    let dir_href = format!("/{}", utils::encode_uri(dir));
+    let resp = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?
+        .get(url(&dir_href))
+        .send()?;
+    assert_eq!(resp.status(), 301);
+    let location = resp.headers().get("location").unwrap().to_str()?;
+    assert_eq!(decode(location), format!("/{dir}/"));
+
+    let body = fetch!(b"PROPFIND", url(&dir_href)).send()?.text()?;
+    assert!(body.contains(&format!("<D:href>{href}</D:href>")));
+
+    let body = reqwest::blocking::get(format!("{}/?feed=rss", url(&dir_href)))?.text()?;
+    assert!(body.contains(&url(&href)));
+    Ok(())
+}
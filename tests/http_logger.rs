@@ -79,3 +79,185 @@ fn no_log(tmpdir: TempDir, port: u16, #[case] args: &[&str]) -> Result<(), Error
     child.kill()?;
     Ok(())
 }
+
+#[rstest]
+fn log_ignore(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let log_file = tmpdir.path().join("dufs.log");
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args(["--log-ignore", "/__dufs__/*", "--log-file"])
+        .arg(&log_file)
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    wait_for_port(port);
+
+    let resp = fetch!(b"GET", &format!("http://localhost:{port}/__dufs__/health")).send()?;
+    assert_eq!(resp.status(), 200);
+    let resp = fetch!(b"GET", &format!("http://localhost:{port}/index.html")).send()?;
+    assert_eq!(resp.status(), 200);
+
+    let output = std::fs::read_to_string(&log_file)?;
+    assert!(!output.contains("/__dufs__/health"));
+    assert!(output.contains(r#""GET /index.html" 200"#));
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn log_rotation(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let log_dir = assert_fs::TempDir::new()?;
+    let log_file = log_dir.path().join("dufs.log");
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args([
+            "--log-max-size",
+            "1K",
+            "--log-max-files",
+            "2",
+            "--log-compress",
+        ])
+        .arg("--log-file")
+        .arg(&log_file)
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    wait_for_port(port);
+
+    for _ in 0..50 {
+        let resp = fetch!(b"GET", &format!("http://localhost:{port}/index.html")).send()?;
+        assert_eq!(resp.status(), 200);
+    }
+
+    let segments = || -> Result<Vec<String>, Error> {
+        let mut names = vec![];
+        for entry in std::fs::read_dir(log_dir.path())? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name != "dufs.log" {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    };
+    for _ in 0..50 {
+        let segments = segments()?;
+        if segments.len() <= 2 && segments.iter().all(|v| v.ends_with(".gz")) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let segments = segments()?;
+    assert!(!segments.is_empty() && segments.len() <= 2, "{segments:?}");
+    assert!(segments.iter().all(|v| v.ends_with(".gz")), "{segments:?}");
+    let output = std::fs::read_to_string(&log_file)?;
+    assert!(output.len() < 1024);
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+#[case(&["--trusted-proxies", "127.0.0.0/8,::1,10.0.0.0/8"], "203.0.113.7")]
+#[case(&["--trusted-proxies", "10.0.0.0/8"], "127.0.0.1")]
+#[case(&[], "127.0.0.1")]
+fn log_forwarded_for(
+    tmpdir: TempDir,
+    port: u16,
+    #[case] args: &[&str],
+    #[case] remote_addr: &str,
+) -> Result<(), Error> {
+    let log_dir = assert_fs::TempDir::new()?;
+    let log_file = log_dir.path().join("dufs.log");
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args(["-b", "127.0.0.1", "--log-format", "$remote_addr"])
+        .args(args)
+        .arg("--log-file")
+        .arg(&log_file)
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    wait_for_port(port);
+
+    let resp = fetch!(b"GET", &format!("http://127.0.0.1:{port}/index.html"))
+        .header("x-forwarded-for", "198.51.100.1, 203.0.113.7, 10.0.0.2")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+
+    let output = std::fs::read_to_string(&log_file)?;
+    assert!(
+        output
+            .trim_end()
+            .ends_with(&format!("INFO - {remote_addr}")),
+        "{output}"
+    );
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn debug_bodies(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let log_dir = TempDir::new()?;
+    let log_file = log_dir.path().join("dufs.log");
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args([
+            "-A",
+            "-a",
+            "user:pass@/:rw",
+            "--auth-session",
+            "--debug-bodies",
+        ])
+        .arg("--log-file")
+        .arg(&log_file)
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    wait_for_port(port);
+
+    let url = format!("http://localhost:{port}");
+    let resp = fetch!(b"GET", format!("{url}/index.html"))
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    let set_cookie = resp.headers().get("set-cookie").unwrap().to_str()?;
+    let session = set_cookie.split(';').next().unwrap()["dufs_session=".len()..].to_string();
+    assert_eq!(resp.text()?, "This is index.html");
+    let resp = fetch!(b"PROPFIND", format!("{url}/index.html"))
+        .basic_auth("user", Some("pass"))
+        .body(r#"<propfind xmlns="DAV:"><propname/></propfind>"#)
+        .send()?;
+    assert_eq!(resp.status(), 207);
+    let resp = fetch!(b"PUT", format!("{url}/upload.txt"))
+        .basic_auth("user", Some("pass"))
+        .body("upload-content")
+        .send()?;
+    assert_eq!(resp.status(), 201);
+
+    let output = std::fs::read_to_string(&log_file)?;
+    assert!(output.contains("Request GET /index.html"));
+    assert!(output.contains("authorization: [redacted]"));
+    // base64 of user:pass
+    assert!(!output.contains("dXNlcjpwYXNz"));
+    assert!(output.contains("set-cookie: [redacted]"));
+    assert!(!output.contains(&session));
+    assert!(output.contains("Response body of GET /index.html\nThis is index.html"));
+    assert!(output.contains(
+        "Request body of PROPFIND /index.html\n<propfind xmlns=\\\"DAV:\\\"><propname/></propfind>"
+    ));
+    assert!(output.contains("Request body of PUT /upload.txt not logged, content-length: 14"));
+    assert!(!output.contains("upload-content"));
+
+    child.kill()?;
+    Ok(())
+}
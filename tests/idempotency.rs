@@ -0,0 +1,66 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn upload(url: &str) -> Result<reqwest::blocking::Response, Error> {
+    let resp = fetch!(b"PUT", url)
+        .header("idempotency-key", "k1")
+        .header("if-none-match", "*")
+        .body(b"abc".to_vec())
+        .send()?;
+    Ok(resp)
+}
+
+#[rstest]
+fn idempotent_upload(
+    #[with(&["-A", "--idempotency-ttl", "60"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}file1", server.url());
+    let resp = upload(&url)?;
+    assert_eq!(resp.status(), 201);
+    let first = (resp.headers().get("location").cloned(), resp.text()?);
+
+    // A rewrite would move the mtime and fail the If-None-Match
+    let path = server.path().join("file1");
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    std::fs::File::options()
+        .write(true)
+        .open(&path)?
+        .set_modified(mtime)?;
+
+    let resp = upload(&url)?;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.headers().get("idempotent-replayed").unwrap(), "true");
+    assert_eq!(
+        (resp.headers().get("location").cloned(), resp.text()?),
+        first
+    );
+    assert_eq!(std::fs::metadata(&path)?.modified()?, mtime);
+    assert_eq!(std::fs::read_to_string(&path)?, "abc");
+
+    // The key is scoped per path
+    let resp = fetch!(b"PUT", format!("{}file2", server.url()))
+        .header("idempotency-key", "k1")
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(resp.headers().get("idempotent-replayed").is_none());
+
+    let resp = fetch!(b"PUT", &url)
+        .header("idempotency-key", "k1")
+        .body(b"abcd".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 422);
+    Ok(())
+}
+
+#[rstest]
+fn idempotency_key_ignored(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}file1", server.url());
+    assert_eq!(upload(&url)?.status(), 201);
+    assert_eq!(upload(&url)?.status(), 412);
+    Ok(())
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "image")]
+
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+#[rstest]
+fn convert_jpeg_to_webp(server: TestServer) -> Result<(), Error> {
+    let img = image::RgbImage::from_fn(13, 7, |x, y| image::Rgb([x as u8 * 10, y as u8 * 20, 0]));
+    img.save(server.path().join("pic.jpg"))?;
+
+    let resp = reqwest::blocking::get(format!("{}pic.jpg?format=webp", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "image/webp");
+    assert_eq!(
+        resp.headers().get("content-disposition").unwrap(),
+        "inline; filename=\"pic.webp\""
+    );
+    let converted = image::load_from_memory(&resp.bytes()?)?;
+    assert_eq!((converted.width(), converted.height()), (13, 7));
+    Ok(())
+}
+
+#[rstest]
+fn convert_unsupported_image(server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}test.txt?format=webp", server.url()))?;
+    assert_eq!(resp.status(), 415);
+    let resp = reqwest::blocking::get(format!("{}test.txt?format=png", server.url()))?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+}
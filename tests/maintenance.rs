@@ -0,0 +1,80 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+#[rstest]
+fn maintenance_toggle(
+    #[with(&["-a", "admin:pass@/:rw", "-a", "guest:pass@/", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let maintenance_url = format!("{}__dufs__/maintenance", server.url());
+    let file_url = format!("{}file1", server.url());
+
+    let resp = fetch!(b"POST", format!("{maintenance_url}?write=off"))
+        .basic_auth("guest", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 403);
+
+    let resp = fetch!(b"POST", format!("{maintenance_url}?write=off"))
+        .basic_auth("admin", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, r#"{"write":"off"}"#);
+
+    let resp = fetch!(b"PUT", &file_url)
+        .basic_auth("admin", Some("pass"))
+        .body("abc")
+        .send()?;
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.headers().get("retry-after").unwrap(), "60");
+
+    let resp = fetch!(b"GET", format!("{}index.html", server.url()))
+        .basic_auth("admin", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+
+    let resp = fetch!(b"POST", format!("{maintenance_url}?write=on"))
+        .basic_auth("admin", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 200);
+
+    let resp = fetch!(b"PUT", &file_url)
+        .basic_auth("admin", Some("pass"))
+        .body("abc")
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    Ok(())
+}
+
+#[rstest]
+fn maintenance_retry_after_jitter(
+    #[with(&[
+        "-a", "admin:pass@/:rw", "-A",
+        "--retry-after-base", "30", "--retry-after-jitter", "10",
+    ])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(
+        b"POST",
+        format!("{}__dufs__/maintenance?write=off", server.url())
+    )
+    .basic_auth("admin", Some("pass"))
+    .send()?;
+    assert_eq!(resp.status(), 200);
+    for _ in 0..5 {
+        let resp = fetch!(b"PUT", format!("{}file1", server.url()))
+            .basic_auth("admin", Some("pass"))
+            .body("abc")
+            .send()?;
+        assert_eq!(resp.status(), 503);
+        let retry_after: u64 = resp
+            .headers()
+            .get("retry-after")
+            .unwrap()
+            .to_str()?
+            .parse()?;
+        assert!((30..=40).contains(&retry_after));
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+#[rstest]
+fn mkdir_with_parents(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}a/b/c?mkdir=1&parents=1", server.url());
+    let resp = fetch!(b"POST", &url).send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("a/b/c").is_dir());
+
+    let resp = fetch!(b"POST", &url).send()?;
+    assert_eq!(resp.status(), 200);
+
+    let resp = fetch!(b"POST", format!("{url}&exist_ok=0")).send()?;
+    assert_eq!(resp.status(), 409);
+    Ok(())
+}
+
+#[rstest]
+fn mkdir_without_parents(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"POST", format!("{}a/b/c?mkdir=1", server.url())).send()?;
+    assert_eq!(resp.status(), 409);
+    assert!(!server.path().join("a").exists());
+
+    let resp = fetch!(b"POST", format!("{}dir1/sub?mkdir=1", server.url())).send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("dir1/sub").is_dir());
+    Ok(())
+}
+
+#[rstest]
+fn mkdir_over_file(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}test.html/sub?mkdir=1&parents=1", server.url());
+    let resp = fetch!(b"POST", url).send()?;
+    assert_eq!(resp.status(), 409);
+    Ok(())
+}
+
+#[rstest]
+fn mkdir_not_allowed(#[with(&["--allow-delete"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"POST", format!("{}a?mkdir=1", server.url())).send()?;
+    assert_eq!(resp.status(), 403);
+    assert!(!server.path().join("a").exists());
+    Ok(())
+}
+
+#[rstest]
+fn mkdir_authorized_at_each_level(
+    #[with(&["--auth", "user:pass@/a/b:rw", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}a/b/c?mkdir=1&parents=1", server.url());
+    let resp = fetch!(b"POST", &url)
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    assert!(!server.path().join("a").exists());
+
+    std::fs::create_dir(server.path().join("a"))?;
+    let resp = fetch!(b"POST", &url)
+        .basic_auth("user", Some("pass"))
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("a/b/c").is_dir());
+    Ok(())
+}
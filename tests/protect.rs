@@ -0,0 +1,62 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+#[rstest]
+fn protect_glob(#[with(&["-A", "--protect", "*.html"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}index.html", server.url());
+    let resp = fetch!(b"DELETE", &url).send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"PUT", &url).body("changed").send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"MOVE", &url)
+        .header("Destination", format!("{}moved.html", server.url()))
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"COPY", format!("{}test.txt", server.url()))
+        .header("Destination", &url)
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "This is index.html");
+
+    // A folder holding protected files can't be deleted either
+    let resp = fetch!(b"DELETE", format!("{}dir1?recursive=1", server.url())).send()?;
+    assert_eq!(resp.status(), 403);
+
+    let resp = fetch!(b"DELETE", format!("{}test.txt", server.url())).send()?;
+    assert_eq!(resp.status(), 204);
+    Ok(())
+}
+
+#[rstest]
+fn protect_marker(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join(".test.txt.dufs-lock"), "")?;
+    let url = format!("{}test.txt", server.url());
+    let resp = fetch!(b"DELETE", &url).send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"PUT", &url).body("changed").send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(
+        b"POST",
+        format!("{}test.txt?rename=renamed.txt", server.url())
+    )
+    .send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "This is test.txt");
+
+    // The marker itself is protected
+    let resp = fetch!(b"DELETE", format!("{}.test.txt.dufs-lock", server.url())).send()?;
+    assert_eq!(resp.status(), 403);
+
+    let resp = fetch!(b"PUT", format!("{}index.html", server.url()))
+        .body("changed")
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    Ok(())
+}
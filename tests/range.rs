@@ -39,3 +39,122 @@ fn get_file_range_invalid(server: TestServer) -> Result<(), Error> {
     assert_eq!(resp.headers().get("content-range").unwrap(), "bytes */18");
     Ok(())
 }
+
+#[rstest]
+fn get_dir_entry_range(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}dir1?entry=index.html", server.url()))
+        .header("range", HeaderValue::from_static("bytes=8-22"))
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(
+        resp.headers().get("content-range").unwrap(),
+        "bytes 8-22/23"
+    );
+    assert_eq!(resp.text()?, "dir1/index.html");
+
+    let resp = fetch!(b"GET", format!("{}?entry=dir1/index.html", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "This is dir1/index.html");
+    Ok(())
+}
+
+#[rstest]
+#[case("dir1?entry=../index.html", 400)]
+#[case("dir1?entry=", 400)]
+#[case("?entry=dir1", 404)]
+#[case("dir1?entry=missing.txt", 404)]
+fn get_dir_entry_invalid(
+    server: TestServer,
+    #[case] url: &str,
+    #[case] status: u16,
+) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}{}", server.url(), url)).send()?;
+    assert_eq!(resp.status(), status);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_multi_range_merged(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}index.html", server.url()))
+        .header("range", HeaderValue::from_static("bytes=0-3,2-6"))
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(resp.headers().get("content-range").unwrap(), "bytes 0-6/18");
+    assert_eq!(resp.text()?, "This is");
+    Ok(())
+}
+
+#[rstest]
+fn get_file_multi_range(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"GET", format!("{}index.html", server.url()))
+        .header("range", HeaderValue::from_static("bytes=8-12,0-3"))
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    let content_type = resp.headers().get("content-type").unwrap().to_str()?;
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .unwrap()
+        .to_string();
+    let content_length: usize = resp
+        .headers()
+        .get("content-length")
+        .unwrap()
+        .to_str()?
+        .parse()?;
+    let body = resp.text()?;
+    assert_eq!(body.len(), content_length);
+    let expected = format!(
+        "\r\n--{boundary}\r\nContent-Type: text/html; charset=UTF-8\r\nContent-Range: bytes 0-3/18\r\n\r\nThis\
+         \r\n--{boundary}\r\nContent-Type: text/html; charset=UTF-8\r\nContent-Range: bytes 8-12/18\r\n\r\nindex\
+         \r\n--{boundary}--\r\n"
+    );
+    assert_eq!(body, expected);
+    Ok(())
+}
+
+#[rstest]
+fn get_file_multi_range_partly_satisfiable(server: TestServer) -> Result<(), Error> {
+    // The ranges past the end are dropped, the rest is served
+    let resp = fetch!(b"GET", format!("{}index.html", server.url()))
+        .header("range", HeaderValue::from_static("bytes=0-3,100-200"))
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(resp.headers().get("content-range").unwrap(), "bytes 0-3/18");
+    assert_eq!(resp.text()?, "This");
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&[] as &[&str]))]
+#[case(server(&["--zero-copy"]))]
+#[case(server(&["--coalesce-downloads"]))]
+fn get_file_range_unsatisfiable_size(#[case] server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join("500.bin"), vec![b'x'; 500])?;
+    for range in ["bytes=500-", "bytes=600-700", "bytes=500-600, 700-800"] {
+        let resp = fetch!(b"GET", format!("{}500.bin", server.url()))
+            .header("range", range)
+            .send()?;
+        assert_eq!(resp.status(), 416);
+        assert_eq!(resp.headers().get("content-range").unwrap(), "bytes */500");
+    }
+    Ok(())
+}
+
+#[rstest]
+fn get_file_read_buffer_size(
+    #[with(&["--read-buffer-size", "1M"])] server: TestServer,
+) -> Result<(), Error> {
+    let data: Vec<u8> = (0..3 * 1024 * 1024 + 7).map(|v| (v % 251) as u8).collect();
+    std::fs::write(server.path().join("large.bin"), &data)?;
+    let url = format!("{}large.bin", server.url());
+    let resp = reqwest::blocking::get(&url)?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.bytes()?, data);
+
+    let resp = fetch!(b"GET", &url)
+        .header("range", "bytes=1000000-2100000")
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(resp.bytes()?, data[1000000..=2100000]);
+    Ok(())
+}
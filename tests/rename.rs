@@ -0,0 +1,120 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+#[rstest]
+fn rename_file(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(
+        b"POST",
+        format!("{}test.html?rename=renamed.html", server.url())
+    )
+    .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(!server.path().join("test.html").exists());
+    assert!(server.path().join("renamed.html").exists());
+
+    let resp = fetch!(
+        b"POST",
+        format!("{}dir1/test.txt?rename=renamed.txt", server.url())
+    )
+    .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("dir1/renamed.txt").exists());
+    Ok(())
+}
+
+#[rstest]
+fn rename_json_move_into_subdir(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"POST", format!("{}dir1", server.url()))
+        .header("content-type", "application/json")
+        .body(r#"{"from":"test.txt","to":"sub/moved.txt"}"#)
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(!server.path().join("dir1/test.txt").exists());
+    assert!(server.path().join("dir1/sub/moved.txt").exists());
+
+    let resp = fetch!(b"POST", server.url())
+        .body(r#"{"from":"dir1/test.html","to":"/dir2/moved.html"}"#)
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(server.path().join("dir2/moved.html").exists());
+    Ok(())
+}
+
+#[rstest]
+fn rename_conflict(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let url = format!("{}test.html?rename=index.html", server.url());
+    let resp = fetch!(b"POST", &url).send()?;
+    assert_eq!(resp.status(), 409);
+    assert!(server.path().join("test.html").exists());
+
+    let resp = fetch!(b"POST", format!("{url}&overwrite=1")).send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(!server.path().join("test.html").exists());
+    let content = std::fs::read_to_string(server.path().join("index.html"))?;
+    assert_eq!(content, "This is test.html");
+    Ok(())
+}
+
+#[rstest]
+#[case("test.html?rename=../escaped.html", 400)]
+#[case("dir1?rename=dir1/inner", 400)]
+#[case("missing.html?rename=other.html", 404)]
+fn rename_invalid(
+    #[with(&["-A"])] server: TestServer,
+    #[case] url: &str,
+    #[case] status: u16,
+) -> Result<(), Error> {
+    let resp = fetch!(b"POST", format!("{}{}", server.url(), url)).send()?;
+    assert_eq!(resp.status(), status);
+    Ok(())
+}
+
+#[rstest]
+fn rename_not_allowed(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(
+        b"POST",
+        format!("{}test.html?rename=renamed.html", server.url())
+    )
+    .send()?;
+    assert_eq!(resp.status(), 403);
+    assert!(server.path().join("test.html").exists());
+    Ok(())
+}
+
+#[rstest]
+fn rename_auth(
+    #[with(&["--auth", "user:pass@/dir1:rw", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"POST", format!("{}dir1", server.url()))
+        .basic_auth("user", Some("pass"))
+        .body(r#"{"from":"test.html","to":"/dir2/test.html"}"#)
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    assert!(server.path().join("dir1/test.html").exists());
+    let resp = fetch!(b"POST", format!("{}dir1", server.url()))
+        .basic_auth("user", Some("pass"))
+        .body(r#"{"from":"test.html","to":"moved.html"}"#)
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    Ok(())
+}
+
+#[rstest]
+#[case(&["-A", "--no-recursive-delete"], 403)]
+#[case(&["-A", "--protect", "*.html"], 403)]
+#[case(&["-A"], 204)]
+fn rename_overwrite_dir(#[case] args: &[&str], #[case] status: u16) -> Result<(), Error> {
+    let server = server(args);
+    let resp = fetch!(
+        b"POST",
+        format!("{}dir2?rename=dir1&overwrite=1", server.url())
+    )
+    .send()?;
+    assert_eq!(resp.status(), status);
+    assert_eq!(server.path().join("dir2").exists(), status != 204);
+    assert!(server.path().join("dir1/index.html").exists());
+    Ok(())
+}
@@ -78,3 +78,46 @@ fn render_spa2(#[with(&["--render-spa"])] server: TestServer) -> Result<(), Erro
     assert_eq!(text, "This is index.html");
     Ok(())
 }
+
+#[rstest]
+fn negotiate_language(#[with(&["--negotiate-language"])] server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join("page.html"), "page")?;
+    std::fs::write(server.path().join("page.fr.html"), "page fr")?;
+    std::fs::write(server.path().join("page.en.html"), "page en")?;
+    let url = format!("{}page.html", server.url());
+    let client = reqwest::blocking::Client::new();
+
+    let resp = client
+        .get(&url)
+        .header("accept-language", "de;q=0.9, fr-CA, en;q=0.5")
+        .send()?;
+    assert_eq!(resp.headers().get("content-language").unwrap(), "fr");
+    assert_eq!(resp.headers().get("vary").unwrap(), "Accept-Language");
+    assert_eq!(resp.text()?, "page fr");
+
+    // Falls back to --default-language without a preferred variant
+    let resp = client.get(&url).header("accept-language", "de").send()?;
+    assert_eq!(resp.headers().get("content-language").unwrap(), "en");
+    assert_eq!(resp.text()?, "page en");
+
+    std::fs::remove_file(server.path().join("page.en.html"))?;
+    let resp = client.get(&url).header("accept-language", "de").send()?;
+    assert!(resp.headers().get("content-language").is_none());
+    assert_eq!(resp.text()?, "page");
+    Ok(())
+}
+
+#[rstest]
+fn negotiate_language_suffix(
+    #[with(&["--negotiate-language", "--language-variant", "suffix", "--default-language", "de"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    std::fs::write(server.path().join("page.html.de"), "seite")?;
+    let resp = reqwest::blocking::Client::new()
+        .get(format!("{}page.html", server.url()))
+        .header("accept-language", "fr")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "seite");
+    Ok(())
+}
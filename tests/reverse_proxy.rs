@@ -0,0 +1,153 @@
+#![cfg(feature = "reverse-proxy")]
+
+mod fixtures;
+mod utils;
+
+use fixtures::{port, server, Error};
+use rstest::rstest;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+/// Answer a single request with its request line and the forwarded headers.
+fn spawn_upstream() -> Result<u16, Error> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut lines = vec![];
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end().to_string();
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line);
+        }
+        let mut body = lines[0].clone();
+        for line in &lines[1..] {
+            let name = line.split(':').next().unwrap().to_lowercase();
+            if matches!(
+                name.as_str(),
+                "host" | "x-forwarded-host" | "connection" | "authorization" | "cookie"
+            ) {
+                body.push('\n');
+                body.push_str(&line.to_lowercase());
+            }
+        }
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nX-Upstream: 1\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+    });
+    Ok(port)
+}
+
+#[rstest]
+fn proxy_forwards_requests() -> Result<(), Error> {
+    let upstream_port = spawn_upstream()?;
+    let server = server(&[
+        "--proxy",
+        &format!("/api=http://127.0.0.1:{upstream_port}/v1"),
+    ]);
+    let resp = reqwest::blocking::get(format!("{}api/users?page=2", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("x-upstream").unwrap(), "1");
+    let body = resp.text()?;
+    let mut lines = body.lines();
+    assert_eq!(lines.next(), Some("GET /v1/api/users?page=2 HTTP/1.1"));
+    let headers: Vec<&str> = lines.collect();
+    assert!(headers.contains(&format!("host: 127.0.0.1:{upstream_port}").as_str()));
+    assert!(headers
+        .iter()
+        .any(|v| v.starts_with("x-forwarded-host: localhost:")));
+    assert!(!headers.iter().any(|v| v.starts_with("connection:")));
+
+    // Paths only sharing the prefix are served from the folder
+    let resp = reqwest::blocking::get(format!("{}apis", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    Ok(())
+}
+
+#[rstest]
+#[case(&[], None, Some("cookie: app=1"))]
+#[case(
+    &["--proxy-pass-credentials"],
+    Some("authorization: basic dxnlcjpwyxnz"),
+    Some("cookie: dufs_sort=name:asc; app=1")
+)]
+fn proxy_credentials(
+    #[case] extra: &[&str],
+    #[case] authorization: Option<&str>,
+    #[case] cookie: Option<&str>,
+) -> Result<(), Error> {
+    let upstream_port = spawn_upstream()?;
+    let proxy = format!("/api=http://127.0.0.1:{upstream_port}");
+    let mut args = vec!["--proxy", &proxy, "--auth", "user:pass@/:rw"];
+    args.extend(extra);
+    let server = server(&args);
+    let resp = fetch!(b"GET", format!("{}api/", server.url()))
+        .basic_auth("user", Some("pass"))
+        .header("cookie", "dufs_sort=name:asc; app=1")
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    let body = resp.text()?;
+    let find = |name: &str| body.lines().find(|v| v.starts_with(name));
+    assert_eq!(find("authorization:"), authorization);
+    assert_eq!(find("cookie:"), cookie);
+    Ok(())
+}
+
+#[rstest]
+fn proxy_upstream_down(port: u16) -> Result<(), Error> {
+    let server = server(&["--proxy", &format!("/api=http://127.0.0.1:{port}")]);
+    let resp = reqwest::blocking::get(format!("{}api/", server.url()))?;
+    assert_eq!(resp.status(), 502);
+    Ok(())
+}
+
+#[rstest]
+fn proxy_upgrades_connections() -> Result<(), Error> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let upstream_port = listener.local_addr()?.port();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        stream
+            .write_all(
+                b"HTTP/1.1 101 Switching Protocols\r\nConnection: upgrade\r\nUpgrade: echo\r\n\r\n",
+            )
+            .unwrap();
+        // Echo the upgraded bytes back
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        stream.write_all(line.as_bytes()).unwrap();
+    });
+    let server = server(&["--proxy", &format!("/ws=http://127.0.0.1:{upstream_port}")]);
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", server.port()))?;
+    stream.write_all(
+        b"GET /ws HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\nUpgrade: echo\r\n\r\n",
+    )?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    assert!(line.starts_with("HTTP/1.1 101"));
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+    stream.write_all(b"ping\n")?;
+    line.clear();
+    reader.read_line(&mut line)?;
+    assert_eq!(line, "ping\n");
+    Ok(())
+}
@@ -0,0 +1,57 @@
+mod fixtures;
+mod utils;
+
+use assert_cmd::prelude::*;
+use assert_fs::fixture::{FileWriteStr, PathChild};
+use assert_fs::TempDir;
+use fixtures::{port, tmpdir, wait_for_port, Error};
+use rstest::rstest;
+use std::process::{Command, Stdio};
+
+const RULES: &str = r#"
+# migrated pages
+/old/*          /new page.html                 301
+/docs/v1        https://example.com/docs      302
+/latest         /dir1/index.html
+/gone.html      -                             410
+"#;
+
+#[rstest]
+fn rewrites(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let rules = tmpdir.child("rules.txt");
+    rules.write_str(RULES)?;
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args(["--rewrites", &rules.path().to_string_lossy()])
+        .stdout(Stdio::null())
+        .spawn()?;
+    wait_for_port(port);
+
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let url = format!("http://localhost:{port}");
+    let resp = client.get(format!("{url}/old/a/b.html")).send()?;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("location").unwrap(), "/new%20page.html");
+    let resp = client.get(format!("{url}/docs/v1")).send()?;
+    assert_eq!(resp.status(), 302);
+    assert_eq!(
+        resp.headers().get("location").unwrap(),
+        "https://example.com/docs"
+    );
+
+    let resp = client.get(format!("{url}/latest")).send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "This is dir1/index.html");
+
+    let resp = client.get(format!("{url}/gone.html")).send()?;
+    assert_eq!(resp.status(), 410);
+    let resp = client.get(format!("{url}/index.html")).send()?;
+    assert_eq!(resp.status(), 200);
+
+    child.kill()?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+
+const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
+
+#[rstest]
+fn default_robots(
+    #[with(&["--auth", "user:pass@/:rw", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}robots.txt", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(resp.text()?, DEFAULT_ROBOTS_TXT);
+    Ok(())
+}
+
+#[rstest]
+fn robots_file_override(server: TestServer) -> Result<(), Error> {
+    std::fs::write(
+        server.path().join("robots.txt"),
+        "User-agent: *\nAllow: /\n",
+    )?;
+    let resp = reqwest::blocking::get(format!("{}robots.txt", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text()?, "User-agent: *\nAllow: /\n");
+    Ok(())
+}
+
+#[rstest]
+fn no_default_robots(#[with(&["--no-default-robots"])] server: TestServer) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}robots.txt", server.url()))?;
+    assert_eq!(resp.status(), 404);
+    Ok(())
+}
+
+#[rstest]
+fn default_favicon(
+    #[with(&["--auth", "user:pass@/:rw", "-A"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(format!("{}favicon.ico", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "image/x-icon");
+    Ok(())
+}
@@ -5,9 +5,11 @@ mod utils;
 
 use assert_cmd::prelude::*;
 use assert_fs::fixture::TempDir;
+use async_zip::{base::write::ZipFileWriter, Compression, ZipEntryBuilder};
 use fixtures::{port, tmpdir, wait_for_port, Error};
 use rstest::rstest;
 use std::process::{Command, Stdio};
+use utils::retrieve_index_paths;
 
 #[rstest]
 #[case("index.html")]
@@ -58,3 +60,70 @@ fn path_prefix_single_file(tmpdir: TempDir, port: u16, #[case] file: &str) -> Re
     child.kill()?;
     Ok(())
 }
+
+fn create_zip(entries: &[(&str, &str)]) -> Result<Vec<u8>, Error> {
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    runtime.block_on(async {
+        let mut writer = ZipFileWriter::new(vec![]);
+        for (name, data) in entries {
+            let builder = ZipEntryBuilder::new(name.to_string().into(), Compression::Deflate);
+            writer.write_entry_whole(builder, data.as_bytes()).await?;
+        }
+        Ok(writer.close().await?)
+    })
+}
+
+#[rstest]
+fn zip_mount(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let zip_path = tmpdir.path().join("site.zip");
+    let data = "0123456789".repeat(1000);
+    std::fs::write(
+        &zip_path,
+        create_zip(&[("index.txt", "index"), ("docs/data.txt", &data)])?,
+    )?;
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(&zip_path)
+        .arg("-p")
+        .arg(port.to_string())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    wait_for_port(port);
+
+    let resp = reqwest::blocking::get(format!("http://localhost:{port}/"))?;
+    assert_eq!(resp.status(), 200);
+    let paths = retrieve_index_paths(&resp.text()?);
+    assert_eq!(paths, ["docs/", "index.txt"].map(String::from).into());
+    let resp = reqwest::blocking::get(format!("http://localhost:{port}/docs"))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.url().path(), "/docs/");
+    let resp = reqwest::blocking::get(format!("http://localhost:{port}/docs?json"))?;
+    assert_eq!(
+        resp.url().as_str(),
+        format!("http://localhost:{port}/docs/?json")
+    );
+
+    let resp = reqwest::blocking::Client::new()
+        .get(format!("http://localhost:{port}/docs/data.txt"))
+        .header("range", "bytes=5003-5007")
+        .send()?;
+    assert_eq!(resp.status(), 206);
+    assert_eq!(
+        resp.headers().get("content-range").unwrap(),
+        "bytes 5003-5007/10000"
+    );
+    assert_eq!(resp.text()?, "34567");
+    let resp = reqwest::blocking::get(format!("http://localhost:{port}/docs/data.txt"))?;
+    assert_eq!(resp.text()?, data);
+
+    let resp = reqwest::blocking::get(format!("http://localhost:{port}/missing.txt"))?;
+    assert_eq!(resp.status(), 404);
+    let resp = reqwest::blocking::Client::new()
+        .put(format!("http://localhost:{port}/new.txt"))
+        .body("abc")
+        .send()?;
+    assert_eq!(resp.status(), 405);
+
+    child.kill()?;
+    Ok(())
+}
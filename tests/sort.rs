@@ -27,3 +27,56 @@ fn search_dir_sort_by_name(server: TestServer) -> Result<(), Error> {
     assert_eq!(paths1, paths2);
     Ok(())
 }
+
+#[rstest]
+fn ls_dir_sort_by_cookie(server: TestServer) -> Result<(), Error> {
+    let url = server.url();
+    let resp = reqwest::blocking::get(format!("{url}?sort=name&order=desc"))?;
+    let expect: Vec<_> = self::utils::retrieve_index_paths(&resp.text()?)
+        .into_iter()
+        .collect();
+    let client = reqwest::blocking::Client::new();
+    let resp = client
+        .get(url.clone())
+        .header("cookie", "theme=dark; dufs_sort=name:desc")
+        .send()?;
+    let paths: Vec<_> = self::utils::retrieve_index_paths(&resp.text()?)
+        .into_iter()
+        .collect();
+    assert_eq!(paths, expect);
+
+    // An explicit sort wins over the cookie
+    let resp = client
+        .get(format!("{url}?sort=name&order=asc"))
+        .header("cookie", "dufs_sort=name:desc")
+        .send()?;
+    let mut paths: Vec<_> = self::utils::retrieve_index_paths(&resp.text()?)
+        .into_iter()
+        .collect();
+    paths.reverse();
+    assert_eq!(paths, expect);
+    Ok(())
+}
+
+#[rstest]
+#[case(server(&[] as &[&str]), "", &["g1/", "file2", "file02", "File10"])]
+#[case(server(&[] as &[&str]), "&dirs=mixed", &["file2", "file02", "File10", "g1/"])]
+#[case(server(&["--default-sort-dirs", "mixed"]), "", &["file2", "file02", "File10", "g1/"])]
+#[case(server(&["--default-sort-dirs", "mixed"]), "&dirs=first", &["g1/", "file2", "file02", "File10"])]
+fn ls_dir_sort_natural(
+    #[case] server: TestServer,
+    #[case] query: &str,
+    #[case] expected: &[&str],
+) -> Result<(), Error> {
+    let dir = server.path().join("natural");
+    std::fs::create_dir_all(dir.join("g1"))?;
+    for name in ["file2", "File10", "file02"] {
+        std::fs::write(dir.join(name), name)?;
+    }
+    let resp = reqwest::blocking::get(format!("{}natural/?sort=name{query}", server.url()))?;
+    let paths: Vec<_> = self::utils::retrieve_index_paths(&resp.text()?)
+        .into_iter()
+        .collect();
+    assert_eq!(paths, expected);
+    Ok(())
+}
@@ -4,6 +4,7 @@ mod utils;
 use assert_fs::fixture::TempDir;
 use fixtures::{server, tmpdir, Error, TestServer};
 use rstest::rstest;
+use serde_json::Value;
 
 #[cfg(unix)]
 use std::os::unix::fs::symlink as symlink_dir;
@@ -44,3 +45,65 @@ fn allow_symlink(
     assert!(paths.contains(&format!("{dir}/")));
     Ok(())
 }
+
+/// Fetch the JSON listing of the root, returning the entry named `name` if it's listed.
+fn find_json_path(server: &TestServer, name: &str) -> Result<Option<Value>, Error> {
+    let resp = reqwest::blocking::get(format!("{}?json", server.url()))?;
+    let data: Value = serde_json::from_str(&resp.text()?)?;
+    let paths = data["entries"].as_array().unwrap();
+    Ok(paths.iter().find(|v| v["name"] == name).cloned())
+}
+
+#[rstest]
+fn symlink_policy_show(server: TestServer) -> Result<(), Error> {
+    symlink_dir("dir1", server.path().join("link")).expect("Couldn't create symlink");
+    let link = find_json_path(&server, "link")?.unwrap();
+    assert_eq!(link["path_type"], "SymlinkDir");
+    assert_eq!(link["is_symlink"], true);
+    assert_eq!(link["target"], "/dir1");
+    let dir = find_json_path(&server, "dir1")?.unwrap();
+    assert!(dir.get("is_symlink").is_none());
+    assert!(dir.get("target").is_none());
+    Ok(())
+}
+
+#[rstest]
+fn symlink_policy_follow(
+    #[with(&["--symlink-policy", "follow"])] server: TestServer,
+) -> Result<(), Error> {
+    symlink_dir("dir1", server.path().join("link")).expect("Couldn't create symlink");
+    let link = find_json_path(&server, "link")?.unwrap();
+    assert_eq!(link["path_type"], "Dir");
+    assert!(link.get("is_symlink").is_none());
+    assert!(link.get("target").is_none());
+    Ok(())
+}
+
+#[rstest]
+fn symlink_policy_hide(
+    #[with(&["--symlink-policy", "hide"])] server: TestServer,
+) -> Result<(), Error> {
+    symlink_dir("dir1", server.path().join("link")).expect("Couldn't create symlink");
+    assert!(find_json_path(&server, "link")?.is_none());
+    assert!(find_json_path(&server, "dir1")?.is_some());
+    let resp = reqwest::blocking::get(format!("{}link/", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
+
+#[rstest]
+fn symlink_target_resolved(#[with(&["--allow-symlink"])] server: TestServer) -> Result<(), Error> {
+    symlink_dir("../dir2", server.path().join("dir1/link")).expect("Couldn't create symlink");
+    let resp = reqwest::blocking::get(format!("{}dir1/?json", server.url()))?;
+    let data: Value = serde_json::from_str(&resp.text()?)?;
+    let paths = data["entries"].as_array().unwrap();
+    let link = paths.iter().find(|v| v["name"] == "link").unwrap();
+    assert_eq!(link["target"], "/dir2");
+
+    // Relative targets leaving the root are left out like absolute ones
+    symlink_dir("../..", server.path().join("out")).expect("Couldn't create symlink");
+    let link = find_json_path(&server, "out")?.unwrap();
+    assert_eq!(link["is_symlink"], true);
+    assert!(link.get("target").is_none());
+    Ok(())
+}
@@ -0,0 +1,79 @@
+#![cfg(unix)]
+
+mod fixtures;
+mod utils;
+
+use assert_cmd::prelude::*;
+use assert_fs::fixture::TempDir;
+use fixtures::{port, server, tmpdir, wait_for_port, Error, TestServer};
+use rstest::rstest;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn upload_through(url: &str, temp_dir: &Path) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{url}dir/file1"))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    let resp = reqwest::blocking::get(format!("{url}dir/file1"))?;
+    assert_eq!(resp.text()?, "abc");
+    assert_eq!(std::fs::read_dir(temp_dir)?.count(), 0);
+    Ok(())
+}
+
+#[rstest]
+fn upload_temp_dir_same_fs(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let temp_dir = tmpdir.path().join(".uploads");
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-p")
+        .arg(port.to_string())
+        .args(["-A", "--temp-dir"])
+        .arg(&temp_dir)
+        .stdout(Stdio::null())
+        .spawn()?;
+    wait_for_port(port);
+
+    let url = format!("http://localhost:{port}/");
+    upload_through(&url, &temp_dir)?;
+    assert!(!tmpdir.path().join("dir/file1.dufsupload").exists());
+
+    // The temp dir is neither listed nor served
+    let resp = reqwest::blocking::get(&url)?;
+    let paths = utils::retrieve_index_paths(&resp.text()?);
+    assert!(!paths.contains(".uploads/"));
+    let resp = reqwest::blocking::get(format!("{url}.uploads/"))?;
+    assert_eq!(resp.status(), 404);
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn upload_temp_dir_cross_fs(port: u16) -> Result<(), Error> {
+    // Needs a tmpfs apart from the served folder, skip elsewhere
+    let shm = Path::new("/dev/shm");
+    let served = TempDir::new()?;
+    if !shm.is_dir() || shm.metadata()?.dev() == served.path().metadata()?.dev() {
+        return Ok(());
+    }
+    let temp_dir = shm.join(format!("dufs-temp-dir-{port}"));
+    let server = server(&["-A", "--temp-dir", temp_dir.to_str().unwrap()]);
+    let ret = upload_cross_fs(&server, &temp_dir);
+    std::fs::remove_dir_all(&temp_dir)?;
+    ret
+}
+
+fn upload_cross_fs(server: &TestServer, temp_dir: &Path) -> Result<(), Error> {
+    upload_through(server.url().as_str(), temp_dir)?;
+    assert!(!server.path().join("dir/file1.dufsupload").exists());
+
+    let resp = fetch!(b"PUT", format!("{}dir/file1", server.url()))
+        .header("if-none-match", "*")
+        .body(b"def".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 412);
+    assert_eq!(std::fs::read_dir(temp_dir)?.count(), 0);
+    Ok(())
+}
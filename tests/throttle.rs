@@ -0,0 +1,41 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::time::{Duration, Instant};
+
+const FILE_SIZE: usize = 16 * 1024;
+
+#[rstest]
+fn download_rate(#[with(&["--download-rate", "8K"])] server: TestServer) -> Result<(), Error> {
+    std::fs::write(server.path().join("big.bin"), vec![0u8; FILE_SIZE])?;
+    let start = Instant::now();
+    let resp = reqwest::blocking::get(format!("{}big.bin", server.url()))?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.bytes()?.len(), FILE_SIZE);
+    assert!(start.elapsed() >= Duration::from_millis(1900));
+    Ok(())
+}
+
+#[rstest]
+fn upload_rate(
+    #[with(&["--upload-rate", "8K", "--allow-upload"])] server: TestServer,
+) -> Result<(), Error> {
+    let start = Instant::now();
+    let resp = fetch!(b"PUT", format!("{}big.bin", server.url()))
+        .body(vec![0u8; FILE_SIZE])
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    assert!(start.elapsed() >= Duration::from_millis(1900));
+    Ok(())
+}
+
+#[rstest]
+fn invalid_rate() -> Result<(), Error> {
+    assert_cmd::Command::cargo_bin("dufs")?
+        .args(["--download-rate", "10X"])
+        .assert()
+        .failure();
+    Ok(())
+}
@@ -0,0 +1,67 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+/// Read until the server closes the connection.
+fn read_until_closed(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut data = vec![];
+    let mut buf = [0; 4096];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+            Err(err) if err.kind() == std::io::ErrorKind::ConnectionReset => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(data)
+}
+
+#[rstest]
+fn header_read_timeout_drops_slow_client(
+    #[with(&["--header-read-timeout", "1"])] server: TestServer,
+) -> Result<(), Error> {
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port()))?;
+    let start = Instant::now();
+    stream.write_all(b"GET / HTTP/1.1\r\n")?;
+    for _ in 0..3 {
+        std::thread::sleep(Duration::from_millis(200));
+        let _ = stream.write_all(b"X-Slow: 1\r\n");
+    }
+    let data = read_until_closed(&mut stream)?;
+    let elapsed = start.elapsed();
+    assert!(!String::from_utf8_lossy(&data).contains("200 OK"));
+    assert!(elapsed >= Duration::from_millis(900), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    Ok(())
+}
+
+#[rstest]
+fn header_read_timeout_allows_prompt_client(
+    #[with(&["--header-read-timeout", "1"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = reqwest::blocking::get(server.url())?;
+    assert_eq!(resp.status(), 200);
+    Ok(())
+}
+
+#[rstest]
+fn keep_alive_timeout_closes_idle_connection(
+    #[with(&["--keep-alive-timeout", "1"])] server: TestServer,
+) -> Result<(), Error> {
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port()))?;
+    stream.write_all(b"GET /index.html HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n")?;
+    let start = Instant::now();
+    let data = read_until_closed(&mut stream)?;
+    let elapsed = start.elapsed();
+    assert!(String::from_utf8_lossy(&data).starts_with("HTTP/1.1 200 OK"));
+    assert!(elapsed >= Duration::from_millis(900), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    Ok(())
+}
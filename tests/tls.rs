@@ -6,6 +6,17 @@ use fixtures::{server, Error, TestServer};
 use predicates::str::contains;
 use reqwest::blocking::ClientBuilder;
 use rstest::rstest;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use tokio_rustls::rustls::{
+    self,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring::default_provider, verify_tls12_signature, verify_tls13_signature},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, ClientConnection, DigitallySignedStruct, HandshakeKind, SignatureScheme,
+    StreamOwned, SupportedProtocolVersion,
+};
 
 use crate::fixtures::port;
 
@@ -71,3 +82,89 @@ fn wrong_path_key() -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Records the OCSP response stapled by the server, accepting any certificate.
+#[derive(Debug, Default)]
+struct StapleRecorder(Mutex<Vec<u8>>);
+
+impl ServerCertVerifier for StapleRecorder {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        *self.0.lock().unwrap() = ocsp_response.to_vec();
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        let algs = default_provider().signature_verification_algorithms;
+        verify_tls12_signature(message, cert, dss, &algs)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        let algs = default_provider().signature_verification_algorithms;
+        verify_tls13_signature(message, cert, dss, &algs)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Staples the OCSP response of --tls-ocsp-file and resumes the sessions of repeat clients.
+#[rstest]
+#[case(&rustls::version::TLS12)]
+#[case(&rustls::version::TLS13)]
+fn tls_ocsp_and_resumption(
+    #[case] version: &'static SupportedProtocolVersion,
+    #[with(&[
+        "--tls-cert", "tests/data/cert.pem",
+        "--tls-key", "tests/data/key_pkcs8.pem",
+        "--tls-ocsp-file", "tests/data/ocsp.der",
+    ])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let verifier = Arc::new(StapleRecorder::default());
+    let config = Arc::new(
+        ClientConfig::builder_with_protocol_versions(&[version])
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone())
+            .with_no_client_auth(),
+    );
+    let mut kinds = vec![];
+    for _ in 0..2 {
+        let conn = ClientConnection::new(config.clone(), "localhost".try_into()?)?;
+        let sock = TcpStream::connect(("localhost", server.port()))?;
+        let mut tls = StreamOwned::new(conn, sock);
+        tls.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+        let mut resp = vec![];
+        tls.read_to_end(&mut resp)?;
+        assert!(resp.starts_with(b"HTTP/1.1 200 OK"));
+        kinds.push(tls.conn.handshake_kind());
+    }
+    assert_eq!(
+        kinds,
+        [Some(HandshakeKind::Full), Some(HandshakeKind::Resumed)]
+    );
+    assert_eq!(
+        *verifier.0.lock().unwrap(),
+        std::fs::read("tests/data/ocsp.der")?
+    );
+    Ok(())
+}
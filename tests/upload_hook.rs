@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+fn wait_for_file(path: &std::path::Path) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(3) {
+        if path.exists() {
+            return true;
+        }
+        sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+#[rstest]
+fn upload_hook_runs(
+    #[with(&["--allow-upload", "--on-upload", "cp {path} {path}.{size}"])] server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}my file.txt", server.url());
+    let resp = fetch!(b"PUT", &url).body("hello").send()?;
+    assert_eq!(resp.status(), 201);
+    let hooked = server.path().join("my file.txt.5");
+    assert!(wait_for_file(&hooked));
+    assert_eq!(std::fs::read_to_string(hooked)?, "hello");
+    Ok(())
+}
+
+#[rstest]
+fn upload_hook_shell(
+    #[with(&["--allow-upload", "--hook-shell", "--on-upload", "echo {name} > {path}.name"])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let url = format!("{}file1", server.url());
+    let resp = fetch!(b"PUT", &url).body("hello").send()?;
+    assert_eq!(resp.status(), 201);
+    let hooked = server.path().join("file1.name");
+    assert!(wait_for_file(&hooked));
+    sleep(Duration::from_millis(100));
+    assert_eq!(std::fs::read_to_string(hooked)?, "file1\n");
+    Ok(())
+}
+
+#[rstest]
+fn upload_hook_shell_escapes(
+    #[with(&[
+        "--allow-upload",
+        "--hook-shell",
+        "--on-upload",
+        "printf %s {name} > {path}.name",
+    ])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let name = r#"a;touch pwned1;$(touch pwned2)`touch pwned3`'"b.txt"#;
+    let url = format!("{}{}", server.url(), utils::encode_uri(name));
+    let resp = fetch!(b"PUT", &url).body("hello").send()?;
+    assert_eq!(resp.status(), 201);
+    let hooked = server.path().join(format!("{name}.name"));
+    assert!(wait_for_file(&hooked));
+    sleep(Duration::from_millis(100));
+    assert_eq!(std::fs::read_to_string(hooked)?, name);
+    for dir in [server.path().to_path_buf(), std::env::current_dir()?] {
+        for pwned in ["pwned1", "pwned2", "pwned3"] {
+            assert!(!dir.join(pwned).exists());
+        }
+    }
+    Ok(())
+}
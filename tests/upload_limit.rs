@@ -0,0 +1,188 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+fn read_status_line(reader: &mut BufReader<TcpStream>) -> Result<String, Error> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    // Skip the headers of the response
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    Ok(line.trim_end().to_string())
+}
+
+fn send_expect_continue(server: &TestServer, name: &str, size: u64) -> Result<TcpStream, Error> {
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port()))?;
+    write!(
+        stream,
+        "PUT /{name} HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Length: {size}\r\nExpect: 100-continue\r\n\r\n"
+    )?;
+    Ok(stream)
+}
+
+#[rstest]
+fn upload_expect_continue_rejected(
+    #[with(&["-A", "--max-upload-size", "1K"])] server: TestServer,
+) -> Result<(), Error> {
+    let stream = send_expect_continue(&server, "big.bin", 10 * 1024 * 1024)?;
+    let mut reader = BufReader::new(stream);
+    assert_eq!(
+        read_status_line(&mut reader)?,
+        "HTTP/1.1 413 Payload Too Large"
+    );
+    assert!(!server.path().join("big.bin").exists());
+    Ok(())
+}
+
+#[rstest]
+fn upload_expect_continue_accepted(
+    #[with(&["-A", "--max-upload-size", "1K"])] server: TestServer,
+) -> Result<(), Error> {
+    let stream = send_expect_continue(&server, "small.txt", 3)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    assert_eq!(read_status_line(&mut reader)?, "HTTP/1.1 100 Continue");
+    (&stream).write_all(b"abc")?;
+    assert_eq!(read_status_line(&mut reader)?, "HTTP/1.1 201 Created");
+    let mut content = String::new();
+    std::fs::File::open(server.path().join("small.txt"))?.read_to_string(&mut content)?;
+    assert_eq!(content, "abc");
+    Ok(())
+}
+
+#[rstest]
+fn upload_chunked_too_large(
+    #[with(&["-A", "--max-upload-size", "1K"])] server: TestServer,
+) -> Result<(), Error> {
+    // A reader body has no known length, so it is sent chunked
+    let body = reqwest::blocking::Body::new(std::io::Cursor::new(vec![b'a'; 2048]));
+    let resp = fetch!(b"PUT", format!("{}big.bin", server.url()))
+        .body(body)
+        .send()?;
+    assert_eq!(resp.status(), 413);
+    assert!(!server.path().join("big.bin").exists());
+    Ok(())
+}
+
+#[rstest]
+#[case("photo.JPG", 201)]
+#[case("doc.pdf", 201)]
+#[case("script.php", 403)]
+#[case("noext", 403)]
+fn upload_allow_ext(
+    #[with(&["-A", "--upload-allow-ext", "jpg,.pdf"])] server: TestServer,
+    #[case] name: &str,
+    #[case] status: u16,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}{name}", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), status);
+    assert_eq!(server.path().join(name).exists(), status == 201);
+    Ok(())
+}
+
+#[rstest]
+#[case("photo.jpg", 201)]
+#[case("shell.SH", 403)]
+#[case("noext", 201)]
+fn upload_deny_ext(
+    #[with(&["-A", "--upload-deny-ext", "exe,sh"])] server: TestServer,
+    #[case] name: &str,
+    #[case] status: u16,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}{name}", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), status);
+    assert_eq!(server.path().join(name).exists(), status == 201);
+    Ok(())
+}
+
+#[rstest]
+fn upload_allow_no_ext(
+    #[with(&["-A", "--upload-allow-ext", "txt", "--upload-allow-no-ext"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}README", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    let resp = fetch!(b"PUT", format!("{}a.bin", server.url()))
+        .body(b"abc".to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    Ok(())
+}
+
+#[rstest]
+fn upload_deny_ext_rename(
+    #[with(&["-A", "--upload-deny-ext", "php"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(
+        b"POST",
+        format!("{}test.html?rename=test.php", server.url())
+    )
+    .send()?;
+    assert_eq!(resp.status(), 403);
+    let resp = fetch!(b"MOVE", format!("{}test.html", server.url()))
+        .header("Destination", format!("{}test.php", server.url()))
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    assert!(server.path().join("test.html").exists());
+    Ok(())
+}
+
+const PNG_HEAD: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+#[rstest]
+#[case("shell.jpg", None, b"<?php system($_GET['c']); ?>", 422)]
+#[case("photo.png", None, PNG_HEAD, 201)]
+#[case("photo", Some("image/jpeg"), b"<?php echo 1; ?>", 422)]
+#[case("photo", Some("image/png"), PNG_HEAD, 201)]
+#[case("photo.jpg", None, PNG_HEAD, 201)]
+#[case("notes.txt", Some("application/x-www-form-urlencoded"), b"abc", 201)]
+fn upload_verify_content_type(
+    #[with(&["-A", "--verify-content-type", "lenient"])] server: TestServer,
+    #[case] name: &str,
+    #[case] content_type: Option<&str>,
+    #[case] body: &[u8],
+    #[case] status: u16,
+) -> Result<(), Error> {
+    let mut req = fetch!(b"PUT", format!("{}{name}", server.url())).body(body.to_vec());
+    if let Some(content_type) = content_type {
+        req = req.header("content-type", content_type);
+    }
+    let resp = req.send()?;
+    assert_eq!(resp.status(), status);
+    assert_eq!(server.path().join(name).exists(), status == 201);
+    assert!(!server.path().join(format!("{name}.dufsupload")).exists());
+    Ok(())
+}
+
+#[rstest]
+fn upload_verify_content_type_strict(
+    #[with(&[
+        "-A",
+        "--verify-content-type",
+        "strict",
+        "--verify-content-type-allow",
+        "image/gif=image/png",
+    ])]
+    server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}photo.jpg", server.url()))
+        .body(PNG_HEAD.to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 422);
+    assert_eq!(resp.text()?, "Content is image/png, not image/jpeg");
+    let resp = fetch!(b"PUT", format!("{}photo.gif", server.url()))
+        .body(PNG_HEAD.to_vec())
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    Ok(())
+}
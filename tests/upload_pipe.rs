@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+mod fixtures;
+mod utils;
+
+use assert_cmd::prelude::*;
+use assert_fs::TempDir;
+use fixtures::{port, server, tmpdir, wait_for_port, Error, TestServer};
+use rstest::rstest;
+use std::process::{Command, Stdio};
+
+#[rstest]
+fn upload_pipe(tmpdir: TempDir, port: u16) -> Result<(), Error> {
+    let output = tmpdir.path().join("piped.txt");
+    let mut child = Command::cargo_bin("dufs")?
+        .arg(tmpdir.path())
+        .arg("-A")
+        .arg("-p")
+        .arg(port.to_string())
+        .arg("--pipe")
+        .arg(format!("/ingest=dd 'of={}'", output.display()))
+        .args(["--pipe", "/fail=false"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    wait_for_port(port);
+
+    let url = format!("http://localhost:{port}");
+    let resp = fetch!(b"PUT", format!("{url}/ingest"))
+        .body("hello")
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    assert_eq!(std::fs::read_to_string(&output)?, "hello");
+    assert!(!tmpdir.path().join("ingest").exists());
+
+    let resp = fetch!(b"PUT", format!("{url}/fail")).body("hello").send()?;
+    assert_eq!(resp.status(), 502);
+    let resp = fetch!(b"GET", format!("{url}/ingest")).send()?;
+    assert_eq!(resp.status(), 404);
+
+    child.kill()?;
+    Ok(())
+}
+
+#[rstest]
+fn upload_pipe_needs_upload(
+    #[with(&["--pipe", "/ingest=cat"])] server: TestServer,
+) -> Result<(), Error> {
+    let resp = fetch!(b"PUT", format!("{}ingest", server.url()))
+        .body("hello")
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    Ok(())
+}
@@ -0,0 +1,185 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error, TestServer};
+use rstest::rstest;
+use serde_json::Value;
+use std::io::Write;
+
+/// A ustar header of an entry of `size` bytes.
+fn tar_header(path: &str, typeflag: u8, mode: u32, size: usize) -> Vec<u8> {
+    let mut block = vec![0; 512];
+    block[..path.len()].copy_from_slice(path.as_bytes());
+    block[100..107].copy_from_slice(format!("{mode:07o}").as_bytes());
+    block[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+    block[136..147].copy_from_slice(b"14524770400");
+    block[156] = typeflag;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[148..156].copy_from_slice(b"        ");
+    let sum: u32 = block.iter().map(|v| *v as u32).sum();
+    block[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+    block
+}
+
+/// A tar archive of folders, named with a trailing slash, and files.
+fn tar(entries: &[(&str, u32, &str)]) -> Vec<u8> {
+    let mut data = vec![];
+    for (path, mode, content) in entries {
+        let typeflag = if path.ends_with('/') { b'5' } else { b'0' };
+        data.extend(tar_header(path, typeflag, *mode, content.len()));
+        data.extend(content.as_bytes());
+        data.resize(data.len().div_ceil(512) * 512, 0);
+    }
+    data.resize(data.len() + 1024, 0);
+    data
+}
+
+fn upload_tree(server: &TestServer, dest: &str, body: Vec<u8>) -> Result<Value, Error> {
+    let resp = fetch!(b"POST", format!("{}{dest}?upload-tree=1", server.url()))
+        .header("content-type", "application/x-tar")
+        .body(body)
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    Ok(serde_json::from_str(&resp.text()?)?)
+}
+
+fn statuses(result: &Value) -> Vec<(String, u64)> {
+    result["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| {
+            (
+                v["path"].as_str().unwrap().to_string(),
+                v["status"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[rstest]
+fn upload_tree_tar(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let archive = tar(&[
+        ("./", 0o755, ""),
+        ("./docs/", 0o750, ""),
+        ("./docs/a.txt", 0o600, "aaa"),
+        ("./docs/sub/b.txt", 0o644, "bb"),
+        ("../escape.txt", 0o644, "x"),
+        ("top.txt", 0o644, "top"),
+    ]);
+    let result = upload_tree(&server, "tree", archive.clone())?;
+    assert_eq!(
+        statuses(&result),
+        [
+            ("tree/docs".to_string(), 201),
+            ("tree/docs/a.txt".to_string(), 201),
+            ("tree/docs/sub/b.txt".to_string(), 201),
+            ("../escape.txt".to_string(), 400),
+            ("tree/top.txt".to_string(), 201),
+        ]
+    );
+    let root = server.path().join("tree");
+    assert_eq!(std::fs::read_to_string(root.join("docs/a.txt"))?, "aaa");
+    assert_eq!(std::fs::read_to_string(root.join("docs/sub/b.txt"))?, "bb");
+    assert_eq!(std::fs::read_to_string(root.join("top.txt"))?, "top");
+    assert!(!server.path().join("escape.txt").exists());
+    let mtime = std::fs::metadata(root.join("top.txt"))?.modified()?;
+    assert_eq!(
+        mtime,
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &str| -> Result<u32, Error> {
+            Ok(std::fs::metadata(root.join(path))?.permissions().mode() & 0o777)
+        };
+        assert_eq!(mode("docs")?, 0o750);
+        assert_eq!(mode("docs/a.txt")?, 0o600);
+    }
+
+    // Sending the archive again resumes it, what is already there is kept
+    let result = upload_tree(&server, "tree", archive)?;
+    assert!(statuses(&result)
+        .iter()
+        .all(|(path, status)| *status == 200 || path == "../escape.txt"));
+    Ok(())
+}
+
+#[rstest]
+fn upload_tree_gzip(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(&tar(&[("a/b.txt", 0o644, "abc")]))?;
+    let resp = fetch!(b"POST", format!("{}?upload-tree", server.url()))
+        .header("content-type", "application/gzip")
+        .body(encoder.finish()?)
+        .send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        std::fs::read_to_string(server.path().join("a/b.txt"))?,
+        "abc"
+    );
+    Ok(())
+}
+
+#[rstest]
+fn upload_tree_rejected(
+    #[with(&["-A", "--upload-deny-ext", "exe", "--max-upload-size", "4K"])] server: TestServer,
+) -> Result<(), Error> {
+    let result = upload_tree(&server, "", tar(&[("a.exe", 0o755, "MZ")]))?;
+    assert_eq!(statuses(&result), [("a.exe".to_string(), 403)]);
+
+    // The limit applies to the extracted size too
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(&tar(&[("big.txt", 0o644, &"0".repeat(8192))]))?;
+    let resp = fetch!(b"POST", format!("{}?upload-tree", server.url()))
+        .header("content-type", "application/gzip")
+        .body(encoder.finish()?)
+        .send()?;
+    assert_eq!(resp.status(), 413);
+    assert!(!server.path().join("big.txt").exists());
+
+    let resp = fetch!(b"POST", format!("{}?upload-tree", server.url()))
+        .header("content-type", "multipart/form-data; boundary=x")
+        .body("--x--")
+        .send()?;
+    assert_eq!(resp.status(), 415);
+
+    let resp = fetch!(b"POST", format!("{}index.html?upload-tree", server.url()))
+        .body(tar(&[]))
+        .send()?;
+    assert_eq!(resp.status(), 409);
+    Ok(())
+}
+
+#[rstest]
+fn upload_tree_forbidden(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"POST", format!("{}?upload-tree", server.url()))
+        .body(tar(&[("a.txt", 0o644, "a")]))
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    Ok(())
+}
+
+#[rstest]
+fn upload_tree_dotfiles(
+    #[with(&["-A", "--deny-dotfiles"])] server: TestServer,
+) -> Result<(), Error> {
+    let archive = tar(&[
+        (".ssh/authorized_keys", 0o600, "key"),
+        ("a/.env", 0o600, "SECRET=1"),
+        ("a/b.txt", 0o644, "b"),
+    ]);
+    let result = upload_tree(&server, "", archive)?;
+    assert_eq!(
+        statuses(&result),
+        [
+            (".ssh/authorized_keys".to_string(), 403),
+            ("a/.env".to_string(), 403),
+            ("a/b.txt".to_string(), 201),
+        ]
+    );
+    assert!(!server.path().join(".ssh").exists());
+    assert!(!server.path().join("a/.env").exists());
+    Ok(())
+}
@@ -5,6 +5,58 @@ use fixtures::{server, Error, TestServer, FILES};
 use rstest::rstest;
 use xml::escape::escape_str_pcdata;
 
+#[rstest]
+#[case(&[], Some("1, 3"))]
+#[case(&["--allow-upload"], Some("1, 2, 3"))]
+#[case(&["-A", "--disable-webdav"], None)]
+fn dav_compliance_classes(
+    #[case] args: &[&str],
+    #[case] classes: Option<&str>,
+) -> Result<(), Error> {
+    let server = server(args);
+    let resp = fetch!(b"OPTIONS", format!("{}index.html", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("dav").map(|v| v.to_str().unwrap()),
+        classes
+    );
+    Ok(())
+}
+
+#[rstest]
+fn disable_webdav(#[with(&["-A", "--disable-webdav"])] server: TestServer) -> Result<(), Error> {
+    for method in [
+        "PROPFIND",
+        "PROPPATCH",
+        "MKCOL",
+        "COPY",
+        "MOVE",
+        "LOCK",
+        "UNLOCK",
+    ] {
+        let resp = reqwest::blocking::Client::new()
+            .request(method.parse()?, format!("{}dir1", server.url()))
+            .header("Destination", format!("{}dir5", server.url()))
+            .send()?;
+        assert_eq!(resp.status(), 405, "{method}");
+    }
+    assert!(!server.path().join("dir5").exists());
+
+    let resp = fetch!(b"OPTIONS", format!("{}index.html", server.url())).send()?;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("allow").unwrap(),
+        "GET,HEAD,PUT,OPTIONS,DELETE,PATCH,POST,CHECKAUTH,LOGOUT"
+    );
+    assert!(!resp.headers().contains_key("dav"));
+
+    let resp = fetch!(b"PUT", format!("{}file1", server.url()))
+        .body("abc")
+        .send()?;
+    assert_eq!(resp.status(), 201);
+    Ok(())
+}
+
 #[rstest]
 fn propfind_dir(server: TestServer) -> Result<(), Error> {
     let resp = fetch!(b"PROPFIND", format!("{}dir1", server.url())).send()?;
@@ -81,6 +133,73 @@ fn propfind_file(server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+fn propfind_file_allprop(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"PROPFIND", format!("{}test.html", server.url()))
+        .body(r#"<?xml version="1.0"?><D:propfind xmlns:D="DAV:"><D:allprop/></D:propfind>"#)
+        .send()?;
+    assert_eq!(resp.status(), 207);
+    let body = resp.text()?;
+    assert!(body.contains("<D:displayname>test.html</D:displayname>"));
+    assert!(body.contains("<D:getcontentlength>"));
+    assert!(body.contains("<D:getlastmodified>"));
+    assert!(body.contains("<D:resourcetype></D:resourcetype>"));
+    assert!(!body.contains("404 Not Found"));
+    Ok(())
+}
+
+#[rstest]
+fn propfind_file_propname(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"PROPFIND", format!("{}test.html", server.url()))
+        .body(r#"<?xml version="1.0"?><propfind xmlns="DAV:"><propname/></propfind>"#)
+        .send()?;
+    assert_eq!(resp.status(), 207);
+    let body = resp.text()?;
+    assert!(body.contains("<D:href>/test.html</D:href>"));
+    for name in [
+        "displayname",
+        "getcontentlength",
+        "getlastmodified",
+        "resourcetype",
+    ] {
+        assert!(body.contains(&format!("<D:{name}/>")), "{name}");
+    }
+    assert!(!body.contains("test.html</D:displayname>"));
+    Ok(())
+}
+
+#[rstest]
+fn propfind_file_prop(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"PROPFIND", format!("{}test.html", server.url()))
+        .body(
+            r#"<?xml version="1.0"?>
+<D:propfind xmlns:D="DAV:" xmlns:X="urn:example">
+  <D:prop><D:displayname/><D:getetag/><X:color/></D:prop>
+</D:propfind>"#,
+        )
+        .send()?;
+    assert_eq!(resp.status(), 207);
+    let body = resp.text()?;
+    let (ok, missing) = body
+        .split_once("<D:status>HTTP/1.1 200 OK</D:status>")
+        .unwrap();
+    assert!(ok.contains("<D:displayname>test.html</D:displayname>"));
+    assert!(!ok.contains("getcontentlength"));
+    assert!(missing.contains("<D:getetag/>"));
+    assert!(missing.contains(r#"<color xmlns="urn:example"/>"#));
+    assert!(missing.contains("<D:status>HTTP/1.1 404 Not Found</D:status>"));
+    Ok(())
+}
+
+#[rstest]
+fn propfind_invalid_body(server: TestServer) -> Result<(), Error> {
+    let resp = fetch!(b"PROPFIND", format!("{}test.html", server.url()))
+        .body("<D:propfind xmlns:D=\"DAV:\"><D:prop>")
+        .send()?;
+    assert_eq!(resp.status(), 400);
+    Ok(())
+}
+
 #[rstest]
 fn proppatch_file(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     let resp = fetch!(b"PROPPATCH", format!("{}test.html", server.url())).send()?;
@@ -165,6 +284,55 @@ fn move_file(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
     Ok(())
 }
 
+#[rstest]
+#[case("test%202.html", "test 2.html")]
+#[case("test 2.html", "test 2.html")]
+#[case("%E6%B5%8B%E8%AF%95.html", "测试.html")]
+#[case("dir1/test2.html?foo=bar", "dir1/test2.html")]
+fn move_file_encoded_destination(
+    #[with(&["-A"])] server: TestServer,
+    #[case] dest: &str,
+    #[case] expect: &str,
+) -> Result<(), Error> {
+    let origin_url = format!("{}test.html", server.url());
+    let resp = fetch!(b"MOVE", &origin_url)
+        .header("Destination", format!("{}{}", server.url(), dest))
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(server.path().join(expect).exists());
+    Ok(())
+}
+
+#[rstest]
+fn move_file_path_destination(#[with(&["-A"])] server: TestServer) -> Result<(), Error> {
+    let origin_url = format!("{}test.html", server.url());
+    let resp = fetch!(b"MOVE", &origin_url)
+        .header("Destination", "/dir1/test2.html")
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    assert!(server.path().join("dir1/test2.html").exists());
+    Ok(())
+}
+
+#[rstest]
+#[case("../test2.html")]
+#[case("dir1/%2E%2E/%2E%2E/test2.html")]
+fn move_file_escape_destination(
+    #[with(&["-A"])] server: TestServer,
+    #[case] dest: &str,
+) -> Result<(), Error> {
+    let origin_url = format!("{}test.html", server.url());
+    let resp = fetch!(b"MOVE", &origin_url)
+        .header(
+            "Destination",
+            format!("http://localhost:{}/{}", server.port(), dest),
+        )
+        .send()?;
+    assert_eq!(resp.status(), 403);
+    assert!(server.path().join("test.html").exists());
+    Ok(())
+}
+
 #[rstest]
 fn move_not_allow_upload(#[with(&["--allow-delete"])] server: TestServer) -> Result<(), Error> {
     let origin_url = format!("{}test.html", server.url());
@@ -0,0 +1,108 @@
+mod fixtures;
+mod utils;
+
+use fixtures::{server, Error};
+use rstest::rstest;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// A received event, its headers by lowercase name along with its body.
+type Received = (HashMap<String, String>, Vec<u8>);
+
+/// Accept webhook requests, answering `200` to each.
+fn spawn_receiver() -> Result<(u16, mpsc::Receiver<Received>), Error> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+            }
+            let len: usize = headers["content-length"].parse().unwrap();
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            tx.send((headers, body)).unwrap();
+        }
+    });
+    Ok((port, rx))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> String {
+    let mut block = [0u8; 64];
+    block[..key.len()].copy_from_slice(key);
+    let pad = |v: u8| block.iter().map(|b| b ^ v).collect::<Vec<u8>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(data)
+        .finalize();
+    let outer = Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize();
+    format!("{:x}", outer)
+}
+
+#[rstest]
+fn webhook_events() -> Result<(), Error> {
+    let (port, rx) = spawn_receiver()?;
+    let server = server(&[
+        "-A",
+        "--webhook-url",
+        &format!("http://127.0.0.1:{port}/hook"),
+        "--webhook-secret",
+        "s3cret",
+    ]);
+    let resp = fetch!(b"PUT", format!("{}upload.txt", server.url()))
+        .body("hello")
+        .send()?;
+    assert_eq!(resp.status(), 201);
+
+    let (headers, body) = rx.recv_timeout(Duration::from_secs(5))?;
+    assert_eq!(headers["content-type"], "application/json");
+    assert_eq!(
+        headers["x-dufs-signature"],
+        format!("sha256={}", hmac_sha256(b"s3cret", &body))
+    );
+    let event: Value = serde_json::from_slice(&body)?;
+    assert_eq!(event["event"], "upload");
+    assert_eq!(event["path"], "/upload.txt");
+    assert_eq!(event["size"], 5);
+    assert!(event["time"].as_u64().unwrap() > 0);
+
+    let resp = fetch!(b"MOVE", format!("{}upload.txt", server.url()))
+        .header("Destination", format!("{}moved.txt", server.url()))
+        .send()?;
+    assert_eq!(resp.status(), 204);
+    let (_, body) = rx.recv_timeout(Duration::from_secs(5))?;
+    let event: Value = serde_json::from_slice(&body)?;
+    assert_eq!(event["event"], "move");
+    assert_eq!(event["path"], "/upload.txt");
+    assert_eq!(event["destination"], "/moved.txt");
+
+    let resp = fetch!(b"DELETE", format!("{}moved.txt", server.url())).send()?;
+    assert_eq!(resp.status(), 204);
+    let (_, body) = rx.recv_timeout(Duration::from_secs(5))?;
+    let event: Value = serde_json::from_slice(&body)?;
+    assert_eq!(event["event"], "delete");
+    assert_eq!(event["path"], "/moved.txt");
+    Ok(())
+}